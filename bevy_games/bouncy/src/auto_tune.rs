@@ -0,0 +1,230 @@
+//! Auto-tune mode, adding or removing balls until the game runs at a target
+//! frame rate
+
+use super::{Ball, CollisionTime, despawn_newest, spawn_bouncies};
+use crate::benchmark::Benchmark;
+use bevy::prelude::*;
+use my_library::{AssetStore, LoadedAssets, RandomNumberGenerator, egui};
+
+/// Seconds between two adjustments, so the average fps can follow
+const ADJUST_INTERVAL: f32 = 1.0;
+/// The fps may deviate from the target by this fraction and still count as
+/// on target
+const TOLERANCE: f64 = 0.05;
+/// Seconds the fps has to stay on target, before the ball count is reported
+const STABLE_SECONDS: u32 = 5;
+/// The fps' deviation is capped at this, so an adjustment at most doubles the
+/// balls. Frame rates far above the target are rarely proportional to the
+/// balls, e.g. when capped by the display.
+const MAX_ERROR: f64 = 2.0;
+
+/// Resource controlling the number of balls by the frame rate
+#[derive(Resource, Debug)]
+pub struct AutoTuner {
+    pub enabled: bool,
+    pub target_fps: f64,
+    /// Seconds since the last adjustment
+    elapsed: f32,
+    /// Adjustments in a row with the fps on target
+    stable_seconds: u32,
+    /// The ball count sustained at the target, once stable
+    sustained: Option<usize>,
+}
+
+impl Default for AutoTuner {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+impl AutoTuner {
+    /// Creates a disabled tuner aiming at `target_fps`
+    pub fn new(target_fps: f64) -> Self {
+        Self {
+            enabled: false,
+            target_fps,
+            elapsed: 0.0,
+            stable_seconds: 0,
+            sustained: None,
+        }
+    }
+
+    /// The number of balls the game sustained at the target fps for
+    /// [`STABLE_SECONDS`], if it did
+    pub fn sustained(&self) -> Option<usize> {
+        self.sustained
+    }
+
+    /// Called once per second with the measured fps and the number of balls.
+    /// Returns how many balls to add, or to remove if negative.
+    pub fn adjust(&mut self, fps: f64, balls: usize) -> isize {
+        let error = fps / self.target_fps - 1.0;
+        if error.abs() <= TOLERANCE {
+            self.stable_seconds += 1;
+            if self.stable_seconds >= STABLE_SECONDS {
+                self.sustained = Some(balls);
+            }
+            return 0;
+        }
+        self.stable_seconds = 0;
+        self.sustained = None;
+        // The frame time grows about linearly with the balls, so the count
+        // is scaled by the fps' deviation. Only half of it is applied, which
+        // approaches the target from one side instead of overshooting.
+        let step = balls.max(1) as f64 * error.min(MAX_ERROR) / 2.0;
+        if step > 0.0 {
+            step.ceil() as isize
+        } else {
+            step.floor() as isize
+        }
+    }
+}
+
+/// Adds or removes balls once per second while the tuner is enabled. Pauses
+/// while a benchmark is running, which sets the balls itself.
+#[allow(clippy::too_many_arguments)]
+pub fn auto_tune(
+    mut tuner: ResMut<AutoTuner>,
+    time: Res<Time>,
+    collision_time: Res<CollisionTime>,
+    balls: Query<(Entity, Ref<Ball>)>,
+    benchmark: Option<Res<Benchmark>>,
+    mut rng: ResMut<RandomNumberGenerator>,
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
+) {
+    if !tuner.enabled || benchmark.is_some() {
+        return;
+    }
+    tuner.elapsed += time.delta_secs();
+    // No fps were measured yet, e.g. right after clearing
+    if tuner.elapsed < ADJUST_INTERVAL || collision_time.fps <= 0.0 {
+        return;
+    }
+    tuner.elapsed = 0.0;
+    let step = tuner.adjust(collision_time.fps, balls.iter().count());
+    if step > 0 {
+        spawn_bouncies(
+            step as usize,
+            &mut commands,
+            &mut rng,
+            &assets,
+            &loaded_assets,
+        );
+    } else if step < 0 {
+        despawn_newest(step.unsigned_abs(), &mut commands, &balls);
+    }
+}
+
+/// Switches the tuner and sets its target fps
+pub fn auto_tune_menu(mut tuner: ResMut<AutoTuner>, mut egui_context: egui::EguiContexts) {
+    egui::egui::Window::new("Auto-Tune").show(egui_context.ctx_mut(), |ui| {
+        let (mut enabled, mut target_fps) = (tuner.enabled, tuner.target_fps);
+        ui.checkbox(&mut enabled, "Find the balls sustained at");
+        ui.add(egui::egui::Slider::new(&mut target_fps, 20.0..=240.0).text("FPS"));
+        if (enabled, target_fps) != (tuner.enabled, tuner.target_fps) {
+            // Start over, as earlier measurements don't apply anymore
+            *tuner = AutoTuner {
+                enabled,
+                ..AutoTuner::new(target_fps)
+            };
+        }
+        if tuner.enabled {
+            match tuner.sustained() {
+                Some(balls) => ui.label(format!(
+                    "Sustained {balls} balls at {:.0} FPS",
+                    tuner.target_fps
+                )),
+                None => ui.label("Tuning..."),
+            };
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// Frame rate of a machine spending 5ms per frame, plus 20µs per ball
+    fn fake_fps(balls: usize) -> f64 {
+        1000.0 / (5.0 + 0.02 * balls as f64)
+    }
+
+    #[test]
+    fn the_tuner_converges_without_oscillating() {
+        // 60 FPS are reached at 583 balls
+        let in_band = |fps: f64| (fps / 60.0 - 1.0).abs() <= TOLERANCE;
+        for start in [1, 300, 2000, 20_000] {
+            let mut tuner = AutoTuner::new(60.0);
+            let mut balls = start;
+            let mut reached = None;
+            for second in 0..60 {
+                let step = tuner.adjust(fake_fps(balls), balls);
+                balls = balls.checked_add_signed(step).unwrap();
+                match reached {
+                    None if in_band(fake_fps(balls)) => reached = Some(second),
+                    // Once on target, the count stays there
+                    Some(_) => assert!(in_band(fake_fps(balls)), "{start}: {balls}"),
+                    None => {}
+                }
+            }
+            assert!(reached.unwrap() < 30, "{start}: {reached:?}");
+            assert_eq!(Some(balls), tuner.sustained());
+        }
+
+        // A sudden drop of the frame rate starts tuning again
+        let mut tuner = AutoTuner::new(60.0);
+        for _ in 0..STABLE_SECONDS {
+            assert_eq!(0, tuner.adjust(60.0, 583));
+        }
+        assert_eq!(Some(583), tuner.sustained());
+        assert!(tuner.adjust(30.0, 583) < 0);
+        assert_eq!(None, tuner.sustained());
+    }
+
+    #[test]
+    fn the_system_adds_and_removes_balls_each_second() {
+        let mut store = AssetStore::default();
+        let mut loaded = LoadedAssets::default();
+        let mut images = Assets::<Image>::default();
+        store.insert_handle(
+            "green_ball",
+            images.add(Image::default()).untyped(),
+            &mut loaded,
+        );
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<CollisionTime>()
+            .insert_resource(AutoTuner {
+                enabled: true,
+                ..AutoTuner::new(60.0)
+            })
+            .insert_resource(RandomNumberGenerator::seeded(1))
+            .insert_resource(store)
+            .insert_resource(loaded)
+            .add_systems(Update, auto_tune);
+        let mut balls = app.world_mut().query_filtered::<(), With<Ball>>();
+        let second = |app: &mut App, fps: f64| {
+            app.world_mut().resource_mut::<CollisionTime>().fps = fps;
+            for _ in 0..4 {
+                app.world_mut()
+                    .resource_mut::<Time>()
+                    .advance_by(Duration::from_millis(250));
+                app.update();
+            }
+        };
+
+        second(&mut app, 120.0);
+        assert_eq!(1, balls.iter(app.world()).count());
+        app.world_mut().spawn_batch((0..99).map(|_| Ball));
+        second(&mut app, 240.0);
+        assert_eq!(200, balls.iter(app.world()).count());
+        second(&mut app, 30.0);
+        assert_eq!(150, balls.iter(app.world()).count());
+        // The newest balls, spawned by the tuner, are removed first
+        let mut spawned = app.world_mut().query_filtered::<(), With<Sprite>>();
+        assert_eq!(51, spawned.iter(app.world()).count());
+    }
+}
@@ -0,0 +1,157 @@
+//! Tints the balls, to show what the physics is doing
+
+use super::Ball;
+use bevy::prelude::*;
+use my_library::{OnCollision, Velocity};
+
+/// Frames a ball flashes white after a collision
+const FLASH_FRAMES: u32 = 8;
+/// The running maximum speed shrinks by this factor per frame, so the
+/// gradient adapts when the balls slow down
+const MAX_SPEED_DECAY: f32 = 0.995;
+
+/// What the color of the balls shows, picked in the performance window
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BallColoring {
+    /// The balls keep the colors of their sprite
+    #[default]
+    Off,
+    /// Slow balls are blue, the fastest red
+    Speed,
+    /// Like [`BallColoring::Speed`], but colliding balls flash white
+    CollisionHeat,
+}
+
+impl BallColoring {
+    pub const ALL: [Self; 3] = [
+        BallColoring::Off,
+        BallColoring::Speed,
+        BallColoring::CollisionHeat,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BallColoring::Off => "Off",
+            BallColoring::Speed => "Speed",
+            BallColoring::CollisionHeat => "Collision heat",
+        }
+    }
+}
+
+/// Frames left of a ball's flash after its last collision
+#[derive(Component, Debug, Default)]
+pub struct CollisionFlash(pub u32);
+
+/// Color of a ball moving at `speed`, from blue when resting to red at
+/// `max_speed`
+pub fn speed_color(speed: f32, max_speed: f32) -> LinearRgba {
+    let fraction = if max_speed > 0.0 {
+        (speed / max_speed).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    LinearRgba::BLUE.mix(&LinearRgba::RED, fraction)
+}
+
+/// Color of a ball that collided, blending from white back to its speed
+/// color while the flash runs out
+pub fn flash_color(base: LinearRgba, flash: u32) -> LinearRgba {
+    base.mix(
+        &LinearRgba::WHITE,
+        flash.min(FLASH_FRAMES) as f32 / FLASH_FRAMES as f32,
+    )
+}
+
+/// Tints the sprites of the balls by the chosen [`BallColoring`]. The color
+/// is written every frame from the ball's state, so it never keeps a tint
+/// that no longer applies.
+pub fn color_balls(
+    coloring: Res<BallColoring>,
+    mut collisions: EventReader<OnCollision<Ball, Ball>>,
+    mut max_speed: Local<f32>,
+    mut balls: Query<(&Velocity, &mut Sprite, &mut CollisionFlash), With<Ball>>,
+) {
+    for collision in collisions.read() {
+        for entity in [collision.entity_a, collision.entity_b] {
+            if let Ok((_, _, mut flash)) = balls.get_mut(entity) {
+                flash.0 = FLASH_FRAMES;
+            }
+        }
+    }
+    let fastest = balls
+        .iter()
+        .map(|(velocity, _, _)| velocity.0.length())
+        .fold(0.0, f32::max);
+    *max_speed = (*max_speed * MAX_SPEED_DECAY).max(fastest);
+
+    for (velocity, mut sprite, mut flash) in balls.iter_mut() {
+        let color = match *coloring {
+            BallColoring::Off => LinearRgba::WHITE,
+            BallColoring::Speed => speed_color(velocity.0.length(), *max_speed),
+            BallColoring::CollisionHeat => {
+                flash_color(speed_color(velocity.0.length(), *max_speed), flash.0)
+            }
+        };
+        flash.0 = flash.0.saturating_sub(1);
+        let color = Color::from(color);
+        // Untouched sprites keep their change ticks
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn speeds_map_onto_the_gradient() {
+        assert_eq!(LinearRgba::BLUE, speed_color(0.0, 2.0));
+        assert_eq!(LinearRgba::RED, speed_color(2.0, 2.0));
+        assert_eq!(LinearRgba::RED, speed_color(3.0, 2.0));
+        assert_eq!(LinearRgba::new(0.5, 0.0, 0.5, 1.0), speed_color(1.0, 2.0));
+        // Resting worlds don't divide by zero
+        assert_eq!(LinearRgba::BLUE, speed_color(0.0, 0.0));
+    }
+
+    #[test]
+    fn flashes_decay_back_to_the_gradient() {
+        let mut app = App::new();
+        app.insert_resource(BallColoring::CollisionHeat)
+            .add_event::<OnCollision<Ball, Ball>>()
+            .add_systems(Update, color_balls);
+        let mut ball = |speed: f32| {
+            app.world_mut()
+                .spawn((
+                    Ball,
+                    Velocity::new_2d(speed, 0.0),
+                    Sprite::default(),
+                    CollisionFlash::default(),
+                ))
+                .id()
+        };
+        let (slow, fast) = (ball(1.0), ball(2.0));
+        let color =
+            |app: &App, entity| LinearRgba::from(app.world().get::<Sprite>(entity).unwrap().color);
+
+        app.world_mut()
+            .send_event(OnCollision::<Ball, Ball>::new(slow, fast));
+        app.update();
+        assert_eq!(LinearRgba::WHITE, color(&app, slow));
+
+        let mut last = LinearRgba::WHITE;
+        for _ in 0..FLASH_FRAMES {
+            app.update();
+            let current = color(&app, slow);
+            assert!(current.green < last.green || current.green == 0.0);
+            last = current;
+        }
+        assert_eq!(speed_color(1.0, 2.0), color(&app, slow));
+        assert_eq!(LinearRgba::RED, color(&app, fast));
+
+        *app.world_mut().resource_mut::<BallColoring>() = BallColoring::Off;
+        app.update();
+        assert_eq!(LinearRgba::WHITE, color(&app, fast));
+    }
+}
@@ -0,0 +1,297 @@
+//! Benchmark mode, ramping up the number of balls and recording the
+//! performance of each step to a CSV file
+
+use super::{Ball, CollisionSettings, CollisionTime, GamePhase, spawn_bouncies};
+use bevy::prelude::*;
+use my_library::{AssetStore, LoadedAssets, RandomNumberGenerator, egui};
+use std::path::PathBuf;
+
+/// File the results are written to
+pub const BENCHMARK_FILE: &str = "bouncy_benchmark.csv";
+/// Seed of the balls' positions and velocities, so runs are comparable
+const BENCHMARK_SEED: u64 = 1234;
+
+/// How the benchmark ramps up the number of balls
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchmarkSchedule {
+    /// Balls added per step
+    pub step: usize,
+    /// Seconds each step is measured
+    pub interval: f32,
+    /// The benchmark ends once this many balls were measured
+    pub max_balls: usize,
+}
+
+impl Default for BenchmarkSchedule {
+    fn default() -> Self {
+        Self {
+            step: 250,
+            interval: 5.0,
+            max_balls: 10_000,
+        }
+    }
+}
+
+/// Measurements of one step
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkSample {
+    pub ball_count: usize,
+    pub fps_avg: f64,
+    /// Average time of the collision system in milliseconds
+    pub collision_ms: f64,
+    /// Overlap checks in the last frame of the step
+    pub checks: u32,
+    pub broadphase: String,
+}
+
+/// Resource with a running benchmark. Insert it to start one.
+#[derive(Resource)]
+pub struct Benchmark {
+    schedule: BenchmarkSchedule,
+    /// Where the CSV file is written
+    path: PathBuf,
+    rng: RandomNumberGenerator,
+    samples: Vec<BenchmarkSample>,
+    /// Whether the balls of the first step are spawned
+    started: bool,
+    /// Set to stop early, keeping the samples so far
+    pub aborted: bool,
+    /// Frames, seconds and collision milliseconds of the current step
+    frames: u32,
+    elapsed: f32,
+    collision_ms: f64,
+}
+
+impl Benchmark {
+    /// Creates a benchmark writing its results to `path`
+    pub fn new(schedule: BenchmarkSchedule, path: impl Into<PathBuf>) -> Self {
+        Self {
+            schedule,
+            path: path.into(),
+            rng: RandomNumberGenerator::seeded(BENCHMARK_SEED),
+            samples: Vec::new(),
+            started: false,
+            aborted: false,
+            frames: 0,
+            elapsed: 0.0,
+            collision_ms: 0.0,
+        }
+    }
+
+    /// The samples as CSV, with a header line
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("ball_count,fps_avg,collision_ms,checks,broadphase_mode\n");
+        for sample in &self.samples {
+            csv += &format!(
+                "{},{:.1},{:.3},{},{}\n",
+                sample.ball_count,
+                sample.fps_avg,
+                sample.collision_ms,
+                sample.checks,
+                sample.broadphase
+            );
+        }
+        csv
+    }
+}
+
+/// Advances a running benchmark: spawns the balls of each step, averages the
+/// measurements over the step, and writes the results and returns to the
+/// menu once it is finished or aborted
+#[allow(clippy::too_many_arguments)]
+pub fn run_benchmark(
+    benchmark: Option<ResMut<Benchmark>>,
+    time: Res<Time>,
+    collision_time: Res<CollisionTime>,
+    settings: Res<CollisionSettings>,
+    balls: Query<Entity, With<Ball>>,
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut state: ResMut<NextState<GamePhase>>,
+    mut commands: Commands,
+) {
+    let Some(mut benchmark) = benchmark else {
+        return;
+    };
+    let benchmark = benchmark.as_mut();
+    if !benchmark.started {
+        // Start from an empty screen, so every run spawns the same balls
+        balls
+            .iter()
+            .for_each(|ball| commands.entity(ball).despawn());
+        spawn_bouncies(
+            benchmark.schedule.step,
+            &mut commands,
+            &mut benchmark.rng,
+            &assets,
+            &loaded_assets,
+        );
+        benchmark.started = true;
+        return;
+    }
+
+    benchmark.frames += 1;
+    benchmark.elapsed += time.delta_secs();
+    benchmark.collision_ms += collision_time.time;
+    if benchmark.elapsed >= benchmark.schedule.interval {
+        let ball_count = balls.iter().count();
+        benchmark.samples.push(BenchmarkSample {
+            ball_count,
+            fps_avg: benchmark.frames as f64 / benchmark.elapsed as f64,
+            collision_ms: benchmark.collision_ms / benchmark.frames as f64,
+            checks: collision_time.checks,
+            broadphase: format!("{:?}", settings.broadphase),
+        });
+        (benchmark.frames, benchmark.elapsed, benchmark.collision_ms) = (0, 0.0, 0.0);
+        if ball_count >= benchmark.schedule.max_balls {
+            benchmark.aborted = true;
+        } else {
+            spawn_bouncies(
+                benchmark.schedule.step,
+                &mut commands,
+                &mut benchmark.rng,
+                &assets,
+                &loaded_assets,
+            );
+        }
+    }
+
+    if benchmark.aborted {
+        match std::fs::write(&benchmark.path, benchmark.to_csv()) {
+            Ok(()) => info!("Wrote the benchmark to {}", benchmark.path.display()),
+            Err(error) => error!("Failed to write the benchmark: {error}"),
+        }
+        commands.remove_resource::<Benchmark>();
+        state.set(GamePhase::MainMenu);
+    }
+}
+
+/// Starts a benchmark, or shows the progress of the running one
+pub fn benchmark_menu(
+    benchmark: Option<ResMut<Benchmark>>,
+    balls: Query<(), With<Ball>>,
+    mut commands: Commands,
+    mut egui_context: egui::EguiContexts,
+) {
+    egui::egui::Window::new("Benchmark").show(egui_context.ctx_mut(), |ui| match benchmark {
+        Some(mut benchmark) => {
+            ui.label(format!(
+                "Measuring {} of {} balls",
+                balls.iter().count(),
+                benchmark.schedule.max_balls
+            ));
+            if ui.button("Abort Benchmark").clicked() {
+                benchmark.aborted = true;
+            }
+        }
+        None => {
+            if ui.button("Run Benchmark").clicked() {
+                commands
+                    .insert_resource(Benchmark::new(BenchmarkSchedule::default(), BENCHMARK_FILE));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{QUAD_TREE_DEPTH, collisions};
+    use bevy::state::app::StatesPlugin;
+    use my_library::{OnCollision, StaticQuadTree};
+    use std::time::Duration;
+
+    fn benchmark_app(path: &std::path::Path) -> App {
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut images = Assets::<Image>::default();
+        let handle = images.add(Image::default());
+        store.insert_handle("green_ball", handle.untyped(), &mut loaded);
+
+        let schedule = BenchmarkSchedule {
+            step: 5,
+            interval: 0.1,
+            max_balls: 20,
+        };
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .init_state::<GamePhase>()
+            .init_resource::<Time>()
+            .init_resource::<CollisionTime>()
+            .init_resource::<CollisionSettings>()
+            .add_event::<OnCollision<Ball, Ball>>()
+            .insert_resource(StaticQuadTree::new(
+                Vec2::new(1024.0, 768.0),
+                QUAD_TREE_DEPTH,
+            ))
+            .insert_resource(store)
+            .insert_resource(loaded)
+            .insert_resource(Benchmark::new(schedule, path))
+            .add_systems(Update, (collisions, run_benchmark).chain());
+        app
+    }
+
+    fn step(app: &mut App) {
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(20));
+        app.update();
+    }
+
+    #[test]
+    fn benchmarks_ramp_up_and_write_a_csv() {
+        let path = std::env::temp_dir().join(format!("bouncy_bench_{}.csv", std::process::id()));
+        let mut app = benchmark_app(&path);
+        app.world_mut().spawn(Ball);
+        let mut frames = 0;
+        while app.world().contains_resource::<Benchmark>() {
+            step(&mut app);
+            frames += 1;
+            assert!(frames < 100, "The benchmark doesn't finish");
+        }
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            Some("ball_count,fps_avg,collision_ms,checks,broadphase_mode"),
+            lines.next()
+        );
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        let counts: Vec<&str> = rows.iter().map(|row| row[0]).collect();
+        assert_eq!(vec!["5", "10", "15", "20"], counts);
+        for row in &rows {
+            assert_eq!(5, row.len());
+            // 20ms frames
+            assert_eq!(50.0, row[1].parse::<f64>().unwrap());
+            assert!(row[2].parse::<f64>().unwrap() >= 0.0);
+            row[3].parse::<u32>().unwrap();
+            assert_eq!("QuadTree", row[4]);
+        }
+        assert!(matches!(
+            app.world().resource::<NextState<GamePhase>>(),
+            NextState::Pending(GamePhase::MainMenu)
+        ));
+    }
+
+    #[test]
+    fn aborted_benchmarks_keep_their_samples() {
+        let path = std::env::temp_dir().join(format!("bouncy_abort_{}.csv", std::process::id()));
+        let mut app = benchmark_app(&path);
+        for _ in 0..8 {
+            step(&mut app);
+        }
+        app.world_mut().resource_mut::<Benchmark>().aborted = true;
+        step(&mut app);
+
+        assert!(!app.world().contains_resource::<Benchmark>());
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let counts: Vec<&str> = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(vec!["5"], counts);
+    }
+}
@@ -1,11 +1,25 @@
 use bevy::{
     diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
-    platform::collections::HashMap,
+    platform::collections::{HashMap, HashSet},
     prelude::*,
 };
 
 use my_library::{egui::egui::Color32, *};
+
+mod auto_tune;
+mod ball_colors;
+mod benchmark;
+mod playground;
+use auto_tune::*;
+use ball_colors::*;
+use benchmark::*;
+use playground::*;
+
 pub const QUAD_TREE_DEPTH: usize = 4;
+/// Radius of a ball, half the size of its sprite
+const BALL_RADIUS: f32 = 4.0;
+/// Size of a cell of the spatial hash, several balls wide
+const SPATIAL_HASH_CELL: f32 = 32.0;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
 pub enum GamePhase {
@@ -22,9 +36,10 @@ struct BouncyElement;
 #[derive(Component)]
 struct Ball;
 
-#[derive(Resource, Default)]
+#[derive(Resource, Default, Debug, PartialEq)]
 struct CollisionTime {
-    time: u128,
+    /// Milliseconds the last collision check took
+    time: f64,
     checks: u32,
     fps: f64,
 }
@@ -33,8 +48,12 @@ fn main() -> anyhow::Result<()> {
     let mut app = App::new();
     add_phase!(app, GamePhase, GamePhase::Bouncing,
       start => [ setup ],
-      run => [ warp_at_edge, collisions, show_performance,
-        continual_parallax, physics_clock, sum_impulses, apply_velocity ],
+      run => [ (configure_playground, wake_balls, physics_clock, apply_gravity, sum_impulses,
+          apply_velocity, settle_balls, bounce_at_bounds, warp_at_edge, rebuild_quad_tree,
+          collisions).chain(),
+        show_performance, run_benchmark.after(collisions), benchmark_menu,
+        color_balls.after(collisions), auto_tune.after(show_performance), auto_tune_menu,
+        playground_menu, continual_parallax ],
       exit => [ cleanup::<BouncyElement> ]
     );
 
@@ -49,14 +68,26 @@ fn main() -> anyhow::Result<()> {
     .add_plugins(FrameTimeDiagnosticsPlugin { ..default() })
     .add_event::<Impulse>()
     .add_event::<PhysicsTick>()
+    .add_event::<OnCollision<Ball, Ball>>()
     .add_plugins(GameStatePlugin::new(
         GamePhase::MainMenu,
         GamePhase::Bouncing,
         GamePhase::GameOver,
     ))
     .add_plugins(RandomPlugin)
-    .add_plugins(AssetManager::new().add_image("green_ball", "green_ball.png")?)
-    .run();
+    .init_resource::<CollisionSettings>()
+    .init_resource::<BallColoring>()
+    .init_resource::<AutoTuner>()
+    .init_resource::<Playground>()
+    .insert_resource(Gravity(PLAYGROUND_GRAVITY))
+    .insert_resource(ScreenBounds::centered(Vec2::new(1024.0, 768.0)))
+    .add_plugins(AssetManager::new().add_image("green_ball", "green_ball.png")?);
+
+    // `--bench` runs the benchmark as soon as the balls start bouncing
+    if std::env::args().any(|arg| arg == "--bench") {
+        app.insert_resource(Benchmark::new(BenchmarkSchedule::default(), BENCHMARK_FILE));
+    }
+    app.run();
 
     Ok(())
 }
@@ -64,7 +95,7 @@ fn main() -> anyhow::Result<()> {
 fn spawn_bouncies(
     to_spawn: usize,
     commands: &mut Commands,
-    rng: &mut ResMut<RandomNumberGenerator>,
+    rng: &mut RandomNumberGenerator,
     assets: &AssetStore,
     loaded_assets: &LoadedAssets,
 ) {
@@ -81,30 +112,57 @@ fn spawn_bouncies(
             &loaded_assets,
             BouncyElement,
             Velocity::new(velocity.x, velocity.y, velocity.z),
-            AxisAlignedBoundingBox::new(8.0, 8.0),
+            PhysicsPosition::new(position.truncate()),
+            AxisAlignedBoundingBox::new(2.0 * BALL_RADIUS, 2.0 * BALL_RADIUS),
+            CollisionFlash::default(),
             Ball
         );
     }
 }
 
+/// Despawns up to `to_remove` balls, the most recently spawned first
+fn despawn_newest(to_remove: usize, commands: &mut Commands, balls: &Query<(Entity, Ref<Ball>)>) {
+    let mut newest: Vec<(Entity, u32)> = balls
+        .iter()
+        .map(|(entity, ball)| (entity, ball.added().get()))
+        .collect();
+    // Balls spawned in the same frame were added at the same tick
+    newest.sort_by_key(|(_, added)| std::cmp::Reverse(*added));
+    for (entity, _) in newest.into_iter().take(to_remove) {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Despawns all balls and starts the collision measurements over
+fn clear_balls(
+    commands: &mut Commands,
+    balls: &Query<(Entity, Ref<Ball>)>,
+    collision_time: &mut CollisionTime,
+) {
+    despawn_newest(balls.iter().count(), commands, balls);
+    *collision_time = CollisionTime::default();
+}
+
 fn setup(
     mut commands: Commands,
     mut rng: ResMut<RandomNumberGenerator>,
     assets: Res<AssetStore>,
     loaded_assets: Res<LoadedAssets>,
+    settings: Res<CollisionSettings>,
 ) {
     commands.spawn(Camera2d::default()).insert(BouncyElement);
     commands.insert_resource(CollisionTime::default());
     commands.insert_resource(StaticQuadTree::new(
         Vec2::new(1024.0, 768.0),
-        QUAD_TREE_DEPTH,
+        settings.quad_tree_depth,
     ));
     spawn_bouncies(1, &mut commands, &mut rng, &assets, &loaded_assets);
 }
 
-fn warp_at_edge(mut query: Query<&mut Transform, With<Ball>>) {
-    for mut transform in query.iter_mut() {
-        let pos = &mut transform.translation;
+/// Wraps the balls around the edges, unless they bounce off walls
+fn warp_at_edge(mut query: Query<&mut PhysicsPosition, (With<Ball>, Without<BounceAtBounds>)>) {
+    for mut position in query.iter_mut() {
+        let mut pos = position.end_frame;
         if pos.x < -512.0 {
             pos.x = 512.0;
         } else if pos.x > 512.0 {
@@ -116,6 +174,10 @@ fn warp_at_edge(mut query: Query<&mut Transform, With<Ball>>) {
         } else if pos.y > 384.0 {
             pos.y = -384.0;
         }
+        if pos != position.end_frame {
+            // Teleport without interpolating across the screen
+            *position = PhysicsPosition::new(pos);
+        }
     }
 }
 
@@ -126,8 +188,10 @@ fn show_performance(
     mut commands: Commands,
     mut rng: ResMut<RandomNumberGenerator>,
     assets: Res<AssetStore>,
-    query: Query<&Transform, With<Ball>>,
+    query: Query<(Entity, Ref<Ball>)>,
     loaded_assets: Res<LoadedAssets>,
+    mut settings: ResMut<CollisionSettings>,
+    mut coloring: ResMut<BallColoring>,
 ) {
     let n_balls = query.iter().count(); // count the number of balls currently simulated
     let fps = diagnostics // get diagnostical information about the average fps of recent frames
@@ -146,99 +210,486 @@ fn show_performance(
         ui.colored_label(color, &fps_text);
         ui.colored_label(
             color,
-            &format!("Collision Time: {} ms", collision_time.time),
+            &format!("Collision Time: {:.2} ms", collision_time.time),
         );
         ui.label(&format!("Collision Checks: {}", collision_time.checks));
         ui.label(&format!("# Balls: {n_balls}"));
+        let mut selected = *settings;
+        egui::egui::ComboBox::from_label("Broadphase")
+            .selected_text(selected.broadphase.name())
+            .show_ui(ui, |ui| {
+                for option in Broadphase::ALL {
+                    ui.selectable_value(&mut selected.broadphase, option, option.name());
+                }
+            });
+        ui.add_enabled(
+            selected.broadphase == Broadphase::QuadTree,
+            egui::egui::Slider::new(&mut selected.quad_tree_depth, 1..=8).text("Quadtree depth"),
+        );
+        // Only write real changes, which rebuild the quadtree
+        settings.set_if_neq(selected);
+        egui::egui::ComboBox::from_label("Ball colors")
+            .selected_text(coloring.name())
+            .show_ui(ui, |ui| {
+                for option in BallColoring::ALL {
+                    ui.selectable_value(coloring.as_mut(), option, option.name());
+                }
+            });
         if ui.button("Add Ball").clicked() {
             // add buttons to user interface for adding more balls
             println!(
-                "{n_balls}, {}, {}, {:.0}",
+                "{n_balls}, {:.2}, {}, {:.0}",
                 collision_time.time, collision_time.checks, collision_time.fps
             );
             spawn_bouncies(1, &mut commands, &mut rng, &assets, &loaded_assets);
         }
         if ui.button("Add 100 Balls").clicked() {
             println!(
-                "{n_balls}, {}, {}, {:.0}",
+                "{n_balls}, {:.2}, {}, {:.0}",
                 collision_time.time, collision_time.checks, collision_time.fps
             );
             spawn_bouncies(100, &mut commands, &mut rng, &assets, &loaded_assets);
         }
         if ui.button("Add 1000 Balls").clicked() {
             println!(
-                "{n_balls}, {}, {}, {:.0}",
+                "{n_balls}, {:.2}, {}, {:.0}",
                 collision_time.time, collision_time.checks, collision_time.fps
             );
             spawn_bouncies(1000, &mut commands, &mut rng, &assets, &loaded_assets);
         }
+        if ui.button("Remove 100 Balls").clicked() {
+            despawn_newest(100, &mut commands, &query);
+        }
+        if ui.button("Clear").clicked() {
+            clear_balls(&mut commands, &query, &mut collision_time);
+        }
     });
 }
 
-fn bounce_on_collision(
-    entity: Entity,
-    ball_a: Vec3,
-    ball_b: Vec3,
-    impulse: &mut EventWriter<Impulse>,
-) {
-    let a_to_b = (ball_a - ball_b).normalize(); // get the direction, normalized to length 1
-    impulse.write(Impulse {
-        target: entity,
-        amount: a_to_b / 8.0, // apply a force based on the direction
-        absolute: false,
-        source: 0,
-    });
+/// A ball's position and velocity
+type BallState = (Vec2, Vec2);
+
+/// Resolves an elastic collision of two balls of equal mass. The balls
+/// exchange the components of their velocities along the collision normal,
+/// and are pushed apart until they just touch. Balls that already move apart
+/// keep their velocities. Returns `None` for balls that don't overlap, e.g.
+/// when only their bounding boxes do.
+fn elastic_collision(a: BallState, b: BallState, radius: f32) -> Option<(BallState, BallState)> {
+    let ((mut pos_a, mut vel_a), (mut pos_b, mut vel_b)) = (a, b);
+    let offset = pos_b - pos_a;
+    let normal = offset.normalize_or(Vec2::X);
+    let penetration = 2.0 * radius - offset.length();
+    if penetration <= 0.0 {
+        return None;
+    }
+    pos_a -= normal * penetration / 2.0;
+    pos_b += normal * penetration / 2.0;
+    let approach = (vel_a - vel_b).dot(normal);
+    if approach > 0.0 {
+        vel_a -= normal * approach;
+        vel_b += normal * approach;
+    }
+    Some(((pos_a, vel_a), (pos_b, vel_b)))
 }
 
+/// Resolves a collision of a ball with a sleeping one, which doesn't move.
+/// The ball is pushed out until they just touch, and keeps `restitution` of
+/// its velocity along the collision normal. Returns `None` for balls that
+/// don't overlap.
+fn resting_collision(
+    ball: BallState,
+    resting: Vec2,
+    radius: f32,
+    restitution: f32,
+) -> Option<BallState> {
+    let (mut position, mut velocity) = ball;
+    let offset = position - resting;
+    let normal = offset.normalize_or(Vec2::Y);
+    let penetration = 2.0 * radius - offset.length();
+    if penetration <= 0.0 {
+        return None;
+    }
+    position += normal * penetration;
+    let approach = velocity.dot(normal);
+    if approach < 0.0 {
+        velocity -= normal * approach * (1.0 + restitution);
+    }
+    Some((position, velocity))
+}
+
+/// Ways of finding the pairs of balls that may overlap, before checking them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Broadphase {
+    /// Checks every ball against every other ball
+    Naive,
+    /// Checks the balls sharing a node of the quadtree
+    #[default]
+    QuadTree,
+    /// Checks the balls sharing a cell of a uniform grid
+    SpatialHash,
+}
+
+impl Broadphase {
+    const ALL: [Self; 3] = [
+        Broadphase::Naive,
+        Broadphase::QuadTree,
+        Broadphase::SpatialHash,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Broadphase::Naive => "Naive O(n²)",
+            Broadphase::QuadTree => "Quadtree",
+            Broadphase::SpatialHash => "Spatial hash",
+        }
+    }
+}
+
+/// Resource with the collision settings, changed in the performance window
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+struct CollisionSettings {
+    broadphase: Broadphase,
+    quad_tree_depth: usize,
+}
+
+impl Default for CollisionSettings {
+    fn default() -> Self {
+        Self {
+            broadphase: Broadphase::default(),
+            quad_tree_depth: QUAD_TREE_DEPTH,
+        }
+    }
+}
+
+/// Rebuilds the quadtree when the settings change
+fn rebuild_quad_tree(settings: Res<CollisionSettings>, mut commands: Commands) {
+    if settings.is_changed() {
+        commands.insert_resource(StaticQuadTree::new(
+            Vec2::new(1024.0, 768.0),
+            settings.quad_tree_depth,
+        ));
+    }
+}
+
+/// Finds the pairs of overlapping balls with the broadphase. Each pair is
+/// listed once, ordered by entity. Returns the pairs and the number of
+/// overlap checks.
+fn overlapping_pairs(
+    broadphase: Broadphase,
+    balls: &[(Entity, Rect2D)],
+    quad_tree: &StaticQuadTree,
+    buckets: &mut QuadTreeBuckets,
+) -> (HashSet<(Entity, Entity)>, u32) {
+    let mut n = 0;
+    let mut pairs = HashSet::new();
+    let mut check = |a: usize, b: usize| {
+        n += 1;
+        let ((entity_a, box_a), (entity_b, box_b)) = (balls[a], balls[b]);
+        if box_a.intersect(&box_b) {
+            pairs.insert((entity_a.min(entity_b), entity_a.max(entity_b)));
+        }
+    };
+    match broadphase {
+        Broadphase::Naive => {
+            for a in 0..balls.len() {
+                for b in a + 1..balls.len() {
+                    check(a, b);
+                }
+            }
+        }
+        Broadphase::QuadTree => {
+            // A pair may be found from both of its balls, but is only
+            // listed once
+            buckets.clear();
+            let index: HashMap<Entity, usize> = balls
+                .iter()
+                .enumerate()
+                .map(|(i, (entity, bbox))| {
+                    buckets.insert(quad_tree, *entity, bbox);
+                    (*entity, i)
+                })
+                .collect();
+            for (a, (entity, bbox)) in balls.iter().enumerate() {
+                for entity_b in buckets.node_entities(quad_tree.smallest_node(bbox)) {
+                    if entity_b != entity {
+                        check(a, index[entity_b]);
+                    }
+                }
+            }
+        }
+        Broadphase::SpatialHash => {
+            let mut cells: HashMap<IVec2, Vec<usize>> = HashMap::new();
+            for (i, (_, bbox)) in balls.iter().enumerate() {
+                let min = (bbox.min() / SPATIAL_HASH_CELL).floor().as_ivec2();
+                let max = (bbox.max() / SPATIAL_HASH_CELL).floor().as_ivec2();
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        cells.entry(IVec2::new(x, y)).or_default().push(i);
+                    }
+                }
+            }
+            for cell in cells.values() {
+                for (j, a) in cell.iter().enumerate() {
+                    for b in &cell[j + 1..] {
+                        check(*a, *b);
+                    }
+                }
+            }
+        }
+    }
+    (pairs, n)
+}
+
+/// Components of a ball taking part in the collisions
+type BallBody<'a> = (
+    Entity,
+    &'a mut PhysicsPosition,
+    &'a mut Velocity,
+    &'a AxisAlignedBoundingBox,
+    Has<Sleeping>,
+);
+
 fn collisions(
     mut collision_time: ResMut<CollisionTime>,
-    query: Query<(Entity, &Transform, &AxisAlignedBoundingBox)>,
-    mut impulse: EventWriter<Impulse>,
+    mut query: Query<BallBody>,
     quad_tree: Res<StaticQuadTree>,
+    settings: Res<CollisionSettings>,
+    mut buckets: Local<QuadTreeBuckets>,
+    mut hits: EventWriter<OnCollision<Ball, Ball>>,
 ) {
     // Start the clock
     let now = std::time::Instant::now();
 
-    let mut spatial_index: HashMap<usize, Vec<(Entity, Rect2D)>> = HashMap::new();
-
-    let tree_positions: Vec<(Entity, usize, Rect2D)> = query
+    let balls: Vec<(Entity, Rect2D)> = query
         .iter()
-        .map(|(entity, transform, bbox)| {
-            let bbox = bbox.as_rect(transform.translation.truncate());
-            let node = quad_tree.smallest_node(&bbox);
-            for in_node in quad_tree.intersecting_nodes(&bbox) {
-                if let Some(contents) = spatial_index.get_mut(&in_node) {
-                    contents.push((entity, bbox));
-                } else {
-                    spatial_index.insert(in_node, vec![(entity, bbox)]);
-                }
-            }
-
-            (entity, node, bbox)
-        })
+        .map(|(entity, position, _, bbox, _)| (entity, bbox.as_rect(position.current())))
         .collect();
+    let (pairs, n) = overlapping_pairs(settings.broadphase, &balls, &quad_tree, &mut buckets);
 
-    // AABB with spatial quadrants
-    let mut n = 0;
-
-    for (entity, node, box_a) in tree_positions {
-        if let Some(entities_here) = spatial_index.get(&node) {
-            if let Some((entity_b, _)) = entities_here
-                .iter()
-                .filter(|(entity_b, _)| *entity_b != entity)
-                .find(|(_, box_b)| {
-                    n += 1;
-                    box_a.intersect(box_b)
-                })
-            {
-                let (_, ball_a, _) = query.get(entity).unwrap();
-                let (_, ball_b, _) = query.get(*entity_b).unwrap();
-                bounce_on_collision(entity, ball_a.translation, ball_b.translation, &mut impulse);
+    for (entity_a, entity_b) in pairs {
+        let [
+            (_, mut pos_a, mut vel_a, _, asleep_a),
+            (_, mut pos_b, mut vel_b, _, asleep_b),
+        ] = query.get_many_mut([entity_a, entity_b]).unwrap();
+        if asleep_a && asleep_b {
+            // Piled up balls stay where they are
+            continue;
+        }
+        if asleep_a || asleep_b {
+            let (mut position, mut velocity, resting) = if asleep_a {
+                (pos_b, vel_b, pos_a.current())
+            } else {
+                (pos_a, vel_a, pos_b.current())
+            };
+            if let Some((new_pos, new_vel)) = resting_collision(
+                (position.current(), velocity.0.truncate()),
+                resting,
+                BALL_RADIUS,
+                RESTITUTION,
+            ) {
+                position.end_frame = new_pos;
+                velocity.0 = new_vel.extend(0.0);
+                hits.write(OnCollision::new(entity_a, entity_b));
             }
+            continue;
         }
+        // Only the bounding boxes may overlap
+        let Some(((new_pos_a, new_vel_a), (new_pos_b, new_vel_b))) = elastic_collision(
+            (pos_a.current(), vel_a.0.truncate()),
+            (pos_b.current(), vel_b.0.truncate()),
+            BALL_RADIUS,
+        ) else {
+            continue;
+        };
+        hits.write(OnCollision::new(entity_a, entity_b));
+        pos_a.end_frame = new_pos_a;
+        pos_b.end_frame = new_pos_b;
+        vel_a.0 = new_vel_a.extend(0.0);
+        vel_b.0 = new_vel_b.extend(0.0);
     }
 
     // Store the time result
-    collision_time.time = now.elapsed().as_millis();
+    collision_time.time = now.elapsed().as_secs_f64() * 1000.0;
     collision_time.checks = n;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn kinetic_energy(velocities: &[Vec2]) -> f32 {
+        velocities.iter().map(|v| 0.5 * v.length_squared()).sum()
+    }
+
+    #[test]
+    fn head_on_collisions_swap_velocities() {
+        let a = (Vec2::new(0.0, 0.0), Vec2::new(1.5, 0.0));
+        let b = (Vec2::new(6.0, 0.0), Vec2::new(-0.5, 0.0));
+        let ((pos_a, vel_a), (pos_b, vel_b)) = elastic_collision(a, b, BALL_RADIUS).unwrap();
+
+        assert_eq!((b.1, a.1), (vel_a, vel_b));
+        assert!((kinetic_energy(&[a.1, b.1]) - kinetic_energy(&[vel_a, vel_b])).abs() < 1e-5);
+        // The balls are pushed apart evenly until they touch
+        assert!((pos_b.x - pos_a.x - 2.0 * BALL_RADIUS).abs() < 1e-5);
+        assert!((pos_a.x + pos_b.x - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn glancing_collisions_conserve_momentum_and_energy() {
+        let a = (Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.3));
+        let b = (Vec2::new(5.0, 4.0), Vec2::new(-0.2, -0.7));
+        let ((_, vel_a), (_, vel_b)) = elastic_collision(a, b, BALL_RADIUS).unwrap();
+
+        assert!(((a.1 + b.1) - (vel_a + vel_b)).length() < 1e-5);
+        assert!((kinetic_energy(&[a.1, b.1]) - kinetic_energy(&[vel_a, vel_b])).abs() < 1e-5);
+
+        // Resolving the pair again doesn't bounce it back together
+        let ((_, again_a), (_, again_b)) =
+            elastic_collision((a.0, vel_a), (b.0, vel_b), BALL_RADIUS).unwrap();
+        assert_eq!((vel_a, vel_b), (again_a, again_b));
+    }
+
+    #[test]
+    fn diagonal_near_misses_dont_collide() {
+        // The bounding boxes overlap, but the balls are 9.9 apart
+        let a = (Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0));
+        let b = (Vec2::new(7.0, 7.0), Vec2::new(-1.0, -1.0));
+        assert_eq!(None, elastic_collision(a, b, BALL_RADIUS));
+        assert_eq!(None, resting_collision(a, b.0, BALL_RADIUS, RESTITUTION));
+    }
+
+    #[test]
+    fn all_broadphases_find_the_same_pairs() {
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let balls: Vec<(Entity, Rect2D)> = (0..300)
+            .map(|i| {
+                let position = Vec2::new(rng.range(-500.0..500.0), rng.range(-370.0..370.0));
+                let bbox = AxisAlignedBoundingBox::new(2.0 * BALL_RADIUS, 2.0 * BALL_RADIUS);
+                (Entity::from_raw(i), bbox.as_rect(position))
+            })
+            .collect();
+        let quad_tree = StaticQuadTree::new(Vec2::new(1024.0, 768.0), QUAD_TREE_DEPTH);
+        let mut buckets = QuadTreeBuckets::default();
+
+        let (naive, naive_checks) =
+            overlapping_pairs(Broadphase::Naive, &balls, &quad_tree, &mut buckets);
+        assert!(naive.len() > 5, "{}", naive.len());
+        assert_eq!(300 * 299 / 2, naive_checks);
+        for broadphase in [Broadphase::QuadTree, Broadphase::SpatialHash] {
+            let (pairs, checks) = overlapping_pairs(broadphase, &balls, &quad_tree, &mut buckets);
+            assert_eq!(naive, pairs, "{broadphase:?}");
+            assert!(checks < naive_checks, "{broadphase:?}");
+        }
+    }
+
+    #[test]
+    fn changing_the_depth_rebuilds_the_quad_tree() {
+        let mut app = App::new();
+        app.init_resource::<CollisionSettings>()
+            .add_systems(Update, rebuild_quad_tree);
+        app.update();
+        // The smallest node of a ball is deeper in deeper trees
+        let ball = AxisAlignedBoundingBox::new(2.0, 2.0).as_rect(Vec2::new(100.0, 100.0));
+        let node = |app: &App| {
+            app.world()
+                .resource::<StaticQuadTree>()
+                .smallest_node(&ball)
+        };
+        let shallow = node(&app);
+        assert_eq!(
+            StaticQuadTree::new(Vec2::new(1024.0, 768.0), QUAD_TREE_DEPTH).smallest_node(&ball),
+            shallow
+        );
+
+        app.world_mut()
+            .resource_mut::<CollisionSettings>()
+            .quad_tree_depth = 8;
+        app.update();
+        let deep = node(&app);
+        assert!(deep > shallow);
+        assert_eq!(
+            StaticQuadTree::new(Vec2::new(1024.0, 768.0), 8).smallest_node(&ball),
+            deep
+        );
+    }
+
+    #[test]
+    fn removing_balls_starts_with_the_newest() {
+        let mut world = World::new();
+        world.insert_resource(CollisionTime {
+            time: 2.5,
+            checks: 40,
+            fps: 60.0,
+        });
+        let old: Vec<Entity> = (0..3).map(|_| world.spawn(Ball).id()).collect();
+        world.increment_change_tick();
+        for _ in 0..2 {
+            world.spawn(Ball);
+        }
+        world
+            .run_system_once(
+                |mut commands: Commands, balls: Query<(Entity, Ref<Ball>)>| {
+                    despawn_newest(3, &mut commands, &balls);
+                },
+            )
+            .unwrap();
+        let mut balls = world.query_filtered::<Entity, With<Ball>>();
+        let left: Vec<Entity> = balls.iter(&world).collect();
+        assert_eq!(2, left.len());
+        assert!(left.iter().all(|ball| old.contains(ball)));
+
+        world
+            .run_system_once(
+                |mut commands: Commands,
+                 balls: Query<(Entity, Ref<Ball>)>,
+                 mut collision_time: ResMut<CollisionTime>| {
+                    clear_balls(&mut commands, &balls, &mut collision_time);
+                },
+            )
+            .unwrap();
+        assert_eq!(0, balls.iter(&world).count());
+        assert_eq!(&CollisionTime::default(), world.resource::<CollisionTime>());
+    }
+
+    #[test]
+    fn colliding_balls_are_resolved_once() {
+        let mut app = App::new();
+        app.init_resource::<CollisionTime>()
+            .init_resource::<CollisionSettings>()
+            .add_event::<OnCollision<Ball, Ball>>()
+            .init_resource::<BallColoring>()
+            .insert_resource(StaticQuadTree::new(
+                Vec2::new(1024.0, 768.0),
+                QUAD_TREE_DEPTH,
+            ))
+            .add_systems(Update, collisions);
+        let mut ball = |position: Vec2, vx: f32| {
+            app.world_mut()
+                .spawn((
+                    PhysicsPosition::new(position),
+                    Velocity::new_2d(vx, 0.0),
+                    AxisAlignedBoundingBox::new(2.0 * BALL_RADIUS, 2.0 * BALL_RADIUS),
+                ))
+                .id()
+        };
+        let a = ball(Vec2::new(10.0, 10.0), 1.0);
+        let b = ball(Vec2::new(17.0, 10.0), -1.0);
+        // A diagonal near miss, whose bounding boxes overlap
+        let c = ball(Vec2::new(100.0, 100.0), 1.0);
+        let d = ball(Vec2::new(107.0, 107.0), -1.0);
+        app.update();
+
+        let velocity = |entity| app.world().get::<Velocity>(entity).unwrap().0.x;
+        assert_eq!((-1.0, 1.0), (velocity(a), velocity(b)));
+        assert_eq!((1.0, -1.0), (velocity(c), velocity(d)));
+        // Only the balls that touched report a hit
+        let hits: Vec<_> = app
+            .world()
+            .resource::<Events<OnCollision<Ball, Ball>>>()
+            .iter_current_update_events()
+            .map(|hit| (hit.entity_a, hit.entity_b))
+            .collect();
+        assert_eq!(vec![(a.min(b), a.max(b))], hits);
+    }
+}
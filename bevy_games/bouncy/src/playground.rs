@@ -0,0 +1,353 @@
+//! Playground modes, exercising more of the physics than wrapping balls
+
+use super::Ball;
+use bevy::prelude::*;
+use my_library::{ApplyGravity, BounceAtBounds, PhysicsTick, Restitution, Velocity, egui};
+
+/// Velocity the balls gain downwards per physics tick in the gravity well
+pub const PLAYGROUND_GRAVITY: f32 = 0.05;
+/// Share of their speed the balls keep when bouncing off the floor, walls,
+/// or sleeping balls in the gravity well
+pub const RESTITUTION: f32 = 0.6;
+/// The balls keep this share of their velocity per physics tick in the
+/// gravity well, so they come to rest
+const DRAG: f32 = 0.98;
+/// Balls slower than this may fall asleep
+const SLEEP_SPEED: f32 = 0.2;
+/// Physics ticks a ball has to stay slow, before it falls asleep
+const SLEEP_TICKS: u32 = 30;
+
+/// What the balls do, picked in the playground window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlaygroundMode {
+    /// Balls leaving the screen come back on the other side
+    #[default]
+    Wrap,
+    /// Balls bounce off the edges of the screen
+    BounceOffWalls,
+    /// Balls fall to the floor and pile up
+    GravityWell,
+}
+
+impl PlaygroundMode {
+    pub const ALL: [Self; 3] = [
+        PlaygroundMode::Wrap,
+        PlaygroundMode::BounceOffWalls,
+        PlaygroundMode::GravityWell,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaygroundMode::Wrap => "Wrap",
+            PlaygroundMode::BounceOffWalls => "Bounce off walls",
+            PlaygroundMode::GravityWell => "Gravity well",
+        }
+    }
+}
+
+/// Resource with the playground settings. Changing them reconfigures the
+/// balls on the fly.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Playground {
+    pub mode: PlaygroundMode,
+    /// Whether resting balls in the gravity well fall asleep
+    pub sleep: bool,
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Self {
+            mode: PlaygroundMode::default(),
+            sleep: true,
+        }
+    }
+}
+
+/// Marks a ball that came to rest. It neither moves nor falls, and other
+/// balls bounce off it like off a wall.
+#[derive(Component, Debug, Default)]
+pub struct Sleeping;
+
+/// Physics ticks in a row a ball in the gravity well was slower than
+/// [`SLEEP_SPEED`]
+#[derive(Component, Debug, Default)]
+pub struct Resting(u32);
+
+/// Gives the balls the components of the playground mode. All balls are
+/// reconfigured, and woken up, when the settings change; otherwise only the
+/// new balls are.
+pub fn configure_playground(
+    playground: Res<Playground>,
+    balls: Query<Entity, With<Ball>>,
+    new_balls: Query<Entity, Added<Ball>>,
+    mut commands: Commands,
+) {
+    let changed: Vec<Entity> = if playground.is_changed() {
+        balls.iter().collect()
+    } else {
+        new_balls.iter().collect()
+    };
+    for ball in changed {
+        let mut ball = commands.entity(ball);
+        ball.remove::<(BounceAtBounds, ApplyGravity, Restitution, Resting, Sleeping)>();
+        match playground.mode {
+            PlaygroundMode::Wrap => {}
+            PlaygroundMode::BounceOffWalls => {
+                ball.insert(BounceAtBounds);
+            }
+            PlaygroundMode::GravityWell => {
+                ball.insert((
+                    BounceAtBounds,
+                    ApplyGravity,
+                    Restitution(RESTITUTION),
+                    Resting::default(),
+                ));
+            }
+        }
+    }
+}
+
+/// Slows the balls in the gravity well down on each physics tick, and puts
+/// the balls to sleep that stayed slow for [`SLEEP_TICKS`]
+pub fn settle_balls(
+    mut tick: EventReader<PhysicsTick>,
+    playground: Res<Playground>,
+    mut balls: Query<(Entity, &mut Velocity, &mut Resting), Without<Sleeping>>,
+    mut commands: Commands,
+) {
+    for _tick in tick.read() {
+        for (entity, mut velocity, mut resting) in balls.iter_mut() {
+            velocity.0 *= DRAG;
+            if !playground.sleep {
+                continue;
+            }
+            if velocity.0.length() < SLEEP_SPEED {
+                resting.0 += 1;
+            } else {
+                resting.0 = 0;
+            }
+            // Compared for equality, so the ball falls asleep once, even if
+            // more ticks pass in this frame
+            if resting.0 == SLEEP_TICKS {
+                velocity.0 = Vec3::ZERO;
+                commands
+                    .entity(entity)
+                    .remove::<ApplyGravity>()
+                    .insert(Sleeping);
+            }
+        }
+    }
+}
+
+/// Wakes the sleeping balls up when balls are removed, as they may have
+/// rested on them
+pub fn wake_balls(
+    mut removed: RemovedComponents<Ball>,
+    sleeping: Query<Entity, With<Sleeping>>,
+    mut commands: Commands,
+) {
+    if removed.read().count() == 0 {
+        return;
+    }
+    for ball in sleeping.iter() {
+        commands
+            .entity(ball)
+            .remove::<Sleeping>()
+            .insert((ApplyGravity, Resting::default()));
+    }
+}
+
+/// Picks the playground mode
+pub fn playground_menu(
+    mut playground: ResMut<Playground>,
+    sleeping: Query<(), With<Sleeping>>,
+    mut egui_context: egui::EguiContexts,
+) {
+    egui::egui::Window::new("Playground").show(egui_context.ctx_mut(), |ui| {
+        let mut selected = *playground;
+        egui::egui::ComboBox::from_label("Mode")
+            .selected_text(selected.mode.name())
+            .show_ui(ui, |ui| {
+                for option in PlaygroundMode::ALL {
+                    ui.selectable_value(&mut selected.mode, option, option.name());
+                }
+            });
+        ui.add_enabled(
+            selected.mode == PlaygroundMode::GravityWell,
+            egui::egui::Checkbox::new(&mut selected.sleep, "Resting balls sleep"),
+        );
+        // Only write real changes, which reconfigure all balls
+        playground.set_if_neq(selected);
+        if playground.mode == PlaygroundMode::GravityWell {
+            ui.label(format!("Sleeping balls: {}", sleeping.iter().count()));
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        BALL_RADIUS, CollisionSettings, CollisionTime, QUAD_TREE_DEPTH, collisions, warp_at_edge,
+    };
+    use my_library::{
+        AxisAlignedBoundingBox, Gravity, IntegrationMethod, MaxPhysicsDelta, OnCollision,
+        PhysicsPaused, PhysicsPosition, RandomNumberGenerator, ScreenBounds, StaticQuadTree,
+        apply_gravity, apply_velocity, bounce_at_bounds, physics_clock,
+    };
+    use std::time::Duration;
+
+    fn playground_app(mode: PlaygroundMode) -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .init_resource::<IntegrationMethod>()
+            .init_resource::<CollisionTime>()
+            .init_resource::<CollisionSettings>()
+            .insert_resource(Gravity(PLAYGROUND_GRAVITY))
+            .insert_resource(ScreenBounds::centered(Vec2::new(1024.0, 768.0)))
+            .insert_resource(StaticQuadTree::new(
+                Vec2::new(1024.0, 768.0),
+                QUAD_TREE_DEPTH,
+            ))
+            .insert_resource(Playground {
+                mode,
+                ..Playground::default()
+            })
+            .add_event::<PhysicsTick>()
+            .add_event::<OnCollision<Ball, Ball>>()
+            .add_systems(
+                Update,
+                (
+                    configure_playground,
+                    wake_balls,
+                    physics_clock,
+                    apply_gravity,
+                    apply_velocity,
+                    settle_balls,
+                    bounce_at_bounds,
+                    warp_at_edge,
+                    collisions,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    fn spawn_ball(app: &mut App, position: Vec2, velocity: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                Ball,
+                Transform::from_translation(position.extend(0.0)),
+                PhysicsPosition::new(position),
+                Velocity::new_2d(velocity.x, velocity.y),
+                AxisAlignedBoundingBox::new(2.0 * BALL_RADIUS, 2.0 * BALL_RADIUS),
+            ))
+            .id()
+    }
+
+    /// Lets one physics tick pass
+    fn tick(app: &mut App) {
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(33));
+        app.update();
+    }
+
+    #[test]
+    fn walls_reflect_the_balls() {
+        let mut app = playground_app(PlaygroundMode::BounceOffWalls);
+        let right = spawn_ball(&mut app, Vec2::new(505.0, 0.0), Vec2::new(2.0, 0.5));
+        let top = spawn_ball(&mut app, Vec2::new(0.0, 378.0), Vec2::new(-0.5, 3.0));
+        let velocity = |app: &App, ball| app.world().get::<Velocity>(ball).unwrap().0.truncate();
+        let position =
+            |app: &App, ball| app.world().get::<PhysicsPosition>(ball).unwrap().current();
+
+        for _ in 0..3 {
+            tick(&mut app);
+        }
+        // Elastic bounces only flip the velocity into the wall
+        assert_eq!(Vec2::new(-2.0, 0.5), velocity(&app, right));
+        assert_eq!(Vec2::new(-0.5, -3.0), velocity(&app, top));
+        assert!(position(&app, right).x <= 512.0 - BALL_RADIUS);
+        assert!(position(&app, top).y <= 384.0 - BALL_RADIUS);
+
+        // Switching back to wrapping lets the balls leave the screen, and come
+        // back on the other side
+        app.world_mut().resource_mut::<Playground>().mode = PlaygroundMode::Wrap;
+        let leaving = spawn_ball(&mut app, Vec2::new(505.0, 0.0), Vec2::new(2.0, 0.0));
+        for _ in 0..5 {
+            tick(&mut app);
+        }
+        assert!(
+            position(&app, leaving).x < -500.0,
+            "{}",
+            position(&app, leaving)
+        );
+        assert!(app.world().get::<BounceAtBounds>(right).is_none());
+    }
+
+    #[test]
+    fn balls_in_the_gravity_well_fall_asleep() {
+        let mut app = playground_app(PlaygroundMode::GravityWell);
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let balls: Vec<Entity> = (0..60)
+            .map(|_| {
+                let position = Vec2::new(rng.range(-500.0..500.0), rng.range(-370.0..370.0));
+                let velocity = Vec2::new(rng.range(-1.0..1.0), rng.range(-1.0..1.0));
+                spawn_ball(&mut app, position, velocity)
+            })
+            .collect();
+        let mut sleeping = app.world_mut().query_filtered::<(), With<Sleeping>>();
+
+        for _ in 0..1500 {
+            tick(&mut app);
+        }
+        let asleep = sleeping.iter(app.world()).count();
+        assert!(
+            asleep * 10 >= balls.len() * 9,
+            "{asleep} of {}",
+            balls.len()
+        );
+        // The balls rest on the floor, or on each other
+        for ball in &balls {
+            let position = app.world().get::<PhysicsPosition>(*ball).unwrap().current();
+            assert!(position.y >= -384.0 + BALL_RADIUS - 0.01, "{position}");
+        }
+
+        // Without sleeping, switching the setting wakes the balls up
+        app.world_mut().resource_mut::<Playground>().sleep = false;
+        tick(&mut app);
+        assert_eq!(0, sleeping.iter(app.world()).count());
+    }
+
+    #[test]
+    fn removing_a_ball_wakes_the_balls_resting_on_it() {
+        let mut app = playground_app(PlaygroundMode::GravityWell);
+        let floor = -384.0 + BALL_RADIUS;
+        let below = spawn_ball(&mut app, Vec2::new(0.0, floor), Vec2::ZERO);
+        let above = spawn_ball(
+            &mut app,
+            Vec2::new(0.0, floor + 2.0 * BALL_RADIUS),
+            Vec2::ZERO,
+        );
+        let asleep = |app: &App, ball| app.world().get::<Sleeping>(ball).is_some();
+        for _ in 0..100 {
+            tick(&mut app);
+        }
+        assert!(asleep(&app, below) && asleep(&app, above));
+
+        app.world_mut().despawn(below);
+        tick(&mut app);
+        assert!(!asleep(&app, above));
+        assert!(app.world().get::<ApplyGravity>(above).is_some());
+        for _ in 0..100 {
+            tick(&mut app);
+        }
+        // The ball fell to the floor, and went to sleep again
+        let position = app.world().get::<PhysicsPosition>(above).unwrap().current();
+        assert!((position.y - floor).abs() < 1.0, "{position}");
+        assert!(asleep(&app, above));
+    }
+}
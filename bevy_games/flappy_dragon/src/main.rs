@@ -1,14 +1,164 @@
-use bevy::prelude::*;
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
 use my_library::*;
 
+/// New wall columns are built at least this far right, off-screen
+const WALL_START_X: f32 = 512.0;
+/// Wall columns left of this are off-screen and replaced
+const WALL_END_X: f32 = -530.0;
+
 #[derive(Component)]
 struct Flappy;
 
 #[derive(Component)]
 struct Obstacle; //(3)
 
+/// All obstacles of one wall column share the same id, so passing the
+/// wall scores only once
+#[derive(Component, Clone, Copy)]
+struct WallSet {
+    id: u32,
+}
+
+/// Score of the current game, kept for the game-over screen
+#[derive(Resource, Default)]
+struct Score {
+    /// Number of walls passed
+    points: u32,
+    /// Ids of the wall sets that were passed already
+    passed: HashSet<u32>,
+    /// Number of wall sets built, used as id for the next one
+    walls_built: u32,
+}
+
+impl Score {
+    /// Returns a new id for the next wall set
+    fn next_wall(&mut self) -> WallSet {
+        self.walls_built += 1;
+        WallSet {
+            id: self.walls_built,
+        }
+    }
+}
+
+/// Controls how the walls get harder as the score increases. The gap
+/// shrinks, the walls speed up and the gap moves over a wider range, each up
+/// to a cap.
+#[derive(Resource, Clone, Copy)]
+struct Difficulty {
+    /// Number of wall columns alive at the same time
+    columns: u32,
+    /// Horizontal distance between two wall columns
+    column_spacing: f32,
+    /// Tiles above and below the gap center that are open at the start
+    start_gap: i32,
+    /// The gap never gets smaller than this
+    min_gap: i32,
+    /// Walls to pass until the gap shrinks by one tile
+    walls_per_gap_step: u32,
+    /// Speed of the walls at the start, in units per physics tick
+    start_speed: f32,
+    /// Additional speed for each wall passed
+    speed_per_wall: f32,
+    /// The walls never get faster than this
+    max_speed: f32,
+    /// Maximum distance of the gap center from the middle at the start, in tiles
+    start_range: i32,
+    /// Walls to pass until the range grows by one tile
+    walls_per_range_step: u32,
+    /// The range never gets wider than this
+    max_range: i32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            column_spacing: 350.0,
+            start_gap: 4,
+            min_gap: 2,
+            walls_per_gap_step: 5,
+            start_speed: 8.0,
+            speed_per_wall: 0.5,
+            max_speed: 16.0,
+            start_range: 5,
+            walls_per_range_step: 3,
+            max_range: 8,
+        }
+    }
+}
+
+impl Difficulty {
+    /// Open tiles above and below the gap center
+    fn gap(&self, score: u32) -> i32 {
+        (self.start_gap - (score / self.walls_per_gap_step) as i32).max(self.min_gap)
+    }
+
+    /// Speed of new walls
+    fn speed(&self, score: u32) -> f32 {
+        (self.start_speed + self.speed_per_wall * score as f32).min(self.max_speed)
+    }
+
+    /// Maximum distance of the gap center from the middle
+    fn gap_range(&self, score: u32) -> i32 {
+        (self.start_range + (score / self.walls_per_range_step) as i32).min(self.max_range)
+    }
+
+    /// Random position of the gap center for the current score
+    fn gap_y(&self, score: u32, rng: &mut RandomNumberGenerator) -> i32 {
+        let range = self.gap_range(score);
+        rng.range(-range..range)
+    }
+
+    /// Builds a wall column at `x` with random gap placement for the current
+    /// score
+    fn build_wall(
+        &self,
+        score: &mut Score,
+        commands: &mut Commands,
+        assets: &AssetStore,
+        loaded_assets: &LoadedAssets,
+        rng: &mut RandomNumberGenerator,
+        x: f32,
+    ) {
+        let wall_set = score.next_wall();
+        build_wall(
+            commands,
+            assets,
+            loaded_assets,
+            x,
+            self.gap_y(score.points, rng),
+            self.gap(score.points),
+            self.speed(score.points),
+            wall_set,
+        );
+    }
+}
+
+/// Finds the wall columns, that left the screen on the left. Returns the
+/// wall set of each, together with the x position of the column replacing
+/// it: one spacing right of the rightmost column, but at least off-screen.
+fn replace_columns(columns: &HashMap<u32, f32>, spacing: f32) -> Vec<(u32, f32)> {
+    let mut rightmost = columns.values().copied().fold(f32::MIN, f32::max);
+    let mut expired: Vec<(u32, f32)> = columns
+        .iter()
+        .filter(|(_, x)| **x < WALL_END_X)
+        .map(|(id, x)| (*id, *x))
+        .collect();
+    expired.sort_by(|a, b| a.1.total_cmp(&b.1));
+    expired
+        .into_iter()
+        .map(|(id, _)| {
+            rightmost = (rightmost + spacing).max(WALL_START_X);
+            (id, rightmost)
+        })
+        .collect()
+}
+
 /// Marker component denoting all entities spawned inside GamePhase::Flapping
-#[derive(Component)]
+#[derive(Component, Clone)]
 struct FlappyElement;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
@@ -25,12 +175,18 @@ fn main() -> anyhow::Result<()> {
 
     add_phase!(app, GamePhase, GamePhase::Flapping,
         start => [ setup ],
-        run => [ flap, clamp, move_walls, hit_wall, cycle_animations, continual_parallax,
+        run => [ flap, clamp, move_walls, hit_wall, crash_sound, cycle_animations, continual_parallax,
                  physics_clock, sum_impulses, apply_gravity, apply_velocity,
-                 check_collisions::<Flappy, Obstacle>, rotate],
+                 check_collisions::<Flappy, Obstacle>, rotate, score_walls, score_display],
         exit => [ cleanup::<FlappyElement> ]
     );
 
+    add_phase!(app, GamePhase, GamePhase::GameOver,
+        start => [],
+        run => [ final_score ],
+        exit => []
+    );
+
     app.add_plugins(DefaultPlugins.set(WindowPlugin {
         primary_window: Some(Window {
             //(5)
@@ -58,6 +214,11 @@ fn main() -> anyhow::Result<()> {
             .add_image("bg_mid", "rocky-nowater-mid.png")?
             .add_image("bg_close", "rocky-nowater-close.png")?,
     )
+    .add_plugins(SpawnOnEnter::new(GamePhase::Flapping).spawn(
+        "bg_static",
+        Vec3::new(0.0, 0.0, 1.0), // static background layer
+        FlappyElement,
+    ))
     .insert_resource(
         Animations::new()
             .with_animation(
@@ -93,6 +254,7 @@ fn main() -> anyhow::Result<()> {
             ),
     )
     .add_event::<OnCollision<Flappy, Obstacle>>()
+    .init_resource::<Difficulty>()
     .run();
 
     Ok(())
@@ -103,6 +265,8 @@ fn setup(
     assets: Res<AssetStore>,
     loaded_assets: AssetResource,
     mut rng: ResMut<RandomNumberGenerator>, //(7)
+    images: Res<Assets<Image>>,
+    difficulty: Res<Difficulty>,
 ) {
     commands.spawn(Camera2d::default()).insert(FlappyElement); //(9)
 
@@ -122,85 +286,43 @@ fn setup(
         PhysicsPosition::new(Vec2::new(-490.0, 0.0))
     );
 
-    let width = 1280.0;
-    spawn_image!(
-        assets,
-        commands,
-        "bg_static",
-        0.0,
-        0.0,
-        1.0, // static background layer
+    // Viewport width, the parallax layers must cover
+    let width = 1024.0;
+    spawn_parallax_layer(
+        &mut commands,
+        &assets,
         &loaded_assets,
-        FlappyElement
-    );
-
-    spawn_image!(
-        assets,
-        commands,
+        &images,
         "bg_far",
-        0.0,
-        0.0,
         2.0, // second parallax layer
-        &loaded_assets,
-        FlappyElement,
-        ContinualParallax::new(width, 66, Vec2::new(1.0, 0.0))
-    );
-    spawn_image!(
-        assets,
-        commands,
-        "bg_far",
+        66,
+        Vec2::new(1.0, 0.0),
         width,
-        0.0,
-        2.0, // second parallax layer
-        &loaded_assets,
         FlappyElement,
-        ContinualParallax::new(width, 66, Vec2::new(1.0, 0.0))
     );
-
-    spawn_image!(
-        assets,
-        commands,
-        "bg_mid",
-        0.0,
-        0.0,
-        3.0, // third parallax layer
+    spawn_parallax_layer(
+        &mut commands,
+        &assets,
         &loaded_assets,
-        FlappyElement,
-        ContinualParallax::new(width, 33, Vec2::new(1.0, 0.0))
-    );
-    spawn_image!(
-        assets,
-        commands,
+        &images,
         "bg_mid",
-        width,
-        0.0,
         3.0, // third parallax layer
-        &loaded_assets,
+        33,
+        Vec2::new(1.0, 0.0),
+        width,
         FlappyElement,
-        ContinualParallax::new(width, 33, Vec2::new(1.0, 0.0))
     );
-
-    spawn_image!(
-        assets,
-        commands,
-        "bg_close",
-        0.0,
-        0.0,
-        4.0, // fourth parallax layer
+    spawn_parallax_layer(
+        &mut commands,
+        &assets,
         &loaded_assets,
-        FlappyElement,
-        ContinualParallax::new(width, 16, Vec2::new(2.0, 0.0))
-    );
-    spawn_image!(
-        assets,
-        commands,
+        &images,
         "bg_close",
-        width,
-        0.0,
         4.0, // fourth parallax layer
-        &loaded_assets,
+        16,
+        Vec2::new(2.0, 0.0),
+        width,
         FlappyElement,
-        ContinualParallax::new(width, 16, Vec2::new(2.0, 0.0))
     );
 
     /*
@@ -223,31 +345,48 @@ fn setup(
     */
 
     commands.insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4));
-    build_wall(&mut commands, &assets, &loaded_assets, rng.range(-5..5)); //(12)
+    let mut score = Score::default();
+    for column in 0..difficulty.columns {
+        difficulty.build_wall(
+            &mut score,
+            &mut commands,
+            &assets,
+            &loaded_assets,
+            &mut rng,
+            WALL_START_X + column as f32 * difficulty.column_spacing,
+        ); //(12)
+    }
+    commands.insert_resource(score);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_wall(
     commands: &mut Commands,
     assets: &AssetStore,
     loaded_assets: &LoadedAssets,
+    x: f32,
     gap_y: i32,
+    gap: i32,
+    speed: f32,
+    wall_set: WallSet,
 ) {
     for y in -12..=12 {
         //(14)
-        if y < gap_y - 4 || y > gap_y + 4 {
+        if y < gap_y - gap || y > gap_y + gap {
             spawn_image!(
                 assets,
                 commands,
                 "wall",
-                512.0,
+                x,
                 y as f32 * 32.0,
                 10.0, // draw above the parallax backgrounds
                 &loaded_assets,
                 Obstacle,
+                wall_set,
                 FlappyElement,
-                Velocity::new_2d(-8.0, 0.0),
+                Velocity::new_2d(-speed, 0.0),
                 AxisAlignedBoundingBox::new(32.0, 32.0),
-                PhysicsPosition::new(Vec2::new(512.0, y as f32 * 32.0))
+                PhysicsPosition::new(Vec2::new(x, y as f32 * 32.0))
             );
             //(15)
         }
@@ -282,38 +421,98 @@ fn clamp(mut query: Query<&mut Transform, With<Flappy>>, mut state: ResMut<NextS
     }
 }
 
+/// Replaces the wall columns that left the screen with new ones, built
+/// off-screen right of the last column
 fn move_walls(
     mut commands: Commands,
-    query: Query<&mut Transform, With<Obstacle>>,
+    mut query: Query<(Entity, &Transform, &WallSet, &mut Velocity), With<Obstacle>>,
     assets: Res<AssetStore>,
     loaded_assets: AssetResource,
-    delete: Query<Entity, With<Obstacle>>,
     mut rng: ResMut<RandomNumberGenerator>,
+    mut score: ResMut<Score>,
+    difficulty: Res<Difficulty>,
 ) {
-    let mut rebuild = false;
-    for transform in query.iter() {
-        if transform.translation.x < -530.0 {
-            rebuild = true; //(23)
+    let columns: HashMap<u32, f32> = query
+        .iter()
+        .map(|(_, transform, wall_set, _)| (wall_set.id, transform.translation.x))
+        .collect();
+    let replacements = replace_columns(&columns, difficulty.column_spacing);
+    if replacements.is_empty() {
+        return;
+    }
+
+    // All columns move at the current speed, so their spacing stays the same
+    let speed = difficulty.speed(score.points);
+    for (entity, _, wall_set, mut velocity) in query.iter_mut() {
+        if replacements.iter().any(|(id, _)| *id == wall_set.id) {
+            commands.entity(entity).despawn(); //(23)
+        } else {
+            velocity.0.x = -speed;
         }
     }
-    if rebuild {
-        for entity in delete.iter() {
-            commands.entity(entity).despawn();
+    for (_, x) in replacements {
+        difficulty.build_wall(
+            &mut score,
+            &mut commands,
+            &assets,
+            &loaded_assets,
+            &mut rng,
+            x,
+        );
+    }
+}
+
+/// Scores once for each wall set the dragon has passed
+fn score_walls(
+    mut score: ResMut<Score>,
+    flappy: Query<&Transform, With<Flappy>>,
+    walls: Query<(&Transform, &WallSet)>,
+) {
+    let Ok(flappy) = flappy.single() else {
+        return;
+    };
+    for (transform, wall_set) in walls.iter() {
+        if transform.translation.x < flappy.translation.x && score.passed.insert(wall_set.id) {
+            score.points += 1;
         }
-        build_wall(&mut commands, &assets, &loaded_assets, rng.range(-5..5));
     }
 }
 
+fn score_display(score: Res<Score>, mut egui_context: egui::EguiContexts) {
+    egui::egui::Window::new("Score").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("Score: {}", score.points));
+    });
+}
+
+/// Shows the score of the last game on the game-over screen
+fn final_score(score: Option<Res<Score>>, mut egui_context: egui::EguiContexts) {
+    let Some(score) = score else {
+        return;
+    };
+    egui::egui::Window::new("Final Score").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("You passed {} walls", score.points));
+    });
+}
+
+/// Ends the game when the dragon's bounding box touches a wall segment
 fn hit_wall(
     mut collisions: EventReader<OnCollision<Flappy, Obstacle>>,
     mut state: ResMut<NextState<GamePhase>>,
+) {
+    for _collision in collisions.read() {
+        let _ = state.set(GamePhase::GameOver);
+    }
+}
+
+/// Plays the crash sound for each collision of the dragon with a wall
+fn crash_sound(
+    mut collisions: EventReader<OnCollision<Flappy, Obstacle>>,
     assets: Res<AssetStore>,
     loaded_assets: Res<LoadedAssets>,
     mut commands: Commands,
 ) {
     for _collision in collisions.read() {
         assets.play("crash", &mut commands, &loaded_assets);
-        let _ = state.set(GamePhase::GameOver);
     }
 }
 
@@ -329,3 +528,144 @@ fn rotate(mut physics_position: Query<(&PhysicsPosition, &mut Transform), With<F
             }
         });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn difficulty_increases_with_score() {
+        let difficulty = Difficulty::default();
+        let at = |score| {
+            (
+                difficulty.gap(score),
+                difficulty.speed(score),
+                difficulty.gap_range(score),
+            )
+        };
+        assert_eq!((4, 8.0, 5), at(0));
+        assert_eq!((4, 10.0, 6), at(4));
+        assert_eq!((3, 10.5, 6), at(5));
+        assert_eq!((2, 13.0, 8), at(10));
+    }
+
+    #[test]
+    fn difficulty_respects_caps() {
+        let difficulty = Difficulty::default();
+        for score in [16, 50, 1000, u32::MAX] {
+            assert_eq!(2, difficulty.gap(score));
+            assert_eq!(16.0, difficulty.speed(score));
+            assert_eq!(8, difficulty.gap_range(score));
+        }
+    }
+
+    #[test]
+    fn wall_columns_are_replaced_off_screen() {
+        let difficulty = Difficulty::default();
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let mut columns: HashMap<u32, f32> = (0..difficulty.columns)
+            .map(|i| (i, WALL_START_X + i as f32 * difficulty.column_spacing))
+            .collect();
+        let mut gaps: Vec<i32> = (0..difficulty.columns)
+            .map(|_| difficulty.gap_y(0, &mut rng))
+            .collect();
+
+        // Travel several screen widths
+        let speed = difficulty.speed(0);
+        for _ in 0..(4.0 * 1024.0 / speed) as usize {
+            columns.values_mut().for_each(|x| *x -= speed);
+            for (id, x) in replace_columns(&columns, difficulty.column_spacing) {
+                assert!(x > WALL_START_X - speed);
+                columns.remove(&id);
+                columns.insert(gaps.len() as u32, x);
+                gaps.push(difficulty.gap_y(0, &mut rng));
+            }
+            assert_eq!(difficulty.columns as usize, columns.len());
+
+            let mut xs: Vec<f32> = columns.values().copied().collect();
+            xs.sort_by(f32::total_cmp);
+            assert!(
+                xs.windows(2)
+                    .all(|w| (w[1] - w[0] - difficulty.column_spacing).abs() < 1e-3)
+            );
+        }
+
+        assert!(gaps.len() > 2 * difficulty.columns as usize);
+        assert!(gaps.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn touching_a_wall_corner_ends_the_game() {
+        let mut app = App::new();
+        app.add_plugins(bevy::state::app::StatesPlugin)
+            .insert_state(GamePhase::Flapping)
+            .add_event::<OnCollision<Flappy, Obstacle>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(
+                Update,
+                (check_collisions::<Flappy, Obstacle>, hit_wall).chain(),
+            );
+
+        // The dragon's top right corner overlaps the wall segment at the
+        // edge of the gap, though their centers are far apart
+        let dragon = Vec2::new(-490.0, 0.0);
+        let wall = dragon + Vec2::new(46.0, 48.0);
+        assert!(dragon.distance(wall) > 32.0);
+        app.world_mut().spawn((
+            Flappy,
+            PhysicsPosition::new(dragon),
+            AxisAlignedBoundingBox::new(62.0, 65.0),
+        ));
+        app.world_mut().spawn((
+            Obstacle,
+            PhysicsPosition::new(wall),
+            AxisAlignedBoundingBox::new(32.0, 32.0),
+        ));
+
+        app.update();
+        app.update();
+        assert_eq!(
+            GamePhase::GameOver,
+            *app.world().resource::<State<GamePhase>>().get()
+        );
+    }
+
+    #[test]
+    fn passing_a_wall_set_scores_once() {
+        let mut app = App::new();
+        app.init_resource::<Score>()
+            .add_systems(Update, score_walls);
+        app.world_mut()
+            .spawn((Flappy, Transform::from_xyz(-490.0, 0.0, 0.0)));
+        let walls: Vec<Entity> = [(1, 0.0), (1, 32.0), (1, 64.0), (2, 200.0), (2, 232.0)]
+            .into_iter()
+            .map(|(id, y)| {
+                app.world_mut()
+                    .spawn((WallSet { id }, Transform::from_xyz(0.0, y, 0.0)))
+                    .id()
+            })
+            .collect();
+
+        let mut points = Vec::new();
+        for x in (-560..=0).rev().step_by(8) {
+            for (i, wall) in walls.iter().enumerate() {
+                // The second wall set trails the first one
+                let offset = if i < 3 { 0.0 } else { 40.0 };
+                app.world_mut()
+                    .get_mut::<Transform>(*wall)
+                    .unwrap()
+                    .translation
+                    .x = x as f32 + offset;
+            }
+            app.update();
+            points.push(app.world().resource::<Score>().points);
+        }
+
+        // Scores exactly once per wall set, as each passes the dragon
+        assert_eq!(2, *points.last().unwrap());
+        let first_pass = points.iter().position(|p| *p == 1).unwrap();
+        let second_pass = points.iter().position(|p| *p == 2).unwrap();
+        assert!(first_pass < second_pass);
+        assert!(points.windows(2).all(|w| w[1] - w[0] <= 1));
+    }
+}
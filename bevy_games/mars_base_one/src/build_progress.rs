@@ -0,0 +1,207 @@
+//! Progress of the world being built in the background, shown while the
+//! player waits
+
+use super::{GamePhase, LoadedRun, World, WorldGenConfig, WorldSeed, spawn_builder};
+use bevy::prelude::*;
+use my_library::{BackgroundTask, RandomNumberGenerator, egui};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
+
+/// Seconds after which the player may give up on a world and try another
+const BUILD_TIMEOUT: f32 = 15.0;
+
+/// Steps of building a world, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuildPhase {
+    /// Opening the first caves
+    Carving,
+    /// Eroding the rock down to the solid fraction
+    Eroding,
+    /// Connecting caves and placing the landing pads
+    Connecting,
+    /// Building the terrain meshes
+    Meshing,
+    /// The world is ready
+    Done,
+}
+
+impl BuildPhase {
+    const ALL: [Self; 5] = [
+        BuildPhase::Carving,
+        BuildPhase::Eroding,
+        BuildPhase::Connecting,
+        BuildPhase::Meshing,
+        BuildPhase::Done,
+    ];
+
+    /// Name shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildPhase::Carving => "Carving caves",
+            BuildPhase::Eroding => "Eroding rock",
+            BuildPhase::Connecting => "Connecting caves",
+            BuildPhase::Meshing => "Building meshes",
+            BuildPhase::Done => "Done",
+        }
+    }
+}
+
+/// Resource with the progress of the world being built. The builder task
+/// reports to a clone of it, so the game can show the progress every frame.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct BuildProgress {
+    /// Index of the phase and percentage of it, written by the builder
+    shared: Arc<[AtomicU8; 2]>,
+    /// Seconds since the build started, counted by the game
+    pub elapsed: f32,
+}
+
+impl BuildProgress {
+    /// Reports the current phase, and the fraction of it done
+    pub fn report(&self, phase: BuildPhase, fraction: f32) {
+        let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u8;
+        self.shared[0].store(phase as u8, Ordering::Relaxed);
+        self.shared[1].store(percent, Ordering::Relaxed);
+    }
+
+    /// The phase reported last
+    pub fn phase(&self) -> BuildPhase {
+        BuildPhase::ALL[self.shared[0].load(Ordering::Relaxed) as usize]
+    }
+
+    /// The fraction of the phase done, from 0.0 to 1.0
+    pub fn fraction(&self) -> f32 {
+        self.shared[1].load(Ordering::Relaxed) as f32 / 100.0
+    }
+
+    /// Whether the build takes longer than [`BUILD_TIMEOUT`]
+    pub fn timed_out(&self) -> bool {
+        self.elapsed > BUILD_TIMEOUT
+    }
+}
+
+/// Gives up on the world being built, and builds one of a new random seed
+fn retry_with_new_seed(commands: &mut Commands, seed: &mut WorldSeed, config: WorldGenConfig) {
+    seed.0 = RandomNumberGenerator::new().next();
+    info!("Retrying with seed {}.", seed.0);
+    spawn_builder(commands, *seed, config);
+}
+
+/// Shows the progress of the world being built, and starts the game once it
+/// is ready. Slow builds of new worlds can be retried with another seed.
+#[allow(clippy::too_many_arguments)]
+pub fn show_builder(
+    mut state: ResMut<NextState<GamePhase>>,
+    mut egui_context: egui::EguiContexts,
+    time: Res<Time>,
+    builder: Option<Res<BackgroundTask<World>>>,
+    progress: Option<ResMut<BuildProgress>>,
+    mut seed: ResMut<WorldSeed>,
+    config: Res<WorldGenConfig>,
+    loaded_run: Option<Res<LoadedRun>>,
+    mut commands: Commands,
+) {
+    let (Some(builder), Some(mut progress)) = (builder, progress) else {
+        return;
+    };
+    if builder.is_ready() {
+        state.set(GamePhase::Playing);
+        return;
+    }
+    progress.elapsed += time.delta_secs();
+    egui::egui::Window::new("Building World").show(egui_context.ctx_mut(), |ui| {
+        ui.label(progress.phase().name());
+        ui.add(egui::egui::ProgressBar::new(progress.fraction()).show_percentage());
+        ui.label(format!("Elapsed: {:.1}s", progress.elapsed));
+        // Saved runs must keep their terrain
+        if progress.timed_out() && loaded_run.is_none() {
+            ui.label("This takes longer than usual.");
+            if ui.button("Retry with new seed").clicked() {
+                retry_with_new_seed(&mut commands, &mut seed, *config);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::build_world;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn progress_is_shared_with_the_builder() {
+        let progress = BuildProgress::default();
+        assert_eq!(
+            (BuildPhase::Carving, 0.0),
+            (progress.phase(), progress.fraction())
+        );
+
+        let builder = progress.clone();
+        std::thread::spawn(move || builder.report(BuildPhase::Eroding, 0.426))
+            .join()
+            .unwrap();
+        assert_eq!(BuildPhase::Eroding, progress.phase());
+        assert_eq!(0.42, progress.fraction());
+
+        progress.report(BuildPhase::Meshing, 2.0);
+        assert_eq!(1.0, progress.fraction());
+    }
+
+    #[test]
+    fn building_a_world_reports_every_phase_in_order() {
+        let config = WorldGenConfig {
+            size: 100,
+            ..default()
+        };
+        let progress = BuildProgress::default();
+        let builder = progress.clone();
+        let task = std::thread::spawn(move || build_world(WorldSeed(42), &config, &builder).tiles);
+
+        let mut seen = vec![progress.phase()];
+        while !task.is_finished() {
+            let phase = progress.phase();
+            assert!(phase >= *seen.last().unwrap(), "{phase:?} after {seen:?}");
+            if phase != *seen.last().unwrap() {
+                seen.push(phase);
+            }
+        }
+        let tiles = task.join().unwrap();
+        assert_eq!(BuildPhase::Done, progress.phase());
+        assert_eq!(1.0, progress.fraction());
+        // Reporting doesn't change the world
+        let again = build_world(WorldSeed(42), &config, &BuildProgress::default());
+        assert_eq!(again.tiles, tiles);
+    }
+
+    #[test]
+    fn slow_builds_can_be_retried_with_a_new_seed() {
+        bevy::tasks::AsyncComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let mut progress = BuildProgress {
+            elapsed: BUILD_TIMEOUT - 0.1,
+            ..default()
+        };
+        assert!(!progress.timed_out());
+        progress.elapsed += 0.2;
+        assert!(progress.timed_out());
+
+        let mut app = App::new();
+        app.insert_resource(WorldSeed(42)).insert_resource(progress);
+        app.world_mut()
+            .run_system_once(|mut commands: Commands, mut seed: ResMut<WorldSeed>| {
+                let config = WorldGenConfig {
+                    size: 100,
+                    ..default()
+                };
+                retry_with_new_seed(&mut commands, &mut seed, config);
+            })
+            .unwrap();
+
+        assert_ne!(42, app.world().resource::<WorldSeed>().0);
+        assert!(app.world().contains_resource::<BackgroundTask<World>>());
+        // The new build starts counting from zero
+        assert!(!app.world().resource::<BuildProgress>().timed_out());
+    }
+}
@@ -0,0 +1,188 @@
+//! Difficulty presets, picked in the main menu
+
+use super::{FULL_FUEL, FULL_SHIELDS};
+use bevy::prelude::*;
+use my_library::egui;
+
+/// Difficulty presets the player chooses from
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    /// Name shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// The values of the preset
+    pub fn config(&self) -> DifficultyConfig {
+        match self {
+            Difficulty::Easy => DifficultyConfig {
+                difficulty: *self,
+                fuel: FULL_FUEL,
+                shields: FULL_SHIELDS,
+                gravity: 0.6,
+                miners: 12,
+                fuel_pickups: 24,
+                batteries: 24,
+                fuel_per_thrust: 1,
+                score_multiplier: 0.5,
+            },
+            Difficulty::Normal => DifficultyConfig {
+                difficulty: *self,
+                fuel: FULL_FUEL,
+                shields: FULL_SHIELDS,
+                gravity: 0.75,
+                miners: 20,
+                fuel_pickups: 20,
+                batteries: 20,
+                fuel_per_thrust: 1,
+                score_multiplier: 1.0,
+            },
+            Difficulty::Hard => DifficultyConfig {
+                difficulty: *self,
+                fuel: FULL_FUEL * 3 / 5,
+                shields: FULL_SHIELDS * 3 / 5,
+                gravity: 0.9,
+                miners: 25,
+                fuel_pickups: 15,
+                batteries: 15,
+                fuel_per_thrust: 2,
+                score_multiplier: 1.5,
+            },
+        }
+    }
+}
+
+/// Resource with the values of the chosen difficulty. Spawning and movement
+/// read it, and the final score is scaled by its multiplier.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyConfig {
+    pub difficulty: Difficulty,
+    /// Fuel of the ship at the start of a run
+    pub fuel: i32,
+    /// Shields of the ship at the start of a run
+    pub shields: i32,
+    /// Velocity the ship loses to gravity per physics tick
+    pub gravity: f32,
+    pub miners: usize,
+    pub fuel_pickups: usize,
+    pub batteries: usize,
+    /// Fuel used per frame the thruster fires
+    pub fuel_per_thrust: i32,
+    pub score_multiplier: f32,
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        Difficulty::default().config()
+    }
+}
+
+impl DifficultyConfig {
+    /// The final score, scaled by the multiplier
+    pub fn final_score(&self, score: u32) -> u32 {
+        (score as f32 * self.score_multiplier).round() as u32
+    }
+}
+
+/// Lets the player pick the difficulty in the main menu
+pub fn difficulty_menu(mut config: ResMut<DifficultyConfig>, mut egui_context: egui::EguiContexts) {
+    egui::egui::Window::new("Difficulty").show(egui_context.ctx_mut(), |ui| {
+        let mut difficulty = config.difficulty;
+        ui.horizontal(|ui| {
+            for option in Difficulty::ALL {
+                ui.radio_value(&mut difficulty, option, option.name());
+            }
+        });
+        if difficulty != config.difficulty {
+            *config = difficulty.config();
+        }
+        ui.label(format!("Score multiplier: x{}", config.score_multiplier));
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        AssetStore, Battery, Fuel, LoadedAssets, Miner, Pickups, Player, PlayerState,
+        spawn_pickups, spawn_player,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// Spawns a new run of the difficulty, and counts the ship's stats and
+    /// the pickups
+    fn spawn_run(difficulty: Difficulty) -> ((i32, i32), [usize; 3]) {
+        let config = difficulty.config();
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut images = Assets::<Image>::default();
+        for tag in ["ship", "spaceman", "fuel", "battery"] {
+            let handle = images.add(Image::default());
+            store.insert_handle(tag, handle.untyped(), &mut loaded);
+        }
+        let mut app = App::new();
+        app.insert_resource(store).insert_resource(loaded);
+        let positions: Vec<(f32, f32)> = (0..60).map(|i| (i as f32 * 24.0, 0.0)).collect();
+        app.world_mut()
+            .run_system_once(
+                move |mut commands: Commands,
+                      assets: Res<AssetStore>,
+                      loaded: Res<LoadedAssets>| {
+                    let player = PlayerState::new_game(1400.0, &config);
+                    let pickups = Pickups::from_spawn_positions(&positions, &config);
+                    spawn_player(&mut commands, &assets, &loaded, &player);
+                    spawn_pickups(&mut commands, &assets, &loaded, &pickups);
+                },
+            )
+            .unwrap();
+
+        let world = app.world_mut();
+        let player = world.query::<&Player>().single(world).unwrap();
+        let stats = (player.fuel, player.shields);
+        let counts = [
+            world
+                .query_filtered::<(), With<Miner>>()
+                .iter(world)
+                .count(),
+            world.query_filtered::<(), With<Fuel>>().iter(world).count(),
+            world
+                .query_filtered::<(), With<Battery>>()
+                .iter(world)
+                .count(),
+        ];
+        (stats, counts)
+    }
+
+    #[test]
+    fn presets_flow_into_the_spawned_run() {
+        for difficulty in Difficulty::ALL {
+            let config = difficulty.config();
+            assert_eq!(
+                (
+                    (config.fuel, config.shields),
+                    [config.miners, config.fuel_pickups, config.batteries]
+                ),
+                spawn_run(difficulty),
+                "{difficulty:?}"
+            );
+        }
+        assert_eq!(Difficulty::Normal.config(), DifficultyConfig::default());
+        let (easy, hard) = (Difficulty::Easy.config(), Difficulty::Hard.config());
+        assert!(easy.gravity < hard.gravity);
+        assert!(easy.fuel_per_thrust <= hard.fuel_per_thrust);
+        assert!(easy.score_multiplier < hard.score_multiplier);
+    }
+}
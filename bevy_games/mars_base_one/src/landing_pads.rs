@@ -0,0 +1,285 @@
+//! Landing pads, on which the ship refuels and repairs its shields
+
+use super::{FULL_FUEL, FULL_SHIELDS, GameElement, Player, TILE_SIZE, World};
+use bevy::prelude::*;
+use my_library::{
+    AxisAlignedBoundingBox, OnCollision, PhysicsPosition, Static, Velocity,
+    egui::{self, egui::Color32},
+};
+
+/// Width of a pad in tiles
+const PAD_WIDTH: usize = 5;
+/// Open tiles needed above a pad, so the ship fits
+const PAD_CLEARANCE: usize = 3;
+/// Height of a pad in world units
+const PAD_HEIGHT: f32 = 8.0;
+/// Most pads in a world
+const MAX_PADS: usize = 3;
+/// The ship rests when it is slower than this
+const REST_SPEED: f32 = 0.5;
+/// Seconds the ship must rest on a pad to dock
+const DOCK_TIME: f32 = 1.0;
+/// Fuel regenerated per second while docked
+const FUEL_PER_SECOND: f32 = 500.0;
+/// Shields regenerated per second while docked
+const SHIELDS_PER_SECOND: f32 = 25.0;
+
+/// Tags a landing pad
+#[derive(Component)]
+pub struct LandingPad;
+
+impl World {
+    /// Finds flat solid floors in the upper half of the world with enough
+    /// room above them for the ship, that can be reached from the entry
+    /// shaft. Returns the leftmost floor tile of each pad, those nearest to
+    /// the surface first.
+    pub fn pad_sites(&self) -> Vec<(usize, usize)> {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        let reachable = self.reachable();
+        let fits = |x: usize, floor: usize| {
+            (x..x + PAD_WIDTH).all(|x| {
+                self.tiles.is_solid(x, floor)
+                    && (floor + 1..=floor + PAD_CLEARANCE).all(|y| !self.tiles.is_solid(x, y))
+                    && reachable[(floor + 1) * width + x]
+            })
+        };
+
+        let mut sites: Vec<(usize, usize)> = Vec::new();
+        for floor in (height / 2 - 2..height - PAD_CLEARANCE - 1).rev() {
+            for x in 1..width - PAD_WIDTH {
+                let apart = sites.iter().all(|(site_x, site_floor)| {
+                    site_floor.abs_diff(floor) > PAD_CLEARANCE || site_x.abs_diff(x) >= PAD_WIDTH
+                });
+                if apart && fits(x, floor) {
+                    sites.push((x, floor));
+                    if sites.len() == MAX_PADS {
+                        return sites;
+                    }
+                }
+            }
+        }
+        sites
+    }
+
+    /// Builds a pad next to the top of the entry shaft, if the caves have no
+    /// room for one. Caves cut off by its floor are connected again.
+    pub fn ensure_landing_pad(&mut self) {
+        if !self.pad_sites().is_empty() {
+            return;
+        }
+        let (entry_x, top) = self.entry();
+        let (left, floor) = (entry_x + 2, top - PAD_CLEARANCE - 1);
+        for x in left..left + PAD_WIDTH {
+            self.tiles.set_solid(x, floor, true);
+            for y in floor + 1..=floor + PAD_CLEARANCE {
+                self.tiles.set_solid(x, y, false);
+            }
+        }
+        // The shaft is open beside the pad, so it can be reached
+        for y in floor + 1..=floor + PAD_CLEARANCE {
+            self.tiles.set_solid(entry_x + 1, y, false);
+        }
+        self.connect_caves();
+    }
+
+    /// Centers of the pads, on top of their floors
+    pub fn pad_positions(&self) -> Vec<(f32, f32)> {
+        let grid = self.tiles.coords();
+        self.pad_sites()
+            .into_iter()
+            .map(|(x, floor)| {
+                let center = grid.tile_to_world((x + PAD_WIDTH / 2, floor));
+                (center.x, center.y + (TILE_SIZE + PAD_HEIGHT) / 2.0)
+            })
+            .collect()
+    }
+}
+
+/// Spawns the landing pads of a world, and resets the docking
+pub fn spawn_landing_pads(commands: &mut Commands, positions: &[(f32, f32)]) {
+    let size = Vec2::new(PAD_WIDTH as f32 * TILE_SIZE, PAD_HEIGHT);
+    for (x, y) in positions {
+        commands.spawn((
+            Sprite::from_color(Color::srgb(0.9, 0.8, 0.2), size),
+            Transform::from_xyz(*x, *y, 5.0),
+            PhysicsPosition::new(Vec2::new(*x, *y)),
+            AxisAlignedBoundingBox::new(size.x, size.y),
+            Static,
+            LandingPad,
+            GameElement,
+        ));
+    }
+    commands.insert_resource(Docking::default());
+}
+
+/// Resource tracking whether the ship rests on a pad long enough to dock
+#[derive(Resource, Default, Debug)]
+pub struct Docking {
+    /// Seconds the ship has been resting on a pad
+    resting: f32,
+    /// Fuel and shields regenerated, but not yet added to the ship
+    fuel: f32,
+    shields: f32,
+}
+
+impl Docking {
+    /// Whether the ship is docked
+    pub fn docked(&self) -> bool {
+        self.resting >= DOCK_TIME
+    }
+
+    /// Advances the time resting on a pad. Leaving the pad or moving faster
+    /// than [`REST_SPEED`] undocks the ship.
+    pub fn update(&mut self, on_pad: bool, speed: f32, delta: f32) {
+        if on_pad && speed < REST_SPEED {
+            self.resting += delta;
+        } else {
+            *self = Self::default();
+        }
+    }
+
+    /// Refuels and repairs a docked ship, up to full fuel and shields
+    pub fn regenerate(&mut self, player: &mut Player, delta: f32) {
+        if !self.docked() {
+            return;
+        }
+        let refill = |level: &mut i32, pending: &mut f32, rate: f32, full: i32| {
+            if *level >= full {
+                *pending = 0.0;
+                return;
+            }
+            *pending += rate * delta;
+            let whole = pending.floor();
+            *pending -= whole;
+            *level = (*level + whole as i32).min(full);
+        };
+        refill(&mut player.fuel, &mut self.fuel, FUEL_PER_SECOND, FULL_FUEL);
+        refill(
+            &mut player.shields,
+            &mut self.shields,
+            SHIELDS_PER_SECOND,
+            FULL_SHIELDS,
+        );
+    }
+}
+
+/// Docks the ship once it rests on a pad for [`DOCK_TIME`] seconds. Taking
+/// off undocks it.
+pub fn dock(
+    time: Res<Time>,
+    mut collisions: EventReader<OnCollision<Player, LandingPad>>,
+    player: Query<&Velocity, With<Player>>,
+    mut docking: ResMut<Docking>,
+) {
+    let on_pad = collisions.read().count() > 0;
+    let Ok(velocity) = player.single() else {
+        return;
+    };
+    docking.update(on_pad, velocity.0.truncate().length(), time.delta_secs());
+}
+
+/// Refuels and repairs the docked ship
+pub fn refuel_and_repair(
+    time: Res<Time>,
+    mut docking: ResMut<Docking>,
+    mut player: Query<&mut Player>,
+) {
+    if let Ok(mut player) = player.single_mut() {
+        docking.regenerate(&mut player, time.delta_secs());
+    }
+}
+
+/// Shows that the ship is docked
+pub fn docking_indicator(docking: Res<Docking>, mut egui_context: egui::EguiContexts) {
+    if !docking.docked() {
+        return;
+    }
+    egui::egui::Area::new(egui::egui::Id::new("docking_indicator"))
+        .anchor(egui::egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(
+                egui::egui::RichText::new("Docked: refueling and repairing")
+                    .size(22.0)
+                    .color(Color32::GOLD),
+            );
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use my_library::TileGrid;
+
+    #[test]
+    fn the_ship_docks_after_resting_for_a_second() {
+        let mut docking = Docking::default();
+        for _ in 0..9 {
+            docking.update(true, 0.2, 0.1);
+        }
+        assert!(!docking.docked());
+        docking.update(true, 0.2, 0.1);
+        assert!(docking.docked());
+
+        // Taking off undocks
+        docking.update(true, 2.0, 0.1);
+        assert!(!docking.docked());
+
+        // Drifting over a pad doesn't dock
+        for _ in 0..20 {
+            docking.update(true, 1.0, 0.1);
+        }
+        assert!(!docking.docked());
+        for _ in 0..20 {
+            docking.update(false, 0.0, 0.1);
+        }
+        assert!(!docking.docked());
+    }
+
+    #[test]
+    fn docked_ships_regenerate_up_to_full() {
+        let mut player = Player {
+            miners_saved: 0,
+            shields: 10,
+            fuel: 0,
+            score: 0,
+        };
+        let mut docking = Docking::default();
+        docking.regenerate(&mut player, 1.0);
+        assert_eq!((0, 10), (player.fuel, player.shields));
+
+        docking.update(true, 0.0, DOCK_TIME);
+        for _ in 0..20 {
+            docking.regenerate(&mut player, 0.1);
+        }
+        assert_eq!(2 * FUEL_PER_SECOND as i32, player.fuel);
+        assert_eq!(10 + 2 * SHIELDS_PER_SECOND as i32, player.shields);
+
+        for _ in 0..600 {
+            docking.regenerate(&mut player, 0.1);
+        }
+        assert_eq!((FULL_FUEL, FULL_SHIELDS), (player.fuel, player.shields));
+
+        // Fuel beyond full isn't taken away
+        player.fuel = FULL_FUEL + 1000;
+        docking.regenerate(&mut player, 0.1);
+        assert_eq!(FULL_FUEL + 1000, player.fuel);
+    }
+
+    #[test]
+    fn every_world_has_a_reachable_pad() {
+        // Solid rock with nothing but the entry shaft
+        let mut world = World::from_tiles(TileGrid::new(100, 100, TILE_SIZE, true));
+        world.clear_entry();
+        assert!(world.pad_sites().is_empty());
+
+        world.ensure_landing_pad();
+        let sites = world.pad_sites();
+        assert_eq!(1, sites.len());
+        let reachable = world.reachable();
+        let (x, floor) = sites[0];
+        for x in x..x + PAD_WIDTH {
+            assert!(world.tiles.is_solid(x, floor));
+            assert!(reachable[(floor + 1) * 100 + x]);
+        }
+    }
+}
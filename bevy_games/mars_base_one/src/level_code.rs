@@ -0,0 +1,242 @@
+//! Level codes, sharing a world seed together with its generation parameters
+//! and difficulty
+
+use super::{CaveAlgorithm, Difficulty, DifficultyConfig, WorldGenConfig, WorldSeed};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use my_library::{
+    anyhow,
+    egui::{self, egui::Color32},
+};
+
+/// Version of the code format, the first byte of every code
+const CODE_VERSION: u8 = 2;
+
+/// A world seed with the parameters it is built with, so players can share
+/// the exact same world
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LevelCode {
+    pub seed: u64,
+    pub config: WorldGenConfig,
+    /// Decides how many pickups are spread over the world
+    pub difficulty: Difficulty,
+}
+
+impl LevelCode {
+    /// Encodes the level into a short, url-safe base64 text
+    pub fn to_code(self) -> String {
+        let config = &self.config;
+        let mut bytes = vec![CODE_VERSION];
+        bytes.extend(self.seed.to_le_bytes());
+        bytes.extend((config.size as u16).to_le_bytes());
+        bytes.extend(config.solid_fraction.to_le_bytes());
+        bytes.extend(config.rescue_fraction.to_le_bytes());
+        bytes.push(self.difficulty as u8);
+        match config.algorithm {
+            CaveAlgorithm::Diffusion => bytes.extend([0, config.holes as u8]),
+            CaveAlgorithm::CellularAutomata {
+                iterations,
+                birth,
+                survive,
+            } => bytes.extend([1, iterations as u8, birth as u8, survive as u8]),
+        }
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Decodes a level from its code. Codes that are damaged, of another
+    /// version, or with parameters out of the menu's ranges are rejected.
+    pub fn from_code(code: &str) -> anyhow::Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code.trim())
+            .map_err(|_| anyhow::anyhow!("This is not a level code"))?;
+        if bytes.first() != Some(&CODE_VERSION) {
+            anyhow::bail!("The level code is of another version");
+        }
+        let field = |range: std::ops::Range<usize>| {
+            bytes
+                .get(range)
+                .ok_or_else(|| anyhow::anyhow!("The level code is too short"))
+        };
+        let seed = u64::from_le_bytes(field(1..9)?.try_into()?);
+        let size = u16::from_le_bytes(field(9..11)?.try_into()?) as usize;
+        let solid_fraction = f32::from_le_bytes(field(11..15)?.try_into()?);
+        let rescue_fraction = f32::from_le_bytes(field(15..19)?.try_into()?);
+        let difficulty = *Difficulty::ALL
+            .get(field(19..20)?[0] as usize)
+            .ok_or_else(|| anyhow::anyhow!("The level code has an unknown difficulty"))?;
+        let (algorithm, holes, length) = match field(20..21)?[0] {
+            0 => (CaveAlgorithm::Diffusion, field(21..22)?[0] as usize, 22),
+            1 => {
+                let rules = field(21..24)?;
+                let algorithm = CaveAlgorithm::CellularAutomata {
+                    iterations: rules[0] as usize,
+                    birth: rules[1] as usize,
+                    survive: rules[2] as usize,
+                };
+                (algorithm, WorldGenConfig::default().holes, 24)
+            }
+            _ => anyhow::bail!("The level code has an unknown cave algorithm"),
+        };
+        if bytes.len() != length {
+            anyhow::bail!("The level code is too long");
+        }
+        let config = WorldGenConfig {
+            size,
+            solid_fraction,
+            holes,
+            algorithm,
+            rescue_fraction,
+        };
+        // The ranges of the world generation menu
+        let in_range = (100..=300).contains(&size)
+            && (0.3..=0.8).contains(&solid_fraction)
+            && (0.25..=1.0).contains(&rescue_fraction)
+            && match algorithm {
+                CaveAlgorithm::Diffusion => (1..=30).contains(&holes),
+                CaveAlgorithm::CellularAutomata {
+                    iterations,
+                    birth,
+                    survive,
+                } => iterations <= 10 && birth <= 8 && survive <= 8,
+            };
+        if !in_range {
+            anyhow::bail!("The level code has invalid parameters");
+        }
+        Ok(Self {
+            seed,
+            config,
+            difficulty,
+        })
+    }
+}
+
+/// Shows the code of the level in the world generation menu, and lets the
+/// player enter a code shared by someone else, which also sets the difficulty
+pub fn level_code_ui(
+    ui: &mut egui::egui::Ui,
+    seed: &mut WorldSeed,
+    config: &mut WorldGenConfig,
+    difficulty: &mut DifficultyConfig,
+    input: &mut String,
+    error: &mut Option<String>,
+) {
+    let code = LevelCode {
+        seed: seed.0,
+        config: *config,
+        difficulty: difficulty.difficulty,
+    }
+    .to_code();
+    ui.horizontal(|ui| {
+        ui.label(format!("Level code: {code}"));
+        if ui.small_button("Copy").clicked() {
+            ui.ctx().copy_text(code);
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(input);
+        if ui.button("Use Code").clicked() {
+            match LevelCode::from_code(input) {
+                Ok(level) => {
+                    seed.0 = level.seed;
+                    *config = level.config;
+                    *difficulty = level.difficulty.config();
+                    input.clear();
+                    *error = None;
+                }
+                Err(message) => *error = Some(format!("{message:#}")),
+            }
+        }
+    });
+    if let Some(error) = error.as_ref() {
+        ui.colored_label(Color32::RED, error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        let levels = [
+            LevelCode {
+                seed: 42,
+                config: WorldGenConfig::default(),
+                difficulty: Difficulty::Normal,
+            },
+            LevelCode {
+                seed: u64::MAX,
+                config: WorldGenConfig {
+                    size: 300,
+                    solid_fraction: 0.33,
+                    algorithm: CaveAlgorithm::CellularAutomata {
+                        iterations: 7,
+                        birth: 5,
+                        survive: 3,
+                    },
+                    rescue_fraction: 0.5,
+                    ..WorldGenConfig::default()
+                },
+                difficulty: Difficulty::Hard,
+            },
+            LevelCode {
+                seed: 7,
+                config: WorldGenConfig::default(),
+                difficulty: Difficulty::Easy,
+            },
+        ];
+        for level in levels {
+            let code = level.to_code();
+            assert!(code.len() <= 32, "{code}");
+            assert!(
+                code.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            );
+            assert_eq!(level, LevelCode::from_code(&code).unwrap());
+            // Pasted codes may come with whitespace
+            assert_eq!(level, LevelCode::from_code(&format!(" {code}\n")).unwrap());
+        }
+        // The same world on another difficulty is another level
+        let easy = LevelCode {
+            difficulty: Difficulty::Easy,
+            ..levels[0]
+        };
+        assert_ne!(levels[0].to_code(), easy.to_code());
+    }
+
+    #[test]
+    fn invalid_codes_are_errors() {
+        let code = LevelCode {
+            seed: 7,
+            config: WorldGenConfig::default(),
+            difficulty: Difficulty::Normal,
+        }
+        .to_code();
+        let huge = LevelCode {
+            seed: 7,
+            config: WorldGenConfig {
+                size: 5000,
+                ..WorldGenConfig::default()
+            },
+            difficulty: Difficulty::Normal,
+        }
+        .to_code();
+        for invalid in [
+            "",
+            "not a code!",
+            "AAAA",
+            &code[..code.len() - 4],
+            &format!("{code}AAAA"),
+            &huge,
+        ] {
+            assert!(LevelCode::from_code(invalid).is_err(), "{invalid}");
+        }
+        let mut bytes = URL_SAFE_NO_PAD.decode(&code).unwrap();
+        bytes[0] = CODE_VERSION + 1;
+        let error = LevelCode::from_code(&URL_SAFE_NO_PAD.encode(&bytes)).unwrap_err();
+        assert!(error.to_string().contains("version"), "{error}");
+        bytes[0] = CODE_VERSION;
+        bytes[19] = 3;
+        let error = LevelCode::from_code(&URL_SAFE_NO_PAD.encode(&bytes)).unwrap_err();
+        assert!(error.to_string().contains("difficulty"), "{error}");
+    }
+}
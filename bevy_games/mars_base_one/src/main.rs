@@ -1,13 +1,30 @@
-use std::sync::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
-
-use bevy::asset::RenderAssetUsages;
 use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
-use bevy::render::mesh::PrimitiveTopology;
 use my_library::egui::egui::Color32;
 use my_library::*;
+use std::time::Duration;
+
+mod build_progress;
+mod difficulty;
+mod landing_pads;
+mod level_code;
+mod meteors;
+mod mission;
+mod overview;
+mod save_game;
+mod terrain;
+mod tractor_beam;
+use build_progress::*;
+use difficulty::*;
+use landing_pads::*;
+use level_code::*;
+use meteors::*;
+use mission::*;
+use overview::*;
+use save_game::*;
+use terrain::*;
+use tractor_beam::*;
 
 /// Game Phases for Mars Base One
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
@@ -21,7 +38,7 @@ enum GamePhase {
 }
 
 ///  Component for identifying game element entities
-#[derive(Component)]
+#[derive(Component, Clone)]
 struct GameElement;
 
 /// Component that identifies the player entity
@@ -42,7 +59,7 @@ struct Player {
 struct MyCamera;
 
 /// Component to tag ground entities
-#[derive(Component)]
+#[derive(Component, Clone)]
 struct Ground;
 
 /// A component to tag miner entities
@@ -57,6 +74,10 @@ struct Battery;
 #[derive(Component)]
 struct Fuel;
 
+/// Component to tag the mothership, where the ship starts
+#[derive(Component)]
+struct Mothership;
+
 /// Event that defines the spawning of new particles
 #[derive(Event)]
 pub struct SpawnParticle {
@@ -68,28 +89,39 @@ pub struct SpawnParticle {
     velocity: Vec3,
 }
 
-/// A component that designates a particle entity
-#[derive(Component)]
-pub struct Particle {
-    /// How log does it take for a particle to fade away?
-    pub lifetime: f32,
+/// Submits final scores to the highscore server
+struct HighScoreServer;
+
+impl egui::HighScoreClient for HighScoreServer {
+    fn submit(&self, name: &str, score: u32) {
+        let entry = HighScoreEntry {
+            name: name.to_string(),
+            score,
+        };
+        std::thread::spawn(move || {
+            ureq::post("http://localhost:3030/submit-score")
+                .timeout(std::time::Duration::from_secs(5))
+                .send_json(entry)
+                .expect("Failed to submit score");
+        });
+    }
 }
 
-/// At the end of the game, this event notifies about the final score
-#[derive(Event)]
-struct FinalScore(u32);
+/// Seed for generating the world, so players can replay and share a layout
+#[derive(Resource, Clone, Copy, PartialEq)]
+struct WorldSeed(pub u64);
 
-/// State for the players score after playing the game
-#[derive(Default)]
-struct ScoreState {
-    /// Optional final score, may not be submitted yet
-    score: Option<u32>,
-    /// Players name
-    player_name: String,
-    /// Has the score been submitted to the highscore server?
-    submitted: bool,
+impl Default for WorldSeed {
+    fn default() -> Self {
+        Self(RandomNumberGenerator::new().next())
+    }
 }
 
+/// Resource set while the player pinned the [`WorldSeed`], e.g. by typing
+/// it in. Otherwise each game starts with a new random seed.
+#[derive(Resource, Clone, Copy, Default)]
+struct PinnedSeed(pub bool);
+
 /// DTO to submit high-score entries to the server
 #[derive(serde::Serialize, serde::Deserialize)]
 struct HighScoreEntry {
@@ -116,37 +148,62 @@ fn main() -> anyhow::Result<()> {
     let mut app = App::new();
 
     add_phase!(app, GamePhase, GamePhase::Playing,
-       start => [ setup ],
-       run => [movement, end_game, physics_clock, sum_impulses, apply_gravity, apply_velocity,
+       start => [ setup, start_meteor_shower ],
+       run => [movement.run_if(running), end_game, pause_menu, physics_clock, sum_impulses, apply_gravity, apply_velocity,
         cap_velocity.after(apply_velocity),
-        check_collisions::<Player, Ground>, bounce, show_performance, score_display,
-        camera_follow.after(cap_velocity),
-        spawn_particle_system, particle_age_system, miner_beacon,
+        check_collisions::<Player, Ground>, bounce, show_performance, hud_display, low_fuel_warning,
+        remove_camera_shake.before(camera_follow),
+        overview_mode.before(physics_clock).before(camera_zoom), camera_zoom.before(camera_follow),
+        camera_follow.after(physics_clock),
+        apply_camera_shake.after(camera_follow),
+        camera_parallax.after(apply_camera_shake),
+        shake_on_bounce, impact_sound, thruster_sound.run_if(running),
+        shield_bar, status_bars.after(shield_bar).after(physics_clock),
+        trail_system.after(physics_clock),
+        spawn_particle_system, particle_system, miner_beacon, flash_system,
         check_collisions::<Player, Miner>,
         check_collisions::<Player, Fuel>,
         check_collisions::<Player, Battery>,
         collect_and_despawn_game_element::<Miner,  { BurstColor:: Green as u8 }>,
         collect_and_despawn_game_element::<Fuel,  { BurstColor:: Orange as u8 }>,
-        collect_and_despawn_game_element::<Battery,  { BurstColor::Magenta as u8 }>
+        collect_and_despawn_game_element::<Battery,  { BurstColor::Magenta as u8 }>,
+        toggle_minimap, minimap_markers.after(physics_clock), minimap_display.after(minimap_markers),
+        meteor_shower.run_if(running), meteor_warning,
+        check_collisions::<Meteor, Ground>, check_collisions::<Player, Meteor>,
+        meteor_impact, meteor_hit,
+        tractor_beam.run_if(running).before(sum_impulses), draw_tractor_beam,
+        mining_laser.run_if(running).before(carve_terrain),
+        check_collisions::<Player, LandingPad>, dock.after(check_collisions::<Player, LandingPad>),
+        refuel_and_repair.run_if(running).after(dock), docking_indicator,
+        carve_terrain.before(check_collisions::<Player, Ground>).before(check_collisions::<Meteor, Ground>),
+        overview_markers.after(minimap_markers)
         ],
-       exit => [submit_score, cleanup::<GameElement>.after(submit_score)]
+       exit => [submit_score, cleanup::<GameElement>.after(submit_score), resume_time, end_overview],
+       transition => [ GamePhase::GameOver => [game_over_sound] ]
     );
 
     add_phase!(app, GamePhase, GamePhase::WorldBuilding,
-        start => [ spawn_builder],
-        run => [ show_builder ],
+        start => [reroll_seed],
+        run => [ world_gen_menu, show_builder ],
         exit => []
     );
 
     add_phase!(app, GamePhase, GamePhase::GameOver,
         start => [],
-        run => [ final_score ],
+        run => [ egui::high_score_submission::<HighScoreServer> ],
         exit => []
     );
 
     app.add_systems(
         Update,
-        highscore_table.run_if(in_state(GamePhase::MainMenu)),
+        (
+            highscore_table,
+            seed_entry,
+            continue_menu,
+            settings_menu,
+            difficulty_menu,
+        )
+            .run_if(in_state(GamePhase::MainMenu)),
     );
     app.add_event::<Impulse>()
         .add_event::<PhysicsTick>()
@@ -154,8 +211,11 @@ fn main() -> anyhow::Result<()> {
         .add_event::<OnCollision<Player, Miner>>()
         .add_event::<OnCollision<Player, Fuel>>()
         .add_event::<OnCollision<Player, Battery>>()
+        .add_event::<OnCollision<Player, Meteor>>()
+        .add_event::<OnCollision<Meteor, Ground>>()
         .add_event::<SpawnParticle>()
-        .add_event::<FinalScore>()
+        .add_event::<CarveTile>()
+        .add_event::<OnCollision<Player, LandingPad>>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Mars Base One".to_string(),
@@ -179,21 +239,105 @@ fn main() -> anyhow::Result<()> {
                 .add_image("particle", "particle.png")?
                 .add_image("spaceman", "spaceman.png")?
                 .add_image("fuel", "fuel.png")?
-                .add_image("battery", "battery.png")?,
+                .add_image("battery", "battery.png")?
+                .add_image("meteor", "meteor.png")?
+                // Placeholder until the thruster and the pickups get
+                // their own sounds
+                .add_sound("thruster", "flap.ogg")?
+                .add_sound("rescue", "flap.ogg")?
+                .add_sound("refuel", "flap.ogg")?
+                .add_sound("recharge", "flap.ogg")?
+                // Placeholder until the warnings and crashes get their
+                // own sounds
+                .add_sound("low_fuel", "crash.ogg")?
+                .add_sound("impact", "crash.ogg")?
+                .add_sound("game_over", "crash.ogg")?,
         )
         .add_plugins(FrameTimeDiagnosticsPlugin { ..default() })
         .insert_resource(Animations::new())
-        .run();
+        .init_resource::<WorldSeed>()
+        .init_resource::<PinnedSeed>()
+        .init_resource::<WorldGenConfig>()
+        .init_resource::<GameSettings>()
+        .init_resource::<DifficultyConfig>()
+        .init_resource::<MinimapSize>()
+        .init_resource::<MinimapMarkers>()
+        .init_resource::<TractorBeam>()
+        .init_resource::<Overview>()
+        .insert_resource(egui::HighScoreSubmit::new(HighScoreServer));
+
+    #[cfg(feature = "dev")]
+    add_console_commands(&mut app);
+
+    app.run();
 
     Ok(())
 }
 
+/// Console commands for testing the cavern, e.g. "set shields 500" or
+/// "spawn fuel"
+#[cfg(feature = "dev")]
+fn add_console_commands(app: &mut App) {
+    use egui::DebugConsoleExt;
+    app.add_plugins(egui::DebugConsolePlugin)
+        .console_command("set", |world, args| {
+            let [stat, value] = args else {
+                anyhow::bail!("Usage: set shields|fuel <value>");
+            };
+            let value: i32 = value.parse()?;
+            let mut player = world.query::<&mut Player>().single_mut(world)?;
+            match *stat {
+                "shields" => player.shields = value,
+                "fuel" => player.fuel = value,
+                _ => anyhow::bail!("Unknown stat {stat}"),
+            }
+            Ok(format!("{stat} = {value}"))
+        })
+        .console_command("spawn", |world, args| {
+            let [tag @ ("fuel" | "battery")] = args else {
+                anyhow::bail!("Usage: spawn fuel|battery");
+            };
+            // Right next to the ship, so it can be collected at once
+            let position = world
+                .query_filtered::<&Transform, With<Player>>()
+                .single(world)?
+                .translation
+                .truncate()
+                + Vec2::new(48.0, 0.0);
+            let Some(image) = world
+                .resource::<AssetStore>()
+                .get_handle(tag, world.resource::<LoadedAssets>())
+            else {
+                anyhow::bail!("No image for {tag}");
+            };
+            let mut pickup = world.spawn((
+                Sprite::from_image(image),
+                Transform::from_translation(position.extend(10.0)),
+                GameElement,
+                Velocity::default(),
+                PhysicsPosition::new(position),
+                AxisAlignedBoundingBox::new(48.0, 48.0),
+            ));
+            if *tag == "fuel" {
+                pickup.insert(Fuel);
+            } else {
+                pickup.insert(Battery);
+            }
+            Ok(format!("Spawned {tag} at {position}"))
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
 fn setup(
     mut commands: Commands,
     assets: Res<AssetStore>,
     loaded_assets: Res<LoadedAssets>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut builder: ResMut<BackgroundTask<World>>,
+    loaded_run: Option<Res<LoadedRun>>,
+    config: Res<WorldGenConfig>,
+    difficulty: Res<DifficultyConfig>,
 ) {
     let camera = Camera2d::default();
     // This determines the transformation from world-coordinates to screen-coordinates.
@@ -201,33 +345,39 @@ fn setup(
     // is done with a *projection matrix*.
     let projection = Projection::Orthographic(OrthographicProjection {
         scaling_mode: ScalingMode::WindowSize,
-        scale: 0.5,
+        scale: DEFAULT_ZOOM,
         ..OrthographicProjection::default_2d()
     });
-    commands.spawn((camera, projection, GameElement, MyCamera));
-
-    let top = WORLD_SIZE as f32 / 2.0 * TILE_SIZE;
+    let world = builder.take_result().unwrap();
+    commands.remove_resource::<BackgroundTask<World>>();
+    let (width, height) = (world.tiles.width() as f32, world.tiles.height() as f32);
+    let top = height / 2.0 * TILE_SIZE;
+
+    let run = loaded_run.as_deref();
+    let state = run.map_or_else(
+        || PlayerState::new_game(200.0 + top, &difficulty),
+        |run| run.0.player,
+    );
+    let player = spawn_player(&mut commands, &assets, &loaded_assets, &state);
 
-    spawn_image!(
-        assets,
-        commands,
-        "ship",
-        0.0,
-        200.0 + top,
-        10.0,
-        &loaded_assets,
-        GameElement,
-        Player {
-            miners_saved: 0,
-            shields: 500,
-            fuel: 100_00,
-            score: 0,
-        },
-        Velocity::default(),
-        PhysicsPosition::new(Vec2::new(0.0, 200.0 + top)),
-        ApplyGravity,
-        AxisAlignedBoundingBox::new(24.0, 24.0)
+    // The camera may show the world and the sky above, where the mothership is
+    let bounds = Rect2D::new(
+        Vec2::splat(-top),
+        Vec2::new(top, top + TOP_MARGIN * TILE_SIZE),
     );
+    commands.spawn((
+        camera,
+        projection,
+        Transform::from_xyz(state.position.0, state.position.1, 10.0),
+        CameraFollow::new(player)
+            .with_smoothing(0.1)
+            .with_deadzone(Vec2::new(48.0, 32.0))
+            .with_bounds(bounds),
+        CameraShake::new(Vec2::splat(8.0), 0.05).with_decay(1.5),
+        CameraZoom::new(DEFAULT_ZOOM, 0.25, 1.0),
+        GameElement,
+        MyCamera,
+    ));
 
     spawn_image!(
         assets,
@@ -237,11 +387,14 @@ fn setup(
         400.0 + top,
         10.0,
         &loaded_assets,
-        GameElement
+        GameElement,
+        Mothership
     );
 
-    let x_scale = WORLD_SIZE as f32 * TILE_SIZE / 1792.0;
-    let y_scale = (WORLD_SIZE as f32 + TOP_MARGIN) * TILE_SIZE / 1024.0;
+    // The backdrop is a bit larger than the world, so it still covers the
+    // screen when shifted by the parallax effect
+    let x_scale = width * TILE_SIZE / 1792.0 * 1.1;
+    let y_scale = (height + TOP_MARGIN) * TILE_SIZE / 1024.0 * 1.1;
 
     let center_x = 0.0; // as f32 * TILE_SIZE - WORLD_SIZE as f32 / 2.0 * TILE_SIZE;
     let center_y = TOP_MARGIN / 2.0 * TILE_SIZE; // as f32 * TILE_SIZE - WORLD_SIZE as f32 / 2.0 * TILE_SIZE;
@@ -253,10 +406,12 @@ fn setup(
             assets.get_handle("backdrop", &loaded_assets).unwrap(),
         ))
         .insert(transform)
+        .insert(CameraParallax::new(
+            Vec2::splat(0.9),
+            Vec2::new(center_x, center_y),
+        ))
         .insert(GameElement);
 
-    let mut lock = NEW_WORD.lock().unwrap();
-    let world = lock.take().unwrap();
     world.spawn(
         &assets,
         &mut commands,
@@ -264,16 +419,128 @@ fn setup(
         &mut meshes,
         &mut materials,
     );
-    commands.insert_resource(StaticQuadTree::new(Vec2::new(10240.0, 7680.0), 6));
+    let pickups = run.map_or_else(
+        || Pickups::from_spawn_positions(&world.spawn_positions, &difficulty),
+        |run| run.0.pickups.clone(),
+    );
+    spawn_pickups(&mut commands, &assets, &loaded_assets, &pickups);
+    spawn_landing_pads(&mut commands, &world.pads);
+    commands.insert_resource(MissionState::new(
+        pickups.miners.len() as u32 + state.miners_saved,
+        config.rescue_fraction,
+    ));
+    commands.remove_resource::<LoadedRun>();
+    commands.insert_resource(ParticlePool::new(MAX_PARTICLES));
+    commands.insert_resource(Gravity(difficulty.gravity));
+    commands.insert_resource(world.minimap.unwrap());
+    commands.insert_resource(Terrain(world.tiles));
+    // The tree covers the world and the sky above it, where the ship starts
+    let extent = Vec2::new(width, height + 2.0 * TOP_MARGIN) * TILE_SIZE;
+    commands.insert_resource(StaticQuadTree::new(
+        extent.max(Vec2::new(10240.0, 7680.0)),
+        6,
+    ));
 }
 
-fn end_game(mut state: ResMut<NextState<GamePhase>>, player_query: Query<&Player>) {
-    let Ok(player) = player_query.single() else {
-        return;
-    };
+/// Spawns the ship, either fresh on a new run or as it was saved
+fn spawn_player(
+    commands: &mut Commands,
+    assets: &AssetStore,
+    loaded_assets: &LoadedAssets,
+    state: &PlayerState,
+) -> Entity {
+    let (x, y) = state.position;
+    spawn_image!(
+        assets,
+        commands,
+        "ship",
+        x,
+        y,
+        10.0,
+        loaded_assets,
+        Transform::from_xyz(x, y, 10.0).with_rotation(Quat::from_rotation_z(state.rotation)),
+        GameElement,
+        state.player(),
+        Velocity::new_2d(state.velocity.0, state.velocity.1),
+        PhysicsPosition::new(Vec2::new(x, y)),
+        ApplyGravity,
+        // The bounding box encloses the ship at any rotation, the convex
+        // collider follows the rotated ship
+        AxisAlignedBoundingBox::new(34.0, 34.0),
+        ConvexCollider::rectangle(24.0, 24.0),
+        FlashOnEvent::new(Color::WHITE, 0.3),
+        StatusBar::new(
+            Vec2::new(40.0, 4.0),
+            Color::srgb(0.2, 0.6, 1.0),
+            Vec2::new(0.0, 32.0)
+        )
+        .hidden_when_full(),
+        Trail::new(24, 6.0, 6.0, LinearRgba::new(1.0, 0.6, 0.2, 0.6))
+    )
+    .id()
+}
+
+/// Spawns the miners to rescue, and the fuel and batteries to collect
+fn spawn_pickups(
+    commands: &mut Commands,
+    assets: &AssetStore,
+    loaded_assets: &LoadedAssets,
+    pickups: &Pickups,
+) {
+    for (i, (x, y)) in pickups.miners.iter().enumerate() {
+        // Spread the beacons over the interval, so they don't flash in sync
+        let beacon = PeriodicEffect::new(BEACON_INTERVAL).with_elapsed(Duration::from_secs_f32(
+            BEACON_INTERVAL * i as f32 / pickups.miners.len() as f32,
+        ));
+        spawn_image!(
+            assets,
+            commands,
+            "spaceman",
+            *x,
+            *y,
+            10.0,
+            loaded_assets,
+            GameElement,
+            Miner,
+            beacon,
+            Velocity::default(),
+            PhysicsPosition::new(Vec2::new(*x, *y)),
+            AxisAlignedBoundingBox::new(48.0, 48.0)
+        );
+    }
+
+    for (x, y) in pickups.fuel.iter() {
+        spawn_image!(
+            assets,
+            commands,
+            "fuel",
+            *x,
+            *y,
+            10.0,
+            loaded_assets,
+            GameElement,
+            Fuel,
+            Velocity::default(),
+            PhysicsPosition::new(Vec2::new(*x, *y)),
+            AxisAlignedBoundingBox::new(48.0, 48.0)
+        );
+    }
 
-    if player.miners_saved == 20 {
-        state.set(GamePhase::GameOver);
+    for (x, y) in pickups.batteries.iter() {
+        spawn_image!(
+            assets,
+            commands,
+            "battery",
+            *x,
+            *y,
+            10.0,
+            loaded_assets,
+            GameElement,
+            Battery,
+            Velocity::default(),
+            PhysicsPosition::new(Vec2::new(*x, *y)),
+            AxisAlignedBoundingBox::new(48.0, 48.0)
+        );
     }
 }
 
@@ -290,11 +557,15 @@ fn spawn_particle(
     });
 }
 
+/// Keys that fire the main thruster
+const THRUST_KEYS: [KeyCode; 2] = [KeyCode::KeyW, KeyCode::ArrowUp];
+
 fn movement(
     keyboard: Res<ButtonInput<KeyCode>>,
     mut player_query: Query<(Entity, &mut Transform, &mut Player)>,
     mut impulses: EventWriter<Impulse>,
     mut particles: EventWriter<SpawnParticle>,
+    difficulty: Res<DifficultyConfig>,
 ) {
     let Ok((entity, mut transform, mut player)) = player_query.single_mut() else {
         return;
@@ -308,7 +579,7 @@ fn movement(
         transform.rotate(Quat::from_rotation_z(f32::to_radians(-2.0)));
         spawn_particle(&mut particles, &transform.local_x(), &transform);
     }
-    if keyboard.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]) {
+    if keyboard.any_pressed(THRUST_KEYS) {
         if player.fuel > 0 {
             impulses.write(Impulse {
                 target: entity,
@@ -317,7 +588,7 @@ fn movement(
                 source: 1,
             });
             spawn_particle(&mut particles, &transform.local_y(), &transform);
-            player.fuel -= 1;
+            player.fuel -= difficulty.fuel_per_thrust;
         }
     }
 }
@@ -334,25 +605,15 @@ fn cap_velocity(mut player_query: Query<&mut Velocity, With<Player>>) {
     }
 }
 
-fn camera_follow(
-    player_query: Query<&Transform, (With<Player>, Without<MyCamera>)>,
-    mut camera_query: Query<&mut Transform, (With<MyCamera>, Without<Player>)>,
-) {
-    let Ok(player) = player_query.single() else {
-        return;
-    };
-    let Ok(mut camera) = camera_query.single_mut() else {
-        return;
-    };
-    camera.translation = Vec3::new(player.translation.x, player.translation.y, 10.0);
-}
-
+#[allow(clippy::too_many_arguments)]
 fn bounce(
     mut collisions: EventReader<OnCollision<Player, Ground>>,
     mut player_query: Query<(&PhysicsPosition, &mut Player)>,
     ground_query: Query<&PhysicsPosition, With<Ground>>,
     mut impulses: EventWriter<Impulse>,
     mut particles: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
+    mut flash: EventWriter<Flash>,
     mut state: ResMut<NextState<GamePhase>>,
 ) {
     let mut bounce = Vec2::default();
@@ -362,7 +623,7 @@ fn bounce(
         if let Ok((player_pos, _)) = player_query.single_mut() {
             if let Ok(ground) = ground_query.get(collision.entity_b) {
                 entity = Some(collision.entity_a);
-                let difference = player_pos.start_frame - ground.start_frame;
+                let difference = player_pos.separation_from(ground);
                 bounces += 1;
                 bounce += difference;
             }
@@ -381,63 +642,133 @@ fn bounce(
             return;
         };
         particle_burst(
-            player_pos.end_frame,
+            player_pos.current(),
             LinearRgba::new(0.0, 0.0, 1.0, 1.0),
             &mut particles,
             3.0,
+            &settings,
         );
         player.shields -= 1;
+        flash.write(Flash {
+            target: entity.unwrap(),
+        });
         if player.shields <= 0 {
             state.set(GamePhase::GameOver);
         }
     }
 }
 
-fn spawn_particle_system(
-    mut commands: Commands,
-    mut reader: EventReader<SpawnParticle>,
+/// Tags the looping sound of the thruster
+#[derive(Component)]
+struct ThrusterSound;
+
+/// Plays the thruster sound while the ship is thrusting
+fn thruster_sound(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    player: Query<&Player>,
+    sounds: Query<Entity, With<ThrusterSound>>,
     assets: Res<AssetStore>,
     loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
 ) {
-    for particle in reader.read() {
-        let mut sprite = Sprite::from_image(assets.get_handle("particle", &loaded_assets).unwrap());
-        sprite.color = particle.color.into();
+    let thrusting =
+        keyboard.any_pressed(THRUST_KEYS) && player.single().is_ok_and(|player| player.fuel > 0);
+    if thrusting && sounds.is_empty() {
+        assets.play_loop(
+            "thruster",
+            &mut commands,
+            &loaded_assets,
+            (ThrusterSound, GameElement),
+        );
+    } else if !thrusting {
+        despawn_tagged(&mut commands, &sounds);
+    }
+}
 
-        commands.spawn((
-            sprite,
-            Transform::from_xyz(particle.position.x, particle.position.y, 5.0),
-            GameElement,
-            Particle { lifetime: 2.0 },
-            Velocity(particle.velocity),
-            PhysicsPosition::new(particle.position),
-        ));
+/// Number of simultaneous ground contacts for the loudest impact sound
+const LOUDEST_IMPACT: f32 = 4.0;
+
+/// Plays an impact sound whenever the ship hits the ground. It gets louder
+/// with the number of tiles hit.
+fn impact_sound(
+    mut collisions: EventReader<OnCollision<Player, Ground>>,
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
+) {
+    let hits = collisions.read().count();
+    if hits > 0 {
+        let volume = (hits as f32 / LOUDEST_IMPACT).clamp(0.25, 1.0);
+        assets.play_at_volume("impact", volume, &mut commands, &loaded_assets);
     }
 }
 
-fn particle_age_system(
-    time: Res<Time>,
+fn game_over_sound(
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Particle, &mut Sprite)>,
 ) {
-    for (entity, mut particle, mut sprite) in query.iter_mut() {
-        particle.lifetime -= time.delta_secs();
-        if particle.lifetime <= 0.0 {
-            commands.entity(entity).despawn();
+    assets.play("game_over", &mut commands, &loaded_assets);
+}
+
+/// Shakes the camera whenever the ship hits the ground
+fn shake_on_bounce(
+    mut collisions: EventReader<OnCollision<Player, Ground>>,
+    mut cameras: Query<&mut CameraShake>,
+) {
+    if collisions.read().count() > 0 {
+        for mut shake in cameras.iter_mut() {
+            shake.add_trauma(0.4);
         }
+    }
+}
 
-        sprite.color.set_alpha(particle.lifetime / 2.0);
+/// Shows the ship's shield level in the bar above it
+fn shield_bar(mut player: Query<(&Player, &mut BarValue)>) {
+    for (player, mut value) in player.iter_mut() {
+        value.0 = player.shields as f32 / FULL_SHIELDS as f32;
     }
 }
 
+/// Shows the requested particles, reusing the entities of the pool
+fn spawn_particle_system(
+    mut commands: Commands,
+    mut reader: EventReader<SpawnParticle>,
+    mut pool: ResMut<ParticlePool>,
+    assets: AssetHandles,
+) {
+    let Some(image) = assets.get("particle") else {
+        return;
+    };
+    for particle in reader.read() {
+        let mut sprite = Sprite::from_image(image.clone());
+        sprite.color = particle.color.into();
+
+        pool.spawn(
+            &mut commands,
+            Particle::new(
+                2.0,
+                particle.velocity.truncate() * PHYSICS_TICKS_PER_SECOND,
+                ParticleOptions::new(particle.color).fading_to(particle.color.with_alpha(0.0)),
+            ),
+            Transform::from_xyz(particle.position.x, particle.position.y, 5.0),
+            sprite,
+            GameElement,
+        );
+    }
+}
+
+/// Spawns a ring of particles flying away from the center, as many as the
+/// particle quality allows
 fn particle_burst(
     center: Vec2,
     color: LinearRgba,
     spawn: &mut EventWriter<SpawnParticle>,
     velocity: f32,
+    settings: &GameSettings,
 ) {
-    for angle in 0..360 {
-        let angle = (angle as f32).to_radians();
-        let velocity = Vec3::new(angle.cos() * velocity, angle.sin() * velocity, 0.0);
+    for direction in unit_circle_points(settings.particles.burst_size()) {
+        let velocity = (*direction * velocity).extend(0.0);
         spawn.write(SpawnParticle {
             position: center,
             color,
@@ -447,68 +778,308 @@ fn particle_burst(
 }
 
 fn miner_beacon(
-    mut rng: ResMut<RandomNumberGenerator>,
-    miners: Query<&Transform, With<Miner>>,
+    time: Res<Time>,
+    mut miners: Query<(&Transform, &mut PeriodicEffect), With<Miner>>,
     mut spawn: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
 ) {
-    for miner in miners.iter() {
-        if rng.range(0..100) == 0 {
+    for (miner, mut beacon) in miners.iter_mut() {
+        for _ in 0..beacon.tick(time.delta()) {
             particle_burst(
                 miner.translation.truncate(),
                 LinearRgba::new(1.0, 1.0, 0.0, 1.0),
                 &mut spawn,
                 10.0,
+                &settings,
             );
         }
     }
 }
 
+/// Converts velocities per physics tick into velocities per second
+const PHYSICS_TICKS_PER_SECOND: f32 = 1000.0 / 33.0;
+/// Most particles shown at the same time
+const MAX_PARTICLES: usize = 3000;
+/// Seconds between two flashes of a miner's beacon
+const BEACON_INTERVAL: f32 = 1.5;
+/// Orthographic scale of the camera when the game starts
+const DEFAULT_ZOOM: f32 = 0.5;
+/// Shield level of the ship when the game starts
+const FULL_SHIELDS: i32 = 500;
+/// Fuel of the ship when the game starts, pickups may fill it beyond
+const FULL_FUEL: i32 = 10_000;
 const WORLD_SIZE: usize = 200;
 const TOP_MARGIN: f32 = 60.0;
 
-fn spawn_builder() {
-    use std::sync::atomic::Ordering;
+/// Algorithm carving the caves of the world
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+enum CaveAlgorithm {
+    /// Connects random holes by tunnels, then widens the caves from within
+    Diffusion,
+    /// Smooths random noise into caves. An open tile turns solid with at
+    /// least `birth` solid neighbors, a solid one stays solid with at least
+    /// `survive`.
+    CellularAutomata {
+        iterations: usize,
+        birth: usize,
+        survive: usize,
+    },
+}
 
-    WORLD_READY.store(false, Ordering::Relaxed);
+impl CaveAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            CaveAlgorithm::Diffusion => "Diffusion",
+            CaveAlgorithm::CellularAutomata { .. } => "Cellular automata",
+        }
+    }
+}
 
-    // Start a new world-building thread. This thread runs outside of
-    // Bevy's systems, and has no access to Bevy's DI container
-    std::thread::spawn(|| {
-        // Give the thread its own rng. So no unsafe reference must be hold
-        // between frames
-        let mut rng = my_library::RandomNumberGenerator::new();
-        // Spawn the world
-        info!("Start building the world.");
+/// Parameters for generating the world, set in the world generation menu
+#[derive(serde::Serialize, serde::Deserialize, Resource, Clone, Copy, Debug, PartialEq)]
+struct WorldGenConfig {
+    /// Width and height of the world in tiles
+    size: usize,
+    /// Fraction of solid tiles the caves are carved down to
+    solid_fraction: f32,
+    /// Number of random caves connected by tunnels, used by diffusion
+    holes: usize,
+    algorithm: CaveAlgorithm,
+    /// Fraction of the miners to rescue, lower for an easier game
+    rescue_fraction: f32,
+}
 
-        let mut world = World::new(WORLD_SIZE, WORLD_SIZE, &mut rng);
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        Self {
+            size: WORLD_SIZE,
+            solid_fraction: SOLID_PERCENT,
+            holes: 10,
+            algorithm: CaveAlgorithm::Diffusion,
+            rescue_fraction: 1.0,
+        }
+    }
+}
 
-        // Shuffle possible miner positions and limit the size to 20
-        use my_library::rand::seq::SliceRandom;
-        world.spawn_positions.shuffle(&mut rng.rng);
+/// How many particles the effects show, lower for slower machines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ParticleQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
 
-        // Swap the world getting exclusive access to its mutex
-        let mut lock = NEW_WORD.lock().unwrap();
-        *lock = Some(world);
+impl ParticleQuality {
+    fn name(&self) -> &'static str {
+        match self {
+            ParticleQuality::Low => "Low",
+            ParticleQuality::Medium => "Medium",
+            ParticleQuality::High => "High",
+        }
+    }
 
-        info!("Finished building the world.");
+    /// Number of particles in a burst
+    fn burst_size(&self) -> usize {
+        match self {
+            ParticleQuality::Low => 90,
+            ParticleQuality::Medium => 180,
+            ParticleQuality::High => 360,
+        }
+    }
+}
 
-        // Notify of successful finished generation
-        WORLD_READY.store(true, Ordering::Relaxed);
+/// Settings of the game, changed in the main menu
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+struct GameSettings {
+    particles: ParticleQuality,
+}
+
+/// Lets the player change the settings in the main menu
+fn settings_menu(mut settings: ResMut<GameSettings>, mut egui_context: egui::EguiContexts) {
+    egui::egui::Window::new("Settings").show(egui_context.ctx_mut(), |ui| {
+        egui::egui::ComboBox::from_label("Particles")
+            .selected_text(settings.particles.name())
+            .show_ui(ui, |ui| {
+                for option in [
+                    ParticleQuality::Low,
+                    ParticleQuality::Medium,
+                    ParticleQuality::High,
+                ] {
+                    ui.selectable_value(&mut settings.particles, option, option.name());
+                }
+            });
     });
 }
 
-fn show_builder(mut state: ResMut<NextState<GamePhase>>, mut egui_context: egui::EguiContexts) {
-    egui::egui::Window::new("Performance").show(egui_context.ctx_mut(), |ui| {
-        ui.label("Building World");
+fn spawn_builder(commands: &mut Commands, seed: WorldSeed, config: WorldGenConfig) {
+    // Start building the world in the background. The task runs outside of
+    // Bevy's systems, and has no access to Bevy's DI container. It reports
+    // its progress through a shared clone.
+    let progress = BuildProgress::default();
+    let builder = progress.clone();
+    commands.insert_resource(progress);
+    commands.insert_resource(BackgroundTask::spawn(move || {
+        build_world(seed, &config, &builder)
+    }));
+}
+
+/// Builds the world that belongs to a seed, reporting the progress
+fn build_world(seed: WorldSeed, config: &WorldGenConfig, progress: &BuildProgress) -> World {
+    // Give the task its own rng. So no unsafe reference must be hold
+    // between frames
+    let mut rng = my_library::RandomNumberGenerator::seeded(seed.0);
+    // Spawn the world
+    info!("Start building the world from seed {}.", seed.0);
+
+    let mut world = World::new(config, &mut rng, progress);
+
+    // Shuffle possible miner positions and limit the size to 20
+    use my_library::rand::seq::SliceRandom;
+    world.spawn_positions.shuffle(&mut rng.rng);
+
+    info!("Finished building the world.");
+    progress.report(BuildPhase::Done, 1.0);
+    world
+}
+
+/// Lets the player tweak the caves before the world is built, unless a saved
+/// run is continued
+#[allow(clippy::too_many_arguments)]
+fn world_gen_menu(
+    mut config: ResMut<WorldGenConfig>,
+    mut seed: ResMut<WorldSeed>,
+    mut difficulty: ResMut<DifficultyConfig>,
+    mut code_input: Local<(String, Option<String>)>,
+    builder: Option<Res<BackgroundTask<World>>>,
+    loaded_run: Option<Res<LoadedRun>>,
+    mut commands: Commands,
+    mut egui_context: egui::EguiContexts,
+) {
+    if builder.is_some() {
+        return;
+    }
+    // A continued run rebuilds its saved terrain instead
+    if let Some(run) = loaded_run {
+        let tiles = run.0.tiles();
+        let progress = BuildProgress::default();
+        let builder = progress.clone();
+        commands.insert_resource(progress);
+        commands.insert_resource(BackgroundTask::spawn(move || {
+            builder.report(BuildPhase::Meshing, 0.0);
+            let world = World::from_tiles(tiles);
+            builder.report(BuildPhase::Done, 1.0);
+            world
+        }));
+        return;
+    }
+    egui::egui::Window::new("World Generation").show(egui_context.ctx_mut(), |ui| {
+        let config = config.as_mut();
+        ui.add(egui::egui::Slider::new(&mut config.size, 100..=300).text("World size"));
+        ui.add(
+            egui::egui::Slider::new(&mut config.solid_fraction, 0.3..=0.8).text("Solid fraction"),
+        );
+        egui::egui::ComboBox::from_label("Algorithm")
+            .selected_text(config.algorithm.name())
+            .show_ui(ui, |ui| {
+                for option in [
+                    CaveAlgorithm::Diffusion,
+                    CaveAlgorithm::CellularAutomata {
+                        iterations: 5,
+                        birth: 5,
+                        survive: 4,
+                    },
+                ] {
+                    let selected = config.algorithm.name() == option.name();
+                    if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                        config.algorithm = option;
+                    }
+                }
+            });
+        match &mut config.algorithm {
+            CaveAlgorithm::Diffusion => {
+                ui.add(egui::egui::Slider::new(&mut config.holes, 1..=30).text("Caves"));
+            }
+            CaveAlgorithm::CellularAutomata {
+                iterations,
+                birth,
+                survive,
+            } => {
+                ui.add(egui::egui::Slider::new(iterations, 0..=10).text("Iterations"));
+                ui.add(egui::egui::Slider::new(birth, 0..=8).text("Birth neighbors"));
+                ui.add(egui::egui::Slider::new(survive, 0..=8).text("Survive neighbors"));
+            }
+        }
+        ui.add(
+            egui::egui::Slider::new(&mut config.rescue_fraction, 0.25..=1.0)
+                .text("Miners to rescue"),
+        );
+        ui.label(format!("Seed: {}", seed.0));
+        let (input, error) = &mut *code_input;
+        level_code_ui(ui, &mut seed, config, &mut difficulty, input, error);
+        if ui.button("Build World").clicked() {
+            spawn_builder(&mut commands, *seed, *config);
+        }
     });
-    if WORLD_READY.load(Ordering::Relaxed) {
-        state.set(GamePhase::Playing);
+}
+
+/// Rolls a new seed for the game starting, unless the player pinned one or a
+/// saved run is continued
+fn reroll_seed(
+    mut seed: ResMut<WorldSeed>,
+    pinned: Res<PinnedSeed>,
+    loaded_run: Option<Res<LoadedRun>>,
+) {
+    if !pinned.0 && loaded_run.is_none() {
+        seed.0 = RandomNumberGenerator::new().next();
+    }
+}
+
+/// Parses the seed entered by the player. An empty input is no seed yet.
+fn parse_seed(text: &str) -> Result<Option<u64>, String> {
+    if text.is_empty() {
+        return Ok(None);
     }
+    text.parse()
+        .map(Some)
+        .map_err(|_| format!("The seed must not be larger than {}", u64::MAX))
+}
+
+/// Lets the player enter the seed of the next world in the main menu. A seed
+/// entered is pinned, and kept for the following games.
+fn seed_entry(
+    mut seed: ResMut<WorldSeed>,
+    mut pinned: ResMut<PinnedSeed>,
+    mut input: Local<String>,
+    mut egui_context: egui::EguiContexts,
+) {
+    // Show seeds changed elsewhere, e.g. rolled for the last game
+    if seed.is_changed() && parse_seed(&input) != Ok(Some(seed.0)) {
+        *input = seed.0.to_string();
+    }
+    egui::egui::Window::new("World Seed").show(egui_context.ctx_mut(), |ui| {
+        if egui::validated_text_input(ui, &mut input, 20, |c| c.is_ascii_digit()).changed() {
+            // Keep the last valid seed while the input is empty or too large
+            if let Ok(Some(entered)) = parse_seed(&input) {
+                seed.set_if_neq(WorldSeed(entered));
+                pinned.0 = true;
+            }
+        }
+        if let Err(error) = parse_seed(&input) {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.checkbox(&mut pinned.0, "Keep this seed");
+        if ui.button("Random Seed").clicked() {
+            seed.0 = RandomNumberGenerator::new().next();
+            pinned.0 = false;
+        }
+    });
 }
 
 fn show_performance(
     mut egui_context: egui::EguiContexts,
     diagnostics: Res<DiagnosticsStore>, // get bevys diagnostic informations as a resource from DI
+    particles: Res<ParticlePool>,
 ) {
     let fps = diagnostics // get diagnostical information about the average fps of recent frames
         .get(&FrameTimeDiagnosticsPlugin::FPS)
@@ -524,62 +1095,114 @@ fn show_performance(
             _ => Color32::GREEN,
         };
         ui.colored_label(color, &fps_text);
+        ui.label(format!(
+            "Particles: {} / {}",
+            particles.live(),
+            particles.cap()
+        ));
     });
 }
 
-fn score_display(player: Query<&Player>, mut egui_context: egui::EguiContexts) {
+/// HUD bars are green above this fraction of their full level
+const HUD_GOOD_FRACTION: f32 = 0.5;
+/// HUD bars turn red and flash below this fraction, the low-fuel warning
+/// sounds when the fuel drops below it
+const HUD_CRITICAL_FRACTION: f32 = 0.15;
+const HUD_GOOD_COLOR: Color32 = Color32::GREEN;
+const HUD_WARNING_COLOR: Color32 = Color32::YELLOW;
+const HUD_CRITICAL_COLOR: Color32 = Color32::RED;
+/// Color of critical bars while flashing
+const HUD_FLASH_COLOR: Color32 = Color32::DARK_RED;
+/// How often critical bars flash per second
+const HUD_FLASH_RATE: f32 = 2.0;
+
+/// Fraction of a full level, capped to 0.0..=1.0
+fn hud_fraction(value: i32, full: i32) -> f32 {
+    egui::stat_fraction(value as f32, full as f32)
+}
+
+/// Color of a HUD bar, depending on how full it is
+fn hud_color(fraction: f32) -> Color32 {
+    if fraction < HUD_CRITICAL_FRACTION {
+        HUD_CRITICAL_COLOR
+    } else if fraction < HUD_GOOD_FRACTION {
+        HUD_WARNING_COLOR
+    } else {
+        HUD_GOOD_COLOR
+    }
+}
+
+/// Shows a bar, critical bars flash over time
+fn hud_bar(ui: &mut egui::egui::Ui, label: &str, value: i32, full: i32, elapsed_secs: f32) {
+    let fraction = hud_fraction(value, full);
+    let flash = (elapsed_secs * HUD_FLASH_RATE * 2.0) as u32 % 2 == 1;
+    let color = if fraction < HUD_CRITICAL_FRACTION && flash {
+        HUD_FLASH_COLOR
+    } else {
+        hud_color(fraction)
+    };
+    egui::stat_bar(ui, label, value as f32, full as f32, color);
+}
+
+fn hud_display(
+    player: Query<&Player>,
+    mission: Res<MissionState>,
+    time: Res<Time>,
+    mut egui_context: egui::EguiContexts,
+) {
     let Ok(player) = player.single() else {
         return;
     };
     egui::egui::Window::new("Score").show(egui_context.ctx_mut(), |ui| {
-        ui.label(format!("Score: {}", player.score));
-        ui.label(format!("Miners Saved: {}", player.miners_saved));
-        ui.label(format!("Shields: {}", player.shields));
-        ui.label(format!("Fuel: {}", player.fuel));
+        ui.label(
+            egui::egui::RichText::new(format!("Score: {}", player.score))
+                .size(20.0)
+                .strong(),
+        );
+        ui.label(
+            egui::egui::RichText::new(format!(
+                "{} / {} miners rescued",
+                player.miners_saved, mission.total
+            ))
+            .color(MarkerKind::Miner.color()),
+        );
+        if mission.required < mission.total {
+            ui.label(format!("{} needed for the mission", mission.required));
+        }
+        let elapsed = time.elapsed_secs();
+        hud_bar(ui, "Fuel", player.fuel, FULL_FUEL, elapsed);
+        hud_bar(ui, "Shields", player.shields, FULL_SHIELDS, elapsed);
     });
 }
 
-/// Emits a FinalScore event with the player's score to subsequent game phases
-fn submit_score(player: Query<&Player>, mut final_score: EventWriter<FinalScore>) {
-    for player in player.iter() {
-        final_score.write(FinalScore(player.score));
+/// Notices when a level drops below a threshold, once per crossing
+#[derive(Default)]
+struct ThresholdCrossing {
+    below: bool,
+}
+
+impl ThresholdCrossing {
+    /// Returns true if the fraction just dropped below the threshold
+    fn update(&mut self, fraction: f32, threshold: f32) -> bool {
+        let was_below = self.below;
+        self.below = fraction < threshold;
+        self.below && !was_below
     }
 }
 
-/// Receives FinalScore events and displays the score to the user
-fn final_score(
-    mut final_score: EventReader<FinalScore>,
-    mut state: Local<ScoreState>,
-    mut egui_context: egui::EguiContexts,
+/// Sounds a warning when the fuel runs low
+fn low_fuel_warning(
+    player: Query<&Player>,
+    mut crossing: Local<ThresholdCrossing>,
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
 ) {
-    // Set the final score to the last received message
-    for score in final_score.read() {
-        state.score = Some(score.0);
-    }
-    if state.submitted {
+    let Ok(player) = player.single() else {
         return;
-    }
-
-    // Display the score window with text input element
-    if let Some(score) = state.score {
-        egui::egui::Window::new("Final Score").show(egui_context.ctx_mut(), |ui| {
-            ui.label(format!("Final score: {}", score));
-            ui.label("Please enter your name:");
-            ui.text_edit_singleline(&mut state.player_name);
-            if ui.button("Submit Score").clicked() {
-                state.submitted = true;
-                let entry = HighScoreEntry {
-                    name: state.player_name.clone(),
-                    score,
-                };
-                std::thread::spawn(move || {
-                    ureq::post("http://localhost:3030/submit-score")
-                        .timeout(std::time::Duration::from_secs(5))
-                        .send_json(entry)
-                        .expect("Failed to submit score");
-                });
-            }
-        });
+    };
+    if crossing.update(hud_fraction(player.fuel, FULL_FUEL), HUD_CRITICAL_FRACTION) {
+        assets.play("low_fuel", &mut commands, &loaded_assets);
     }
 }
 
@@ -620,105 +1243,295 @@ fn highscore_table(mut state: Local<HighScoreTableState>, mut egui_context: egui
 
 /// Defines the world by a 2d-matrix of tiles.
 struct World {
-    /// If a tile is a solid wall for each given index
-    solid: Vec<bool>,
-    /// Horizontal map size
-    width: usize,
-    /// Vertical map size
-    height: usize,
-    /// The mesh representing each tile
-    mesh: Option<Mesh>,
-    /// The position of each tile
+    /// Which tiles are solid walls
+    tiles: TileGrid,
+    /// The meshes representing the tiles, one per chunk
+    chunks: Vec<(TileChunk, Mesh)>,
+    /// The position of each tile that needs physics
     tile_positions: Vec<(f32, f32)>,
     /// Positions on which entites can be spawned
     spawn_positions: Vec<(f32, f32)>,
+    /// Centers of the landing pads
+    pads: Vec<(f32, f32)>,
+    /// Overview of the tiles, shown in a corner of the screen
+    minimap: Option<Minimap>,
 }
 
 const TILE_SIZE: f32 = 24.0;
+/// Number of tiles along each side of a terrain chunk
+const CHUNK_SIZE: usize = 32;
 const SOLID_PERCENT: f32 = 0.6;
-
-static WORLD_READY: AtomicBool = AtomicBool::new(false);
-static NEW_WORD: Mutex<Option<World>> = Mutex::new(None);
+/// Unreachable caves with fewer tiles are filled instead of connected
+const MIN_CAVE_SIZE: usize = 20;
+/// Random picks of a tile, before a solid one is searched among all tiles
+const CLOSED_TILE_ATTEMPTS: usize = 100;
 
 impl World {
-    /// Calculates the 1d index for a given cell in the 2d matrix
-    fn map_idx(&self, x: usize, y: usize) -> usize {
-        y * self.width + x
-    }
-
     /// Creates a new world
-    fn new(width: usize, height: usize, rng: &mut RandomNumberGenerator) -> Self {
+    fn new(
+        config: &WorldGenConfig,
+        rng: &mut RandomNumberGenerator,
+        progress: &BuildProgress,
+    ) -> Self {
+        let (width, height) = (config.size, config.size);
         let mut result = Self {
-            width,
-            height,
-            solid: vec![true; width * height],
-            mesh: None,
+            tiles: TileGrid::new(width, height, TILE_SIZE, true),
+            chunks: Vec::new(),
             tile_positions: Vec::new(),
             spawn_positions: Vec::new(),
+            pads: Vec::new(),
+            minimap: None,
+        };
+
+        match config.algorithm {
+            CaveAlgorithm::Diffusion => result.diffusion(config, progress, rng),
+            CaveAlgorithm::CellularAutomata {
+                iterations,
+                birth,
+                survive,
+            } => result.cellular_automata(config, iterations, birth, survive, progress, rng),
+        }
+        progress.report(BuildPhase::Connecting, 0.0);
+        result.connect_caves();
+        progress.report(BuildPhase::Connecting, 0.5);
+        result.ensure_landing_pad();
+
+        progress.report(BuildPhase::Meshing, 0.0);
+        Self::from_tiles(result.tiles)
+    }
+
+    /// Creates a world from existing tiles, e.g. of a saved run
+    fn from_tiles(tiles: TileGrid) -> Self {
+        let grid = tiles.coords();
+        let mut result = Self {
+            chunks: tiles.build_chunk_meshes(CHUNK_SIZE, TileUv::PerTile),
+            tile_positions: tiles
+                .edge_tiles()
+                .map(|tile| grid.tile_to_world(tile).into())
+                .collect(),
+            spawn_positions: Vec::new(),
+            pads: Vec::new(),
+            minimap: Some(Minimap::new(&tiles)),
+            tiles,
         };
+        result.spawn_positions = result.miner_positions();
+        result.pads = result.pad_positions();
+        info!("{} tiles need physics", result.tile_positions.len());
+
+        result
+    }
+
+    /// Tile at the top of the entry shaft, where the ship enters the caves
+    fn entry(&self) -> (usize, usize) {
+        (self.tiles.width() / 2, self.tiles.height() - 1)
+    }
 
-        result.clear_tiles(width / 2, height / 2);
+    /// Opens the central cave and the shaft above it
+    fn clear_entry(&mut self) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        for y in height / 2..height {
+            self.clear_tiles(width / 2, y);
+        }
+    }
+
+    /// Connects random holes by tunnels and widens the caves from within
+    fn diffusion(
+        &mut self,
+        config: &WorldGenConfig,
+        progress: &BuildProgress,
+        rng: &mut RandomNumberGenerator,
+    ) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        self.clear_tiles(width / 2, height / 2);
 
         let mut holes = vec![(width / 2, height / 2)];
 
-        for _ in 0..10 {
+        for hole in 0..config.holes {
+            progress.report(BuildPhase::Carving, hole as f32 / config.holes as f32);
             let x = rng.range(5..width - 5);
             let y = rng.range(5..height - 5);
             holes.push((x, y));
-            result.clear_tiles(x, y);
-            result.clear_tiles(x + 2, y);
-            result.clear_tiles(x - 2, y);
-            result.clear_tiles(x, y + 2);
-            result.clear_tiles(x, y - 2);
+            self.clear_tiles(x, y);
+            self.clear_tiles(x + 2, y);
+            self.clear_tiles(x - 2, y);
+            self.clear_tiles(x, y + 2);
+            self.clear_tiles(x, y - 2);
         }
         for i in 0..holes.len() {
             let start = holes[i];
             let end = holes[(i + 1) % holes.len()];
-            result.clear_line(start, end);
+            self.clear_line(start, end);
         }
 
-        for y in height / 2..height {
-            result.clear_tiles(width / 2, y);
+        self.clear_entry();
+        self.outward_diffusion(&holes, config.solid_fraction, progress, rng);
+    }
+
+    /// Smooths random noise into caves. Smoothing and connecting the caves
+    /// opens them up, so the noise starts a bit more solid than the target.
+    fn cellular_automata(
+        &mut self,
+        config: &WorldGenConfig,
+        iterations: usize,
+        birth: usize,
+        survive: usize,
+        progress: &BuildProgress,
+        rng: &mut RandomNumberGenerator,
+    ) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        let fill = (config.solid_fraction + 0.1).min(1.0);
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                self.tiles.set_solid(x, y, rng.range(0.0..1.0) < fill);
+            }
         }
 
-        result.outward_diffusion(&holes, rng);
+        for iteration in 0..iterations {
+            progress.report(BuildPhase::Carving, iteration as f32 / iterations as f32);
+            let previous = self.tiles.clone();
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let neighbors = [
+                        (x - 1, y - 1),
+                        (x, y - 1),
+                        (x + 1, y - 1),
+                        (x - 1, y),
+                        (x + 1, y),
+                        (x - 1, y + 1),
+                        (x, y + 1),
+                        (x + 1, y + 1),
+                    ]
+                    .into_iter()
+                    .filter(|(x, y)| previous.is_solid(*x, *y))
+                    .count();
+                    let solid = if previous.is_solid(x, y) {
+                        neighbors >= survive
+                    } else {
+                        neighbors >= birth
+                    };
+                    self.tiles.set_solid(x, y, solid);
+                }
+            }
+        }
 
-        let (mesh, tile_positions, spawn_positions) = result.build_mesh();
-        result.mesh = Some(mesh);
-        result.tile_positions = tile_positions;
-        result.spawn_positions = spawn_positions;
+        self.clear_tiles(width / 2, height / 2);
+        self.clear_entry();
+        self.outward_diffusion(
+            &[(width / 2, height / 2)],
+            config.solid_fraction,
+            progress,
+            rng,
+        );
+    }
 
-        result
+    /// Finds all open tiles connected to `start`, marking them as seen
+    fn flood_fill(&self, start: (usize, usize), seen: &mut [bool]) -> Vec<(usize, usize)> {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        let mut cave = Vec::new();
+        let mut open = vec![start];
+        seen[start.1 * width + start.0] = true;
+        while let Some((x, y)) = open.pop() {
+            cave.push((x, y));
+            let neighbors = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbors {
+                if nx < width
+                    && ny < height
+                    && !seen[ny * width + nx]
+                    && !self.tiles.is_solid(nx, ny)
+                {
+                    seen[ny * width + nx] = true;
+                    open.push((nx, ny));
+                }
+            }
+        }
+        cave
     }
 
-    fn find_random_closed_tile(&self, rng: &mut RandomNumberGenerator) -> (usize, usize) {
-        loop {
-            let x = rng.range(0..self.width);
-            let y = rng.range(0..self.height);
-            let idx = self.map_idx(x, y);
-            if self.solid[idx] {
-                return (x, y);
+    /// Marks all open tiles that can be reached from the entry shaft
+    fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.tiles.width() * self.tiles.height()];
+        self.flood_fill(self.entry(), &mut seen);
+        seen
+    }
+
+    /// Makes every open tile reachable from the entry shaft. Caves that
+    /// can't be reached are joined to the central cave by a tunnel, small
+    /// pockets are filled.
+    fn connect_caves(&mut self) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        let mut seen = self.reachable();
+        let mut caves = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if !seen[y * width + x] && !self.tiles.is_solid(x, y) {
+                    caves.push(self.flood_fill((x, y), &mut seen));
+                }
+            }
+        }
+        for cave in caves.iter().filter(|cave| cave.len() >= MIN_CAVE_SIZE) {
+            self.clear_line(cave[0], (width / 2, height / 2));
+        }
+
+        let reachable = self.reachable();
+        for y in 0..height {
+            for x in 0..width {
+                if !reachable[y * width + x] {
+                    self.tiles.set_solid(x, y, true);
+                }
             }
         }
     }
 
-    fn outward_diffusion(&mut self, holes: &Vec<(usize, usize)>, rng: &mut RandomNumberGenerator) {
+    /// Picks a random solid tile, or `None` if no tile is solid anymore
+    fn find_random_closed_tile(&self, rng: &mut RandomNumberGenerator) -> Option<(usize, usize)> {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        for _ in 0..CLOSED_TILE_ATTEMPTS {
+            let x = rng.range(0..width);
+            let y = rng.range(0..height);
+            if self.tiles.is_solid(x, y) {
+                return Some((x, y));
+            }
+        }
+        // Mostly open worlds rarely hit a solid tile by chance
+        let solid: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|(x, y)| self.tiles.is_solid(*x, *y))
+            .collect();
+        (!solid.is_empty()).then(|| solid[rng.range(0..solid.len())])
+    }
+
+    /// Widens the caves from the holes, until at most `solid_fraction` of
+    /// the tiles are solid
+    fn outward_diffusion(
+        &mut self,
+        holes: &[(usize, usize)],
+        solid_fraction: f32,
+        progress: &BuildProgress,
+        rng: &mut RandomNumberGenerator,
+    ) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
         let mut done = false;
         while !done {
-            let start_tile = holes[rng.range(0..10)];
-            let target = self.find_random_closed_tile(rng);
+            let start_tile = holes[rng.range(0..holes.len())];
+            let Some(target) = self.find_random_closed_tile(rng) else {
+                break;
+            };
 
             let (mut x, mut y) = (start_tile.0 as f32, start_tile.1 as f32);
             let (slope_x, slope_y) = (
-                (target.0 as f32 - x) / self.width as f32,
-                (target.1 as f32 - y) / self.height as f32,
+                (target.0 as f32 - x) / width as f32,
+                (target.1 as f32 - y) / height as f32,
             );
             loop {
-                if x < 1.0 || x >= self.width as f32 || y < 1.0 || y >= self.height as f32 {
+                if x < 1.0 || x >= width as f32 || y < 1.0 || y >= height as f32 {
                     break;
                 }
-                let tile_id = self.map_idx(x as usize, y as usize);
-                if self.solid[tile_id] {
+                if self.tiles.is_solid(x as usize, y as usize) {
                     self.clear_tiles(x as usize, y as usize);
                     break;
                 }
@@ -726,15 +1539,19 @@ impl World {
                 y += slope_y;
             }
 
-            let solid_count = self.solid.iter().filter(|s| **s).count();
-            let solid_percent = solid_count as f32 / (self.width * self.height) as f32;
-            if solid_percent < SOLID_PERCENT {
+            let solid_count = self.tiles.solid_count();
+            let solid_percent = solid_count as f32 / (width * height) as f32;
+            progress.report(
+                BuildPhase::Eroding,
+                (1.0 - solid_percent) / (1.0 - solid_fraction),
+            );
+            if solid_percent < solid_fraction {
                 done = true;
             }
         }
     }
 
-    /// Spawns the world into the game
+    /// Spawns the terrain of the world into the game
     fn spawn(
         &self,
         assets: &AssetStore,
@@ -743,75 +1560,23 @@ impl World {
         meshes: &mut Assets<Mesh>,
         materials: &mut Assets<ColorMaterial>,
     ) {
-        let mesh = self.mesh.as_ref().unwrap().clone();
-        let mesh_handle = meshes.add(mesh);
-        let material_handle = materials.add(ColorMaterial {
-            texture: Some(assets.get_handle("ground", loaded_assets).unwrap()),
-            ..default()
-        });
-        commands
-            .spawn(Mesh2d(mesh_handle))
-            .insert(MeshMaterial2d(material_handle))
-            .insert(Transform::from_xyz(0.0, 0.0, 0.0));
-
-        for (x, y) in self.tile_positions.iter() {
-            commands
-                .spawn_empty()
-                .insert(GameElement)
-                .insert(Ground)
-                .insert(PhysicsPosition::new(Vec2::new(*x, *y)))
-                .insert(AxisAlignedBoundingBox::new(TILE_SIZE, TILE_SIZE));
-        }
-        for (x, y) in self.spawn_positions.iter().take(20) {
-            spawn_image!(
-                assets,
-                commands,
-                "spaceman",
-                *x,
-                *y,
-                10.0,
-                loaded_assets,
-                GameElement,
-                Miner,
-                Velocity::default(),
-                PhysicsPosition::new(Vec2::new(*x, *y)),
-                AxisAlignedBoundingBox::new(48.0, 48.0)
-            );
-        }
-
-        for (x, y) in self.spawn_positions.iter().skip(20).take(20) {
-            spawn_image!(
-                assets,
-                commands,
-                "fuel",
-                *x,
-                *y,
-                10.0,
-                loaded_assets,
-                GameElement,
-                Fuel,
-                Velocity::default(),
-                PhysicsPosition::new(Vec2::new(*x, *y)),
-                AxisAlignedBoundingBox::new(48.0, 48.0)
-            );
-        }
+        spawn_tile_chunks(
+            commands,
+            self.chunks.clone(),
+            assets,
+            loaded_assets,
+            meshes,
+            materials,
+            "ground",
+            (GameElement, Transform::from_xyz(0.0, 0.0, 1.0)),
+        );
 
-        for (x, y) in self.spawn_positions.iter().skip(40).take(20) {
-            spawn_image!(
-                assets,
-                commands,
-                "battery",
-                *x,
-                *y,
-                10.0,
-                loaded_assets,
-                GameElement,
-                Battery,
-                Velocity::default(),
-                PhysicsPosition::new(Vec2::new(*x, *y)),
-                AxisAlignedBoundingBox::new(48.0, 48.0)
-            );
-        }
+        spawn_colliders(
+            commands,
+            self.tile_positions.iter().map(|(x, y)| Vec2::new(*x, *y)),
+            Vec2::splat(TILE_SIZE),
+            (GameElement, Ground),
+        );
     }
 
     fn clear_tiles(&mut self, x: usize, y: usize) {
@@ -821,23 +1586,27 @@ impl World {
                 let y = y as isize + offset_y;
 
                 // The checks ensure that there will always be a solid one-cell border around the map
-                if 0 < x && x < self.width as isize - 1 && 0 < y && y < self.height as isize {
-                    let idx = self.map_idx(x as usize, y as usize);
-                    self.solid[idx] = false;
+                if 0 < x
+                    && x < self.tiles.width() as isize - 1
+                    && 0 < y
+                    && y < self.tiles.height() as isize
+                {
+                    self.tiles.set_solid(x as usize, y as usize, false);
                 }
             }
         }
     }
 
     fn clear_line(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
         let (mut x, mut y) = (start.0 as f32, start.1 as f32);
         let (slope_x, slope_y) = (
-            (end.0 as f32 - x) / self.width as f32,
-            (end.1 as f32 - y) / self.height as f32,
+            (end.0 as f32 - x) / width as f32,
+            (end.1 as f32 - y) / height as f32,
         );
         loop {
             let (tx, ty) = (x as usize, y as usize);
-            if tx < 1 || tx >= self.width || ty < 1 || ty >= self.height {
+            if tx < 1 || tx >= width || ty < 1 || ty >= height {
                 break;
             }
             if tx == end.0 && ty == end.1 {
@@ -849,91 +1618,233 @@ impl World {
         }
     }
 
-    fn build_mesh(&self) -> (Mesh, Vec<(f32, f32)>, Vec<(f32, f32)>) {
-        let mut position = Vec::new();
-        let mut uv = Vec::new();
-        let mut tile_positions = Vec::new();
+    /// Finds open tiles right above solid ground, on which miners can stand
+    fn miner_positions(&self) -> Vec<(f32, f32)> {
+        let (width, height) = (self.tiles.width(), self.tiles.height());
+        let grid = self.tiles.coords();
         let mut possible_miner_positions = Vec::new();
+        for y in 2..height - 3 {
+            for x in 2..width - 3 {
+                if !self.tiles.is_solid(x, y) && self.tiles.is_solid(x, y - 1) {
+                    possible_miner_positions.push(grid.tile_to_world((x, y)).into());
+                }
+            }
+        }
+        possible_miner_positions
+    }
+}
 
-        let x_offset = self.width as f32 / 2.0 * TILE_SIZE;
-        let y_offset = self.height as f32 / 2.0 * TILE_SIZE;
-
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let left = x as f32 * TILE_SIZE - x_offset;
-                let right = (x as f32 + 1.0) * TILE_SIZE - x_offset;
-                let top = y as f32 * TILE_SIZE - y_offset;
-                let bottom = (y as f32 + 1.0) * TILE_SIZE - y_offset;
-                if self.solid[self.map_idx(x, y)] {
-                    position.push([left, bottom, 1.0]);
-                    position.push([right, bottom, 1.0]);
-                    position.push([right, top, 1.0]);
-                    position.push([right, top, 1.0]);
-                    position.push([left, bottom, 1.0]);
-                    position.push([left, top, 1.0]);
-
-                    uv.push([0.0, 1.0]);
-                    uv.push([1.0, 1.0]);
-                    uv.push([1.0, 0.0]);
-                    uv.push([1.0, 0.0]);
-                    uv.push([0.0, 1.0]);
-                    uv.push([0.0, 0.0]);
-
-                    let needs_physics;
-
-                    // Only enable physics on tiles that are on the edge or not
-                    // completely surronded by solid tiles
-
-                    if x == 0 || x > self.width - 3 || y == 0 || y > self.height - 3 {
-                        needs_physics = true;
-                    } else {
-                        let solid_count = self.solid[self.map_idx(x - 1, y)] as u8
-                            + self.solid[self.map_idx(x + 1, y)] as u8
-                            + self.solid[self.map_idx(x, y - 1)] as u8
-                            + self.solid[self.map_idx(x, y + 1)] as u8;
-
-                        needs_physics = solid_count < 4;
-                    }
+/// Colors of the minimap's pixels
+const MINIMAP_SOLID: [u8; 4] = [150, 75, 40, 255];
+const MINIMAP_OPEN: [u8; 4] = [20, 10, 10, 200];
+
+/// Overview of the world's tiles with one pixel per tile. It is built along
+/// with the world, so it is ready when the game starts.
+#[derive(Resource, Debug, Clone, PartialEq)]
+struct Minimap {
+    /// Position of the tiles in the world
+    coords: GridCoords,
+    /// RGBA pixels, row by row from the top
+    pixels: Vec<u8>,
+}
 
-                    if needs_physics {
-                        tile_positions.push((left + TILE_SIZE / 2.0, top + TILE_SIZE / 2.0));
-                    }
-                } else {
-                    if x > 1
-                        && x < self.width - 3
-                        && y > 1
-                        && y < self.height - 3
-                        && self.solid[self.map_idx(x, y - 1)]
-                    {
-                        possible_miner_positions
-                            .push((left + TILE_SIZE / 2.0, top + TILE_SIZE / 2.0));
-                    }
-                }
+impl Minimap {
+    /// Draws solid and open tiles of the grid
+    fn new(tiles: &TileGrid) -> Self {
+        let coords = tiles.coords();
+        let mut minimap = Self {
+            coords,
+            pixels: vec![0; coords.width * coords.height * 4],
+        };
+        for y in 0..coords.height {
+            for x in 0..coords.width {
+                minimap.set_tile((x, y), tiles.is_solid(x, y));
             }
         }
+        minimap
+    }
 
-        info!("{} tiles need physics", tile_positions.len());
+    /// Draws a tile as solid or open, e.g. after it was carved
+    fn set_tile(&mut self, tile: (usize, usize), solid: bool) {
+        let (px, py) = self.tile_to_pixel(tile);
+        let color = if solid { MINIMAP_SOLID } else { MINIMAP_OPEN };
+        let start = (py * self.coords.width + px) * 4;
+        self.pixels[start..start + 4].copy_from_slice(&color);
+    }
 
-        (
-            Mesh::new(
-                PrimitiveTopology::TriangleList,
-                RenderAssetUsages::default(),
-            )
-            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, position)
-            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uv),
-            tile_positions,
-            possible_miner_positions,
+    /// Pixel that shows a tile. Tile rows count upwards, pixel rows downwards.
+    fn tile_to_pixel(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        (x, self.coords.height - 1 - y)
+    }
+
+    /// Position of a world coordinate on the map, as a fraction of its size
+    /// from the top-left corner. Positions outside of the world, like the
+    /// sky above it, stick to the map's edge.
+    fn world_to_map(&self, position: Vec2) -> Vec2 {
+        let size =
+            Vec2::new(self.coords.width as f32, self.coords.height as f32) * self.coords.tile_size;
+        let fraction = (position + size / 2.0) / size;
+        Vec2::new(fraction.x, 1.0 - fraction.y).clamp(Vec2::ZERO, Vec2::ONE)
+    }
+
+    /// Converts the pixels into an image for egui
+    fn image(&self) -> egui::egui::ColorImage {
+        egui::egui::ColorImage::from_rgba_unmultiplied(
+            [self.coords.width, self.coords.height],
+            &self.pixels,
         )
     }
 }
 
+/// How large the minimap is shown, cycled with Tab
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+enum MinimapSize {
+    #[default]
+    Small,
+    Large,
+    Hidden,
+}
+
+impl MinimapSize {
+    /// Edge length of the minimap on screen, if shown
+    fn pixels(&self) -> Option<f32> {
+        match self {
+            MinimapSize::Small => Some(160.0),
+            MinimapSize::Large => Some(400.0),
+            MinimapSize::Hidden => None,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            MinimapSize::Small => MinimapSize::Large,
+            MinimapSize::Large => MinimapSize::Hidden,
+            MinimapSize::Hidden => MinimapSize::Small,
+        }
+    }
+}
+
+/// Things that are marked on the minimap
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MarkerKind {
+    Player,
+    Mothership,
+    Miner,
+    Fuel,
+    Battery,
+    LandingPad,
+}
+
+impl MarkerKind {
+    fn color(&self) -> Color32 {
+        match self {
+            MarkerKind::Player => Color32::WHITE,
+            MarkerKind::Mothership => Color32::LIGHT_BLUE,
+            MarkerKind::Miner => Color32::GREEN,
+            MarkerKind::Fuel => Color32::ORANGE,
+            MarkerKind::Battery => Color32::from_rgb(255, 0, 255),
+            MarkerKind::LandingPad => Color32::GOLD,
+        }
+    }
+}
+
+/// Positions of everything marked on the minimap, updated each frame
+#[derive(Resource, Default, Debug)]
+struct MinimapMarkers(Vec<(MarkerKind, Vec2)>);
+
+fn toggle_minimap(keyboard: Res<ButtonInput<KeyCode>>, mut size: ResMut<MinimapSize>) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        *size = size.next();
+    }
+}
+
+/// Everything that is marked on the minimap
+type MinimapThing = (
+    &'static Transform,
+    AnyOf<(
+        &'static Player,
+        &'static Mothership,
+        &'static Miner,
+        &'static Fuel,
+        &'static Battery,
+        &'static LandingPad,
+    )>,
+);
+
+/// Collects the markers of the minimap. Collected pickups are despawned, so
+/// they vanish from the map.
+fn minimap_markers(things: Query<MinimapThing>, mut markers: ResMut<MinimapMarkers>) {
+    markers.0.clear();
+    for (transform, (player, mothership, miner, fuel, battery, _)) in things.iter() {
+        let kind = match (player, mothership, miner, fuel, battery) {
+            (Some(_), ..) => MarkerKind::Player,
+            (_, Some(_), ..) => MarkerKind::Mothership,
+            (_, _, Some(_), ..) => MarkerKind::Miner,
+            (_, _, _, Some(_), _) => MarkerKind::Fuel,
+            (.., Some(_)) => MarkerKind::Battery,
+            _ => MarkerKind::LandingPad,
+        };
+        markers.0.push((kind, transform.translation.truncate()));
+    }
+    // The player is drawn on top of everything else
+    markers
+        .0
+        .sort_by_key(|(kind, _)| *kind == MarkerKind::Player);
+}
+
+fn minimap_display(
+    size: Res<MinimapSize>,
+    minimap: Res<Minimap>,
+    markers: Res<MinimapMarkers>,
+    mut texture: Local<Option<egui::egui::TextureHandle>>,
+    mut egui_context: egui::EguiContexts,
+) {
+    let Some(edge) = size.pixels() else {
+        return;
+    };
+    let ctx = egui_context.ctx_mut();
+    // Each world has its own map
+    if texture.is_none() || minimap.is_changed() {
+        *texture = Some(ctx.load_texture(
+            "minimap",
+            minimap.image(),
+            egui::egui::TextureOptions::NEAREST,
+        ));
+    }
+    let texture = texture.as_ref().unwrap();
+
+    egui::egui::Area::new(egui::egui::Id::new("minimap"))
+        .anchor(egui::egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .show(ctx, |ui| {
+            let (rect, _) =
+                ui.allocate_exact_size(egui::egui::Vec2::splat(edge), egui::egui::Sense::hover());
+            let painter = ui.painter();
+            let uv = egui::egui::Rect::from_min_max(
+                egui::egui::pos2(0.0, 0.0),
+                egui::egui::pos2(1.0, 1.0),
+            );
+            painter.image(texture.id(), rect, uv, Color32::WHITE);
+            let radius = edge / 80.0;
+            for (kind, position) in markers.0.iter() {
+                let position = minimap.world_to_map(*position);
+                let center = rect.lerp_inside(egui::egui::vec2(position.x, position.y));
+                painter.circle_filled(center, radius, kind.color());
+            }
+        });
+}
+
 /// A trait for collecting things for the player
 trait OnCollect {
+    /// Tag of the sound played when collecting it
+    const SOUND: &'static str;
+
     /// The effect of collecting a particular thing
     fn effect(player: &mut Player);
 }
 
 impl OnCollect for Miner {
+    const SOUND: &'static str = "rescue";
+
     fn effect(player: &mut Player) {
         player.miners_saved += 1;
 
@@ -948,12 +1859,16 @@ impl OnCollect for Miner {
 }
 
 impl OnCollect for Fuel {
+    const SOUND: &'static str = "refuel";
+
     fn effect(player: &mut Player) {
         player.fuel += 1000;
     }
 }
 
 impl OnCollect for Battery {
+    const SOUND: &'static str = "recharge";
+
     fn effect(player: &mut Player) {
         player.shields += 100;
     }
@@ -992,6 +1907,8 @@ fn collect_and_despawn_game_element<T: Component + OnCollect, const COLOR: u8>(
     mut commands: Commands,
     mut player: Query<(&mut Player, &Transform)>,
     mut spawn: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
+    assets: AssetHandles,
 ) {
     let mut collected = Vec::new();
     for collision in collisions.read() {
@@ -1014,6 +1931,428 @@ fn collect_and_despawn_game_element<T: Component + OnCollect, const COLOR: u8>(
             BurstColor::from(COLOR).into(),
             &mut spawn,
             2.0,
+            &settings,
+        );
+        assets.play(T::SOUND, &mut commands);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::collections::HashMap;
+
+    #[test]
+    fn seeds_are_parsed_up_to_the_largest_u64() {
+        assert_eq!(Ok(None), parse_seed(""));
+        assert_eq!(Ok(Some(42)), parse_seed("42"));
+        assert_eq!(Ok(Some(u64::MAX)), parse_seed(&u64::MAX.to_string()));
+        assert!(parse_seed("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn seeds_are_rerolled_unless_pinned() {
+        let mut app = App::new();
+        app.insert_resource(WorldSeed(42))
+            .init_resource::<PinnedSeed>();
+        app.world_mut().run_system_once(reroll_seed).unwrap();
+        assert_ne!(42, app.world().resource::<WorldSeed>().0);
+
+        app.insert_resource(WorldSeed(42))
+            .insert_resource(PinnedSeed(true));
+        app.world_mut().run_system_once(reroll_seed).unwrap();
+        assert_eq!(42, app.world().resource::<WorldSeed>().0);
+    }
+
+    #[test]
+    fn world_seed_determines_the_map() {
+        let config = WorldGenConfig::default();
+        let world = build_world(WorldSeed(42), &config, &BuildProgress::default());
+        let again = build_world(WorldSeed(42), &config, &BuildProgress::default());
+        assert_eq!(world.tiles, again.tiles);
+        assert_eq!(world.spawn_positions, again.spawn_positions);
+
+        assert_ne!(
+            world.tiles,
+            build_world(WorldSeed(43), &config, &BuildProgress::default()).tiles
+        );
+    }
+
+    #[test]
+    fn worlds_are_built_in_the_background() {
+        bevy::tasks::AsyncComputeTaskPool::get_or_init(bevy::tasks::TaskPool::default);
+        let config = WorldGenConfig {
+            size: 100,
+            ..default()
+        };
+        let mut app = App::new();
+        app.add_plugins(bevy::state::app::StatesPlugin)
+            .init_resource::<Time>()
+            .init_resource::<egui::EguiUserTextures>()
+            .insert_resource(WorldSeed(42))
+            .insert_resource(config)
+            .insert_state(GamePhase::WorldBuilding);
+        add_phase!(app, GamePhase, GamePhase::WorldBuilding,
+            start => [],
+            run => [show_builder],
+            exit => []
+        );
+        app.world_mut().spawn((
+            Window::default(),
+            bevy::window::PrimaryWindow,
+            egui::EguiContext::default(),
+        ));
+        app.world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                spawn_builder(&mut commands, WorldSeed(42), config)
+            })
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        loop {
+            let was_ready = app.world().resource::<BackgroundTask<World>>().is_ready();
+            let mut contexts = app.world_mut().query::<&mut egui::EguiContext>();
+            let ctx = contexts
+                .single_mut(app.world_mut())
+                .unwrap()
+                .get_mut()
+                .clone();
+            ctx.begin_pass(egui::egui::RawInput::default());
+            app.update();
+            let _ = ctx.end_pass();
+            if *app.world().resource::<State<GamePhase>>() == GamePhase::Playing {
+                // The game only starts with the world finished
+                assert!(was_ready);
+                break;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(30),
+                "World never finished"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let world = app
+            .world_mut()
+            .resource_mut::<BackgroundTask<World>>()
+            .take_result()
+            .unwrap();
+        assert_eq!(
+            build_world(WorldSeed(42), &config, &BuildProgress::default()).tiles,
+            world.tiles
+        );
+    }
+
+    /// Checks that every open tile, the entry shaft and all spawn positions
+    /// can be reached, and that the caves are close to the solid target
+    fn assert_playable(world: &World, config: &WorldGenConfig) {
+        let (width, height) = (world.tiles.width(), world.tiles.height());
+        let reachable = world.reachable();
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(
+                    !world.tiles.is_solid(x, y),
+                    reachable[y * width + x],
+                    "{x}/{y} with {config:?}"
+                );
+            }
+        }
+        for y in height / 2..height {
+            assert!(reachable[y * width + width / 2], "Shaft blocked at {y}");
+        }
+        let grid = world.tiles.coords();
+        assert!(world.spawn_positions.len() >= 60);
+        assert!(!world.pads.is_empty(), "No landing pad with {config:?}");
+        for (x, y) in world.spawn_positions.iter() {
+            assert!(!world.tiles.is_solid_world(Vec2::new(*x, *y)));
+            let (x, y) = grid.world_to_tile(Vec2::new(*x, *y)).unwrap();
+            assert!(reachable[y * width + x]);
+        }
+
+        let solid = world.tiles.solid_count() as f32 / (width * height) as f32;
+        assert!(
+            (solid - config.solid_fraction).abs() < 0.03,
+            "{solid} solid with {config:?}"
         );
     }
+
+    #[test]
+    fn diffusion_caves_are_reachable() {
+        for (seed, solid_fraction) in [(1, 0.6), (2, 0.5), (3, 0.7)] {
+            let config = WorldGenConfig {
+                size: 100,
+                solid_fraction,
+                ..default()
+            };
+            assert_playable(
+                &build_world(WorldSeed(seed), &config, &BuildProgress::default()),
+                &config,
+            );
+        }
+    }
+
+    #[test]
+    fn cellular_automata_caves_are_reachable() {
+        for (seed, solid_fraction) in [(1, 0.6), (2, 0.5), (3, 0.7)] {
+            let config = WorldGenConfig {
+                size: 100,
+                solid_fraction,
+                holes: 0,
+                algorithm: CaveAlgorithm::CellularAutomata {
+                    iterations: 5,
+                    birth: 5,
+                    survive: 4,
+                },
+                ..WorldGenConfig::default()
+            };
+            let world = build_world(WorldSeed(seed), &config, &BuildProgress::default());
+            assert_playable(&world, &config);
+            assert_eq!(
+                world.tiles,
+                build_world(WorldSeed(seed), &config, &BuildProgress::default()).tiles
+            );
+        }
+    }
+
+    #[test]
+    fn hud_fractions_are_capped() {
+        assert_eq!(1.0, hud_fraction(FULL_FUEL, FULL_FUEL));
+        assert_eq!(0.25, hud_fraction(125, FULL_SHIELDS));
+        assert_eq!(1.0, hud_fraction(FULL_FUEL + 1000, FULL_FUEL));
+        assert_eq!(0.0, hud_fraction(-3, FULL_SHIELDS));
+    }
+
+    #[test]
+    fn hud_colors_follow_the_fraction() {
+        assert_eq!(HUD_GOOD_COLOR, hud_color(1.0));
+        assert_eq!(HUD_GOOD_COLOR, hud_color(HUD_GOOD_FRACTION));
+        assert_eq!(HUD_WARNING_COLOR, hud_color(0.3));
+        assert_eq!(HUD_WARNING_COLOR, hud_color(HUD_CRITICAL_FRACTION));
+        assert_eq!(HUD_CRITICAL_COLOR, hud_color(0.1));
+        assert_eq!(HUD_CRITICAL_COLOR, hud_color(0.0));
+    }
+
+    #[test]
+    fn low_fuel_warns_once_per_crossing() {
+        let mut crossing = ThresholdCrossing::default();
+        let warnings = [1.0, 0.5, 0.14, 0.1, 0.05, 0.5, 0.16, 0.14, 0.0]
+            .into_iter()
+            .filter(|fraction| crossing.update(*fraction, HUD_CRITICAL_FRACTION))
+            .count();
+        assert_eq!(2, warnings);
+    }
+
+    /// Creates an app with a distinct sound for each tag
+    fn sound_app(tags: &[&str]) -> (App, HashMap<String, Handle<AudioSource>>) {
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut sounds = Assets::<AudioSource>::default();
+        let mut handles = HashMap::new();
+        for tag in tags {
+            let handle = sounds.add(AudioSource {
+                bytes: Vec::new().into(),
+            });
+            store.insert_handle(tag, handle.clone().untyped(), &mut loaded);
+            handles.insert(tag.to_string(), handle);
+        }
+        let mut app = App::new();
+        app.insert_resource(store).insert_resource(loaded);
+        (app, handles)
+    }
+
+    fn playing(app: &mut App) -> Vec<Handle<AudioSource>> {
+        app.world_mut()
+            .query::<&AudioPlayer>()
+            .iter(app.world())
+            .map(|player| player.0.clone())
+            .collect()
+    }
+
+    #[test]
+    fn thruster_loops_only_while_thrusting() {
+        let (mut app, handles) = sound_app(&["thruster"]);
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .add_systems(Update, thruster_sound);
+        app.world_mut().spawn(Player {
+            miners_saved: 0,
+            shields: FULL_SHIELDS,
+            fuel: FULL_FUEL,
+            score: 0,
+        });
+        let press = |app: &mut App, pressed: bool| {
+            let mut keyboard = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            if pressed {
+                keyboard.press(KeyCode::KeyW);
+            } else {
+                keyboard.release(KeyCode::KeyW);
+            }
+            app.update();
+        };
+
+        press(&mut app, false);
+        assert!(playing(&mut app).is_empty());
+        press(&mut app, true);
+        press(&mut app, true);
+        assert_eq!(vec![handles["thruster"].clone()], playing(&mut app));
+        press(&mut app, false);
+        assert!(playing(&mut app).is_empty());
+    }
+
+    #[test]
+    fn particles_stay_below_the_cap() {
+        let (mut app, _) = sound_app(&[]);
+        let mut loaded = app.world_mut().resource_mut::<LoadedAssets>();
+        let mut store = AssetStore::default();
+        store.insert_handle(
+            "particle",
+            Handle::<Image>::default().untyped(),
+            &mut loaded,
+        );
+        app.insert_resource(store)
+            .init_resource::<Time>()
+            .init_resource::<GameSettings>()
+            .insert_resource(ParticlePool::new(MAX_PARTICLES))
+            .add_event::<SpawnParticle>()
+            .add_systems(
+                Update,
+                (
+                    |mut spawn: EventWriter<SpawnParticle>, settings: Res<GameSettings>| {
+                        // A bounce off the walls in every frame
+                        particle_burst(Vec2::ZERO, LinearRgba::BLUE, &mut spawn, 3.0, &settings);
+                    },
+                    spawn_particle_system,
+                    particle_system,
+                )
+                    .chain(),
+            );
+
+        // 10 seconds at 60 frames per second
+        for _ in 0..600 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(1.0 / 60.0));
+            app.update();
+            let particles = app.world_mut().query::<&Sprite>().iter(app.world()).count();
+            assert!(particles <= MAX_PARTICLES, "{particles} particles");
+        }
+        assert_eq!(MAX_PARTICLES, app.world().resource::<ParticlePool>().live());
+
+        // Lower quality makes smaller bursts
+        let low = GameSettings {
+            particles: ParticleQuality::Low,
+        };
+        assert!(low.particles.burst_size() < GameSettings::default().particles.burst_size());
+    }
+
+    #[test]
+    fn pickups_play_their_sound() {
+        let (mut app, handles) = sound_app(&["rescue", "refuel", "recharge"]);
+        app.add_event::<OnCollision<Player, Fuel>>()
+            .add_event::<SpawnParticle>()
+            .init_resource::<GameSettings>()
+            .add_systems(
+                Update,
+                collect_and_despawn_game_element::<Fuel, { BurstColor::Orange as u8 }>,
+            );
+        let player = app
+            .world_mut()
+            .spawn((
+                Player {
+                    miners_saved: 0,
+                    shields: FULL_SHIELDS,
+                    fuel: 0,
+                    score: 0,
+                },
+                Transform::default(),
+            ))
+            .id();
+        let fuel = app.world_mut().spawn(Fuel).id();
+        app.world_mut()
+            .send_event(OnCollision::<Player, Fuel>::new(player, fuel));
+        app.update();
+
+        assert_eq!(vec![handles["refuel"].clone()], playing(&mut app));
+        assert!(app.world().get_entity(fuel).is_err());
+    }
+
+    #[test]
+    fn closed_tiles_are_found_in_open_worlds() {
+        let mut world = World {
+            tiles: TileGrid::new(50, 50, TILE_SIZE, false),
+            chunks: Vec::new(),
+            tile_positions: Vec::new(),
+            spawn_positions: Vec::new(),
+            pads: Vec::new(),
+            minimap: None,
+        };
+        let mut rng = RandomNumberGenerator::seeded(42);
+        assert_eq!(None, world.find_random_closed_tile(&mut rng));
+
+        world.tiles.set_solid(7, 3, true);
+        assert_eq!(Some((7, 3)), world.find_random_closed_tile(&mut rng));
+    }
+
+    #[test]
+    fn minimap_pixels_follow_the_tiles() {
+        let mut tiles = TileGrid::new(4, 3, TILE_SIZE, true);
+        tiles.set_solid(0, 0, false);
+        let minimap = Minimap::new(&tiles);
+        assert_eq!(4 * 3 * 4, minimap.pixels.len());
+
+        // The bottom-left tile is the first pixel of the last row
+        assert_eq!((0, 2), minimap.tile_to_pixel((0, 0)));
+        assert_eq!((3, 0), minimap.tile_to_pixel((3, 2)));
+        let bottom_left = 2 * 4 * 4;
+        assert_eq!(MINIMAP_OPEN, minimap.pixels[bottom_left..bottom_left + 4]);
+        assert_eq!(MINIMAP_SOLID, minimap.pixels[0..4]);
+
+        let grid = tiles.coords();
+        assert_eq!(Vec2::new(0.5, 0.5), minimap.world_to_map(Vec2::ZERO));
+        let corner = minimap.world_to_map(grid.tile_to_world((0, 0)));
+        assert_eq!(Vec2::new(0.125, 1.0 - 1.0 / 6.0), corner);
+        // The sky sticks to the top edge
+        assert_eq!(0.0, minimap.world_to_map(Vec2::new(0.0, 1000.0)).y);
+    }
+
+    #[test]
+    fn collected_pickups_leave_the_minimap() {
+        let mut app = App::new();
+        app.init_resource::<MinimapMarkers>()
+            .add_systems(Update, minimap_markers);
+        let world = app.world_mut();
+        world.spawn((Transform::from_xyz(1.0, 2.0, 0.0), Mothership));
+        let miner = world.spawn((Transform::default(), Miner)).id();
+        world.spawn((Transform::default(), Fuel));
+        world.spawn((Transform::default(), Battery));
+        world.spawn(Transform::default());
+        app.update();
+
+        let kinds = |app: &App| -> Vec<MarkerKind> {
+            let markers = app.world().resource::<MinimapMarkers>();
+            markers.0.iter().map(|(kind, _)| *kind).collect()
+        };
+        let mut found = kinds(&app);
+        assert_eq!(4, found.len());
+        for kind in [
+            MarkerKind::Mothership,
+            MarkerKind::Miner,
+            MarkerKind::Fuel,
+            MarkerKind::Battery,
+        ] {
+            assert!(found.contains(&kind));
+        }
+        let markers = app.world().resource::<MinimapMarkers>();
+        assert!(
+            markers
+                .0
+                .contains(&(MarkerKind::Mothership, Vec2::new(1.0, 2.0)))
+        );
+
+        app.world_mut().despawn(miner);
+        app.update();
+        found = kinds(&app);
+        assert_eq!(3, found.len());
+        assert!(!found.contains(&MarkerKind::Miner));
+    }
 }
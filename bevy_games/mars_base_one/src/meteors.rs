@@ -0,0 +1,335 @@
+//! Meteor showers, that rain on the caves from time to time
+
+use super::{
+    GameElement, GamePhase, GameSettings, Ground, MyCamera, Player, SpawnParticle, TILE_SIZE,
+    Terrain, WorldSeed, particle_burst,
+};
+use bevy::prelude::*;
+use my_library::{
+    ApplyGravity, AssetStore, AxisAlignedBoundingBox, Flash, Impulse, LoadedAssets, OnCollision,
+    PhysicsPosition, RandomNumberGenerator, Velocity,
+    egui::{self, egui::Color32},
+    spawn_image,
+};
+
+/// Seconds between two showers
+const SHOWER_INTERVAL: std::ops::Range<f32> = 20.0..40.0;
+/// Seconds the warning is shown before the meteors arrive
+const SHOWER_WARNING: f32 = 3.0;
+/// Meteors in one shower
+const METEORS_PER_SHOWER: std::ops::RangeInclusive<usize> = 4..=8;
+/// No more meteors are spawned while this many are falling
+const MAX_METEORS: usize = 12;
+/// Meteors spawn this far above the camera, out of sight even when zoomed out
+const METEOR_HEIGHT: f32 = 700.0;
+/// Shields lost when a meteor hits the ship
+const METEOR_DAMAGE: i32 = 25;
+
+/// Tags a falling meteor
+#[derive(Component)]
+pub struct Meteor;
+
+/// Resource timing the meteor showers. It has its own generator, seeded by
+/// the world seed, so a world always has the same showers.
+#[derive(Resource)]
+pub struct MeteorShower {
+    rng: RandomNumberGenerator,
+    /// Seconds until the next warning
+    countdown: f32,
+    /// Seconds until the announced meteors arrive
+    warning: Option<f32>,
+}
+
+impl MeteorShower {
+    /// Schedules the first shower
+    pub fn new(seed: u64) -> Self {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        Self {
+            countdown: rng.range(SHOWER_INTERVAL),
+            rng,
+            warning: None,
+        }
+    }
+
+    /// Whether a shower is about to hit
+    pub fn warning(&self) -> bool {
+        self.warning.is_some()
+    }
+
+    /// Advances the timers, and returns how many meteors arrive right now
+    pub fn tick(&mut self, delta: f32) -> usize {
+        match self.warning.as_mut() {
+            Some(warning) => {
+                *warning -= delta;
+                if *warning > 0.0 {
+                    return 0;
+                }
+                self.warning = None;
+                self.countdown = self.rng.range(SHOWER_INTERVAL);
+                self.rng.range(METEORS_PER_SHOWER)
+            }
+            None => {
+                self.countdown -= delta;
+                if self.countdown <= 0.0 {
+                    self.warning = Some(SHOWER_WARNING);
+                }
+                0
+            }
+        }
+    }
+}
+
+/// Schedules the showers of a new run
+pub fn start_meteor_shower(seed: Res<WorldSeed>, mut commands: Commands) {
+    commands.insert_resource(MeteorShower::new(seed.0));
+}
+
+/// Spawns the meteors of a shower above the camera, falling with random
+/// velocities
+#[allow(clippy::too_many_arguments)]
+pub fn meteor_shower(
+    time: Res<Time>,
+    mut shower: ResMut<MeteorShower>,
+    meteors: Query<(), With<Meteor>>,
+    camera: Query<&Transform, With<MyCamera>>,
+    terrain: Res<Terrain>,
+    assets: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
+) {
+    let arriving = shower.tick(time.delta_secs());
+    let count = arriving.min(MAX_METEORS.saturating_sub(meteors.iter().count()));
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    let edge = terrain.0.width() as f32 / 2.0 * TILE_SIZE - 2.0 * TILE_SIZE;
+    for _ in 0..count {
+        let x = (camera.translation.x + shower.rng.range(-400.0..400.0)).clamp(-edge, edge);
+        let y = camera.translation.y + METEOR_HEIGHT;
+        let velocity = Velocity::new_2d(shower.rng.range(-2.0..2.0), shower.rng.range(-3.0..-1.0));
+        spawn_image!(
+            assets,
+            commands,
+            "meteor",
+            x,
+            y,
+            10.0,
+            &loaded_assets,
+            GameElement,
+            Meteor,
+            velocity,
+            PhysicsPosition::new(Vec2::new(x, y)),
+            ApplyGravity,
+            AxisAlignedBoundingBox::new(16.0, 16.0)
+        );
+    }
+}
+
+/// Shows a banner while a shower is announced
+pub fn meteor_warning(shower: Res<MeteorShower>, mut egui_context: egui::EguiContexts) {
+    if !shower.warning() {
+        return;
+    }
+    egui::egui::Area::new(egui::egui::Id::new("meteor_warning"))
+        .anchor(egui::egui::Align2::CENTER_TOP, [0.0, 40.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(
+                egui::egui::RichText::new("Meteor shower incoming!")
+                    .size(28.0)
+                    .color(Color32::RED),
+            );
+        });
+}
+
+/// Meteors burst on the rocks
+pub fn meteor_impact(
+    mut collisions: EventReader<OnCollision<Meteor, Ground>>,
+    meteors: Query<&PhysicsPosition, With<Meteor>>,
+    mut particles: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
+    mut commands: Commands,
+) {
+    let mut burst = Vec::new();
+    for collision in collisions.read() {
+        // A meteor may touch several tiles at once
+        if burst.contains(&collision.entity_a) {
+            continue;
+        }
+        let Ok(position) = meteors.get(collision.entity_a) else {
+            continue;
+        };
+        burst.push(collision.entity_a);
+        particle_burst(
+            position.current(),
+            LinearRgba::new(0.6, 0.5, 0.4, 1.0),
+            &mut particles,
+            2.0,
+            &settings,
+        );
+        commands.entity(collision.entity_a).try_despawn();
+    }
+}
+
+/// Meteors hitting the ship knock it away and damage its shields
+#[allow(clippy::too_many_arguments)]
+pub fn meteor_hit(
+    mut collisions: EventReader<OnCollision<Player, Meteor>>,
+    mut player_query: Query<(&PhysicsPosition, &mut Player)>,
+    meteors: Query<&PhysicsPosition, With<Meteor>>,
+    mut impulses: EventWriter<Impulse>,
+    mut particles: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
+    mut flash: EventWriter<Flash>,
+    mut state: ResMut<NextState<GamePhase>>,
+    mut commands: Commands,
+) {
+    for collision in collisions.read() {
+        let (Ok((player_pos, mut player)), Ok(meteor)) = (
+            player_query.get_mut(collision.entity_a),
+            meteors.get(collision.entity_b),
+        ) else {
+            continue;
+        };
+        let knock = player_pos.separation_from(meteor).normalize_or(Vec2::Y);
+        impulses.write(Impulse {
+            target: collision.entity_a,
+            amount: knock.extend(0.0),
+            absolute: true,
+            source: 2,
+        });
+        particle_burst(
+            player_pos.current(),
+            LinearRgba::new(1.0, 0.0, 0.0, 1.0),
+            &mut particles,
+            3.0,
+            &settings,
+        );
+        flash.write(Flash {
+            target: collision.entity_a,
+        });
+        commands.entity(collision.entity_b).try_despawn();
+        player.shields -= METEOR_DAMAGE;
+        if player.shields <= 0 {
+            state.set(GamePhase::GameOver);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use my_library::{StaticQuadTree, check_collisions};
+
+    #[test]
+    fn showers_follow_the_seed() {
+        // Seconds at which meteors arrive, and how many
+        let showers = |seed| {
+            let mut shower = MeteorShower::new(seed);
+            let mut arrivals = Vec::new();
+            let mut warned = 0.0;
+            for step in 1..=2000 {
+                let now = step as f32 * 0.1;
+                let count = shower.tick(0.1);
+                if shower.warning() {
+                    warned += 0.1;
+                }
+                if count > 0 {
+                    assert!((SHOWER_WARNING - 0.15..=SHOWER_WARNING + 0.15).contains(&warned));
+                    warned = 0.0;
+                    arrivals.push((now, count));
+                }
+            }
+            arrivals
+        };
+        let arrivals = showers(42);
+        assert_eq!(arrivals, showers(42));
+        assert_ne!(arrivals, showers(43));
+
+        assert!(arrivals.len() >= 4);
+        let mut last = 0.0;
+        for (now, count) in arrivals {
+            let interval = now - last - SHOWER_WARNING;
+            assert!((19.9..40.1).contains(&interval), "{interval}");
+            assert!(METEORS_PER_SHOWER.contains(&count));
+            last = now;
+        }
+    }
+
+    fn meteor_app() -> App {
+        let mut app = App::new();
+        app.add_event::<OnCollision<Player, Meteor>>()
+            .add_event::<OnCollision<Meteor, Ground>>()
+            .add_event::<Impulse>()
+            .add_event::<SpawnParticle>()
+            .add_event::<Flash>()
+            .init_resource::<GameSettings>()
+            .init_resource::<NextState<GamePhase>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4));
+        app
+    }
+
+    fn meteor(app: &mut App, position: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                Meteor,
+                PhysicsPosition::new(position),
+                AxisAlignedBoundingBox::new(16.0, 16.0),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn meteors_damage_the_ship() {
+        let mut app = meteor_app();
+        app.add_systems(Update, meteor_hit);
+        let player = app
+            .world_mut()
+            .spawn((
+                Player {
+                    miners_saved: 0,
+                    shields: 100,
+                    fuel: 100,
+                    score: 0,
+                },
+                PhysicsPosition::new(Vec2::ZERO),
+            ))
+            .id();
+        let meteor = meteor(&mut app, Vec2::new(0.0, 10.0));
+        app.world_mut()
+            .send_event(OnCollision::<Player, Meteor>::new(player, meteor));
+        app.update();
+
+        assert_eq!(
+            100 - METEOR_DAMAGE,
+            app.world().get::<Player>(player).unwrap().shields
+        );
+        assert!(app.world().get_entity(meteor).is_err());
+        let impulses = app.world().resource::<Events<Impulse>>();
+        let knock: Vec<Vec3> = impulses
+            .iter_current_update_events()
+            .map(|impulse| impulse.amount)
+            .collect();
+        assert_eq!(vec![Vec3::new(0.0, -1.0, 0.0)], knock);
+    }
+
+    #[test]
+    fn meteors_burst_on_the_ground() {
+        let mut app = meteor_app();
+        app.add_systems(
+            Update,
+            (check_collisions::<Meteor, Ground>, meteor_impact).chain(),
+        );
+        app.world_mut().spawn((
+            Ground,
+            PhysicsPosition::new(Vec2::ZERO),
+            AxisAlignedBoundingBox::new(TILE_SIZE, TILE_SIZE),
+        ));
+        let falling = meteor(&mut app, Vec2::new(0.0, 100.0));
+        let landed = meteor(&mut app, Vec2::new(0.0, 15.0));
+        app.update();
+
+        assert!(app.world().get_entity(falling).is_ok());
+        assert!(app.world().get_entity(landed).is_err());
+        assert!(!app.world().resource::<Events<SpawnParticle>>().is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! The mission: rescue the miners before the shields give out
+
+use super::{DifficultyConfig, GamePhase, HighScoreServer, Player, WorldSeed};
+use bevy::prelude::*;
+use my_library::egui;
+
+/// Bonus for accomplishing the mission
+const MISSION_BONUS: u32 = 5000;
+
+/// Resource with the goal of the running game, set when the world is spawned
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct MissionState {
+    /// Number of miners in the world, including those already rescued
+    pub total: u32,
+    /// Number of miners to rescue for accomplishing the mission
+    pub required: u32,
+}
+
+impl MissionState {
+    /// Creates a mission to rescue a fraction of `total` miners, rounded up.
+    /// At least one miner must be rescued, so a world without miners can't
+    /// be won.
+    pub fn new(total: u32, fraction: f32) -> Self {
+        let required = (total as f32 * fraction.clamp(0.0, 1.0)).ceil() as u32;
+        Self {
+            total,
+            required: required.max(1),
+        }
+    }
+
+    /// Checks if the player rescued enough miners
+    pub fn accomplished(&self, player: &Player) -> bool {
+        player.miners_saved >= self.required
+    }
+
+    /// How the game ended for the player
+    pub fn outcome(&self, player: &Player) -> MissionOutcome {
+        if player.shields <= 0 {
+            MissionOutcome::ShieldsLost
+        } else if self.accomplished(player) {
+            MissionOutcome::Accomplished
+        } else if player.fuel <= 0 {
+            MissionOutcome::OutOfFuel
+        } else {
+            MissionOutcome::Failed
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissionOutcome {
+    /// Enough miners were rescued
+    Accomplished,
+    /// The ship was lost, as the shields gave out
+    ShieldsLost,
+    /// The ship ran out of fuel before enough miners were rescued
+    OutOfFuel,
+    /// The game ended otherwise before enough miners were rescued
+    Failed,
+}
+
+impl MissionOutcome {
+    /// Message shown on the game-over screen
+    pub fn message(&self) -> &'static str {
+        match self {
+            MissionOutcome::Accomplished => "Mission accomplished! The miners are safe.",
+            MissionOutcome::ShieldsLost => "Mission failed. The shields gave out.",
+            MissionOutcome::OutOfFuel => "Mission failed. The ship ran out of fuel.",
+            MissionOutcome::Failed => "Mission failed. Not enough miners were rescued.",
+        }
+    }
+
+    /// Points added to the final score. The fuel left counts towards an
+    /// accomplished mission.
+    pub fn bonus(&self, player: &Player) -> u32 {
+        match self {
+            MissionOutcome::Accomplished => MISSION_BONUS + player.fuel.max(0) as u32 / 10,
+            _ => 0,
+        }
+    }
+}
+
+/// Ends the game once enough miners are rescued
+pub fn end_game(
+    mut state: ResMut<NextState<GamePhase>>,
+    mission: Res<MissionState>,
+    player_query: Query<&Player>,
+) {
+    let Ok(player) = player_query.single() else {
+        return;
+    };
+
+    if mission.accomplished(player) {
+        state.set(GamePhase::GameOver);
+    }
+}
+
+/// Starts the high-score submission with the player's final score,
+/// including the bonus of the outcome
+pub fn submit_score(
+    player: Query<&Player>,
+    seed: Res<WorldSeed>,
+    mission: Res<MissionState>,
+    difficulty: Res<DifficultyConfig>,
+    mut submission: ResMut<egui::HighScoreSubmit<HighScoreServer>>,
+) {
+    for player in player.iter() {
+        let outcome = mission.outcome(player);
+        let bonus = outcome.bonus(player);
+        submission.start(difficulty.final_score(player.score + bonus));
+        submission.details.push(outcome.message().to_string());
+        submission.details.push(format!(
+            "Miners rescued: {} / {}",
+            player.miners_saved, mission.total
+        ));
+        if bonus > 0 {
+            submission.details.push(format!("Mission bonus: {bonus}"));
+        }
+        submission.details.push(format!(
+            "Difficulty: {} (x{})",
+            difficulty.difficulty.name(),
+            difficulty.score_multiplier
+        ));
+        submission.details.push(format!("World seed: {}", seed.0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mission_app(mission: MissionState, miners_saved: u32, shields: i32) -> App {
+        let mut app = App::new();
+        app.init_resource::<NextState<GamePhase>>()
+            .init_resource::<WorldSeed>()
+            .init_resource::<DifficultyConfig>()
+            .insert_resource(mission)
+            .insert_resource(egui::HighScoreSubmit::new(HighScoreServer))
+            .add_systems(Update, (end_game, submit_score).chain());
+        app.world_mut().spawn(Player {
+            miners_saved,
+            shields,
+            fuel: 2000,
+            score: 3000,
+        });
+        app.update();
+        app
+    }
+
+    fn game_over(app: &App) -> bool {
+        matches!(
+            app.world().resource::<NextState<GamePhase>>(),
+            NextState::Pending(GamePhase::GameOver)
+        )
+    }
+
+    #[test]
+    fn rescuing_all_miners_wins() {
+        let app = mission_app(MissionState::new(20, 1.0), 19, 100);
+        assert!(!game_over(&app));
+
+        let app = mission_app(MissionState::new(20, 1.0), 20, 100);
+        assert!(game_over(&app));
+        let submission = app
+            .world()
+            .resource::<egui::HighScoreSubmit<HighScoreServer>>();
+        assert_eq!(Some(3000 + MISSION_BONUS + 200), submission.score());
+        assert_eq!(
+            MissionOutcome::Accomplished.message(),
+            submission.details[0]
+        );
+    }
+
+    #[test]
+    fn losing_the_shields_fails() {
+        let app = mission_app(MissionState::new(20, 1.0), 12, 0);
+        let submission = app
+            .world()
+            .resource::<egui::HighScoreSubmit<HighScoreServer>>();
+        assert_eq!(Some(3000), submission.score());
+        assert_eq!(MissionOutcome::ShieldsLost.message(), submission.details[0]);
+        assert_eq!("Miners rescued: 12 / 20", submission.details[1]);
+    }
+
+    #[test]
+    fn failures_report_their_cause() {
+        let mission = MissionState::new(20, 1.0);
+        let player = |shields, fuel| Player {
+            miners_saved: 12,
+            shields,
+            fuel,
+            score: 0,
+        };
+        assert_eq!(
+            MissionOutcome::ShieldsLost,
+            mission.outcome(&player(0, 500))
+        );
+        assert_eq!(MissionOutcome::OutOfFuel, mission.outcome(&player(50, 0)));
+        assert_eq!(MissionOutcome::Failed, mission.outcome(&player(50, 500)));
+        assert_ne!(
+            MissionOutcome::ShieldsLost.message(),
+            MissionOutcome::OutOfFuel.message()
+        );
+    }
+
+    #[test]
+    fn worlds_without_miners_cannot_be_won() {
+        let mission = MissionState::new(0, 1.0);
+        let player = Player {
+            miners_saved: 0,
+            shields: 100,
+            fuel: 2000,
+            score: 0,
+        };
+        assert!(!mission.accomplished(&player));
+        assert_eq!(MissionOutcome::Failed, mission.outcome(&player));
+
+        let app = mission_app(mission, 0, 100);
+        assert!(!game_over(&app));
+    }
+
+    #[test]
+    fn the_difficulty_scales_the_final_score() {
+        let mut app = App::new();
+        app.init_resource::<WorldSeed>()
+            .insert_resource(crate::Difficulty::Hard.config())
+            .insert_resource(MissionState::new(20, 1.0))
+            .insert_resource(egui::HighScoreSubmit::new(HighScoreServer))
+            .add_systems(Update, submit_score);
+        app.world_mut().spawn(Player {
+            miners_saved: 12,
+            shields: 0,
+            fuel: 0,
+            score: 3001,
+        });
+        app.update();
+
+        let submission = app
+            .world()
+            .resource::<egui::HighScoreSubmit<HighScoreServer>>();
+        assert_eq!(Some(4502), submission.score());
+        assert!(
+            submission
+                .details
+                .contains(&"Difficulty: Hard (x1.5)".to_string())
+        );
+    }
+
+    #[test]
+    fn easier_missions_need_a_fraction_of_the_miners() {
+        assert_eq!(10, MissionState::new(20, 0.5).required);
+        assert_eq!(2, MissionState::new(3, 0.5).required);
+        assert_eq!(1, MissionState::new(20, 0.0).required);
+
+        let app = mission_app(MissionState::new(20, 0.5), 9, 100);
+        assert!(!game_over(&app));
+        let app = mission_app(MissionState::new(20, 0.5), 10, 100);
+        assert!(game_over(&app));
+    }
+}
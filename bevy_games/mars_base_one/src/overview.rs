@@ -0,0 +1,191 @@
+//! Overview of the whole world, shown while a key is held
+
+use super::{MarkerKind, MinimapMarkers, MyCamera};
+use bevy::prelude::*;
+use my_library::{CameraFollow, CameraZoom, PhysicsPaused, Rect2D};
+
+/// Key to hold for the overview
+pub const OVERVIEW_KEY: KeyCode = KeyCode::KeyM;
+
+/// Size of the pickup markers in the overview, in screen pixels
+const OVERVIEW_MARKER_SIZE: f32 = 10.0;
+
+/// Resource holding the zoom of the camera from before the overview, while
+/// the overview is shown
+#[derive(Resource, Default)]
+pub struct Overview {
+    previous: Option<CameraZoom>,
+}
+
+impl Overview {
+    /// Whether the overview is shown
+    pub fn active(&self) -> bool {
+        self.previous.is_some()
+    }
+}
+
+/// Scale at which a view of `area`, seen at `scale`, covers the bounds
+fn overview_scale(scale: f32, area: Rect, bounds: &Rect2D) -> f32 {
+    let size = bounds.max() - bounds.min();
+    scale * (size / area.size()).max_element()
+}
+
+/// Zooms out to the whole world and freezes the physics while
+/// [`OVERVIEW_KEY`] is held. Releasing it restores the previous zoom.
+/// Schedule it before `physics_clock` and `camera_zoom`.
+pub fn overview_mode(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut overview: ResMut<Overview>,
+    mut paused: ResMut<PhysicsPaused>,
+    mut cameras: Query<(&mut CameraZoom, &Projection, &CameraFollow), With<MyCamera>>,
+) {
+    let Ok((mut zoom, projection, follow)) = cameras.single_mut() else {
+        return;
+    };
+    match (keyboard.pressed(OVERVIEW_KEY), overview.active()) {
+        (true, false) => {
+            let (Projection::Orthographic(ortho), Some(bounds)) = (projection, follow.bounds)
+            else {
+                return;
+            };
+            // The camera centers on the bounds, once its view is larger
+            let scale = overview_scale(ortho.scale, ortho.area, &bounds);
+            overview.previous = Some(*zoom);
+            *zoom = CameraZoom::new(scale, scale, scale).with_speed(zoom.speed);
+            paused.0 = true;
+        }
+        (false, true) => {
+            *zoom = overview.previous.take().unwrap();
+            paused.0 = false;
+        }
+        _ => {}
+    }
+}
+
+/// Leaves the overview when the game ends
+pub fn end_overview(mut overview: ResMut<Overview>, mut paused: ResMut<PhysicsPaused>) {
+    overview.previous = None;
+    paused.0 = false;
+}
+
+/// Highlights the pickups in the overview with pulsing circles
+pub fn overview_markers(
+    overview: Res<Overview>,
+    markers: Res<MinimapMarkers>,
+    time: Res<Time>,
+    cameras: Query<&Projection, With<MyCamera>>,
+    mut gizmos: Gizmos,
+) {
+    let (true, Ok(Projection::Orthographic(ortho))) = (overview.active(), cameras.single()) else {
+        return;
+    };
+    let pulse = 1.0 + 0.4 * (time.elapsed_secs() * 6.0).sin();
+    let radius = OVERVIEW_MARKER_SIZE * ortho.scale * pulse;
+    for (kind, position) in markers.0.iter() {
+        if matches!(
+            kind,
+            MarkerKind::Miner | MarkerKind::Fuel | MarkerKind::Battery
+        ) {
+            let [r, g, b, a] = kind.color().to_array();
+            gizmos.circle_2d(*position, radius, Color::srgba_u8(r, g, b, a));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use my_library::{MaxPhysicsDelta, PhysicsTick, camera_zoom, physics_clock};
+    use std::time::Duration;
+
+    /// An app with the camera of a 1024x768 window at zoom 0.4, following the
+    /// player within bounds of 4800x6240
+    fn camera_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .init_resource::<Overview>()
+            .add_event::<PhysicsTick>()
+            .add_event::<bevy::input::mouse::MouseWheel>()
+            .add_systems(Update, (overview_mode, physics_clock, camera_zoom).chain());
+        let camera = app
+            .world_mut()
+            .spawn((
+                MyCamera,
+                Projection::Orthographic(OrthographicProjection {
+                    scale: 0.4,
+                    area: Rect::from_center_half_size(Vec2::ZERO, Vec2::new(1024.0, 768.0) * 0.2),
+                    ..OrthographicProjection::default_2d()
+                }),
+                CameraZoom::new(0.4, 0.25, 1.0),
+                CameraFollow::new(Entity::PLACEHOLDER).with_bounds(Rect2D::new(
+                    Vec2::new(-2400.0, -2400.0),
+                    Vec2::new(2400.0, 3840.0),
+                )),
+            ))
+            .id();
+        (app, camera)
+    }
+
+    /// Runs a frame of 100ms with the key held or released, and returns the
+    /// physics ticks of the frame
+    fn frame(app: &mut App, key: Option<KeyCode>, held: bool) -> usize {
+        let mut keyboard = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard.clear();
+        if let Some(key) = key {
+            if held {
+                keyboard.press(key);
+            } else {
+                keyboard.release(key);
+            }
+        }
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(100));
+        app.update();
+        let mut ticks = app.world_mut().resource_mut::<Events<PhysicsTick>>();
+        let count = ticks.len();
+        ticks.clear();
+        count
+    }
+
+    fn scale(app: &App, camera: Entity) -> f32 {
+        match app.world().get::<Projection>(camera).unwrap() {
+            Projection::Orthographic(ortho) => ortho.scale,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn zoom_keys_change_the_projection_scale() {
+        let (mut app, camera) = camera_app();
+        frame(&mut app, Some(KeyCode::Equal), true);
+        assert!(scale(&app, camera) < 0.4);
+
+        for _ in 0..30 {
+            frame(&mut app, Some(KeyCode::Minus), true);
+            frame(&mut app, Some(KeyCode::Minus), false);
+        }
+        assert!((scale(&app, camera) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn overview_freezes_physics_and_restores_the_zoom() {
+        let (mut app, camera) = camera_app();
+        assert_eq!(3, frame(&mut app, None, false));
+
+        // The view grows until the whole height of the bounds fits
+        assert_eq!(0, frame(&mut app, Some(OVERVIEW_KEY), true));
+        for _ in 0..20 {
+            assert_eq!(0, frame(&mut app, None, false));
+        }
+        assert!(app.world().resource::<Overview>().active());
+        assert!((scale(&app, camera) - 0.4 * 6240.0 / 307.2).abs() < 0.1);
+
+        assert_eq!(3, frame(&mut app, Some(OVERVIEW_KEY), false));
+        assert_eq!(0.4, app.world().get::<CameraZoom>(camera).unwrap().level);
+        assert!(!app.world().resource::<PhysicsPaused>().0);
+    }
+}
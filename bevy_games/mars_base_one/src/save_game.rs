@@ -0,0 +1,432 @@
+//! Saves a run from the pause menu, so it can be continued from the main menu
+
+use super::{
+    Battery, Difficulty, DifficultyConfig, Fuel, GamePhase, Miner, Player, TILE_SIZE, Terrain,
+    WorldGenConfig, WorldSeed,
+};
+use bevy::prelude::*;
+use my_library::{
+    PhysicsPaused, PhysicsPosition, TileGrid, Velocity,
+    anyhow::{self, Context},
+    egui::{self, egui::Color32},
+};
+use std::path::Path;
+
+/// File the run is saved to
+pub const SAVE_FILE: &str = "mars_save.ron";
+
+/// Version of the save format. Increase it whenever [`SaveGame`] changes, so
+/// old saves are rejected instead of being misread.
+pub const SAVE_VERSION: u32 = 3;
+
+/// Key to pause the game and open the pause menu
+pub const PAUSE_KEY: KeyCode = KeyCode::Escape;
+
+/// A run loaded from the save file. It replaces world generation, and is
+/// removed once the run is set up.
+#[derive(Resource)]
+pub struct LoadedRun(pub SaveGame);
+
+/// The ship's position, motion and stats
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PlayerState {
+    pub position: (f32, f32),
+    /// Rotation around the z-axis in radians
+    pub rotation: f32,
+    pub velocity: (f32, f32),
+    pub fuel: i32,
+    pub shields: i32,
+    pub score: u32,
+    pub miners_saved: u32,
+}
+
+impl PlayerState {
+    /// The ship at the start of a new run of the difficulty, hovering at
+    /// `height`
+    pub fn new_game(height: f32, difficulty: &DifficultyConfig) -> Self {
+        Self {
+            position: (0.0, height),
+            rotation: 0.0,
+            velocity: (0.0, 0.0),
+            fuel: difficulty.fuel,
+            shields: difficulty.shields,
+            score: 0,
+            miners_saved: 0,
+        }
+    }
+
+    /// The player component with the saved stats
+    pub fn player(&self) -> Player {
+        Player {
+            miners_saved: self.miners_saved,
+            shields: self.shields,
+            fuel: self.fuel,
+            score: self.score,
+        }
+    }
+}
+
+/// Positions of the miners, fuel and batteries that are left
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Pickups {
+    pub miners: Vec<(f32, f32)>,
+    pub fuel: Vec<(f32, f32)>,
+    pub batteries: Vec<(f32, f32)>,
+}
+
+impl Pickups {
+    /// Places as many of each kind as the difficulty asks for on the
+    /// (shuffled) spawn positions of a new world
+    pub fn from_spawn_positions(positions: &[(f32, f32)], difficulty: &DifficultyConfig) -> Self {
+        let mut positions = positions.iter().copied();
+        Self {
+            miners: positions.by_ref().take(difficulty.miners).collect(),
+            fuel: positions.by_ref().take(difficulty.fuel_pickups).collect(),
+            batteries: positions.take(difficulty.batteries).collect(),
+        }
+    }
+}
+
+/// Just the version of a save, read before the rest of it
+#[derive(serde::Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// Everything needed to continue a run
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct SaveGame {
+    pub version: u32,
+    /// Seed the world was built from, shown with the high score
+    pub seed: u64,
+    /// Difficulty the run is played on
+    pub difficulty: Difficulty,
+    /// Fraction of the miners to rescue for accomplishing the mission
+    pub rescue_fraction: f32,
+    /// The world's tiles row by row from the top, '#' for solid and '.' for
+    /// open tiles
+    pub rows: Vec<String>,
+    pub player: PlayerState,
+    pub pickups: Pickups,
+}
+
+impl SaveGame {
+    /// Captures the running game
+    pub fn capture(world: &mut World) -> anyhow::Result<Self> {
+        let seed = world.resource::<WorldSeed>().0;
+        let difficulty = world.resource::<DifficultyConfig>().difficulty;
+        let rescue_fraction = world.resource::<WorldGenConfig>().rescue_fraction;
+        let tiles = &world
+            .get_resource::<Terrain>()
+            .context("There is no run to save")?
+            .0;
+        let rows = (0..tiles.height())
+            .rev()
+            .map(|y| {
+                (0..tiles.width())
+                    .map(|x| if tiles.is_solid(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        let (player, transform, position, velocity) = world
+            .query::<(&Player, &Transform, &PhysicsPosition, &Velocity)>()
+            .single(world)?;
+        let player = PlayerState {
+            position: position.current().into(),
+            rotation: transform.rotation.to_euler(EulerRot::ZYX).0,
+            velocity: velocity.0.truncate().into(),
+            fuel: player.fuel,
+            shields: player.shields,
+            score: player.score,
+            miners_saved: player.miners_saved,
+        };
+        let pickups = Pickups {
+            miners: positions::<Miner>(world),
+            fuel: positions::<Fuel>(world),
+            batteries: positions::<Battery>(world),
+        };
+        Ok(Self {
+            version: SAVE_VERSION,
+            seed,
+            difficulty,
+            rescue_fraction,
+            rows,
+            player,
+            pickups,
+        })
+    }
+
+    /// Loads a save, and rejects saves of other versions of the format
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        let header: SaveHeader = ron::from_str(&text).context("This is not a saved run")?;
+        if header.version != SAVE_VERSION {
+            anyhow::bail!(
+                "The run was saved in version {} of the save format, but only version {SAVE_VERSION} can be loaded",
+                header.version
+            );
+        }
+        let save: Self = ron::from_str(&text).context("The saved run is damaged")?;
+        let width = save.rows.first().map_or(0, String::len);
+        if width == 0
+            || save
+                .rows
+                .iter()
+                .any(|row| row.len() != width || row.chars().any(|c| c != '#' && c != '.'))
+        {
+            anyhow::bail!("The saved world is damaged");
+        }
+        Ok(save)
+    }
+
+    /// Saves the run, replacing an earlier save
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Restores the world's tiles
+    pub fn tiles(&self) -> TileGrid {
+        let (width, height) = (self.rows[0].len(), self.rows.len());
+        let mut tiles = TileGrid::new(width, height, TILE_SIZE, true);
+        for (row, line) in self.rows.iter().enumerate() {
+            for (x, tile) in line.chars().enumerate() {
+                tiles.set_solid(x, height - 1 - row, tile == '#');
+            }
+        }
+        tiles
+    }
+}
+
+/// Deletes the save, e.g. once the run is continued, so it can't be
+/// continued twice. A missing save is fine.
+pub fn delete_save(path: &Path) {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            error!("Failed to delete the saved run: {error}")
+        }
+        _ => {}
+    }
+}
+
+/// Positions of all entities with component `T`
+fn positions<T: Component>(world: &mut World) -> Vec<(f32, f32)> {
+    world
+        .query_filtered::<&PhysicsPosition, With<T>>()
+        .iter(world)
+        .map(|position| position.current().into())
+        .collect()
+}
+
+/// Run condition for systems that stop while the game or its physics are
+/// paused
+pub fn running(time: Res<Time<Virtual>>, physics: Res<PhysicsPaused>) -> bool {
+    !time.is_paused() && !physics.0
+}
+
+/// Pauses the game with [`PAUSE_KEY`], and offers to save the run and quit
+/// to the main menu
+pub fn pause_menu(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut commands: Commands,
+    mut egui_context: egui::EguiContexts,
+) {
+    if keyboard.just_pressed(PAUSE_KEY) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+    if !time.is_paused() {
+        return;
+    }
+    egui::egui::Window::new("Paused").show(egui_context.ctx_mut(), |ui| {
+        if ui.button("Resume").clicked() {
+            time.unpause();
+        }
+        if ui.button("Save and Quit").clicked() {
+            commands.queue(|world: &mut World| {
+                match SaveGame::capture(world).and_then(|save| save.save(Path::new(SAVE_FILE))) {
+                    Ok(()) => world
+                        .resource_mut::<NextState<GamePhase>>()
+                        .set(GamePhase::MainMenu),
+                    Err(error) => error!("Failed to save the run: {error:#}"),
+                }
+            });
+        }
+    });
+}
+
+/// Resumes the time when leaving a paused game
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+/// Offers to continue the saved run in the main menu
+pub fn continue_menu(
+    mut load_error: Local<Option<String>>,
+    mut config: ResMut<WorldGenConfig>,
+    mut commands: Commands,
+    mut state: ResMut<NextState<GamePhase>>,
+    mut egui_context: egui::EguiContexts,
+) {
+    let path = Path::new(SAVE_FILE);
+    if !path.exists() {
+        return;
+    }
+    egui::egui::Window::new("Saved Run").show(egui_context.ctx_mut(), |ui| {
+        if ui.button("Continue").clicked() {
+            match SaveGame::load(path) {
+                Ok(save) => {
+                    delete_save(path);
+                    commands.insert_resource(WorldSeed(save.seed));
+                    commands.insert_resource(save.difficulty.config());
+                    config.rescue_fraction = save.rescue_fraction;
+                    commands.insert_resource(LoadedRun(save));
+                    state.set(GamePhase::WorldBuilding);
+                    *load_error = None;
+                }
+                Err(error) => *load_error = Some(format!("{error:#}")),
+            }
+        }
+        if let Some(error) = load_error.as_ref() {
+            ui.colored_label(Color32::RED, error);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        AssetStore, BuildProgress, LoadedAssets, build_world, spawn_pickups, spawn_player,
+    };
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// Creates an app with placeholder images for the ship and pickups
+    fn image_app() -> App {
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut images = Assets::<Image>::default();
+        for tag in ["ship", "spaceman", "fuel", "battery"] {
+            let handle = images.add(Image::default());
+            store.insert_handle(tag, handle.untyped(), &mut loaded);
+        }
+        let mut app = App::new();
+        app.insert_resource(store).insert_resource(loaded);
+        app
+    }
+
+    fn restore(app: &mut App, player: PlayerState, pickups: Pickups) {
+        app.world_mut()
+            .run_system_once(
+                move |mut commands: Commands,
+                      assets: Res<AssetStore>,
+                      loaded: Res<LoadedAssets>| {
+                    spawn_player(&mut commands, &assets, &loaded, &player);
+                    spawn_pickups(&mut commands, &assets, &loaded, &pickups);
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn saved_runs_continue_where_they_stopped() {
+        let config = crate::WorldGenConfig {
+            size: 100,
+            ..default()
+        };
+        let world = build_world(WorldSeed(7), &config, &BuildProgress::default());
+        let mut app = image_app();
+        app.insert_resource(WorldSeed(7))
+            .init_resource::<DifficultyConfig>()
+            .insert_resource(crate::WorldGenConfig {
+                rescue_fraction: 0.5,
+                ..config
+            })
+            .insert_resource(Terrain(world.tiles.clone()));
+        restore(
+            &mut app,
+            PlayerState::new_game(1400.0, &DifficultyConfig::default()),
+            Pickups::from_spawn_positions(&world.spawn_positions, &DifficultyConfig::default()),
+        );
+
+        // Play a bit: rescue two miners and use up fuel and shields
+        let mut player = app
+            .world_mut()
+            .query::<&mut Player>()
+            .single_mut(app.world_mut())
+            .unwrap();
+        player.fuel = 1234;
+        player.shields = 77;
+        player.score = 5000;
+        player.miners_saved = 2;
+        let rescued: Vec<Entity> = app
+            .world_mut()
+            .query_filtered::<Entity, With<Miner>>()
+            .iter(app.world())
+            .take(2)
+            .collect();
+        for miner in rescued {
+            app.world_mut().despawn(miner);
+        }
+
+        let path = std::env::temp_dir().join(format!("mars_save_{}.ron", std::process::id()));
+        SaveGame::capture(app.world_mut())
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let save = SaveGame::load(&path).unwrap();
+        delete_save(&path);
+        assert!(!path.exists());
+        // Deleting it again, e.g. at game over, is fine
+        delete_save(&path);
+        assert_eq!(world.tiles, save.tiles());
+        assert_eq!(0.5, save.rescue_fraction);
+
+        let mut app = image_app();
+        restore(&mut app, save.player, save.pickups);
+        let player = app
+            .world_mut()
+            .query::<&Player>()
+            .single(app.world())
+            .unwrap();
+        assert_eq!(
+            (1234, 77, 5000, 2),
+            (
+                player.fuel,
+                player.shields,
+                player.score,
+                player.miners_saved
+            )
+        );
+        let miners = app
+            .world_mut()
+            .query_filtered::<(), With<Miner>>()
+            .iter(app.world())
+            .count();
+        assert_eq!(18, miners);
+    }
+
+    #[test]
+    fn saves_of_other_versions_are_rejected() {
+        let save = SaveGame {
+            version: SAVE_VERSION + 1,
+            seed: 7,
+            difficulty: Difficulty::default(),
+            rescue_fraction: 1.0,
+            rows: vec!["#.#".to_string()],
+            player: PlayerState::new_game(0.0, &DifficultyConfig::default()),
+            pickups: Pickups::default(),
+        };
+        let path = std::env::temp_dir().join(format!("mars_old_save_{}.ron", std::process::id()));
+        save.save(&path).unwrap();
+        let error = SaveGame::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("version"), "{error}");
+    }
+}
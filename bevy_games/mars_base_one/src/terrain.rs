@@ -0,0 +1,290 @@
+//! Destructible terrain, carved by the ship's mining laser
+
+use super::{
+    GameElement, GameSettings, Ground, Minimap, Player, SpawnParticle, TILE_SIZE, particle_burst,
+};
+use bevy::prelude::*;
+use my_library::{
+    PhysicsPosition, TileChunk, TileGrid, TileUv, rebuild_tile_chunks, spawn_colliders,
+};
+
+/// Key to hold for firing the mining laser
+pub const MINING_KEY: KeyCode = KeyCode::Space;
+
+/// Distance the mining laser reaches
+const LASER_RANGE: f32 = 4.0 * TILE_SIZE;
+/// Seconds the laser needs to carve a tile
+const LASER_INTERVAL: f32 = 0.4;
+/// Fuel used for each carved tile
+const LASER_FUEL: i32 = 50;
+
+/// The tiles of the running game's world. Carving them updates the physics,
+/// the minimap and the mesh of the carved tile's chunk at once.
+#[derive(Resource)]
+pub struct Terrain(pub TileGrid);
+
+impl Terrain {
+    /// Opens a solid tile. Returns the solid neighbors it exposed, which need
+    /// colliders now, or None if the tile can't be removed. The border of
+    /// the world is never removed, so nobody can leave it.
+    pub fn remove_tile(&mut self, x: usize, y: usize) -> Option<Vec<(usize, usize)>> {
+        let tiles = &mut self.0;
+        if x == 0
+            || y == 0
+            || x >= tiles.width() - 1
+            || y >= tiles.height() - 1
+            || !tiles.is_solid(x, y)
+        {
+            return None;
+        }
+        let hidden: Vec<(usize, usize)> = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .filter(|&(x, y)| tiles.is_solid(x, y) && !tiles.is_edge(x, y))
+            .collect();
+        tiles.set_solid(x, y, false);
+        Some(hidden)
+    }
+}
+
+/// Event to carve a tile out of the terrain
+#[derive(Event)]
+pub struct CarveTile {
+    /// The tile to open
+    pub tile: (usize, usize),
+}
+
+/// Carves tiles out of the terrain. The ground colliders change at once, so
+/// schedule it before the collision checks to fly through the same frame.
+#[allow(clippy::too_many_arguments)]
+pub fn carve_terrain(
+    mut carved: EventReader<CarveTile>,
+    mut terrain: ResMut<Terrain>,
+    mut minimap: ResMut<Minimap>,
+    ground: Query<(Entity, &PhysicsPosition), With<Ground>>,
+    chunks: Query<(&TileChunk, &Mesh2d)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut particles: EventWriter<SpawnParticle>,
+    settings: Res<GameSettings>,
+    mut commands: Commands,
+) {
+    let coords = terrain.0.coords();
+    let mut removed = Vec::new();
+    let mut exposed = Vec::new();
+    for CarveTile { tile } in carved.read() {
+        let Some(hidden) = terrain.remove_tile(tile.0, tile.1) else {
+            continue;
+        };
+        removed.push(*tile);
+        exposed.extend(hidden);
+        minimap.set_tile(*tile, false);
+        particle_burst(
+            coords.tile_to_world(*tile),
+            LinearRgba::new(0.6, 0.4, 0.3, 1.0),
+            &mut particles,
+            1.5,
+            &settings,
+        );
+    }
+    if removed.is_empty() {
+        return;
+    }
+    rebuild_tile_chunks(
+        &terrain.0,
+        removed.iter().copied(),
+        TileUv::PerTile,
+        &chunks,
+        &mut meshes,
+    );
+
+    for (entity, position) in ground.iter() {
+        if coords
+            .world_to_tile(position.current())
+            .is_some_and(|tile| removed.contains(&tile))
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+    // A tile exposed by one carved tile may be carved by the next
+    exposed.retain(|&(x, y)| terrain.0.is_solid(x, y));
+    exposed.dedup();
+    spawn_colliders(
+        &mut commands,
+        exposed.into_iter().map(|tile| coords.tile_to_world(tile)),
+        Vec2::splat(TILE_SIZE),
+        (GameElement, Ground),
+    );
+}
+
+/// Fires the mining laser from the ship's nose while [`MINING_KEY`] is held.
+/// It carves the first tile it reaches every [`LASER_INTERVAL`] seconds, at
+/// the cost of some fuel.
+pub fn mining_laser(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    terrain: Res<Terrain>,
+    mut player: Query<(&Transform, &mut Player)>,
+    mut carve: EventWriter<CarveTile>,
+    mut heat: Local<f32>,
+    mut gizmos: Gizmos,
+) {
+    let Ok((transform, mut player)) = player.single_mut() else {
+        return;
+    };
+    if !keyboard.pressed(MINING_KEY) || player.fuel < LASER_FUEL {
+        *heat = 0.0;
+        return;
+    }
+    let ship = transform.translation.truncate();
+    let end = ship + transform.local_y().truncate() * LASER_RANGE;
+    let hit = terrain.0.raycast(ship, end);
+    let coords = terrain.0.coords();
+    gizmos.line_2d(
+        ship,
+        hit.map_or(end, |tile| coords.tile_to_world(tile)),
+        LinearRgba::new(1.0, 0.2, 0.1, 0.9),
+    );
+
+    let Some(tile) = hit else {
+        *heat = 0.0;
+        return;
+    };
+    *heat += time.delta_secs();
+    if *heat >= LASER_INTERVAL {
+        *heat = 0.0;
+        player.fuel -= LASER_FUEL;
+        carve.write(CarveTile { tile });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CHUNK_SIZE;
+    use my_library::{AxisAlignedBoundingBox, OnCollision, StaticQuadTree, check_collisions};
+
+    /// An app with a solid 7x7 terrain, open in the center, and a small
+    /// player on the tile to the right of it
+    fn terrain_app() -> (App, Entity) {
+        let mut tiles = TileGrid::new(7, 7, TILE_SIZE, true);
+        tiles.set_solid(3, 3, false);
+        let mut app = App::new();
+        app.add_event::<CarveTile>()
+            .add_event::<SpawnParticle>()
+            .init_resource::<GameSettings>()
+            .add_event::<OnCollision<Player, Ground>>()
+            .insert_resource(Minimap::new(&tiles))
+            .init_resource::<Assets<Mesh>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(
+                Update,
+                (carve_terrain, check_collisions::<Player, Ground>).chain(),
+            );
+        let coords = tiles.coords();
+        let edges: Vec<Vec2> = tiles
+            .edge_tiles()
+            .map(|tile| coords.tile_to_world(tile))
+            .collect();
+        spawn_colliders(
+            &mut app.world_mut().commands(),
+            edges,
+            Vec2::splat(TILE_SIZE),
+            (GameElement, Ground),
+        );
+        for (chunk, mesh) in tiles.build_chunk_meshes(CHUNK_SIZE, TileUv::PerTile) {
+            let handle = app.world_mut().resource_mut::<Assets<Mesh>>().add(mesh);
+            app.world_mut().spawn((chunk, Mesh2d(handle)));
+        }
+        app.insert_resource(Terrain(tiles));
+        let player = app
+            .world_mut()
+            .spawn((
+                Player {
+                    miners_saved: 0,
+                    shields: 100,
+                    fuel: 100,
+                    score: 0,
+                },
+                PhysicsPosition::new(coords.tile_to_world((4, 3))),
+                AxisAlignedBoundingBox::new(8.0, 8.0),
+            ))
+            .id();
+        app.world_mut().flush();
+        (app, player)
+    }
+
+    fn ground_tiles(app: &mut App) -> Vec<(usize, usize)> {
+        let coords = app.world().resource::<Terrain>().0.coords();
+        let mut tiles: Vec<_> = app
+            .world_mut()
+            .query_filtered::<&PhysicsPosition, With<Ground>>()
+            .iter(app.world())
+            .filter_map(|position| coords.world_to_tile(position.current()))
+            .collect();
+        tiles.sort();
+        tiles
+    }
+
+    fn terrain_vertices(app: &mut App) -> usize {
+        let handles: Vec<Handle<Mesh>> = app
+            .world_mut()
+            .query::<&Mesh2d>()
+            .iter(app.world())
+            .map(|mesh| mesh.0.clone())
+            .collect();
+        let meshes = app.world().resource::<Assets<Mesh>>();
+        handles
+            .iter()
+            .map(|handle| meshes.get(handle).unwrap().count_vertices())
+            .sum()
+    }
+
+    fn collisions(app: &App) -> usize {
+        app.world()
+            .resource::<Events<OnCollision<Player, Ground>>>()
+            .iter_current_update_events()
+            .count()
+    }
+
+    #[test]
+    fn carved_tiles_stop_colliding_at_once() {
+        let (mut app, _) = terrain_app();
+        app.update();
+        assert_eq!(1, collisions(&app));
+
+        app.world_mut().send_event(CarveTile { tile: (4, 3) });
+        app.update();
+        assert_eq!(0, collisions(&app));
+        assert!(!app.world().resource::<Terrain>().0.is_solid(4, 3));
+        assert!(!app.world().resource::<Events<SpawnParticle>>().is_empty());
+        assert_eq!(6 * 47, terrain_vertices(&mut app));
+    }
+
+    #[test]
+    fn carving_exposes_the_neighbors() {
+        let (mut app, _) = terrain_app();
+        let before = ground_tiles(&mut app);
+        assert!(before.contains(&(4, 3)));
+        assert!(!before.contains(&(4, 2)) && !before.contains(&(4, 4)));
+
+        app.world_mut().send_event(CarveTile { tile: (4, 3) });
+        app.update();
+        let after = ground_tiles(&mut app);
+        assert!(!after.contains(&(4, 3)));
+        // The enclosed neighbors gained colliders
+        for tile in [(5, 3), (4, 2), (4, 4)] {
+            assert!(after.contains(&tile));
+        }
+        assert_eq!(before.len() + 2, after.len());
+    }
+
+    #[test]
+    fn the_border_stays_solid() {
+        let (mut app, _) = terrain_app();
+        let mut terrain = app.world_mut().resource_mut::<Terrain>();
+        assert_eq!(None, terrain.remove_tile(6, 3));
+        assert_eq!(None, terrain.remove_tile(3, 3));
+        // Neighbors next to the open center had colliders before
+        assert_eq!(Some(vec![(1, 2), (2, 1)]), terrain.remove_tile(2, 2));
+        assert_eq!(None, terrain.remove_tile(2, 2));
+    }
+}
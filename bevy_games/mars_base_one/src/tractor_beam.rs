@@ -0,0 +1,266 @@
+//! Tractor beam, that pulls a miner to the ship from a distance
+
+use super::{Miner, Player, Terrain};
+use bevy::prelude::*;
+use my_library::{Impulse, PhysicsPosition, TileGrid};
+
+/// Key to hold for pulling the nearest miner
+pub const TRACTOR_KEY: KeyCode = KeyCode::KeyS;
+
+/// Resource configuring the tractor beam, and holding the miner it is
+/// tethered to
+#[derive(Resource)]
+pub struct TractorBeam {
+    /// Miners farther away than this cannot be caught
+    pub range: f32,
+    /// Pull per unit of distance to the ship, like a spring
+    pub stiffness: f32,
+    /// Fastest speed a miner is pulled with
+    pub max_speed: f32,
+    /// Fuel used while the beam is on
+    pub fuel_per_second: f32,
+    /// The miner being pulled
+    target: Option<Entity>,
+    /// Fuel used, but not yet taken from the tank
+    fuel_used: f32,
+}
+
+impl Default for TractorBeam {
+    fn default() -> Self {
+        Self {
+            range: 150.0,
+            stiffness: 0.05,
+            max_speed: 4.0,
+            fuel_per_second: 300.0,
+            target: None,
+            fuel_used: 0.0,
+        }
+    }
+}
+
+impl TractorBeam {
+    /// The miner being pulled, if any
+    pub fn target(&self) -> Option<Entity> {
+        self.target
+    }
+}
+
+/// Impulse source of the beam, see [`Impulse::source`]
+const TRACTOR_IMPULSE: i32 = 4;
+
+/// Finds the nearest miner in range, that can be seen from the ship
+fn nearest_miner(
+    ship: Vec2,
+    miners: impl Iterator<Item = (Entity, Vec2)>,
+    range: f32,
+    tiles: &TileGrid,
+) -> Option<Entity> {
+    miners
+        .map(|(miner, position)| (miner, position, ship.distance(position)))
+        .filter(|(_, position, distance)| {
+            *distance <= range && tiles.raycast(ship, *position).is_none()
+        })
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+        .map(|(miner, _, _)| miner)
+}
+
+/// Pulls the nearest miner towards the ship while [`TRACTOR_KEY`] is held,
+/// using fuel while a miner is caught. The beam breaks when rock gets
+/// between the ship and the miner.
+pub fn tractor_beam(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut beam: ResMut<TractorBeam>,
+    terrain: Res<Terrain>,
+    mut player: Query<(&PhysicsPosition, &mut Player)>,
+    miners: Query<(Entity, &PhysicsPosition), With<Miner>>,
+    mut impulses: EventWriter<Impulse>,
+) {
+    let Ok((ship, mut player)) = player.single_mut() else {
+        return;
+    };
+    let ship = ship.current();
+    let held = keyboard.pressed(TRACTOR_KEY) && player.fuel > 0;
+
+    // Keep the tethered miner while it is still in sight
+    let tethered = beam.target.and_then(|miner| {
+        let position = miners.get(miner).ok()?.1.current();
+        (held && terrain.0.raycast(ship, position).is_none()).then_some((miner, position))
+    });
+    if let (None, Some(miner)) = (tethered, beam.target.take()) {
+        // Released miners stay where they are
+        impulses.write(Impulse {
+            target: miner,
+            amount: Vec3::ZERO,
+            absolute: true,
+            source: TRACTOR_IMPULSE,
+        });
+        return;
+    }
+    if !held {
+        return;
+    }
+
+    let target = tethered.map(|(miner, _)| miner).or_else(|| {
+        nearest_miner(
+            ship,
+            miners
+                .iter()
+                .map(|(miner, position)| (miner, position.current())),
+            beam.range,
+            &terrain.0,
+        )
+    });
+    beam.target = target;
+    let Some(miner) = target else {
+        return;
+    };
+    let position = miners.get(miner).unwrap().1.current();
+    impulses.write(Impulse {
+        target: miner,
+        amount: ((ship - position) * beam.stiffness)
+            .clamp_length_max(beam.max_speed)
+            .extend(0.0),
+        absolute: true,
+        source: TRACTOR_IMPULSE,
+    });
+
+    beam.fuel_used += beam.fuel_per_second * time.delta_secs();
+    let used = beam.fuel_used.floor();
+    player.fuel -= used as i32;
+    beam.fuel_used -= used;
+}
+
+/// Draws the beam between the ship and the tethered miner
+pub fn draw_tractor_beam(
+    beam: Res<TractorBeam>,
+    player: Query<&Transform, With<Player>>,
+    miners: Query<&Transform, With<Miner>>,
+    mut gizmos: Gizmos,
+) {
+    let (Ok(ship), Some(Ok(miner))) = (player.single(), beam.target().map(|m| miners.get(m)))
+    else {
+        return;
+    };
+    gizmos.line_2d(
+        ship.translation.truncate(),
+        miner.translation.truncate(),
+        LinearRgba::new(0.3, 1.0, 0.6, 0.8),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TILE_SIZE;
+    use std::time::Duration;
+
+    #[test]
+    fn the_nearest_visible_miner_is_caught() {
+        let mut tiles = TileGrid::new(20, 20, TILE_SIZE, false);
+        let mut world = World::new();
+        let (near, far, hidden, away) = (
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+        );
+        let miners = [
+            (far, Vec2::new(100.0, 0.0)),
+            (near, Vec2::new(-60.0, 0.0)),
+            (hidden, Vec2::new(0.0, 55.0)),
+            (away, Vec2::new(0.0, -200.0)),
+        ];
+        // A rock between the ship and the closest miner
+        let rock = tiles.coords().world_to_tile(Vec2::new(0.0, 30.0)).unwrap();
+        tiles.set_solid(rock.0, rock.1, true);
+
+        assert_eq!(
+            Some(near),
+            nearest_miner(Vec2::ZERO, miners.into_iter(), 150.0, &tiles)
+        );
+        assert_eq!(
+            Some(far),
+            nearest_miner(Vec2::ZERO, miners[..1].iter().copied(), 150.0, &tiles)
+        );
+        assert_eq!(
+            None,
+            nearest_miner(Vec2::ZERO, miners[2..].iter().copied(), 150.0, &tiles)
+        );
+    }
+
+    /// An app with a ship at the origin, a miner to its right and the
+    /// tractor key held
+    fn beam_app() -> (App, Entity, Entity) {
+        let mut app = App::new();
+        let mut keyboard = ButtonInput::<KeyCode>::default();
+        keyboard.press(TRACTOR_KEY);
+        app.add_event::<Impulse>()
+            .insert_resource(keyboard)
+            .init_resource::<Time>()
+            .init_resource::<TractorBeam>()
+            .insert_resource(Terrain(TileGrid::new(20, 20, TILE_SIZE, false)))
+            .add_systems(Update, tractor_beam);
+        let player = app
+            .world_mut()
+            .spawn((
+                Player {
+                    miners_saved: 0,
+                    shields: 100,
+                    fuel: 1000,
+                    score: 0,
+                },
+                PhysicsPosition::new(Vec2::ZERO),
+            ))
+            .id();
+        let miner = app
+            .world_mut()
+            .spawn((Miner, PhysicsPosition::new(Vec2::new(100.0, 12.0))))
+            .id();
+        (app, player, miner)
+    }
+
+    fn pulls(app: &App) -> Vec<Vec3> {
+        app.world()
+            .resource::<Events<Impulse>>()
+            .iter_current_update_events()
+            .map(|impulse| impulse.amount)
+            .collect()
+    }
+
+    #[test]
+    fn the_beam_uses_fuel_while_pulling() {
+        let (mut app, player, miner) = beam_app();
+        for _ in 0..4 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(250));
+            app.update();
+        }
+
+        assert_eq!(Some(miner), app.world().resource::<TractorBeam>().target());
+        assert_eq!(1, pulls(&app).len());
+        assert!(pulls(&app)[0].x < 0.0);
+        assert_eq!(700, app.world().get::<Player>(player).unwrap().fuel);
+    }
+
+    #[test]
+    fn rocks_break_the_beam() {
+        let (mut app, _, miner) = beam_app();
+        app.update();
+        assert_eq!(Some(miner), app.world().resource::<TractorBeam>().target());
+
+        let mut terrain = app.world_mut().resource_mut::<Terrain>();
+        let rock = terrain
+            .0
+            .coords()
+            .world_to_tile(Vec2::new(50.0, 6.0))
+            .unwrap();
+        terrain.0.set_solid(rock.0, rock.1, true);
+        app.update();
+
+        assert_eq!(None, app.world().resource::<TractorBeam>().target());
+        // The miner stops where it is
+        assert_eq!(vec![Vec3::ZERO], pulls(&app));
+    }
+}
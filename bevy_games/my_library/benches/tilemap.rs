@@ -0,0 +1,27 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use my_library::*;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    // A world as large as Mars Base One's, with a random cave layout
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut grid = TileGrid::new(200, 200, 24.0, false);
+    for y in 0..200 {
+        for x in 0..200 {
+            grid.set_solid(x, y, rng.range(0..3) > 0);
+        }
+    }
+
+    // Rebuilding everything after changing one tile
+    c.bench_function("tilemap whole mesh", |b| {
+        b.iter(|| grid.build_mesh(TileUv::PerTile))
+    });
+
+    // Rebuilding only the chunk containing the tile
+    let chunk = TileChunk::containing((100, 100), 32);
+    c.bench_function("tilemap one chunk", |b| {
+        b.iter(|| grid.build_chunk_mesh(chunk, TileUv::PerTile))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,12 +1,14 @@
 use bevy::{platform::collections::HashMap, prelude::*};
 
-use crate::AssetStore;
+use crate::{AssetStore, AtlasFrames};
 
 /// Supported asset types
 #[derive(Clone, PartialEq, Debug)]
 pub enum AssetType {
     Image,
     Sound,
+    /// A font for text labels, e.g. `.ttf` or `.otf`
+    Font,
     /// Defines a set of frames (sub-images) on an image
     SpriteSheet {
         /// The frame size (x, y)
@@ -16,6 +18,11 @@ pub enum AssetType {
         /// number of rows
         sprites_y: usize,
     },
+    /// Defines frames (sub-images) at arbitrary rectangles on an image
+    AtlasRects {
+        /// The frame rectangles in pixels
+        rects: Vec<URect>,
+    },
 }
 
 /// The bevy resource to manages assets.
@@ -63,6 +70,16 @@ impl AssetManager {
         Ok(self)
     }
 
+    /// Adds a font to the asset manager, e.g. for [`spawn_text!`](crate::spawn_text)
+    pub fn add_font<S: ToString>(mut self, tag: S, filename: S) -> anyhow::Result<Self> {
+        let filename = filename.to_string();
+        Self::asset_exists(&filename)?;
+
+        self.asset_list
+            .push((tag.to_string(), filename, AssetType::Font));
+        Ok(self)
+    }
+
     /// Adds a sprite sheet to the asset manager
     pub fn add_sprite_sheet<S: ToString>(
         mut self,
@@ -89,6 +106,23 @@ impl AssetManager {
         Ok(self)
     }
 
+    /// Adds a sprite sheet with frames at arbitrary rectangles to the asset
+    /// manager, e.g. for sheets packed by a tool
+    pub fn add_atlas_rects<S: ToString>(
+        mut self,
+        tag: S,
+        filename: S,
+        rects: Vec<URect>,
+    ) -> anyhow::Result<Self> {
+        let filename = filename.to_string();
+        Self::asset_exists(&filename)?;
+
+        self.asset_list
+            .push((tag.to_string(), filename, AssetType::AtlasRects { rects }));
+
+        Ok(self)
+    }
+
     fn asset_exists(filename: &String) -> Result<(), anyhow::Error> {
         let current_directory = std::env::current_dir()?;
         let assets = current_directory.join("assets");
@@ -128,21 +162,24 @@ pub(crate) fn setup_asset_store(
                 tile_size,
                 sprites_x,
                 sprites_y,
-            } => {
-                // Load the underlying image and place it under a special tag
-                let image_handle = asset_server.load_untyped(filename);
-                let base_tag = format!("{tag}_base");
-                assets.asset_index.insert(base_tag.clone(), image_handle);
-
-                // Add atlas details with the original tag
-                assets.atlases_to_build.push(crate::FutureAtlas {
-                    tag: tag.clone(),
-                    texture_tag: base_tag,
+            } => add_atlas(
+                &mut assets,
+                asset_server,
+                tag,
+                filename,
+                AtlasFrames::Grid {
                     tile_size: *tile_size,
                     sprites_x: *sprites_x,
                     sprites_y: *sprites_y,
-                });
-            }
+                },
+            ),
+            AssetType::AtlasRects { rects } => add_atlas(
+                &mut assets,
+                asset_server,
+                tag,
+                filename,
+                AtlasFrames::Rects(rects.clone()),
+            ),
             _ => {
                 assets
                     .asset_index
@@ -154,6 +191,27 @@ pub(crate) fn setup_asset_store(
     assets
 }
 
+/// Loads the image of an atlas, whose layout is built once it is loaded
+fn add_atlas(
+    assets: &mut AssetStore,
+    asset_server: &AssetServer,
+    tag: &str,
+    filename: &str,
+    frames: AtlasFrames,
+) {
+    // Load the underlying image and place it under a special tag
+    let image_handle = asset_server.load_untyped(filename);
+    let base_tag = format!("{tag}_base");
+    assets.asset_index.insert(base_tag.clone(), image_handle);
+
+    // Add atlas details with the original tag
+    assets.atlases_to_build.push(crate::FutureAtlas {
+        tag: tag.to_string(),
+        texture_tag: base_tag,
+        frames,
+    });
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,5 +1,6 @@
 use bevy::{
     asset::{Asset, LoadedUntypedAsset},
+    ecs::system::SystemParam,
     platform::collections::HashMap,
     prelude::*,
 };
@@ -12,8 +13,48 @@ pub type LoadedAssets = Assets<LoadedUntypedAsset>;
 /// Resource of loaded assets
 pub type AssetResource<'w> = Res<'w, LoadedAssets>;
 
+/// System parameter bundling the [`AssetStore`] with the loaded assets, so
+/// systems need one parameter to get handles by tag
+///
+/// ```ignore
+/// fn spawn_ship(mut commands: Commands, assets: AssetHandles) {
+///     commands.spawn(Sprite::from_image(assets.image("ship")));
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct AssetHandles<'w> {
+    /// Handles of the assets by tag
+    pub store: Res<'w, AssetStore>,
+    /// The assets loaded so far
+    pub loaded: AssetResource<'w>,
+}
+
+impl AssetHandles<'_> {
+    /// Returns a handle to a stored resource, once it is loaded
+    pub fn get<T: Asset>(&self, tag: &str) -> Option<Handle<T>> {
+        self.store.get_handle(tag, &self.loaded)
+    }
+
+    /// Returns a handle to a stored image. Panics, if it is not loaded.
+    pub fn image(&self, tag: &str) -> Handle<Image> {
+        self.get(tag)
+            .unwrap_or_else(|| panic!("Image [{tag}] is not loaded"))
+    }
+
+    /// Returns a handle to a stored font. Panics, if it is not loaded.
+    pub fn font(&self, tag: &str) -> Handle<Font> {
+        self.get(tag)
+            .unwrap_or_else(|| panic!("Font [{tag}] is not loaded"))
+    }
+
+    /// Plays a sound
+    pub fn play(&self, sound_name: &str, commands: &mut Commands) {
+        self.store.play(sound_name, commands, &self.loaded);
+    }
+}
+
 /// Stores the handles for resources defined by the `AssetManager`
-#[derive(Resource, Clone)]
+#[derive(Resource, Clone, Default)]
 pub struct AssetStore {
     pub(crate) asset_index: HashMap<String, Handle<LoadedUntypedAsset>>,
     pub(crate) atlases_to_build: Vec<FutureAtlas>,
@@ -36,19 +77,102 @@ impl AssetStore {
         }
     }
 
+    /// Returns the size of a stored image in pixels, once it is loaded
+    pub fn get_image_size(
+        &self,
+        index: &str,
+        assets: &LoadedAssets,
+        images: &Assets<Image>,
+    ) -> Option<Vec2> {
+        let handle: Handle<Image> = self.get_handle(index, assets)?;
+        images.get(&handle).map(|image| image.size_f32())
+    }
+
+    /// Describes each stored asset by its tag, path and load state, sorted
+    /// by tag. Atlases are listed with the state of their layout.
+    pub fn debug_lines(&self, asset_server: &AssetServer, loaded: &LoadedAssets) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .asset_index
+            .iter()
+            .map(|(tag, handle)| {
+                let path = asset_server
+                    .get_path(handle.id())
+                    .map_or("<no path>".to_string(), |path| path.to_string());
+                // Once resolved, the state of the actual asset is more telling
+                let state = match loaded.get(handle) {
+                    Some(asset) => asset_server.get_load_state(asset.handle.id()),
+                    None => asset_server.get_load_state(handle.id()),
+                };
+                format!("{tag}: {path} ({state:?})")
+            })
+            .collect();
+        lines.extend(self.atlases_to_build.iter().map(|atlas| {
+            let state = if self.atlases.contains_key(&atlas.tag) {
+                "built"
+            } else {
+                "waiting for image"
+            };
+            format!("{}: atlas on [{}] ({state})", atlas.tag, atlas.texture_tag)
+        }));
+        lines.sort();
+        lines
+    }
+
+    /// Logs all stored assets with their load state, e.g. to find out why a
+    /// sprite is invisible
+    pub fn debug_dump(&self, asset_server: &AssetServer, loaded: &LoadedAssets) {
+        info!("Asset store contains {} assets", self.asset_index.len());
+        for line in self.debug_lines(asset_server, loaded) {
+            info!("{line}");
+        }
+    }
+
     /// Plays a sound
     pub fn play(&self, sound_name: &str, commands: &mut Commands, assets: &LoadedAssets) {
+        self.play_at_volume(sound_name, 1.0, commands, assets);
+    }
+
+    /// Plays a sound at a volume relative to the `GlobalVolume`, e.g. 0.5 for
+    /// half as loud
+    pub fn play_at_volume(
+        &self,
+        sound_name: &str,
+        volume: f32,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+    ) {
         let sound_handle: Handle<AudioSource> = self.get_handle(sound_name, assets).unwrap();
 
         commands.spawn((
             AudioPlayer::new(sound_handle.clone()),
             PlaybackSettings {
                 mode: bevy::audio::PlaybackMode::Despawn,
+                volume: bevy::audio::Volume::Linear(volume),
                 ..default()
             },
         ));
     }
 
+    /// Plays a sound over and over, e.g. an engine. The bundle is added to
+    /// the returned entity, despawn it to stop the sound.
+    pub fn play_loop(
+        &self,
+        sound_name: &str,
+        commands: &mut Commands,
+        assets: &LoadedAssets,
+        bundle: impl Bundle,
+    ) -> Entity {
+        let sound_handle: Handle<AudioSource> = self.get_handle(sound_name, assets).unwrap();
+
+        commands
+            .spawn((
+                AudioPlayer::new(sound_handle),
+                PlaybackSettings::LOOP,
+                bundle,
+            ))
+            .id()
+    }
+
     /// Returns a handle to both the sprite image and the atlas layout
     pub fn get_atlas_handle(
         &self,
@@ -59,13 +183,173 @@ impl AssetStore {
         }
         None
     }
+
+    /// Stores the handle of an asset created at runtime, so it can be used
+    /// like a loaded one
+    pub fn insert_handle(&mut self, index: &str, handle: UntypedHandle, assets: &mut LoadedAssets) {
+        let loaded = assets.add(LoadedUntypedAsset { handle });
+        self.asset_index.insert(index.to_string(), loaded);
+    }
+
+    /// Stores the handles of an atlas created at runtime, e.g. from a
+    /// generated image, so it can be used like a loaded sprite sheet
+    pub fn insert_atlas_handle(
+        &mut self,
+        index: &str,
+        image: Handle<Image>,
+        layout: Handle<TextureAtlasLayout>,
+    ) {
+        self.atlases.insert(index.to_string(), (image, layout));
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct FutureAtlas {
     pub(crate) tag: String,
     pub(crate) texture_tag: String,
-    pub(crate) tile_size: Vec2,
-    pub(crate) sprites_x: usize,
-    pub(crate) sprites_y: usize,
+    pub(crate) frames: AtlasFrames,
+}
+
+/// Describes where the frames of an atlas are located on its image
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum AtlasFrames {
+    /// Frames of equal size, arranged in a grid
+    Grid {
+        tile_size: Vec2,
+        sprites_x: usize,
+        sprites_y: usize,
+    },
+    /// Frames at arbitrary positions, e.g. packed by a tool
+    Rects(Vec<URect>),
+}
+
+impl AtlasFrames {
+    /// Builds the atlas layout for the frames
+    pub(crate) fn layout(&self) -> TextureAtlasLayout {
+        match self {
+            AtlasFrames::Grid {
+                tile_size,
+                sprites_x,
+                sprites_y,
+            } => TextureAtlasLayout::from_grid(
+                tile_size.as_uvec2(),
+                *sprites_x as u32,
+                *sprites_y as u32,
+                None,
+                None,
+            ),
+            AtlasFrames::Rects(rects) => {
+                // The layout covers all frames, the image may be larger
+                let size = rects
+                    .iter()
+                    .fold(UVec2::ZERO, |size, rect| size.max(rect.max));
+                let mut layout = TextureAtlasLayout::new_empty(size);
+                for rect in rects {
+                    layout.add_texture(*rect);
+                }
+                layout
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn debug_lines_list_every_tag() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let store = AssetStore {
+            asset_index: ["ship", "explosion", "sheet_base"]
+                .into_iter()
+                .map(|tag| (tag.to_string(), Handle::default()))
+                .collect(),
+            atlases_to_build: vec![FutureAtlas {
+                tag: "sheet".to_string(),
+                texture_tag: "sheet_base".to_string(),
+                frames: AtlasFrames::Rects(Vec::new()),
+            }],
+            atlases: HashMap::new(),
+        };
+
+        let lines = store.debug_lines(
+            app.world().resource::<AssetServer>(),
+            &LoadedAssets::default(),
+        );
+        assert_eq!(4, lines.len());
+        for tag in ["explosion: ", "sheet: ", "sheet_base: ", "ship: "] {
+            assert!(lines.iter().any(|line| line.starts_with(tag)), "{tag}");
+        }
+    }
+
+    #[test]
+    fn asset_handles_fetch_images_by_tag() {
+        let mut app = App::new();
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let image = Assets::<Image>::default().add(Image::default());
+        store.insert_handle("ship", image.clone().untyped(), &mut loaded);
+        app.insert_resource(store).insert_resource(loaded);
+
+        let (ship, missing) = app
+            .world_mut()
+            .run_system_once(|assets: AssetHandles| {
+                (assets.image("ship"), assets.get::<Image>("missing"))
+            })
+            .unwrap();
+        assert_eq!(image, ship);
+        assert_eq!(None, missing);
+    }
+
+    #[test]
+    fn looping_sounds_play_until_despawned() {
+        let mut app = App::new();
+        let mut assets = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let sound = Assets::<AudioSource>::default().add(AudioSource {
+            bytes: Vec::new().into(),
+        });
+        store.insert_handle("engine", sound.clone().untyped(), &mut assets);
+
+        let engine = app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                store.play_loop("engine", &mut commands, &assets, Name::new("Engine"))
+            })
+            .unwrap();
+        let entity = app.world().entity(engine);
+        assert_eq!(sound, entity.get::<AudioPlayer>().unwrap().0);
+        assert!(matches!(
+            entity.get::<PlaybackSettings>().unwrap().mode,
+            bevy::audio::PlaybackMode::Loop
+        ));
+        assert!(entity.contains::<Name>());
+    }
+
+    #[test]
+    fn grid_layout_has_a_frame_per_tile() {
+        let frames = AtlasFrames::Grid {
+            tile_size: Vec2::new(16.0, 32.0),
+            sprites_x: 3,
+            sprites_y: 2,
+        };
+        let layout = frames.layout();
+        assert_eq!(6, layout.len());
+        assert_eq!(URect::new(16, 32, 32, 64), layout.textures[4]);
+    }
+
+    #[test]
+    fn rect_layout_has_exact_rects() {
+        let rects = vec![
+            URect::new(0, 0, 24, 40),
+            URect::new(24, 0, 40, 16),
+            URect::new(24, 16, 64, 48),
+        ];
+        let layout = AtlasFrames::Rects(rects.clone()).layout();
+        assert_eq!(rects, layout.textures);
+        assert_eq!(UVec2::new(64, 48), layout.size);
+    }
 }
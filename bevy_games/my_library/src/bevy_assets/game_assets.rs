@@ -0,0 +1,155 @@
+//! Typed access to the assets of a game
+
+/// Declares the assets of a game once, and generates a system parameter with
+/// one accessor per asset. Tags are checked at compile time, so a typo is a
+/// compile error instead of a panic during the game.
+///
+/// Each asset is declared by its kind, its tag and how to load it:
+///
+/// - `image tag = "file.png"` returns a `Handle<Image>`
+/// - `sound tag = "file.ogg"` returns a `Handle<AudioSource>`
+/// - `font tag = "file.ttf"` returns a `Handle<Font>`
+/// - `sprite_sheet tag = ("file.png", width, height, columns, rows)` returns
+///   the image and atlas layout handles
+///
+/// ```ignore
+/// game_assets! {
+///     /// Assets of my game
+///     struct MyAssets {
+///         image ship = "ship.png",
+///         sound crash = "crash.ogg",
+///     }
+/// }
+///
+/// app.add_plugins(MyAssets::asset_manager()?);
+///
+/// fn spawn_ship(mut commands: Commands, assets: MyAssets) {
+///     commands.spawn(Sprite::from_image(assets.ship()));
+/// }
+/// ```
+///
+/// The accessors panic, if the assets are not loaded yet.
+#[macro_export]
+macro_rules! game_assets {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($kind:ident $tag:ident = $source:tt),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(bevy::ecs::system::SystemParam)]
+        $vis struct $name<'w> {
+            store: bevy::prelude::Res<'w, $crate::AssetStore>,
+            loaded: bevy::prelude::Res<'w, $crate::LoadedAssets>,
+        }
+
+        #[allow(dead_code)]
+        impl $name<'_> {
+            /// Creates an asset manager, that loads all declared assets
+            $vis fn asset_manager() -> $crate::anyhow::Result<$crate::AssetManager> {
+                let manager = $crate::AssetManager::new();
+                $(let manager = $crate::game_assets!(@add manager, $kind, $tag, $source);)*
+                Ok(manager)
+            }
+
+            $($crate::game_assets!(@get $vis, $kind, $tag);)*
+        }
+    };
+
+    (@add $manager:ident, image, $tag:ident, $file:literal) => {
+        $manager.add_image(stringify!($tag), $file)?
+    };
+    (@add $manager:ident, sound, $tag:ident, $file:literal) => {
+        $manager.add_sound(stringify!($tag), $file)?
+    };
+    (@add $manager:ident, font, $tag:ident, $file:literal) => {
+        $manager.add_font(stringify!($tag), $file)?
+    };
+    (@add $manager:ident, sprite_sheet, $tag:ident,
+        ($file:literal, $width:expr, $height:expr, $columns:expr, $rows:expr)) => {
+        $manager.add_sprite_sheet(stringify!($tag), $file, $width, $height, $columns, $rows)?
+    };
+
+    (@get $vis:vis, image, $tag:ident) => {
+        #[doc = concat!("Handle to the `", stringify!($tag), "` image")]
+        $vis fn $tag(&self) -> bevy::prelude::Handle<bevy::prelude::Image> {
+            self.store
+                .get_handle(stringify!($tag), &self.loaded)
+                .expect(concat!("Image [", stringify!($tag), "] is not loaded"))
+        }
+    };
+    (@get $vis:vis, sound, $tag:ident) => {
+        #[doc = concat!("Handle to the `", stringify!($tag), "` sound")]
+        $vis fn $tag(&self) -> bevy::prelude::Handle<bevy::prelude::AudioSource> {
+            self.store
+                .get_handle(stringify!($tag), &self.loaded)
+                .expect(concat!("Sound [", stringify!($tag), "] is not loaded"))
+        }
+    };
+    (@get $vis:vis, font, $tag:ident) => {
+        #[doc = concat!("Handle to the `", stringify!($tag), "` font")]
+        $vis fn $tag(&self) -> bevy::prelude::Handle<bevy::prelude::Font> {
+            self.store
+                .get_handle(stringify!($tag), &self.loaded)
+                .expect(concat!("Font [", stringify!($tag), "] is not loaded"))
+        }
+    };
+    (@get $vis:vis, sprite_sheet, $tag:ident) => {
+        #[doc = concat!("Handles to the `", stringify!($tag), "` image and atlas layout")]
+        $vis fn $tag(
+            &self,
+        ) -> (
+            bevy::prelude::Handle<bevy::prelude::Image>,
+            bevy::prelude::Handle<bevy::prelude::TextureAtlasLayout>,
+        ) {
+            self.store
+                .get_atlas_handle(stringify!($tag))
+                .expect(concat!("Sprite sheet [", stringify!($tag), "] is not loaded"))
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AssetStore, LoadedAssets};
+    use bevy::{
+        asset::LoadedUntypedAsset, ecs::system::RunSystemOnce, platform::collections::HashMap,
+        prelude::*,
+    };
+
+    game_assets! {
+        /// Assets used by the test
+        struct TestAssets {
+            image existing = "existing.png",
+            sound missing = "missing.ogg",
+        }
+    }
+
+    #[test]
+    fn known_tag_resolves_to_handle() {
+        let mut app = App::new();
+        let image = Handle::<Image>::default();
+        let mut loaded = LoadedAssets::default();
+        let untyped = loaded.add(LoadedUntypedAsset {
+            handle: image.clone().untyped(),
+        });
+        app.insert_resource(loaded).insert_resource(AssetStore {
+            asset_index: HashMap::from_iter([("existing".to_string(), untyped)]),
+            atlases_to_build: Vec::new(),
+            atlases: HashMap::new(),
+        });
+
+        let resolved = app
+            .world_mut()
+            .run_system_once(|assets: TestAssets| assets.existing())
+            .unwrap();
+        assert_eq!(image, resolved);
+    }
+
+    #[test]
+    fn asset_manager_checks_declared_files() {
+        // The sound file does not exist in the assets directory
+        assert!(TestAssets::asset_manager().is_err());
+    }
+}
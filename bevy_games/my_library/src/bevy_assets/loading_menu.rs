@@ -62,6 +62,29 @@ pub(crate) fn run<T>(
     });
 }
 
+/// Key to skip the loading screen with the `dev` feature
+#[cfg(feature = "dev")]
+pub(crate) const SKIP_LOADING_KEY: KeyCode = KeyCode::F12;
+
+/// Skips to the menu while assets are still loading, if the skip key is pressed
+#[cfg(feature = "dev")]
+pub(crate) fn skip<T>(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    to_load: Res<AsstesToLoad>,
+    mut state: ResMut<NextState<T>>,
+    menu_info: Res<MenuResource<T>>,
+) where
+    T: States + FromWorld + FreelyMutableState,
+{
+    if keyboard.just_pressed(SKIP_LOADING_KEY) {
+        warn!(
+            "Skipping the loading screen, {} assets are still pending",
+            to_load.0.len()
+        );
+        state.set(menu_info.menu_state.clone());
+    }
+}
+
 /// Cleanup resources after loading stage
 pub(crate) fn exit(mut commands: Commands) {
     commands.remove_resource::<AsstesToLoad>();
@@ -74,15 +97,7 @@ fn load_atlases(
     loaded_assets: &LoadedAssets,
 ) {
     for new_atlas in store.atlases_to_build.iter() {
-        let atlas = TextureAtlasLayout::from_grid(
-            new_atlas.tile_size.as_uvec2(),
-            new_atlas.sprites_x as u32,
-            new_atlas.sprites_y as u32,
-            None,
-            None,
-        );
-
-        let atlas_handle = texture_atlases.add(atlas);
+        let atlas_handle = texture_atlases.add(new_atlas.frames.layout());
         let img = store
             .get_handle(&new_atlas.texture_tag, loaded_assets)
             .unwrap();
@@ -91,3 +106,40 @@ fn load_atlases(
             .insert(new_atlas.tag.clone(), (img, atlas_handle));
     }
 }
+
+#[cfg(all(test, feature = "dev"))]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+    enum TestPhase {
+        #[default]
+        Loading,
+        Menu,
+        Playing,
+        GameOver,
+    }
+
+    #[test]
+    fn skip_key_sets_menu_state() {
+        let mut app = App::new();
+        let mut keyboard = ButtonInput::<KeyCode>::default();
+        keyboard.press(SKIP_LOADING_KEY);
+        app.insert_resource(keyboard)
+            .insert_resource(AsstesToLoad(Vec::new()))
+            .insert_resource(NextState::<TestPhase>::default())
+            .insert_resource(MenuResource {
+                menu_state: TestPhase::Menu,
+                game_start_state: TestPhase::Playing,
+                game_end_state: TestPhase::GameOver,
+            })
+            .add_systems(Update, skip::<TestPhase>);
+
+        app.update();
+
+        assert!(matches!(
+            app.world().resource::<NextState<TestPhase>>(),
+            NextState::Pending(TestPhase::Menu)
+        ));
+    }
+}
@@ -8,11 +8,14 @@
 //! easily.
 mod asset_manager;
 mod asset_store;
+mod game_assets;
 mod loading_menu;
+mod playlist;
 
 pub use asset_manager::AssetManager;
 pub use asset_store::*;
 pub(crate) use loading_menu::*;
+pub use playlist::*;
 
 /// Spawns an image stored by the asset manager
 #[macro_export]
@@ -26,3 +29,58 @@ macro_rules! spawn_image {
             )*
     };
 }
+
+/// Spawns a text label in the world, e.g. a floating score over a rescued
+/// miner. The font is a handle, usually of a font stored by the asset
+/// manager.
+///
+/// ```ignore
+/// spawn_text!(commands, assets.font("score"), "+1000", x, y, 10.0, Lifetime(1.0));
+/// ```
+#[macro_export]
+macro_rules! spawn_text {
+    ($commands:expr, $font:expr, $text:expr, $x:expr, $y:expr, $z:expr $(, $component:expr)* $(,)?) => {
+        $commands.spawn((
+            Text2d::new($text),
+            TextFont {
+                font: $font,
+                ..Default::default()
+            },
+            Transform::from_xyz($x, $y, $z)))
+            $(
+                .insert($component)
+            )*
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AssetStore, LoadedAssets};
+    use bevy::prelude::*;
+
+    #[derive(Component)]
+    struct FloatingScore;
+
+    #[test]
+    fn spawned_text_carries_its_content_and_position() {
+        let mut world = World::new();
+        let mut store = AssetStore::default();
+        let mut loaded = LoadedAssets::default();
+        let font: Handle<Font> = bevy::asset::weak_handle!("6f1c2a7e-3b4d-4c5e-8f90-a1b2c3d4e5f6");
+        store.insert_handle("score", font.clone().untyped(), &mut loaded);
+
+        let handle = store.get_handle("score", &loaded).unwrap();
+        let mut commands = world.commands();
+        let label = spawn_text!(commands, handle, "+1000", 10.0, 20.0, 5.0, FloatingScore).id();
+        world.flush();
+
+        let label = world.entity(label);
+        assert_eq!("+1000", label.get::<Text2d>().unwrap().0);
+        assert_eq!(font, label.get::<TextFont>().unwrap().font);
+        assert_eq!(
+            Vec3::new(10.0, 20.0, 5.0),
+            label.get::<Transform>().unwrap().translation
+        );
+        assert!(label.contains::<FloatingScore>());
+    }
+}
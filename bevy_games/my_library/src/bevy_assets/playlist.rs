@@ -0,0 +1,125 @@
+//! Plays a list of sounds one after another, e.g. music in the menu
+
+use super::{AssetStore, LoadedAssets};
+use bevy::prelude::*;
+
+/// Resource holding the sound tags of a playlist, in the order they are
+/// played. After the last track, the playlist starts over.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Playlist {
+    /// Tags of the sounds in the asset store
+    tracks: Vec<String>,
+    /// Index of the track played next
+    next: usize,
+    /// Audio entity of the track playing
+    playing: Option<Entity>,
+}
+
+impl Playlist {
+    /// Creates a playlist of the sounds stored under the given tags
+    pub fn new<S: ToString>(tracks: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            tracks: tracks.into_iter().map(|tag| tag.to_string()).collect(),
+            ..default()
+        }
+    }
+
+    /// Tag of the track playing, if any
+    pub fn current(&self) -> Option<&str> {
+        self.playing?;
+        let index = (self.next + self.tracks.len() - 1) % self.tracks.len();
+        Some(&self.tracks[index])
+    }
+}
+
+/// Marks the audio entity of a [`Playlist`]'s track
+#[derive(Component)]
+pub struct PlaylistTrack;
+
+/// System that starts the next track of the [`Playlist`] when the last one
+/// has finished. Tracks play with `PlaybackMode::Despawn`, so a finished
+/// track's entity is gone.
+pub fn play_playlist(
+    mut playlist: ResMut<Playlist>,
+    tracks: Query<(), With<PlaylistTrack>>,
+    store: Res<AssetStore>,
+    loaded_assets: Res<LoadedAssets>,
+    mut commands: Commands,
+) {
+    if playlist.tracks.is_empty() || playlist.playing.is_some_and(|track| tracks.contains(track)) {
+        return;
+    }
+    let tag = &playlist.tracks[playlist.next];
+    let Some(sound) = store.get_handle::<AudioSource>(tag, &loaded_assets) else {
+        warn!("Sound [{tag}] of the playlist is not loaded");
+        return;
+    };
+    let track = commands
+        .spawn((
+            AudioPlayer::new(sound),
+            PlaybackSettings::DESPAWN,
+            PlaylistTrack,
+        ))
+        .id();
+    playlist.playing = Some(track);
+    playlist.next = (playlist.next + 1) % playlist.tracks.len();
+}
+
+/// System that stops the [`Playlist`], e.g. when leaving the menu. It starts
+/// over with the track after the stopped one.
+pub fn stop_playlist(
+    mut playlist: ResMut<Playlist>,
+    tracks: Query<Entity, With<PlaylistTrack>>,
+    mut commands: Commands,
+) {
+    tracks
+        .iter()
+        .for_each(|track| commands.entity(track).despawn());
+    playlist.playing = None;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_next_track_starts_when_one_ends() {
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut sounds = Assets::<AudioSource>::default();
+        let handles: Vec<Handle<AudioSource>> = ["intro", "theme"]
+            .into_iter()
+            .map(|tag| {
+                let handle = sounds.add(AudioSource {
+                    bytes: Vec::new().into(),
+                });
+                store.insert_handle(tag, handle.clone().untyped(), &mut loaded);
+                handle
+            })
+            .collect();
+        let mut app = App::new();
+        app.insert_resource(store)
+            .insert_resource(loaded)
+            .insert_resource(Playlist::new(["intro", "theme"]))
+            .add_systems(Update, play_playlist);
+
+        // Plays each track once the previous one is gone, then starts over
+        for expected in [0, 1, 0] {
+            app.update();
+            app.update();
+            let playing: Vec<(Entity, Handle<AudioSource>)> = app
+                .world_mut()
+                .query_filtered::<(Entity, &AudioPlayer), With<PlaylistTrack>>()
+                .iter(app.world())
+                .map(|(entity, player)| (entity, player.0.clone()))
+                .collect();
+            assert_eq!(1, playing.len());
+            assert_eq!(handles[expected], playing[0].1);
+            assert_eq!(
+                Some(["intro", "theme"][expected]),
+                app.world().resource::<Playlist>().current()
+            );
+            app.world_mut().despawn(playing[0].0);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Run expensive work, like world generation, in the background without
+//! blocking the game loop
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on},
+};
+
+/// Resource holding work that runs on Bevy's async compute task pool. Games
+/// can insert it when a generation phase starts, poll it with
+/// [`is_ready`](Self::is_ready) each frame and finally take the result.
+#[derive(Resource)]
+pub struct BackgroundTask<T: Send + 'static> {
+    /// The running task. None once the result was taken.
+    task: Option<Task<T>>,
+}
+
+impl<T: Send + 'static> BackgroundTask<T> {
+    /// Starts a function on the async compute task pool
+    pub fn spawn(work: impl FnOnce() -> T + Send + 'static) -> Self {
+        let task = AsyncComputeTaskPool::get().spawn(async move { work() });
+        Self { task: Some(task) }
+    }
+
+    /// Checks if the work is done and the result wasn't taken yet
+    pub fn is_ready(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| task.is_finished())
+    }
+
+    /// Takes the result, if the work is done. Returns the result only once,
+    /// None afterwards.
+    pub fn take_result(&mut self) -> Option<T> {
+        if self.is_ready() {
+            self.task.take().map(block_on)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::tasks::TaskPool;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn result_can_be_taken_exactly_once() {
+        AsyncComputeTaskPool::get_or_init(TaskPool::default);
+        let mut task = BackgroundTask::spawn(|| (1..=10).sum::<u32>());
+
+        let started = Instant::now();
+        while !task.is_ready() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "Task never finished"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(Some(55), task.take_result());
+        assert!(!task.is_ready());
+        assert_eq!(None, task.take_result());
+    }
+}
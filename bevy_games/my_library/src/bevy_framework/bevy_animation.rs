@@ -1,8 +1,11 @@
 //! This module defines a mini scripting language for animations.
 
+use bevy::ecs::system::SystemParam;
 use bevy::platform::collections::HashMap;
 use bevy::{log, prelude::*};
 
+use crate::{AxisAlignedBoundingBox, CameraLayer, RandomNumberGenerator};
+
 /// Actions that can uccor in any given frame.
 pub enum AnimationOption {
     /// Do nothing. Freezes the animation.
@@ -11,10 +14,15 @@ pub enum AnimationOption {
     NextFrame,
     /// Jump to a numbered frame. Allows for skipping or repeating.
     GoToFrame(usize),
+    /// Jump to a random frame of the sequence, e.g. for rolling dice
+    GoToRandomFrame,
     /// Switch to a different animation
     SwitchToAnimation(String),
     /// Play a sound. Synchronize animation with sound effects
     PlaySound(String),
+    /// Replace the entity's collider, e.g. to extend the hitbox on the
+    /// frames of an attack
+    SetCollider(AxisAlignedBoundingBox),
 }
 
 /// Defines a frame that is part of the animated sequence
@@ -67,6 +75,16 @@ impl Animations {
         self.0.insert(tag.to_string(), animation);
         self
     }
+
+    /// Checks if an animation sequence is stored under a given tag
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.contains_key(tag)
+    }
+
+    /// Lists the tags of all stored animation sequences
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
 }
 
 /// A component to attach the animation state machine to the animated entity
@@ -81,6 +99,24 @@ pub struct AnimationCycle {
     /// The time elapsed since animation was rendered the last time. This keeps
     /// the timer state independently for each executing animation.
     timer: u128,
+
+    /// Time in ms after which the animation stops, and the sprite index
+    /// shown from then on
+    settle: Option<(u128, usize)>,
+
+    /// The time elapsed since the animation started
+    elapsed: u128,
+
+    /// Has the animation settled?
+    finished: bool,
+}
+
+/// Event sent when an animation settled on its final sprite, see
+/// [`AnimationCycle::settle_after`]
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFinished {
+    /// The animated entity
+    pub entity: Entity,
 }
 
 impl AnimationCycle {
@@ -90,9 +126,34 @@ impl AnimationCycle {
             animation_tag: tag.to_string(),
             current_frame: 0,
             timer: 0,
+            settle: None,
+            elapsed: 0,
+            finished: false,
         }
     }
 
+    /// Stops the animation after `duration_ms`, showing `sprite_index` from
+    /// then on and sending an [`AnimationFinished`] event
+    pub fn settle_after(mut self, duration_ms: u128, sprite_index: usize) -> Self {
+        self.settle = Some((duration_ms, sprite_index));
+        self
+    }
+
+    /// Checks if the animation settled on its final sprite
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The tag of the animation sequence currently playing
+    pub fn current_tag(&self) -> &str {
+        &self.animation_tag
+    }
+
+    /// The frame of the sequence currently shown, counting from 0
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
     /// Switches an already running animation to a *different* animation sequence
     pub fn switch<S: ToString>(&mut self, new: S) {
         let new = new.to_string();
@@ -104,19 +165,81 @@ impl AnimationCycle {
     }
 }
 
-/// System that animates frame sequences by using animation data
+/// Marks animated entities that keep moving while the game time is paused,
+/// e.g. animations and parallax layers in the background of menus
+#[derive(Component)]
+pub struct IgnoreTimePause;
+
+/// Time source shared by the animation systems. It follows the virtual game
+/// time, which stands still while paused, unless an entity is marked to
+/// [`IgnoreTimePause`].
+#[derive(SystemParam)]
+pub struct AnimationTime<'w> {
+    virtual_time: Res<'w, Time<Virtual>>,
+    real_time: Res<'w, Time<Real>>,
+}
+
+impl AnimationTime<'_> {
+    /// Milliseconds since the last frame, taken from real time for entities
+    /// ignoring the pause
+    pub fn delta_ms(&self, ignore_pause: bool) -> u128 {
+        if ignore_pause {
+            self.real_time.delta().as_millis()
+        } else {
+            self.virtual_time.delta().as_millis()
+        }
+    }
+
+    /// Whether the time stands still for an entity, i.e. the game is paused
+    /// and the entity doesn't ignore it
+    pub fn is_paused(&self, ignore_pause: bool) -> bool {
+        !ignore_pause && self.virtual_time.is_paused()
+    }
+}
+
+/// Components of an animated entity. The collider is optional and only
+/// replaced by [`AnimationOption::SetCollider`].
+type Animated<'a> = (
+    Entity,
+    &'a mut AnimationCycle,
+    &'a mut Sprite,
+    Option<&'a mut AxisAlignedBoundingBox>,
+    Has<IgnoreTimePause>,
+);
+
+/// System that animates frame sequences by using animation data. Animations
+/// stop while the game time is paused, unless marked to [`IgnoreTimePause`].
+#[allow(clippy::too_many_arguments)]
 pub fn cycle_animations(
     animations: Res<Animations>,
-    mut animated: Query<(&mut AnimationCycle, &mut Sprite)>, // mutable access to all entities with both AnimationCycle and Sprite components
-    time: Res<Time>,
+    mut animated: Query<Animated>, // mutable access to all entities with both AnimationCycle and Sprite components
+    time: AnimationTime,
     assets: Res<crate::AssetStore>,
     mut commands: Commands,
     loaded_assets: Res<crate::LoadedAssets>,
+    mut rng: Local<RandomNumberGenerator>,
+    mut finished: EventWriter<AnimationFinished>,
 ) {
-    let ms_since_last_call = time.delta().as_millis();
+    // Borrowed mutably, as the generator needs it without the locking feature
+    let rng: &mut RandomNumberGenerator = &mut rng;
+    for (entity, mut animation, mut sprite, mut collider, unpausable) in animated.iter_mut() {
+        if animation.finished {
+            continue;
+        }
+        let delta = time.delta_ms(unpausable);
+        animation.timer += delta;
 
-    animated.iter_mut().for_each(|(mut animation, mut sprite)| {
-        animation.timer += ms_since_last_call;
+        if let Some((duration, sprite_index)) = animation.settle {
+            animation.elapsed += delta;
+            if animation.elapsed >= duration {
+                animation.finished = true;
+                if let Some(texture_atlas) = &mut sprite.texture_atlas {
+                    texture_atlas.index = sprite_index;
+                }
+                finished.write(AnimationFinished { entity });
+                continue;
+            }
+        }
 
         if let Some(cycle) = animations.0.get(&animation.animation_tag) {
             let current_frame = &cycle.frames[animation.current_frame];
@@ -135,9 +258,18 @@ pub fn cycle_animations(
                         AnimationOption::GoToFrame(frame) => {
                             animation.current_frame = *frame;
                         }
+                        AnimationOption::GoToRandomFrame => {
+                            animation.current_frame = rng.range(0..cycle.frames.len());
+                        }
                         AnimationOption::PlaySound(tag) => {
                             assets.play(tag, &mut commands, &loaded_assets);
                         }
+                        AnimationOption::SetCollider(bbox) => match collider.as_mut() {
+                            Some(collider) => **collider = *bbox,
+                            None => {
+                                commands.entity(entity).insert(*bbox);
+                            }
+                        },
                     }
 
                     if let Some(texture_atlas) = &mut sprite.texture_atlas {
@@ -148,7 +280,7 @@ pub fn cycle_animations(
         } else {
             log::warn!("Animation Cycle [{}] not found!", animation.animation_tag);
         }
-    });
+    }
 }
 
 /// Spawns an animated sprite
@@ -178,24 +310,100 @@ macro_rules! spawn_animated_sprite {
 /// Define a Parallax background level
 #[derive(Component)]
 pub struct ContinualParallax {
-    /// Image width, defines where to move when no longer visible
-    image_width: f32,
-    // Move the image every n milliseconds
+    /// Image size per axis, defines where to move when no longer visible.
+    /// An extent of 0.0 disables wrapping on that axis.
+    extent: Vec2,
+    // Move the image every n milliseconds, or every frame if 0
     move_every_ms: u128,
     // How far should the image move each tick?
     scroll_speed: Vec2,
     // Internal state for animations timer
     timer: u128,
+    // Position per axis at which the image is out of sight on the left/bottom
+    wrap_at: Vec2,
+    // How far the image jumps per axis when out of sight
+    wrap_by: Vec2,
+    // Stops scrolling while paused
+    paused: bool,
 }
 
 impl ContinualParallax {
-    /// Creates a new parallax layer definition
+    /// Creates a new parallax layer definition, wrapping horizontally
     pub fn new(image_width: f32, move_every_ms: u128, scroll_speed: Vec2) -> Self {
         Self {
-            image_width,
+            extent: Vec2::ZERO,
             move_every_ms,
             scroll_speed,
             timer: 0,
+            wrap_at: Vec2::ZERO,
+            wrap_by: Vec2::ZERO,
+            paused: false,
+        }
+        .with_extent(Vec2::new(image_width, 0.0))
+    }
+
+    /// Sets the image extent per axis for a conveyor belt of two copies on
+    /// each wrapped axis. Use 0.0 for axes that shouldn't wrap.
+    pub fn with_extent(mut self, extent: Vec2) -> Self {
+        self.extent = extent;
+        self.wrap_at = -extent;
+        self.wrap_by = 2.0 * extent;
+        self
+    }
+
+    /// Wraps the image as one of `copies` side-by-side copies covering a
+    /// viewport centered at x = 0
+    pub fn wrapping(mut self, viewport_width: f32, copies: usize) -> Self {
+        self.wrap_at.x = -(viewport_width + self.extent.x) / 2.0;
+        self.wrap_by.x = copies as f32 * self.extent.x;
+        self
+    }
+
+    /// Wraps the image as one of `copies` stacked copies covering a
+    /// viewport centered at y = 0
+    pub fn wrapping_vertical(mut self, viewport_height: f32, copies: usize) -> Self {
+        self.wrap_at.y = -(viewport_height + self.extent.y) / 2.0;
+        self.wrap_by.y = copies as f32 * self.extent.y;
+        self
+    }
+
+    /// Stops scrolling the layer
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Continues scrolling the layer
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Checks if scrolling is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Moves the image by one tick, putting it behind the last copy when it
+    /// moved out of sight
+    fn scroll(&self, translation: &mut Vec3) {
+        translation.x -= self.scroll_speed.x;
+        translation.y -= self.scroll_speed.y;
+
+        translation.x = Self::wrap(translation.x, self.wrap_at.x, self.wrap_by.x);
+        translation.y = Self::wrap(translation.y, self.wrap_at.y, self.wrap_by.y);
+    }
+
+    /// Keeps a position within the band (low, low + by], by moving it to the
+    /// other end when it left the band on either side
+    fn wrap(position: f32, low: f32, by: f32) -> f32 {
+        if by <= 0.0 {
+            // This axis doesn't wrap
+            position
+        } else if position <= low {
+            position + by
+        } else if position > low + by {
+            position - by
+        } else {
+            position
         }
     }
 }
@@ -203,25 +411,792 @@ impl ContinualParallax {
 /// Implements a _conveyor belt_ that puts a second version of the image to
 /// the invisible right side of the first. When the first image is moved left
 /// out of the screen, it is positioned on the invisible right edge of the
-/// remaining image.
+/// remaining image. Layers stop while the game time is paused, unless they
+/// are marked to [`IgnoreTimePause`].
 pub fn continual_parallax(
-    mut animated: Query<(&mut ContinualParallax, &mut Transform)>,
-    time: Res<Time>,
+    mut animated: Query<(&mut ContinualParallax, &mut Transform, Has<IgnoreTimePause>)>,
+    time: AnimationTime,
 ) {
-    let ms_since_last_call = time.delta().as_millis();
     animated
         .iter_mut()
-        .for_each(|(mut parallax, mut transform)| {
-            parallax.timer += ms_since_last_call;
+        .for_each(|(mut parallax, mut transform, unpausable)| {
+            if parallax.paused || time.is_paused(unpausable) {
+                return;
+            }
+            // Frames shorter than a millisecond still move layers without an
+            // interval
+            parallax.timer += time.delta_ms(unpausable);
             if parallax.timer >= parallax.move_every_ms {
                 parallax.timer = 0;
-                transform.translation.x -= parallax.scroll_speed.x;
-                transform.translation.y -= parallax.scroll_speed.y;
-
-                // Check if image moved out ouf sight on the left screen border
-                if transform.translation.x <= -parallax.image_width {
-                    transform.translation.x = parallax.image_width
-                }
+                parallax.scroll(&mut transform.translation);
             }
         });
 }
+
+/// Calculates how many copies of an image are needed to cover the viewport
+/// without gaps while scrolling
+fn parallax_copies(image_width: f32, viewport_width: f32) -> usize {
+    (viewport_width / image_width).ceil() as usize + 1
+}
+
+/// Spawns enough copies of an image side-by-side to seamlessly scroll a
+/// parallax layer through the viewport. The image width is taken from the
+/// loaded image. Each copy is spawned together with the `components` bundle,
+/// e.g. a marker for cleaning up the game phase.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_parallax_layer<B: Bundle + Clone>(
+    commands: &mut Commands,
+    assets: &crate::AssetStore,
+    loaded_assets: &crate::LoadedAssets,
+    images: &Assets<Image>,
+    tag: &str,
+    z: f32,
+    move_every_ms: u128,
+    scroll_speed: Vec2,
+    viewport_width: f32,
+    components: B,
+) {
+    let image_width = assets
+        .get_image_size(tag, loaded_assets, images)
+        .unwrap_or_else(|| panic!("Image [{tag}] is not loaded"))
+        .x;
+    let copies = parallax_copies(image_width, viewport_width);
+    let left = (image_width - viewport_width) / 2.0;
+
+    for copy in 0..copies {
+        commands.spawn((
+            Sprite::from_image(assets.get_handle(tag, loaded_assets).unwrap()),
+            Transform::from_xyz(left + copy as f32 * image_width, 0.0, z),
+            ContinualParallax::new(image_width, move_every_ms, scroll_speed)
+                .wrapping(viewport_width, copies),
+            components.clone(),
+        ));
+    }
+}
+
+/// Define a parallax background layer that moves with the camera rather than
+/// on a timer. This suits games with free movement, where layers farther away
+/// should shift less than the world when the camera moves.
+#[derive(Component)]
+pub struct CameraParallax {
+    /// How much the layer sticks to the world per axis: 0.0 keeps the layer
+    /// fixed to the camera, 1.0 keeps it fixed in the world.
+    pub factor: Vec2,
+    /// Position of the layer when the camera is at the origin
+    pub anchor: Vec2,
+}
+
+impl CameraParallax {
+    /// Creates a new camera relative parallax layer definition
+    pub fn new(factor: Vec2, anchor: Vec2) -> Self {
+        Self { factor, anchor }
+    }
+
+    /// Calculates the position of the layer for a given camera position
+    pub fn position(&self, camera: Vec2) -> Vec2 {
+        self.anchor + camera * (Vec2::ONE - self.factor)
+    }
+}
+
+/// Filters the main cameras, leaving out secondary [`CameraLayer`] cameras
+/// and the parallax layers `L` moved by them
+type MainCameras<L> = (Without<L>, Without<CameraLayer>);
+
+/// Positions camera relative parallax layers depending on the active main
+/// camera, ignoring secondary [`CameraLayer`] cameras like a minimap.
+/// Schedule it after the systems moving the camera to avoid lagging behind.
+pub fn camera_parallax(
+    cameras: Query<(&Camera, &Transform), MainCameras<CameraParallax>>,
+    mut layers: Query<(&CameraParallax, &mut Transform)>,
+) {
+    let Some((_, camera)) = cameras.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+    let camera = camera.translation.truncate();
+    layers.iter_mut().for_each(|(parallax, mut transform)| {
+        let position = parallax.position(camera);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    });
+}
+
+/// Where a [`VelocityParallax`] layer takes its movement from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParallaxSource {
+    /// Follow the movement of the active main camera, ignoring secondary
+    /// [`CameraLayer`] cameras
+    Camera,
+    /// Follow the movement of a given entity, e.g. the player
+    Entity(Entity),
+}
+
+/// Define a parallax background layer that scrolls proportionally to the
+/// movement of a source, e.g. the player's ship. Faster movement lets the
+/// layer drift faster, while it stands still when the source doesn't move.
+/// Wraps like a [`ContinualParallax`] conveyor belt of two copies.
+#[derive(Component)]
+pub struct VelocityParallax {
+    /// How far the layer scrolls per axis for each unit the source moved
+    pub factor: Vec2,
+    /// Whose movement drives the layer
+    pub source: ParallaxSource,
+    /// Image size per axis. An extent of 0.0 disables wrapping on that axis.
+    extent: Vec2,
+    // Position of the source during the last frame
+    last_position: Option<Vec2>,
+}
+
+impl VelocityParallax {
+    /// Creates a new velocity driven parallax layer definition
+    pub fn new(factor: Vec2, source: ParallaxSource) -> Self {
+        Self {
+            factor,
+            source,
+            extent: Vec2::ZERO,
+            last_position: None,
+        }
+    }
+
+    /// Sets the image extent per axis for a conveyor belt of two copies on
+    /// each wrapped axis. Use 0.0 for axes that shouldn't wrap.
+    pub fn with_extent(mut self, extent: Vec2) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    /// Moves the layer against the movement of its source since the last
+    /// frame, putting it behind the other copy when it moved out of sight
+    fn scroll(&mut self, source: Vec2, translation: &mut Vec3) {
+        let delta = source - self.last_position.unwrap_or(source);
+        self.last_position = Some(source);
+
+        translation.x -= delta.x * self.factor.x;
+        translation.y -= delta.y * self.factor.y;
+
+        translation.x = ContinualParallax::wrap(translation.x, -self.extent.x, 2.0 * self.extent.x);
+        translation.y = ContinualParallax::wrap(translation.y, -self.extent.y, 2.0 * self.extent.y);
+    }
+}
+
+/// Scrolls velocity driven parallax layers by the movement of their sources
+/// since the last frame. Schedule it after the systems moving the sources.
+pub fn velocity_parallax(
+    cameras: Query<(&Camera, &Transform), MainCameras<VelocityParallax>>,
+    sources: Query<&Transform, Without<VelocityParallax>>,
+    mut layers: Query<(&mut VelocityParallax, &mut Transform)>,
+) {
+    let camera = cameras
+        .iter()
+        .find(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation.truncate());
+
+    layers.iter_mut().for_each(|(mut parallax, mut transform)| {
+        let source = match parallax.source {
+            ParallaxSource::Camera => camera,
+            ParallaxSource::Entity(entity) => sources
+                .get(entity)
+                .ok()
+                .map(|transform| transform.translation.truncate()),
+        };
+        if let Some(source) = source {
+            parallax.scroll(source, &mut transform.translation);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rect2D;
+
+    /// Checks that side-by-side images centered at `positions` cover the viewport
+    fn covers_viewport(positions: &[f32], image_width: f32, viewport_width: f32) -> bool {
+        let mut positions = positions.to_vec();
+        positions.sort_by(f32::total_cmp);
+        let mut covered = -viewport_width / 2.0;
+        for x in positions {
+            if x - image_width / 2.0 > covered + 0.01 {
+                return false;
+            }
+            covered = covered.max(x + image_width / 2.0);
+        }
+        covered >= viewport_width / 2.0
+    }
+
+    #[test]
+    fn parallax_layer_never_shows_gaps() {
+        let cases = [
+            // (image width, viewport width, scroll speed)
+            (1280.0, 1024.0, 1.0),
+            (1000.0, 1024.0, 3.0),
+            (333.0, 1024.0, 7.0),
+            (700.0, 1920.0, 2.5),
+            (512.0, 1024.0, 4.0),
+            (97.0, 800.0, 13.0),
+        ];
+
+        for (image_width, viewport_width, speed) in cases {
+            let copies = parallax_copies(image_width, viewport_width);
+            let parallax = ContinualParallax::new(image_width, 0, Vec2::new(speed, 0.0))
+                .wrapping(viewport_width, copies);
+            let left = (image_width - viewport_width) / 2.0;
+            let mut positions: Vec<Vec3> = (0..copies)
+                .map(|copy| Vec3::new(left + copy as f32 * image_width, 0.0, 0.0))
+                .collect();
+
+            for _ in 0..10_000 {
+                let xs: Vec<f32> = positions.iter().map(|p| p.x).collect();
+                assert!(
+                    covers_viewport(&xs, image_width, viewport_width),
+                    "gap for image {image_width} in viewport {viewport_width}: {xs:?}"
+                );
+                positions.iter_mut().for_each(|p| parallax.scroll(p));
+            }
+        }
+    }
+
+    /// Scrolls parallax copies over many wraps and checks that they keep
+    /// within the band of positions on both axes
+    fn assert_stays_in_band(parallax: ContinualParallax, mut copies: Vec<Vec3>) {
+        let low = -parallax.extent;
+        let high = parallax.extent;
+        for _ in 0..10_000 {
+            copies.iter_mut().for_each(|copy| parallax.scroll(copy));
+            for copy in copies.iter() {
+                assert!(copy.x > low.x - 0.01 && copy.x <= high.x + 0.01, "{copy}");
+                assert!(copy.y > low.y - 0.01 && copy.y <= high.y + 0.01, "{copy}");
+            }
+        }
+    }
+
+    #[test]
+    fn parallax_scrolls_vertically() {
+        let parallax =
+            ContinualParallax::new(0.0, 0, Vec2::new(0.0, 3.0)).with_extent(Vec2::new(0.0, 500.0));
+        let copies = vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 500.0, 0.0)];
+        assert_stays_in_band(parallax, copies);
+    }
+
+    #[test]
+    fn parallax_does_not_wrap_axis_without_extent() {
+        let parallax = ContinualParallax::new(100.0, 0, Vec2::new(1.0, 1.0));
+        let mut position = Vec3::ZERO;
+        for _ in 0..1000 {
+            parallax.scroll(&mut position);
+        }
+        assert_eq!(-1000.0, position.y);
+        assert!(position.x > -100.0 && position.x <= 100.0);
+    }
+
+    #[test]
+    fn parallax_scrolls_diagonally() {
+        for speed in [
+            Vec2::new(2.0, 3.0),
+            Vec2::new(-2.5, 1.5),
+            Vec2::new(-7.0, -4.0),
+        ] {
+            let parallax =
+                ContinualParallax::new(0.0, 0, speed).with_extent(Vec2::new(400.0, 300.0));
+            let copies = vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(400.0, 0.0, 0.0),
+                Vec3::new(0.0, 300.0, 0.0),
+                Vec3::new(400.0, 300.0, 0.0),
+            ];
+            assert_stays_in_band(parallax, copies);
+        }
+    }
+
+    #[test]
+    fn animations_contain_stored_tags_only() {
+        let animations = Animations::new()
+            .with_animation("Flapping", PerFrameAnimation::new(Vec::new()))
+            .with_animation("Straight", PerFrameAnimation::new(Vec::new()));
+
+        assert!(animations.contains("Flapping"));
+        assert!(animations.contains("Straight"));
+        assert!(!animations.contains("Diving"));
+        assert!(!animations.contains("flapping"));
+
+        let mut tags: Vec<&str> = animations.tags().collect();
+        tags.sort();
+        assert_eq!(vec!["Flapping", "Straight"], tags);
+        assert_eq!(0, Animations::new().tags().count());
+    }
+
+    fn time_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time<Virtual>>()
+            .init_resource::<Time<Real>>();
+        app
+    }
+
+    /// Lets a frame of 16ms pass in real time, and in virtual time unless it
+    /// is paused
+    fn next_frame(app: &mut App) {
+        let frame = std::time::Duration::from_millis(16);
+        let world = app.world_mut();
+        world.resource_mut::<Time<Real>>().advance_by(frame);
+        let mut virtual_time = world.resource_mut::<Time<Virtual>>();
+        let delta = if virtual_time.is_paused() {
+            std::time::Duration::ZERO
+        } else {
+            frame
+        };
+        virtual_time.advance_by(delta);
+        app.update();
+    }
+
+    #[test]
+    fn parallax_without_interval_moves_every_frame() {
+        let mut app = time_app();
+        app.add_systems(Update, continual_parallax);
+        let every_frame = app
+            .world_mut()
+            .spawn((
+                ContinualParallax::new(100.0, 0, Vec2::new(1.0, 0.0)),
+                Transform::default(),
+            ))
+            .id();
+        let every_10ms = app
+            .world_mut()
+            .spawn((
+                ContinualParallax::new(100.0, 10, Vec2::new(1.0, 0.0)),
+                Transform::default(),
+            ))
+            .id();
+        let x = |app: &App, entity| app.world().get::<Transform>(entity).unwrap().translation.x;
+
+        // Frames faster than a millisecond
+        for _ in 0..3 {
+            let frame = std::time::Duration::from_micros(500);
+            let world = app.world_mut();
+            world.resource_mut::<Time<Real>>().advance_by(frame);
+            world.resource_mut::<Time<Virtual>>().advance_by(frame);
+            app.update();
+        }
+        assert_eq!(-3.0, x(&app, every_frame));
+        assert_eq!(0.0, x(&app, every_10ms));
+    }
+
+    #[test]
+    fn paused_parallax_does_not_move() {
+        let mut app = time_app();
+        app.add_systems(Update, continual_parallax);
+        let mut parallax = ContinualParallax::new(100.0, 0, Vec2::new(1.0, 0.0));
+        parallax.pause();
+        let layer = app.world_mut().spawn((parallax, Transform::default())).id();
+
+        next_frame(&mut app);
+        assert_eq!(
+            Vec3::ZERO,
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+
+        app.world_mut()
+            .get_mut::<ContinualParallax>(layer)
+            .unwrap()
+            .resume();
+        next_frame(&mut app);
+        assert_eq!(
+            Vec3::new(-1.0, 0.0, 0.0),
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+    }
+
+    #[test]
+    fn parallax_stops_while_time_is_paused() {
+        let mut app = time_app();
+        app.add_systems(Update, continual_parallax);
+        let layer = app
+            .world_mut()
+            .spawn((
+                ContinualParallax::new(100.0, 0, Vec2::new(1.0, 0.0)),
+                Transform::default(),
+            ))
+            .id();
+        let menu_layer = app
+            .world_mut()
+            .spawn((
+                ContinualParallax::new(100.0, 0, Vec2::new(1.0, 0.0)),
+                Transform::default(),
+                IgnoreTimePause,
+            ))
+            .id();
+        let x = |app: &App, entity| app.world().get::<Transform>(entity).unwrap().translation.x;
+
+        next_frame(&mut app);
+        assert_eq!(-1.0, x(&app, layer));
+        assert_eq!(-1.0, x(&app, menu_layer));
+
+        app.world_mut().resource_mut::<Time<Virtual>>().pause();
+        for _ in 0..5 {
+            next_frame(&mut app);
+        }
+        assert_eq!(-1.0, x(&app, layer));
+        assert_eq!(-6.0, x(&app, menu_layer));
+
+        app.world_mut().resource_mut::<Time<Virtual>>().unpause();
+        next_frame(&mut app);
+        assert_eq!(-2.0, x(&app, layer));
+        assert_eq!(-7.0, x(&app, menu_layer));
+    }
+
+    #[test]
+    fn animation_reports_its_tag_and_frame() {
+        let mut app = time_app();
+        let frames = (0..3)
+            .map(|index| AnimationFrame::new(index, 10, vec![AnimationOption::NextFrame]))
+            .chain([AnimationFrame::new(
+                3,
+                10,
+                vec![AnimationOption::GoToFrame(0)],
+            )])
+            .collect();
+        app.insert_resource(
+            Animations::new()
+                .with_animation("Flapping", PerFrameAnimation::new(frames))
+                .with_animation("Straight and Level", PerFrameAnimation::new(Vec::new())),
+        )
+        .init_resource::<crate::AssetStore>()
+        .init_resource::<crate::LoadedAssets>()
+        .add_event::<AnimationFinished>()
+        .add_systems(Update, cycle_animations);
+        let dragon = app
+            .world_mut()
+            .spawn((
+                Sprite::from_atlas_image(default(), TextureAtlas::default()),
+                AnimationCycle::new("Flapping"),
+            ))
+            .id();
+        let cycle = |app: &App| {
+            let cycle = app.world().get::<AnimationCycle>(dragon).unwrap();
+            (cycle.current_tag().to_string(), cycle.current_frame())
+        };
+
+        assert_eq!(("Flapping".to_string(), 0), cycle(&app));
+        next_frame(&mut app);
+        assert_eq!(("Flapping".to_string(), 1), cycle(&app));
+        next_frame(&mut app);
+        assert_eq!(("Flapping".to_string(), 2), cycle(&app));
+
+        // Switching to the same sequence keeps the frame
+        let mut animation = app.world_mut().get_mut::<AnimationCycle>(dragon).unwrap();
+        animation.switch("Flapping");
+        assert_eq!(2, animation.current_frame());
+        animation.switch("Straight and Level");
+        assert_eq!("Straight and Level", animation.current_tag());
+        assert_eq!(0, animation.current_frame());
+    }
+
+    #[test]
+    fn attack_frames_swap_the_collider() {
+        let mut app = time_app();
+        let normal = AxisAlignedBoundingBox::new(16.0, 16.0);
+        let extended = AxisAlignedBoundingBox::new(32.0, 16.0);
+        let frames = vec![
+            AnimationFrame::new(
+                0,
+                10,
+                vec![
+                    AnimationOption::SetCollider(extended),
+                    AnimationOption::NextFrame,
+                ],
+            ),
+            AnimationFrame::new(
+                1,
+                10,
+                vec![
+                    AnimationOption::SetCollider(normal),
+                    AnimationOption::GoToFrame(0),
+                ],
+            ),
+        ];
+        app.insert_resource(
+            Animations::new().with_animation("Punch", PerFrameAnimation::new(frames)),
+        )
+        .init_resource::<crate::AssetStore>()
+        .init_resource::<crate::LoadedAssets>()
+        .add_event::<AnimationFinished>()
+        .add_systems(Update, cycle_animations);
+        let sprite = || Sprite::from_atlas_image(default(), TextureAtlas::default());
+        let fighter = app
+            .world_mut()
+            .spawn((sprite(), AnimationCycle::new("Punch"), normal))
+            .id();
+        let unboxed = app
+            .world_mut()
+            .spawn((sprite(), AnimationCycle::new("Punch")))
+            .id();
+        let collider =
+            |app: &App, entity| app.world().get::<AxisAlignedBoundingBox>(entity).copied();
+
+        next_frame(&mut app);
+        assert_eq!(Some(extended), collider(&app, fighter));
+        // Entities without a collider get one
+        assert_eq!(Some(extended), collider(&app, unboxed));
+        assert_eq!(
+            Rect2D::new(Vec2::new(-16.0, -8.0), Vec2::new(16.0, 8.0)),
+            collider(&app, fighter).unwrap().as_rect(Vec2::ZERO)
+        );
+
+        next_frame(&mut app);
+        assert_eq!(Some(normal), collider(&app, fighter));
+        assert_eq!(Some(normal), collider(&app, unboxed));
+    }
+
+    #[test]
+    fn settling_animation_shows_final_sprite() {
+        let mut app = time_app();
+        let frames = (0..6)
+            .map(|face| AnimationFrame::new(face, 10, vec![AnimationOption::GoToRandomFrame]))
+            .collect();
+        app.insert_resource(
+            Animations::new().with_animation("Rolling", PerFrameAnimation::new(frames)),
+        )
+        .init_resource::<crate::AssetStore>()
+        .init_resource::<crate::LoadedAssets>()
+        .add_event::<AnimationFinished>()
+        .add_systems(Update, cycle_animations);
+        let die = app
+            .world_mut()
+            .spawn((
+                Sprite::from_atlas_image(default(), TextureAtlas::default()),
+                AnimationCycle::new("Rolling").settle_after(100, 4),
+            ))
+            .id();
+        let index = |app: &App| {
+            app.world()
+                .get::<Sprite>(die)
+                .unwrap()
+                .texture_atlas
+                .as_ref()
+                .unwrap()
+                .index
+        };
+        let finished = |app: &App| {
+            app.world()
+                .resource::<Events<AnimationFinished>>()
+                .iter_current_update_events()
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        // Still rolling after 96ms
+        for _ in 0..6 {
+            next_frame(&mut app);
+            assert!(index(&app) < 6);
+            assert!(finished(&app).is_empty());
+        }
+        next_frame(&mut app);
+        assert_eq!(4, index(&app));
+        assert_eq!(vec![AnimationFinished { entity: die }], finished(&app));
+        assert!(
+            app.world()
+                .get::<AnimationCycle>(die)
+                .unwrap()
+                .is_finished()
+        );
+
+        // Stays on the final sprite, and finishes only once
+        for _ in 0..5 {
+            next_frame(&mut app);
+            assert_eq!(4, index(&app));
+            assert!(finished(&app).is_empty());
+        }
+    }
+
+    #[test]
+    fn camera_parallax_positions_for_factors() {
+        let anchor = Vec2::new(10.0, -20.0);
+        let cases = [
+            // (factor, camera, expected layer position)
+            (Vec2::ZERO, Vec2::ZERO, anchor),
+            (Vec2::ZERO, Vec2::new(100.0, 50.0), Vec2::new(110.0, 30.0)),
+            (Vec2::ONE, Vec2::new(100.0, 50.0), anchor),
+            (
+                Vec2::splat(0.5),
+                Vec2::new(100.0, 50.0),
+                Vec2::new(60.0, 5.0),
+            ),
+            (
+                Vec2::new(0.25, 1.0),
+                Vec2::new(-40.0, 80.0),
+                Vec2::new(-20.0, -20.0),
+            ),
+        ];
+
+        for (factor, camera, expected) in cases {
+            let parallax = CameraParallax::new(factor, anchor);
+            assert_eq!(expected, parallax.position(camera));
+        }
+    }
+
+    #[test]
+    fn camera_parallax_follows_active_camera() {
+        let mut app = App::new();
+        app.add_systems(Update, camera_parallax);
+        app.world_mut().spawn((
+            Camera {
+                is_active: false,
+                ..default()
+            },
+            Transform::from_xyz(-500.0, -500.0, 0.0),
+        ));
+        let camera = app
+            .world_mut()
+            .spawn((Camera::default(), Transform::from_xyz(200.0, 100.0, 0.0)))
+            .id();
+        let layer = app
+            .world_mut()
+            .spawn((
+                CameraParallax::new(Vec2::splat(0.75), Vec2::ZERO),
+                Transform::from_xyz(0.0, 0.0, -5.0),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(
+            Vec3::new(50.0, 25.0, -5.0),
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+
+        app.world_mut()
+            .get_mut::<Transform>(camera)
+            .unwrap()
+            .translation = Vec3::new(-400.0, 0.0, 0.0);
+        app.update();
+        assert_eq!(
+            Vec3::new(-100.0, 0.0, -5.0),
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+    }
+
+    #[test]
+    fn parallax_ignores_layer_cameras() {
+        let mut app = App::new();
+        app.add_systems(Update, (camera_parallax, velocity_parallax));
+        // A minimap camera spawned first would be found first
+        app.world_mut().spawn((
+            Camera::default(),
+            CameraLayer(1),
+            Transform::from_xyz(-500.0, -500.0, 0.0),
+        ));
+        let camera = app
+            .world_mut()
+            .spawn((Camera::default(), Transform::from_xyz(200.0, 100.0, 0.0)))
+            .id();
+        let following = app
+            .world_mut()
+            .spawn((
+                CameraParallax::new(Vec2::splat(0.75), Vec2::ZERO),
+                Transform::from_xyz(0.0, 0.0, -5.0),
+            ))
+            .id();
+        let drifting = app
+            .world_mut()
+            .spawn((
+                VelocityParallax::new(Vec2::ONE, ParallaxSource::Camera),
+                Transform::default(),
+            ))
+            .id();
+        app.update();
+
+        app.world_mut()
+            .get_mut::<Transform>(camera)
+            .unwrap()
+            .translation
+            .x += 40.0;
+        app.update();
+        let translation = |entity| app.world().get::<Transform>(entity).unwrap().translation;
+        assert_eq!(Vec3::new(60.0, 25.0, -5.0), translation(following));
+        assert_eq!(Vec3::new(-40.0, 0.0, 0.0), translation(drifting));
+    }
+
+    fn velocity_parallax_app(factor: Vec2) -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_systems(Update, velocity_parallax);
+        let ship = app.world_mut().spawn(Transform::default()).id();
+        let layer = app
+            .world_mut()
+            .spawn((
+                VelocityParallax::new(factor, ParallaxSource::Entity(ship)),
+                Transform::from_xyz(0.0, 0.0, -5.0),
+            ))
+            .id();
+        // The first frame only records where the source starts
+        app.update();
+        (app, ship, layer)
+    }
+
+    #[test]
+    fn velocity_parallax_moves_proportional_to_source() {
+        let (mut app, ship, layer) = velocity_parallax_app(Vec2::new(0.5, 0.25));
+        let layer_at = |app: &App| app.world().get::<Transform>(layer).unwrap().translation;
+
+        app.world_mut()
+            .get_mut::<Transform>(ship)
+            .unwrap()
+            .translation
+            .x += 4.0;
+        app.update();
+        assert_eq!(Vec3::new(-2.0, 0.0, -5.0), layer_at(&app));
+
+        // Twice as fast moves the layer twice as far
+        app.world_mut()
+            .get_mut::<Transform>(ship)
+            .unwrap()
+            .translation += Vec3::new(8.0, 8.0, 0.0);
+        app.update();
+        assert_eq!(Vec3::new(-6.0, -2.0, -5.0), layer_at(&app));
+    }
+
+    #[test]
+    fn velocity_parallax_does_not_drift_when_stationary() {
+        let (mut app, ship, layer) = velocity_parallax_app(Vec2::ONE);
+        app.world_mut()
+            .get_mut::<Transform>(ship)
+            .unwrap()
+            .translation = Vec3::new(100.0, 50.0, 0.0);
+        app.update();
+        let before = app.world().get::<Transform>(layer).unwrap().translation;
+
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(
+            before,
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+    }
+
+    #[test]
+    fn velocity_parallax_wraps_and_follows_camera() {
+        let mut app = App::new();
+        app.add_systems(Update, velocity_parallax);
+        let camera = app
+            .world_mut()
+            .spawn((Camera::default(), Transform::default()))
+            .id();
+        let layer = app
+            .world_mut()
+            .spawn((
+                VelocityParallax::new(Vec2::ONE, ParallaxSource::Camera)
+                    .with_extent(Vec2::new(100.0, 0.0)),
+                Transform::default(),
+            ))
+            .id();
+        app.update();
+
+        app.world_mut()
+            .get_mut::<Transform>(camera)
+            .unwrap()
+            .translation
+            .x = 150.0;
+        app.update();
+        assert_eq!(
+            Vec3::new(50.0, 0.0, 0.0),
+            app.world().get::<Transform>(layer).unwrap().translation
+        );
+    }
+}
@@ -0,0 +1,649 @@
+//! Camera helpers, e.g. following the player around the world
+
+use crate::{RandomNumberGenerator, Rect2D};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+};
+
+/// How much one step of the mouse wheel or one key press zooms
+const ZOOM_STEP: f32 = 1.1;
+
+/// Lets a camera follow a target entity. The camera catches up smoothly,
+/// keeps still while the target moves within the deadzone, and never shows
+/// anything outside of the bounds.
+#[derive(Component)]
+pub struct CameraFollow {
+    /// The entity to follow, e.g. the player
+    pub target: Entity,
+    /// Time in seconds the camera takes to catch up about two thirds of the
+    /// distance to the target. 0.0 snaps to the target immediately.
+    pub smoothing: f32,
+    /// Half size of the rectangle around the center of the view, in which the
+    /// target can move without moving the camera
+    pub deadzone: Vec2,
+    /// The area of the world the camera may show. None for an unbounded camera.
+    pub bounds: Option<Rect2D>,
+}
+
+impl CameraFollow {
+    /// Creates a camera that snaps to the target without any deadzone or bounds
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            smoothing: 0.0,
+            deadzone: Vec2::ZERO,
+            bounds: None,
+        }
+    }
+
+    /// Lets the camera catch up smoothly over a time in seconds
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Lets the target move freely within a rectangle of half size `deadzone`
+    pub fn with_deadzone(mut self, deadzone: Vec2) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Keeps the view of the camera within the bounds
+    pub fn with_bounds(mut self, bounds: Rect2D) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Calculates where the camera should be centered for a target position,
+    /// so that the target is within the deadzone
+    fn desired(&self, camera: Vec2, target: Vec2) -> Vec2 {
+        let offset = target - camera;
+        let outside = (offset.abs() - self.deadzone).max(Vec2::ZERO);
+        camera + outside * offset.signum()
+    }
+
+    /// Calculates the next camera position after `delta_secs` seconds
+    fn step(&self, camera: Vec2, target: Vec2, delta_secs: f32, half_view: Vec2) -> Vec2 {
+        let desired = self.desired(camera, target);
+        let progress = if self.smoothing <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-delta_secs / self.smoothing).exp()
+        };
+        let position = camera.lerp(desired, progress);
+
+        match self.bounds {
+            Some(bounds) => Self::clamp(position, &bounds, half_view),
+            None => position,
+        }
+    }
+
+    /// Keeps a view of `half_view` around the position within the bounds.
+    /// Centers the view on any axis where it is larger than the bounds.
+    fn clamp(position: Vec2, bounds: &Rect2D, half_view: Vec2) -> Vec2 {
+        let low = bounds.min() + half_view;
+        let high = bounds.max() - half_view;
+        let center = bounds.center();
+        Vec2::new(
+            if low.x <= high.x {
+                position.x.clamp(low.x, high.x)
+            } else {
+                center.x
+            },
+            if low.y <= high.y {
+                position.y.clamp(low.y, high.y)
+            } else {
+                center.y
+            },
+        )
+    }
+}
+
+/// Moves cameras toward their targets. Schedule it after the physics have
+/// written the transforms of this frame, e.g. after `physics_clock`, to
+/// avoid jitter.
+pub fn camera_follow(
+    time: Res<Time>,
+    targets: Query<&Transform, Without<CameraFollow>>,
+    mut cameras: Query<(&CameraFollow, &mut Transform, Option<&Projection>)>,
+) {
+    for (follow, mut transform, projection) in cameras.iter_mut() {
+        let Ok(target) = targets.get(follow.target) else {
+            continue;
+        };
+        let half_view = match projection {
+            Some(Projection::Orthographic(ortho)) => ortho.area.half_size(),
+            _ => Vec2::ZERO,
+        };
+        let position = follow.step(
+            transform.translation.truncate(),
+            target.translation.truncate(),
+            time.delta_secs(),
+            half_view,
+        );
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+/// Shakes a camera after impacts. Each impact adds trauma, which decays over
+/// time. The camera is displaced and rotated randomly, proportional to the
+/// square of the trauma. The offset is applied on top of the camera's base
+/// position, so it works together with [`CameraFollow`].
+#[derive(Component)]
+pub struct CameraShake {
+    /// Current trauma between 0.0 (calm) and 1.0 (maximum shake)
+    trauma: f32,
+    /// How much trauma is lost per second
+    pub decay: f32,
+    /// Maximum displacement per axis at full trauma
+    pub max_offset: Vec2,
+    /// Maximum rotation in radians at full trauma
+    pub max_angle: f32,
+    /// Offset and rotation currently applied to the camera
+    applied: (Vec2, f32),
+    /// Random numbers for the shake. Seed it for reproducible shakes.
+    rng: RandomNumberGenerator,
+}
+
+impl CameraShake {
+    /// Creates a calm camera shake with a maximum displacement and rotation
+    pub fn new(max_offset: Vec2, max_angle: f32) -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 1.0,
+            max_offset,
+            max_angle,
+            applied: (Vec2::ZERO, 0.0),
+            rng: RandomNumberGenerator::new(),
+        }
+    }
+
+    /// Sets how much trauma is lost per second
+    pub fn with_decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Uses a seeded random number generator for reproducible shakes
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = RandomNumberGenerator::seeded(seed);
+        self
+    }
+
+    /// Adds trauma from an impact. Trauma is capped at 1.0.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    /// Current trauma between 0.0 and 1.0
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Decays the trauma and rolls a new offset and rotation
+    fn step(&mut self, delta_secs: f32) -> (Vec2, f32) {
+        self.trauma = (self.trauma - self.decay * delta_secs).max(0.0);
+        let shake = self.trauma * self.trauma;
+        if shake == 0.0 {
+            return (Vec2::ZERO, 0.0);
+        }
+        let offset = Vec2::new(
+            self.max_offset.x * shake * self.rng.range(-1.0..=1.0),
+            self.max_offset.y * shake * self.rng.range(-1.0..=1.0),
+        );
+        (offset, self.max_angle * shake * self.rng.range(-1.0..=1.0))
+    }
+}
+
+/// Removes the shake of the last frame, restoring the camera's base
+/// position. Schedule it before [`camera_follow`].
+pub fn remove_camera_shake(mut cameras: Query<(&mut CameraShake, &mut Transform)>) {
+    for (mut shake, mut transform) in cameras.iter_mut() {
+        let (offset, angle) = shake.applied;
+        transform.translation -= offset.extend(0.0);
+        transform.rotate_z(-angle);
+        shake.applied = (Vec2::ZERO, 0.0);
+    }
+}
+
+/// Shakes cameras on top of their base position. Schedule it after
+/// [`camera_follow`].
+pub fn apply_camera_shake(time: Res<Time>, mut cameras: Query<(&mut CameraShake, &mut Transform)>) {
+    for (mut shake, mut transform) in cameras.iter_mut() {
+        let (offset, angle) = shake.step(time.delta_secs());
+        transform.translation += offset.extend(0.0);
+        transform.rotate_z(angle);
+        shake.applied = (offset, angle);
+    }
+}
+
+/// Lets the player zoom an orthographic camera with the mouse wheel or the
+/// `+`/`-` keys. The scale of the projection follows the zoom level smoothly,
+/// easing out as it gets close. Games can zoom for dramatic moments with
+/// [`zoom_to`](Self::zoom_to).
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraZoom {
+    /// The orthographic scale to zoom to. Larger values show more of the world.
+    pub level: f32,
+    /// Smallest scale, i.e. the closest zoom
+    pub min: f32,
+    /// Largest scale, i.e. the farthest zoom
+    pub max: f32,
+    /// How fast the scale follows the level. Larger is faster.
+    pub speed: f32,
+}
+
+impl CameraZoom {
+    /// Creates a zoom starting at `level`, limited to `min..=max`
+    pub fn new(level: f32, min: f32, max: f32) -> Self {
+        Self {
+            level: level.clamp(min, max),
+            min,
+            max,
+            speed: 8.0,
+        }
+    }
+
+    /// Sets how fast the scale follows the level
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Zooms in for positive and out for negative steps, within min and max
+    pub fn zoom(&mut self, steps: f32) {
+        self.level = (self.level / ZOOM_STEP.powf(steps)).clamp(self.min, self.max);
+    }
+
+    /// Zooms smoothly to a scale, within min and max
+    pub fn zoom_to(&mut self, level: f32) {
+        self.level = level.clamp(self.min, self.max);
+    }
+
+    /// Calculates the scale after `delta_secs` seconds of moving toward the level
+    fn step(&self, scale: f32, delta_secs: f32) -> f32 {
+        let progress = 1.0 - (-self.speed * delta_secs).exp();
+        let next = scale + (self.level - scale) * progress;
+        if (self.level - next).abs() < 1e-4 {
+            self.level
+        } else {
+            next
+        }
+    }
+}
+
+/// Calculates the largest integer scale at which a game with a virtual
+/// resolution fits into the window. Retro games use it to keep pixels sharp.
+/// Never returns less than 1.
+pub fn pixel_perfect_scale(window_size: Vec2, virtual_resolution: Vec2) -> u32 {
+    let fits = (window_size / virtual_resolution).floor().min_element();
+    (fits as u32).max(1)
+}
+
+/// Zooms cameras on mouse wheel and key input. Schedule it before
+/// [`camera_follow`], which keeps the zoomed view within its bounds.
+pub fn camera_zoom(
+    time: Res<Time>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut cameras: Query<(&mut CameraZoom, &mut Projection)>,
+) {
+    let mut steps: f32 = wheel
+        .read()
+        .map(|scroll| match scroll.unit {
+            MouseScrollUnit::Line => scroll.y,
+            MouseScrollUnit::Pixel => scroll.y / 16.0,
+        })
+        .sum();
+    if keyboard.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        steps += 1.0;
+    }
+    if keyboard.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        steps -= 1.0;
+    }
+
+    for (mut zoom, mut projection) in cameras.iter_mut() {
+        let Projection::Orthographic(ortho) = projection.as_mut() else {
+            continue;
+        };
+        if steps != 0.0 {
+            zoom.zoom(steps);
+        }
+        let scale = zoom.step(ortho.scale, time.delta_secs());
+        if scale != ortho.scale {
+            // Bevy updates the visible area later in the frame, but following
+            // cameras need it now to stay within their bounds
+            let area = ortho.area;
+            let factor = scale / ortho.scale;
+            ortho.area = Rect::from_center_half_size(area.center(), area.half_size() * factor);
+            ortho.scale = scale;
+        }
+    }
+}
+
+/// Tags a secondary camera that only renders the entities on its render
+/// layer, e.g. a minimap. Entities without [`RenderLayers`] are on layer 0,
+/// which is rendered by the main camera only.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CameraLayer(pub usize);
+
+/// Spawns an orthographic camera for a render layer. It draws into the
+/// `viewport`, given in physical pixels of the window, on top of the cameras
+/// of lower layers. Put entities on the layer with [`RenderLayers::layer`].
+pub fn spawn_layer_camera(
+    commands: &mut Commands,
+    layer: usize,
+    viewport: URect,
+    scale: f32,
+    bundle: impl Bundle,
+) -> Entity {
+    commands
+        .spawn((
+            Camera2d,
+            Camera {
+                order: layer as isize,
+                viewport: Some(Viewport {
+                    physical_position: viewport.min,
+                    physical_size: viewport.size(),
+                    ..default()
+                }),
+                ..default()
+            },
+            Projection::Orthographic(OrthographicProjection {
+                scale,
+                ..OrthographicProjection::default_2d()
+            }),
+            RenderLayers::layer(layer),
+            CameraLayer(layer),
+            bundle,
+        ))
+        .id()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn smoothing_converges_to_target() {
+        let follow = CameraFollow::new(Entity::PLACEHOLDER).with_smoothing(0.2);
+        let target = Vec2::new(100.0, -50.0);
+        let mut camera = Vec2::ZERO;
+        let mut distance = camera.distance(target);
+        for _ in 0..120 {
+            camera = follow.step(camera, target, 1.0 / 60.0, Vec2::ZERO);
+            let next_distance = camera.distance(target);
+            assert!(next_distance < distance);
+            distance = next_distance;
+        }
+        assert!(distance < 0.01);
+
+        let snapping = CameraFollow::new(Entity::PLACEHOLDER);
+        assert_eq!(
+            target,
+            snapping.step(Vec2::ZERO, target, 1.0 / 60.0, Vec2::ZERO)
+        );
+    }
+
+    #[test]
+    fn target_within_deadzone_does_not_move_camera() {
+        let follow = CameraFollow::new(Entity::PLACEHOLDER).with_deadzone(Vec2::new(20.0, 10.0));
+        let camera = Vec2::new(5.0, 5.0);
+
+        for target in [Vec2::new(25.0, 15.0), Vec2::new(-15.0, -5.0), camera] {
+            assert_eq!(camera, follow.step(camera, target, 1.0 / 60.0, Vec2::ZERO));
+        }
+
+        // Leaving the deadzone drags the camera along, keeping the target on
+        // its edge
+        assert_eq!(
+            Vec2::new(10.0, 5.0),
+            follow.step(camera, Vec2::new(30.0, 0.0), 1.0 / 60.0, Vec2::ZERO)
+        );
+    }
+
+    #[test]
+    fn camera_stays_within_bounds_at_world_edges() {
+        let bounds = Rect2D::new(Vec2::new(-500.0, -300.0), Vec2::new(500.0, 300.0));
+        let follow = CameraFollow::new(Entity::PLACEHOLDER).with_bounds(bounds);
+        let half_view = Vec2::new(200.0, 100.0);
+        let step = |target| follow.step(Vec2::ZERO, target, 1.0 / 60.0, half_view);
+
+        assert_eq!(Vec2::new(100.0, -50.0), step(Vec2::new(100.0, -50.0)));
+        assert_eq!(Vec2::new(300.0, 200.0), step(Vec2::new(480.0, 290.0)));
+        assert_eq!(Vec2::new(-300.0, -200.0), step(Vec2::new(-1000.0, -1000.0)));
+
+        // A view larger than the bounds is centered on them
+        let wide = follow.step(
+            Vec2::ZERO,
+            Vec2::new(480.0, 0.0),
+            1.0 / 60.0,
+            Vec2::new(600.0, 100.0),
+        );
+        assert_eq!(Vec2::new(0.0, 0.0), wide);
+    }
+
+    #[test]
+    fn camera_follows_target_entity() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_systems(Update, camera_follow);
+        let player = app
+            .world_mut()
+            .spawn(Transform::from_xyz(900.0, 0.0, 10.0))
+            .id();
+        let camera = app
+            .world_mut()
+            .spawn((
+                CameraFollow::new(player)
+                    .with_bounds(Rect2D::new(Vec2::splat(-1000.0), Vec2::splat(1000.0))),
+                Transform::from_xyz(0.0, 0.0, 10.0),
+                Projection::Orthographic(OrthographicProjection {
+                    area: Rect::new(-200.0, -100.0, 200.0, 100.0),
+                    ..OrthographicProjection::default_2d()
+                }),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(
+            Vec3::new(800.0, 0.0, 10.0),
+            app.world().get::<Transform>(camera).unwrap().translation
+        );
+    }
+
+    #[test]
+    fn camera_shake_decays_and_returns_to_base() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_systems(Update, (remove_camera_shake, apply_camera_shake).chain());
+        let base = Transform::from_xyz(100.0, 50.0, 10.0);
+        let mut shake = CameraShake::new(Vec2::splat(20.0), 0.1)
+            .with_decay(2.0)
+            .with_seed(42);
+        shake.add_trauma(0.8);
+        let camera = app.world_mut().spawn((shake, base)).id();
+
+        let mut shaken = false;
+        for _ in 0..30 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(std::time::Duration::from_millis(20));
+            app.update();
+            shaken |= *app.world().get::<Transform>(camera).unwrap() != base;
+        }
+        assert!(shaken);
+        assert_eq!(
+            0.0,
+            app.world().get::<CameraShake>(camera).unwrap().trauma()
+        );
+
+        let transform = app.world().get::<Transform>(camera).unwrap();
+        assert!(transform.translation.abs_diff_eq(base.translation, 1e-4));
+        assert!(transform.rotation.abs_diff_eq(Quat::IDENTITY, 1e-5));
+    }
+
+    #[test]
+    #[cfg(not(feature = "secure"))]
+    fn seeded_shakes_are_reproducible() {
+        let offsets = || {
+            let mut shake = CameraShake::new(Vec2::splat(20.0), 0.1).with_seed(7);
+            shake.add_trauma(1.0);
+            (0..10).map(|_| shake.step(0.02)).collect::<Vec<_>>()
+        };
+        assert_eq!(offsets(), offsets());
+    }
+
+    #[test]
+    fn zoom_is_clamped_to_min_and_max() {
+        let mut zoom = CameraZoom::new(1.0, 0.5, 2.0);
+        zoom.zoom(100.0);
+        assert_eq!(0.5, zoom.level);
+        zoom.zoom(-100.0);
+        assert_eq!(2.0, zoom.level);
+        zoom.zoom(1.0);
+        assert_eq!(2.0 / ZOOM_STEP, zoom.level);
+
+        assert_eq!(0.5, CameraZoom::new(0.1, 0.5, 2.0).level);
+    }
+
+    #[test]
+    fn zoom_scale_moves_smoothly_to_level() {
+        let zoom = CameraZoom::new(2.0, 0.5, 2.0);
+        let mut scale = 1.0;
+        let mut frames = 0;
+        while scale != zoom.level {
+            let next = zoom.step(scale, 1.0 / 60.0);
+            assert!(next > scale && next <= zoom.level);
+            // No frame jumps more than a quarter of the way
+            assert!(next - scale < 0.25);
+            scale = next;
+            frames += 1;
+            assert!(frames < 600, "Zoom never reached its level");
+        }
+        assert!(frames > 5);
+    }
+
+    #[test]
+    fn zoom_keeps_area_in_sync_with_scale() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<MouseWheel>()
+            .add_systems(Update, camera_zoom);
+        let camera = app
+            .world_mut()
+            .spawn((
+                CameraZoom::new(2.0, 0.5, 2.0).with_speed(f32::INFINITY),
+                Projection::Orthographic(OrthographicProjection {
+                    area: Rect::new(-200.0, -100.0, 200.0, 100.0),
+                    ..OrthographicProjection::default_2d()
+                }),
+            ))
+            .id();
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(std::time::Duration::from_millis(16));
+        app.update();
+
+        let Projection::Orthographic(ortho) = app.world().get::<Projection>(camera).unwrap() else {
+            panic!("Camera is not orthographic");
+        };
+        assert_eq!(2.0, ortho.scale);
+        assert_eq!(Vec2::new(400.0, 200.0), ortho.area.half_size());
+    }
+
+    #[test]
+    fn zoom_to_eases_the_projection_to_the_target() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<MouseWheel>()
+            .add_systems(Update, camera_zoom);
+        let mut zoom = CameraZoom::new(1.0, 0.5, 2.0).with_speed(4.0);
+        zoom.zoom_to(10.0);
+        assert_eq!(2.0, zoom.level);
+        zoom.zoom_to(0.6);
+        let camera = app
+            .world_mut()
+            .spawn((
+                zoom,
+                Projection::Orthographic(OrthographicProjection::default_2d()),
+            ))
+            .id();
+        let scale = |app: &App| match app.world().get::<Projection>(camera).unwrap() {
+            Projection::Orthographic(ortho) => ortho.scale,
+            _ => unreachable!(),
+        };
+
+        let mut previous = scale(&app);
+        for _ in 0..120 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(std::time::Duration::from_secs_f32(1.0 / 60.0));
+            app.update();
+            let current = scale(&app);
+            assert!(current <= previous && current >= 0.6, "{current}");
+            previous = current;
+        }
+        assert!((scale(&app) - 0.6).abs() < 1e-3);
+    }
+
+    #[test]
+    fn pixel_perfect_scale_fits_window() {
+        let virtual_resolution = Vec2::new(320.0, 180.0);
+        let cases = [
+            (Vec2::new(1920.0, 1080.0), 6),
+            (Vec2::new(1280.0, 720.0), 4),
+            (Vec2::new(1024.0, 768.0), 3),
+            (Vec2::new(1366.0, 768.0), 4),
+            (Vec2::new(2560.0, 1080.0), 6),
+            (Vec2::new(200.0, 100.0), 1),
+        ];
+        for (window_size, expected) in cases {
+            assert_eq!(
+                expected,
+                pixel_perfect_scale(window_size, virtual_resolution)
+            );
+        }
+    }
+
+    #[test]
+    fn layer_cameras_get_their_layers_and_viewports() {
+        use bevy::ecs::system::RunSystemOnce;
+        let mut world = World::new();
+        let (minimap, radar) = world
+            .run_system_once(|mut commands: Commands| {
+                (
+                    spawn_layer_camera(&mut commands, 1, URect::new(0, 0, 200, 150), 8.0, ()),
+                    spawn_layer_camera(
+                        &mut commands,
+                        2,
+                        URect::new(800, 600, 1024, 768),
+                        1.0,
+                        Name::new("Radar"),
+                    ),
+                )
+            })
+            .unwrap();
+
+        for (camera, layer, position, size) in [
+            (minimap, 1, UVec2::ZERO, UVec2::new(200, 150)),
+            (radar, 2, UVec2::new(800, 600), UVec2::new(224, 168)),
+        ] {
+            let entity = world.entity(camera);
+            assert_eq!(Some(&CameraLayer(layer)), entity.get::<CameraLayer>());
+            assert_eq!(
+                Some(&RenderLayers::layer(layer)),
+                entity.get::<RenderLayers>()
+            );
+            let viewport = entity.get::<Camera>().unwrap().viewport.as_ref().unwrap();
+            assert_eq!(position, viewport.physical_position);
+            assert_eq!(size, viewport.physical_size);
+        }
+        assert!(world.entity(radar).contains::<Name>());
+    }
+}
@@ -5,7 +5,7 @@ use bevy::prelude::*;
 
 /// Defines an axis-aligned bounding box (AABB) for
 /// collision detection of an entity
-#[derive(Component)]
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
 pub struct AxisAlignedBoundingBox {
     /// Stores the size of the parent entity,
     /// wherever position it is rendered in space
@@ -24,4 +24,34 @@ impl AxisAlignedBoundingBox {
     pub fn as_rect(&self, translate: Vec2) -> Rect2D {
         Rect2D::new(translate - &self.half_size, translate + &self.half_size)
     }
+
+    /// Converts an AABB with a position into a Rect2D, scaling it like the
+    /// entity's sprite, e.g. by the `scale` of its `Transform`
+    pub fn as_rect_scaled(&self, translate: Vec2, scale: Vec2) -> Rect2D {
+        let half_size = self.half_size * scale.abs();
+        Rect2D::new(translate - half_size, translate + half_size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scaled_boxes_grow_with_their_scale() {
+        let bbox = AxisAlignedBoundingBox::new(24.0, 16.0);
+        let position = Vec2::new(100.0, 50.0);
+        assert_eq!(
+            Rect2D::new(Vec2::new(76.0, 34.0), Vec2::new(124.0, 66.0)),
+            bbox.as_rect_scaled(position, Vec2::splat(2.0))
+        );
+        assert_eq!(
+            Rect2D::new(Vec2::new(76.0, 42.0), Vec2::new(124.0, 58.0)),
+            bbox.as_rect_scaled(position, Vec2::new(2.0, 1.0))
+        );
+        assert_eq!(
+            bbox.as_rect(position),
+            bbox.as_rect_scaled(position, Vec2::ONE)
+        );
+    }
 }
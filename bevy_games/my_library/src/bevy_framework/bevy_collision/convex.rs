@@ -0,0 +1,127 @@
+//! Detect collisions of rotated convex polygons with the Separating Axis Theorem (SAT)
+
+use super::rect2d::Rect2D;
+use bevy::prelude::*;
+
+/// Defines a convex polygon for the narrow phase of collision detection.
+/// It is an opt-in refinement of the [`AxisAlignedBoundingBox`](super::AxisAlignedBoundingBox),
+/// which is still required for the quadtree broad phase and should enclose
+/// the polygon at any rotation.
+#[derive(Component, Debug, Clone)]
+pub struct ConvexCollider {
+    /// Corners of the polygon relative to the entity's position, in
+    /// winding order
+    pub points: Vec<Vec2>,
+}
+
+impl ConvexCollider {
+    /// Creates a new convex collider from its corners
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Self { points }
+    }
+
+    /// Creates a rectangular collider centered on the entity
+    pub fn rectangle(width: f32, height: f32) -> Self {
+        let half = Vec2::new(width / 2.0, height / 2.0);
+        Self::new(vec![
+            Vec2::new(-half.x, -half.y),
+            Vec2::new(half.x, -half.y),
+            Vec2::new(half.x, half.y),
+            Vec2::new(-half.x, half.y),
+        ])
+    }
+
+    /// Scales, rotates and moves the polygon into world space, in the order
+    /// a `Transform` applies them
+    pub fn world_points(&self, translate: Vec2, rotation: Quat, scale: Vec2) -> Vec<Vec2> {
+        self.points
+            .iter()
+            .map(|point| translate + (rotation * (*point * scale).extend(0.0)).truncate())
+            .collect()
+    }
+}
+
+/// Checks if two convex polygons intersect. Polygons are separated if there is
+/// an axis, taken from the edge normals of both polygons, on which their
+/// projections do not overlap.
+pub fn sat_intersect(a: &[Vec2], b: &[Vec2]) -> bool {
+    !axes(a).chain(axes(b)).any(|axis| {
+        let (min_a, max_a) = project(a, axis);
+        let (min_b, max_b) = project(b, axis);
+        max_a < min_b || max_b < min_a
+    })
+}
+
+/// Checks if a convex polygon intersects with an axis-aligned rectangle
+pub fn sat_intersect_rect(polygon: &[Vec2], rect: &Rect2D) -> bool {
+    sat_intersect(polygon, &rect.corners())
+}
+
+/// Yields the edge normals of a polygon as separating axis candidates
+fn axes(polygon: &[Vec2]) -> impl Iterator<Item = Vec2> + '_ {
+    (0..polygon.len()).map(|i| {
+        let edge = polygon[(i + 1) % polygon.len()] - polygon[i];
+        edge.perp()
+    })
+}
+
+/// Projects a polygon onto an axis, returning the covered interval
+fn project(polygon: &[Vec2], axis: Vec2) -> (f32, f32) {
+    polygon
+        .iter()
+        .map(|point| point.dot(axis))
+        .fold((f32::MAX, f32::MIN), |(min, max), p| {
+            (min.min(p), max.max(p))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotated_rectangles_overlapping_intersect() {
+        let collider = ConvexCollider::rectangle(20.0, 4.0);
+        let a = collider.world_points(
+            Vec2::ZERO,
+            Quat::from_rotation_z(45_f32.to_radians()),
+            Vec2::ONE,
+        );
+        let b = collider.world_points(
+            Vec2::new(4.0, 0.0),
+            Quat::from_rotation_z(-45_f32.to_radians()),
+            Vec2::ONE,
+        );
+
+        assert!(sat_intersect(&a, &b));
+    }
+
+    #[test]
+    fn rotated_rectangles_separated_do_not_intersect() {
+        // Both bounding boxes overlap, but the thin diagonal bars are parallel
+        // and don't touch each other.
+        let collider = ConvexCollider::rectangle(20.0, 2.0);
+        let rotation = Quat::from_rotation_z(45_f32.to_radians());
+        let a = collider.world_points(Vec2::ZERO, rotation, Vec2::ONE);
+        let b = collider.world_points(Vec2::new(4.0, -4.0), rotation, Vec2::ONE);
+
+        assert!(Rect2D::bounding(&a).intersect(&Rect2D::bounding(&b)));
+        assert!(!sat_intersect(&a, &b));
+    }
+
+    #[test]
+    fn rotated_rectangle_intersects_rect() {
+        let collider = ConvexCollider::rectangle(20.0, 2.0);
+        let polygon = collider.world_points(
+            Vec2::ZERO,
+            Quat::from_rotation_z(45_f32.to_radians()),
+            Vec2::ONE,
+        );
+
+        let touching = Rect2D::new(Vec2::new(2.0, 2.0), Vec2::new(6.0, 6.0));
+        let in_corner = Rect2D::new(Vec2::new(4.0, -8.0), Vec2::new(8.0, -4.0));
+
+        assert!(sat_intersect_rect(&polygon, &touching));
+        assert!(!sat_intersect_rect(&polygon, &in_corner));
+    }
+}
@@ -1,15 +1,28 @@
 //! This module implements a collision detection framework for bevy
 
 mod aabb;
+mod convex;
+mod proximity;
 mod rect2d;
 mod static_quadtree;
+mod tracker;
 
 pub use aabb::AxisAlignedBoundingBox;
+pub use convex::*;
+pub use proximity::*;
 pub use rect2d::Rect2D;
 pub use static_quadtree::*;
+pub use tracker::*;
 
 use crate::PhysicsPosition;
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{
+    ecs::{
+        bundle::{DynamicBundle, NoBundleEffect},
+        query::QueryItem,
+    },
+    platform::collections::HashMap,
+    prelude::*,
+};
 use std::marker::PhantomData;
 /// This event messages when a collision was detected
 #[derive(Event)]
@@ -25,42 +38,415 @@ where
     marker: PhantomData<(A, B)>,
 }
 
+impl<A, B> OnCollision<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Creates a collision event, e.g. to simulate a collision in a test
+    pub fn new(entity_a: Entity, entity_b: Entity) -> Self {
+        Self {
+            entity_a,
+            entity_b,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Marks colliders that never move, e.g. the tiles of a level. Collisions
+/// between two static colliders are never checked.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Static;
+
+/// Components used to detect collisions of an entity. The convex collider
+/// is optional and refines the bounding box in a narrow phase.
+type Collider<'a> = (
+    Entity,
+    &'a PhysicsPosition,
+    &'a AxisAlignedBoundingBox,
+    Option<&'a ConvexCollider>,
+    Option<&'a Transform>,
+    Has<Static>,
+);
+
+/// Entities with their bounding box, optional world space polygon, static
+/// flag and payload, listed by the quadtree node they are contained in
+type SpatialIndex<T> = HashMap<usize, Vec<(Entity, Rect2D, Option<Vec<Vec2>>, bool, T)>>;
+
 /// Checks whether collisions between Components of type A and B occur
 pub fn check_collisions<A, B>(
     quad_tree: Res<StaticQuadTree>,
-    query_a: Query<(Entity, &PhysicsPosition, &AxisAlignedBoundingBox), With<A>>,
-    query_b: Query<(Entity, &PhysicsPosition, &AxisAlignedBoundingBox), With<B>>,
+    query_a: Query<Collider, With<A>>,
+    query_b: Query<Collider, With<B>>,
     mut sender: EventWriter<OnCollision<A, B>>,
 ) where
     A: Component,
     B: Component,
 {
-    let mut spatial_index: HashMap<usize, Vec<(Entity, Rect2D)>> = HashMap::new();
-
-    query_b.iter().for_each(|(entity, transform, bbox)| {
-        let bbox = bbox.as_rect(transform.end_frame);
-        let in_node = quad_tree.smallest_node(&bbox);
-        if let Some(contents) = spatial_index.get_mut(&in_node) {
-            contents.push((entity, bbox));
-        } else {
-            spatial_index.insert(in_node, vec![(entity, bbox)]);
-        }
-    });
+    find_collisions(
+        &quad_tree,
+        query_a.iter(),
+        query_b.iter().map(|collider| (collider, ())),
+        |entity_a, entity_b, _| {
+            sender.write(OnCollision::new(entity_a, entity_b));
+        },
+    );
+}
+
+/// Implemented by collider components that attach gameplay meaning to their
+/// collisions, e.g. the damage dealt by spikes
+pub trait CollisionPayload: Component {
+    /// The value delivered with each collision
+    type Payload: Copy + Send + Sync + 'static;
+
+    /// Derives the payload from the component
+    fn payload(&self) -> Self::Payload;
+}
+
+/// This event messages when a collision with a payload-carrying collider was
+/// detected
+#[derive(Event)]
+pub struct OnPayloadCollision<A, B>
+where
+    A: Component,
+    B: CollisionPayload,
+{
+    /// First colliding entity identifier
+    pub entity_a: Entity,
+    /// Second colliding entity identifier
+    pub entity_b: Entity,
+    /// Payload of the second entity's collider
+    pub payload: B::Payload,
+    marker: PhantomData<A>,
+}
+
+/// Checks whether collisions between Components of type A and B occur, and
+/// sends the payload of B with each collision
+pub fn check_payload_collisions<A, B>(
+    quad_tree: Res<StaticQuadTree>,
+    query_a: Query<Collider, With<A>>,
+    query_b: Query<(Collider, &B)>,
+    mut sender: EventWriter<OnPayloadCollision<A, B>>,
+) where
+    A: Component,
+    B: CollisionPayload,
+{
+    find_collisions(
+        &quad_tree,
+        query_a.iter(),
+        query_b
+            .iter()
+            .map(|(collider, component)| (collider, component.payload())),
+        |entity_a, entity_b, payload| {
+            sender.write(OnPayloadCollision {
+                entity_a,
+                entity_b,
+                payload,
+                marker: PhantomData,
+            });
+        },
+    );
+}
+
+/// Finds all colliding pairs of entities in `colliders_a` and `colliders_b`,
+/// and calls `on_collision` with both entities and the payload of the
+/// second one
+fn find_collisions<'a, T: Copy>(
+    quad_tree: &StaticQuadTree,
+    colliders_a: impl Iterator<Item = QueryItem<'a, Collider<'a>>>,
+    colliders_b: impl Iterator<Item = (QueryItem<'a, Collider<'a>>, T)>,
+    mut on_collision: impl FnMut(Entity, Entity, T),
+) {
+    let mut spatial_index: SpatialIndex<T> = HashMap::new();
 
-    query_a.iter().for_each(|(entity_a, transform_a, bbox_a)| {
-        let bbox_a = bbox_a.as_rect(transform_a.end_frame);
-        for node in quad_tree.intersecting_nodes(&bbox_a) {
-            if let Some(contents) = spatial_index.get(&node) {
-                for (entity_b, bbox_b) in contents {
-                    if entity_a != *entity_b && bbox_a.intersect(bbox_b) {
-                        sender.write(OnCollision {
-                            entity_a: entity_a,
-                            entity_b: *entity_b,
-                            marker: PhantomData,
-                        });
+    colliders_b.for_each(
+        |((entity, transform, bbox, convex, rotation, is_static), payload)| {
+            let bbox = bounding_rect(transform, bbox, rotation);
+            let polygon = convex_points(transform, convex, rotation);
+            let in_node = quad_tree.smallest_node(&bbox);
+            let collider = (entity, bbox, polygon, is_static, payload);
+            if let Some(contents) = spatial_index.get_mut(&in_node) {
+                contents.push(collider);
+            } else {
+                spatial_index.insert(in_node, vec![collider]);
+            }
+        },
+    );
+
+    colliders_a.for_each(
+        |(entity_a, transform_a, bbox_a, convex_a, rotation_a, static_a)| {
+            let bbox_a = bounding_rect(transform_a, bbox_a, rotation_a);
+            let polygon_a = convex_points(transform_a, convex_a, rotation_a);
+            for node in quad_tree.intersecting_nodes(&bbox_a) {
+                if let Some(contents) = spatial_index.get(&node) {
+                    for (entity_b, bbox_b, polygon_b, static_b, payload) in contents {
+                        if entity_a != *entity_b
+                            && !(static_a && *static_b)
+                            && bbox_a.intersect(bbox_b)
+                            && narrow_phase(&bbox_a, &polygon_a, bbox_b, polygon_b)
+                        {
+                            on_collision(entity_a, *entity_b, *payload);
+                        }
                     }
                 }
             }
-        }
+        },
+    );
+}
+
+/// Spawns static colliders of the same size at many positions in one batch,
+/// e.g. for the tiles of a level. This reduces the command overhead compared to
+/// spawning each entity on its own. Each collider is marked [`Static`] and
+/// spawned together with the `components` bundle.
+pub fn spawn_colliders<B>(
+    commands: &mut Commands,
+    positions: impl IntoIterator<Item = Vec2>,
+    size: Vec2,
+    components: B,
+) where
+    B: Bundle + Clone,
+    <B as DynamicBundle>::Effect: NoBundleEffect,
+{
+    let colliders: Vec<_> = positions
+        .into_iter()
+        .map(|position| {
+            (
+                PhysicsPosition::new(position),
+                AxisAlignedBoundingBox::new(size.x, size.y),
+                Static,
+                components.clone(),
+            )
+        })
+        .collect();
+    commands.spawn_batch(colliders);
+}
+
+/// Places the bounding box of an entity in world space, scaled like the
+/// entity's `Transform`
+fn bounding_rect(
+    position: &PhysicsPosition,
+    bbox: &AxisAlignedBoundingBox,
+    transform: Option<&Transform>,
+) -> Rect2D {
+    let scale = transform.map_or(Vec2::ONE, |t| t.scale.truncate());
+    bbox.as_rect_scaled(position.end_frame, scale)
+}
+
+/// Places the convex collider of an entity in world space, if it has one,
+/// rotated and scaled like the entity's `Transform`
+fn convex_points(
+    position: &PhysicsPosition,
+    convex: Option<&ConvexCollider>,
+    transform: Option<&Transform>,
+) -> Option<Vec<Vec2>> {
+    let (rotation, scale) = transform.map_or((Quat::IDENTITY, Vec2::ONE), |t| {
+        (t.rotation, t.scale.truncate())
     });
+    convex.map(|convex| convex.world_points(position.end_frame, rotation, scale))
+}
+
+/// Refines a bounding box hit with SAT, if any of the entities has a convex collider
+fn narrow_phase(
+    bbox_a: &Rect2D,
+    polygon_a: &Option<Vec<Vec2>>,
+    bbox_b: &Rect2D,
+    polygon_b: &Option<Vec<Vec2>>,
+) -> bool {
+    match (polygon_a, polygon_b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => sat_intersect(a, b),
+        (Some(a), None) => sat_intersect_rect(a, bbox_b),
+        (None, Some(b)) => sat_intersect_rect(b, bbox_a),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Component, Clone)]
+    struct Tile;
+
+    #[test]
+    fn spawn_colliders_spawns_all_positions() {
+        let mut world = World::new();
+        let positions: Vec<Vec2> = (0..100).map(|i| Vec2::new(i as f32 * 24.0, 0.0)).collect();
+
+        spawn_colliders(
+            &mut world.commands(),
+            positions.clone(),
+            Vec2::splat(24.0),
+            Tile,
+        );
+        world.flush();
+
+        let mut query =
+            world.query_filtered::<(&PhysicsPosition, &AxisAlignedBoundingBox), With<Tile>>();
+        let spawned: Vec<Vec2> = query.iter(&world).map(|(p, _)| p.end_frame).collect();
+        assert_eq!(positions.len(), spawned.len());
+        assert!(positions.iter().all(|p| spawned.contains(p)));
+        assert!(query.iter(&world).all(|(position, bbox)| {
+            let rect = bbox.as_rect(position.end_frame);
+            rect.intersect(&Rect2D::new(
+                position.end_frame + Vec2::splat(11.9),
+                position.end_frame + Vec2::splat(12.0),
+            )) && !rect.intersect(&Rect2D::new(
+                position.end_frame + Vec2::splat(12.1),
+                position.end_frame + Vec2::splat(13.0),
+            ))
+        }));
+    }
+
+    #[test]
+    fn static_pairs_do_not_collide() {
+        let mut app = App::new();
+        app.add_event::<OnCollision<Tile, Tile>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(Update, check_collisions::<Tile, Tile>);
+        let mut spawn = |x: f32, is_static: bool| {
+            let mut entity = app.world_mut().spawn((
+                Tile,
+                PhysicsPosition::new(Vec2::new(x, 0.0)),
+                AxisAlignedBoundingBox::new(24.0, 24.0),
+            ));
+            if is_static {
+                entity.insert(Static);
+            }
+            entity.id()
+        };
+        // Two overlapping static tiles, and a dynamic one touching a third
+        spawn(0.0, true);
+        spawn(20.0, true);
+        let dynamic = spawn(100.0, false);
+        let touched = spawn(120.0, true);
+        app.update();
+
+        let events = app.world().resource::<Events<OnCollision<Tile, Tile>>>();
+        let mut pairs: Vec<(Entity, Entity)> = events
+            .iter_current_update_events()
+            .map(|event| (event.entity_a, event.entity_b))
+            .collect();
+        pairs.sort();
+        let mut expected = vec![(dynamic, touched), (touched, dynamic)];
+        expected.sort();
+        assert_eq!(expected, pairs);
+    }
+
+    #[test]
+    fn scaled_entities_collide_with_their_scaled_box() {
+        let mut app = App::new();
+        app.add_event::<OnCollision<Tile, Tile>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(Update, check_collisions::<Tile, Tile>);
+        // 30 units apart, the boxes only touch when one is scaled up 2x
+        app.world_mut().spawn((
+            Tile,
+            PhysicsPosition::new(Vec2::ZERO),
+            AxisAlignedBoundingBox::new(24.0, 24.0),
+            Transform::from_scale(Vec3::new(2.0, 2.0, 1.0)),
+        ));
+        app.world_mut().spawn((
+            Tile,
+            PhysicsPosition::new(Vec2::new(30.0, 0.0)),
+            AxisAlignedBoundingBox::new(24.0, 24.0),
+            Static,
+        ));
+        app.update();
+
+        let events = app.world().resource::<Events<OnCollision<Tile, Tile>>>();
+        assert_eq!(2, events.iter_current_update_events().count());
+    }
+
+    #[test]
+    fn scaled_convex_colliders_collide_with_their_scaled_polygon() {
+        let mut app = App::new();
+        app.add_event::<OnCollision<Tile, Tile>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(Update, check_collisions::<Tile, Tile>);
+        // The box encloses the polygon at any rotation. Scaled up 2x, both
+        // reach into the tile 30 units away.
+        let scaled = app
+            .world_mut()
+            .spawn((
+                Tile,
+                PhysicsPosition::new(Vec2::ZERO),
+                AxisAlignedBoundingBox::new(34.0, 34.0),
+                ConvexCollider::rectangle(24.0, 24.0),
+                Transform::from_scale(Vec3::new(2.0, 2.0, 1.0)),
+            ))
+            .id();
+        app.world_mut().spawn((
+            Tile,
+            PhysicsPosition::new(Vec2::new(30.0, 0.0)),
+            AxisAlignedBoundingBox::new(24.0, 24.0),
+            Static,
+        ));
+        app.update();
+        let hits = |app: &App| {
+            let events = app.world().resource::<Events<OnCollision<Tile, Tile>>>();
+            events.iter_current_update_events().count()
+        };
+        assert_eq!(2, hits(&app));
+
+        // Scaled down, the box still reaches the tile, but the polygon doesn't
+        app.world_mut().get_mut::<Transform>(scaled).unwrap().scale = Vec3::new(1.4, 1.4, 1.0);
+        app.update();
+        assert_eq!(0, hits(&app));
+    }
+
+    #[derive(Component)]
+    struct Player;
+
+    #[derive(Component)]
+    struct Spikes {
+        damage: u32,
+    }
+
+    impl CollisionPayload for Spikes {
+        type Payload = u32;
+
+        fn payload(&self) -> u32 {
+            self.damage
+        }
+    }
+
+    #[test]
+    fn collision_event_delivers_payload() {
+        let mut app = App::new();
+        app.add_event::<OnPayloadCollision<Player, Spikes>>()
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(Update, check_payload_collisions::<Player, Spikes>);
+        let player = app
+            .world_mut()
+            .spawn((
+                Player,
+                PhysicsPosition::new(Vec2::ZERO),
+                AxisAlignedBoundingBox::new(24.0, 24.0),
+            ))
+            .id();
+        let spikes = app
+            .world_mut()
+            .spawn((
+                Spikes { damage: 7 },
+                PhysicsPosition::new(Vec2::new(20.0, 0.0)),
+                AxisAlignedBoundingBox::new(24.0, 24.0),
+            ))
+            .id();
+        app.world_mut().spawn((
+            Spikes { damage: 3 },
+            PhysicsPosition::new(Vec2::new(100.0, 0.0)),
+            AxisAlignedBoundingBox::new(24.0, 24.0),
+        ));
+        app.update();
+
+        let events = app
+            .world()
+            .resource::<Events<OnPayloadCollision<Player, Spikes>>>();
+        let hits: Vec<(Entity, Entity, u32)> = events
+            .get_cursor()
+            .read(events)
+            .map(|hit| (hit.entity_a, hit.entity_b, hit.payload))
+            .collect();
+        assert_eq!(vec![(player, spikes, 7)], hits);
+    }
 }
@@ -0,0 +1,242 @@
+//! Detect entities coming close to each other within a configurable radius
+
+use super::{CollisionTracker, Rect2D, StaticQuadTree};
+use crate::PhysicsPosition;
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use std::marker::PhantomData;
+
+/// Resource that configures the distance at which entities of type A and B
+/// are considered near to each other
+#[derive(Resource)]
+pub struct ProximityConfig<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Maximum distance (inclusive) between both entities
+    pub radius: f32,
+    marker: PhantomData<(A, B)>,
+}
+
+impl<A, B> ProximityConfig<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Creates a new proximity configuration with a given radius
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// This event messages each frame while two entities are within range
+#[derive(Event)]
+pub struct OnNear<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Entity of type A
+    pub entity_a: Entity,
+    /// Entity of type B
+    pub entity_b: Entity,
+    /// Current distance between both entities
+    pub distance: f32,
+    marker: PhantomData<(A, B)>,
+}
+
+/// This event messages when two entities come within range
+#[derive(Event)]
+pub struct OnNearEnter<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Entity of type A
+    pub entity_a: Entity,
+    /// Entity of type B
+    pub entity_b: Entity,
+    marker: PhantomData<(A, B)>,
+}
+
+/// This event messages when two entities leave the range
+#[derive(Event)]
+pub struct OnNearExit<A, B>
+where
+    A: Component,
+    B: Component,
+{
+    /// Entity of type A
+    pub entity_a: Entity,
+    /// Entity of type B
+    pub entity_b: Entity,
+    marker: PhantomData<(A, B)>,
+}
+
+/// Checks whether Components of type A and B are within the radius of
+/// the `ProximityConfig<A, B>` resource
+#[allow(clippy::too_many_arguments)]
+pub fn check_proximity<A, B>(
+    config: Res<ProximityConfig<A, B>>,
+    quad_tree: Res<StaticQuadTree>,
+    query_a: Query<(Entity, &PhysicsPosition), With<A>>,
+    query_b: Query<(Entity, &PhysicsPosition), With<B>>,
+    mut tracker: Local<CollisionTracker>,
+    mut near: EventWriter<OnNear<A, B>>,
+    mut enter: EventWriter<OnNearEnter<A, B>>,
+    mut exit: EventWriter<OnNearExit<A, B>>,
+) where
+    A: Component,
+    B: Component,
+{
+    let mut spatial_index: HashMap<usize, Vec<(Entity, Vec2)>> = HashMap::new();
+
+    query_b.iter().for_each(|(entity, position)| {
+        let in_node = quad_tree.smallest_node(&Rect2D::new(position.end_frame, position.end_frame));
+        spatial_index
+            .entry(in_node)
+            .or_default()
+            .push((entity, position.end_frame));
+    });
+
+    let mut in_range = HashSet::new();
+    query_a.iter().for_each(|(entity_a, position_a)| {
+        let center = position_a.end_frame;
+        for node in quad_tree.nodes_in_radius(center, config.radius) {
+            if let Some(contents) = spatial_index.get(&node) {
+                for (entity_b, position_b) in contents {
+                    let distance = center.distance(*position_b);
+                    if entity_a != *entity_b && distance <= config.radius {
+                        in_range.insert((entity_a, *entity_b));
+                        near.write(OnNear {
+                            entity_a,
+                            entity_b: *entity_b,
+                            distance,
+                            marker: PhantomData,
+                        });
+                    }
+                }
+            }
+        }
+    });
+
+    let (entered, exited) = tracker.update(in_range);
+    for (entity_a, entity_b) in entered {
+        enter.write(OnNearEnter {
+            entity_a,
+            entity_b,
+            marker: PhantomData,
+        });
+    }
+    for (entity_a, entity_b) in exited {
+        exit.write(OnNearExit {
+            entity_a,
+            entity_b,
+            marker: PhantomData,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::event::EventCursor;
+
+    #[derive(Component)]
+    struct Seeker;
+
+    #[derive(Component)]
+    struct Target;
+
+    fn setup(radius: f32) -> App {
+        let mut app = App::new();
+        app.add_event::<OnNear<Seeker, Target>>()
+            .add_event::<OnNearEnter<Seeker, Target>>()
+            .add_event::<OnNearExit<Seeker, Target>>()
+            .insert_resource(ProximityConfig::<Seeker, Target>::new(radius))
+            .insert_resource(StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4))
+            .add_systems(Update, check_proximity::<Seeker, Target>);
+        app
+    }
+
+    fn count<E: Event>(app: &App, cursor: &mut EventCursor<E>) -> usize {
+        cursor.read(app.world().resource::<Events<E>>()).count()
+    }
+
+    #[test]
+    fn within_exact_radius_is_near() {
+        let mut app = setup(5.0);
+        app.world_mut()
+            .spawn((Seeker, PhysicsPosition::new(Vec2::ZERO)));
+        app.world_mut()
+            .spawn((Target, PhysicsPosition::new(Vec2::new(3.0, 4.0))));
+        app.update();
+
+        let events = app.world().resource::<Events<OnNear<Seeker, Target>>>();
+        let distances: Vec<f32> = events
+            .get_cursor()
+            .read(events)
+            .map(|near| near.distance)
+            .collect();
+        assert_eq!(vec![5.0], distances);
+    }
+
+    #[test]
+    fn beyond_radius_is_not_near() {
+        let mut app = setup(5.0);
+        app.world_mut()
+            .spawn((Seeker, PhysicsPosition::new(Vec2::ZERO)));
+        app.world_mut()
+            .spawn((Target, PhysicsPosition::new(Vec2::new(3.0, 4.01))));
+        app.update();
+
+        assert_eq!(
+            0,
+            count(&app, &mut EventCursor::<OnNear<Seeker, Target>>::default())
+        );
+    }
+
+    #[test]
+    fn passing_by_enters_and_exits_once() {
+        let mut app = setup(5.0);
+        app.world_mut()
+            .spawn((Seeker, PhysicsPosition::new(Vec2::ZERO)));
+        let target = app
+            .world_mut()
+            .spawn((Target, PhysicsPosition::new(Vec2::new(-10.0, 0.0))))
+            .id();
+
+        let (mut entered, mut exited, mut near) = (0, 0, 0);
+        let mut enter_events = EventCursor::<OnNearEnter<Seeker, Target>>::default();
+        let mut exit_events = EventCursor::<OnNearExit<Seeker, Target>>::default();
+        let mut near_events = EventCursor::<OnNear<Seeker, Target>>::default();
+        for x in -10..=10 {
+            app.world_mut()
+                .get_mut::<PhysicsPosition>(target)
+                .unwrap()
+                .end_frame = Vec2::new(x as f32, 0.0);
+            app.update();
+
+            entered += count(&app, &mut enter_events);
+            exited += count(&app, &mut exit_events);
+            near += count(&app, &mut near_events);
+
+            match x {
+                -5 => assert!(entered == 1 && exited == 0),
+                6 => assert!(entered == 1 && exited == 1),
+                _ => {}
+            }
+        }
+
+        assert_eq!(1, entered);
+        assert_eq!(1, exited);
+        // One event per frame for x in -5..=5
+        assert_eq!(11, near);
+    }
+}
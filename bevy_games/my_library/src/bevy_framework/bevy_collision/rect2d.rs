@@ -3,7 +3,7 @@
 use bevy::prelude::*;
 
 /// Two-dimensional rectangle for collision detection
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect2D {
     /// Top-left corner of the rectangle
     min: Vec2,
@@ -17,6 +17,16 @@ impl Rect2D {
         Self { min, max }
     }
 
+    /// The corner with the smallest coordinates
+    pub fn min(&self) -> Vec2 {
+        self.min
+    }
+
+    /// The corner with the largest coordinates
+    pub fn max(&self) -> Vec2 {
+        self.max
+    }
+
     /// Checks if this rect intersects with other
     pub fn intersect(&self, other: &Self) -> bool {
         self.min.x <= other.max.x
@@ -25,6 +35,23 @@ impl Rect2D {
             && self.max.y >= other.min.y
     }
 
+    /// Creates the smallest Rect2D that contains all given points
+    pub fn bounding(points: &[Vec2]) -> Self {
+        let min = points.iter().fold(Vec2::MAX, |min, p| min.min(*p));
+        let max = points.iter().fold(Vec2::MIN, |max, p| max.max(*p));
+        Self::new(min, max)
+    }
+
+    /// Lists the four corners of this rect in winding order
+    pub fn corners(&self) -> [Vec2; 4] {
+        [
+            self.min,
+            Vec2::new(self.max.x, self.min.y),
+            self.max,
+            Vec2::new(self.min.x, self.max.y),
+        ]
+    }
+
     /// Calculates the center coordinates of this rect
     pub fn center(&self) -> Vec2 {
         (self.min + self.max) / 2.0
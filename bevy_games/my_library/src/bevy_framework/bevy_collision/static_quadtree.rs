@@ -102,6 +102,13 @@ impl StaticQuadTree {
         result
     }
 
+    /// finds all nodes of the tree that may contain entities within a radius
+    /// around a given center
+    pub fn nodes_in_radius(&self, center: Vec2, radius: f32) -> HashSet<usize> {
+        let reach = Vec2::splat(radius);
+        self.intersecting_nodes(&Rect2D::new(center - reach, center + reach))
+    }
+
     fn intersect(&self, index: usize, result: &mut HashSet<usize>, target: &Rect2D) {
         if self.nodes[index].bounds.intersect(target) {
             result.insert(index);
@@ -113,3 +120,70 @@ impl StaticQuadTree {
         }
     }
 }
+
+/// Entities sorted into the nodes of a [`StaticQuadTree`] they overlap, e.g.
+/// to group them or to write custom collision logic. Keep it in a system's
+/// `Local` and rebuild it once per frame, after the entities moved, so the
+/// memory of the nodes is reused.
+#[derive(Debug, Default)]
+pub struct QuadTreeBuckets {
+    /// Entities of each node, indexed like the tree's nodes
+    buckets: Vec<Vec<Entity>>,
+}
+
+impl QuadTreeBuckets {
+    /// Empties all nodes, but keeps their memory for the next frame
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(Vec::clear);
+    }
+
+    /// Adds an entity to every node its bounding box overlaps
+    pub fn insert(&mut self, tree: &StaticQuadTree, entity: Entity, bounds: &Rect2D) {
+        for node in tree.intersecting_nodes(bounds) {
+            if self.buckets.len() <= node {
+                self.buckets.resize_with(node + 1, Vec::new);
+            }
+            self.buckets[node].push(entity);
+        }
+    }
+
+    /// The entities that overlap a node
+    pub fn node_entities(&self, index: usize) -> &[Entity] {
+        self.buckets.get(index).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buckets_hold_the_entities_of_their_node() {
+        let tree = StaticQuadTree::new(Vec2::new(1024.0, 768.0), 4);
+        let mut world = World::new();
+        let (a, b, far) = (
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+            world.spawn_empty().id(),
+        );
+        let near = Rect2D::new(Vec2::new(100.0, 100.0), Vec2::new(110.0, 110.0));
+        let also_near = Rect2D::new(Vec2::new(102.0, 102.0), Vec2::new(108.0, 108.0));
+        let away = Rect2D::new(Vec2::new(-300.0, -300.0), Vec2::new(-290.0, -290.0));
+
+        let mut buckets = QuadTreeBuckets::default();
+        buckets.insert(&tree, a, &near);
+        buckets.insert(&tree, b, &also_near);
+        buckets.insert(&tree, far, &away);
+
+        let node = tree.smallest_node(&near);
+        assert_ne!(0, node);
+        assert_eq!(&[a, b], buckets.node_entities(node));
+        assert_eq!(&[far], buckets.node_entities(tree.smallest_node(&away)));
+        // The root overlaps everything
+        assert_eq!(3, buckets.node_entities(0).len());
+
+        buckets.clear();
+        assert!(buckets.node_entities(node).is_empty());
+        assert!(buckets.node_entities(usize::MAX).is_empty());
+    }
+}
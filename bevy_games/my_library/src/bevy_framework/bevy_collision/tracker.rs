@@ -0,0 +1,30 @@
+//! Keeps track of entity pairs over frames to detect when contacts start or end
+
+use bevy::{platform::collections::HashSet, prelude::*};
+
+/// A pair of entities in contact
+pub type EntityPair = (Entity, Entity);
+
+/// Remembers the pairs of entities that were in contact during the last
+/// frame. Diffing them with the current frame tells which contacts were
+/// entered and which were exited.
+#[derive(Default, Debug)]
+pub struct CollisionTracker {
+    pairs: HashSet<EntityPair>,
+}
+
+impl CollisionTracker {
+    /// Replaces the tracked pairs by the pairs of the current frame. Returns the
+    /// pairs that are new in this frame and those that are gone since the last one.
+    pub fn update(&mut self, current: HashSet<EntityPair>) -> (Vec<EntityPair>, Vec<EntityPair>) {
+        let entered = current.difference(&self.pairs).copied().collect();
+        let exited = self.pairs.difference(&current).copied().collect();
+        self.pairs = current;
+        (entered, exited)
+    }
+
+    /// Checks if a pair of entities was in contact during the last update
+    pub fn contains(&self, entity_a: Entity, entity_b: Entity) -> bool {
+        self.pairs.contains(&(entity_a, entity_b))
+    }
+}
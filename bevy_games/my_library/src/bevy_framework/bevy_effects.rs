@@ -0,0 +1,325 @@
+//! Visual effects for sprites
+
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// Lets a sprite flash in a given color when a [`Flash`] event targets its
+/// entity. The sprite fades back to its original color over the duration.
+#[derive(Component)]
+pub struct FlashOnEvent {
+    /// The color to tint the sprite with
+    color: Color,
+    /// How long does it take to fade back (in seconds)?
+    duration: f32,
+    /// Time left until the flash is over
+    remaining: f32,
+    /// The sprite's color before flashing. None if not flashing.
+    original: Option<Color>,
+}
+
+impl FlashOnEvent {
+    /// Creates a new flash effect with a color and a duration in seconds
+    pub fn new(color: Color, duration: f32) -> Self {
+        Self {
+            color,
+            duration,
+            remaining: 0.0,
+            original: None,
+        }
+    }
+}
+
+/// Event to trigger the flash effect of an entity
+#[derive(Event)]
+pub struct Flash {
+    /// The entity that should flash
+    pub target: Entity,
+}
+
+/// System that starts flashes on [`Flash`] events, and fades flashing
+/// sprites back to their original color
+pub fn flash_system(
+    mut flashes: EventReader<Flash>,
+    time: Res<Time>,
+    mut query: Query<(&mut FlashOnEvent, &mut Sprite)>,
+) {
+    for flash in flashes.read() {
+        if let Ok((mut effect, mut sprite)) = query.get_mut(flash.target) {
+            // Keep the color from before, if the sprite is already flashing
+            if effect.original.is_none() {
+                effect.original = Some(sprite.color);
+            }
+            effect.remaining = effect.duration;
+            sprite.color = effect.color;
+        }
+    }
+
+    for (mut effect, mut sprite) in query.iter_mut() {
+        let Some(original) = effect.original else {
+            continue;
+        };
+        effect.remaining -= time.delta_secs();
+        if effect.remaining <= 0.0 {
+            sprite.color = original;
+            effect.original = None;
+        } else {
+            sprite.color = original.mix(&effect.color, effect.remaining / effect.duration);
+        }
+    }
+}
+
+/// Triggers an effect on a fixed wall-clock interval, independent of the
+/// frame rate
+#[derive(Component)]
+pub struct PeriodicEffect {
+    /// Repeating timer that finishes each time the effect should trigger
+    pub interval: Timer,
+}
+
+impl PeriodicEffect {
+    /// Creates an effect triggering every `seconds`
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            interval: Timer::from_seconds(seconds, TimerMode::Repeating),
+        }
+    }
+
+    /// Starts the interval with some time already elapsed, e.g. to keep
+    /// several effects from triggering in sync
+    pub fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.interval.set_elapsed(elapsed);
+        self
+    }
+
+    /// Advances the interval by the time since the last frame. Returns how
+    /// often the effect triggered meanwhile.
+    pub fn tick(&mut self, delta: Duration) -> u32 {
+        self.interval.tick(delta).times_finished_this_tick()
+    }
+}
+
+/// How the progress of a [`BackgroundCycle`] advances
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CycleProgress {
+    /// Loops through the keyframes every `duration` seconds
+    Timed {
+        /// Seconds for one full cycle
+        duration: f32,
+        /// Seconds into the current cycle
+        elapsed: f32,
+    },
+    /// The game sets the progress from 0.0 to 1.0
+    External(f32),
+}
+
+/// Resource that lerps the clear color, e.g. through dawn, day, dusk and
+/// night. Sprites marked with [`TintWithBackground`] are tinted as well.
+#[derive(Resource, Debug, Clone)]
+pub struct BackgroundCycle {
+    /// Colors at a progress from 0.0 to 1.0, sorted by progress
+    keyframes: Vec<(f32, LinearRgba)>,
+    /// How the progress advances
+    pub progress: CycleProgress,
+}
+
+/// Tints the sprite of an entity with the color of the [`BackgroundCycle`]
+#[derive(Component)]
+pub struct TintWithBackground;
+
+impl BackgroundCycle {
+    /// Creates a cycle through the keyframes that loops every `duration`
+    /// seconds of virtual time
+    pub fn timed(keyframes: Vec<(f32, LinearRgba)>, duration: f32) -> Self {
+        Self::new(
+            keyframes,
+            CycleProgress::Timed {
+                duration,
+                elapsed: 0.0,
+            },
+        )
+    }
+
+    /// Creates a cycle through the keyframes, that is driven by the game
+    /// using [`BackgroundCycle::set_progress`]
+    pub fn external(keyframes: Vec<(f32, LinearRgba)>) -> Self {
+        Self::new(keyframes, CycleProgress::External(0.0))
+    }
+
+    fn new(mut keyframes: Vec<(f32, LinearRgba)>, progress: CycleProgress) -> Self {
+        assert!(!keyframes.is_empty(), "A background cycle needs keyframes");
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            keyframes,
+            progress,
+        }
+    }
+
+    /// Sets the progress of an externally driven cycle
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = CycleProgress::External(progress);
+    }
+
+    /// Current progress from 0.0 to 1.0
+    pub fn current_progress(&self) -> f32 {
+        match self.progress {
+            CycleProgress::Timed { duration, elapsed } => elapsed / duration,
+            CycleProgress::External(progress) => progress,
+        }
+    }
+
+    /// Interpolates the color at a progress. The cycle wraps around, so the
+    /// last keyframe blends into the first one, and 1.25 equals 0.25.
+    pub fn color_at(&self, progress: f32) -> LinearRgba {
+        let progress = progress.rem_euclid(1.0);
+        let next = self.keyframes.partition_point(|(at, _)| *at <= progress);
+        let (from, to) = match next {
+            0 => {
+                let (at, color) = self.keyframes[self.keyframes.len() - 1];
+                ((at - 1.0, color), self.keyframes[0])
+            }
+            n if n == self.keyframes.len() => {
+                let (at, color) = self.keyframes[0];
+                (self.keyframes[n - 1], (at + 1.0, color))
+            }
+            n => (self.keyframes[n - 1], self.keyframes[n]),
+        };
+        if to.0 <= from.0 {
+            return from.1;
+        }
+        from.1.mix(&to.1, (progress - from.0) / (to.0 - from.0))
+    }
+
+    /// Advances a timed cycle
+    fn tick(&mut self, delta: f32) {
+        if let CycleProgress::Timed { duration, elapsed } = &mut self.progress {
+            *elapsed = (*elapsed + delta).rem_euclid(*duration);
+        }
+    }
+}
+
+/// System advancing the [`BackgroundCycle`] with virtual time, so it pauses
+/// with the game, and applying its color to the clear color and tinted
+/// sprites
+pub fn background_cycle(
+    time: Res<Time<Virtual>>,
+    mut cycle: ResMut<BackgroundCycle>,
+    mut clear_color: ResMut<ClearColor>,
+    mut tinted: Query<&mut Sprite, With<TintWithBackground>>,
+) {
+    cycle.tick(time.delta_secs());
+    let color = Color::from(cycle.color_at(cycle.current_progress()));
+    clear_color.0 = color;
+    for mut sprite in tinted.iter_mut() {
+        sprite.color = color;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sprite_color_returns_to_original_after_flash() {
+        let original = Color::srgb(1.0, 0.0, 0.0);
+        let mut app = App::new();
+        app.add_event::<Flash>()
+            .init_resource::<Time>()
+            .add_systems(Update, flash_system);
+        let entity = app
+            .world_mut()
+            .spawn((
+                Sprite::from_color(original, Vec2::ONE),
+                FlashOnEvent::new(Color::WHITE, 0.2),
+            ))
+            .id();
+        let color = |app: &App| app.world().get::<Sprite>(entity).unwrap().color;
+        let linear = |color: Color| Vec4::from_array(color.to_linear().to_f32_array());
+
+        app.world_mut().send_event(Flash { target: entity });
+        app.update();
+        assert!(linear(color(&app)).abs_diff_eq(linear(Color::WHITE), 1e-4));
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(100));
+        app.update();
+        assert_ne!(original, color(&app));
+        assert_ne!(Color::WHITE, color(&app));
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(150));
+        app.update();
+        assert_eq!(original, color(&app));
+    }
+
+    #[test]
+    fn periodic_effect_triggers_independent_of_frame_rate() {
+        for fps in [20, 30, 60, 144, 240] {
+            let mut effect = PeriodicEffect::new(0.5);
+            let frame = Duration::from_secs(1) / fps;
+            // Simulate 3.25 seconds
+            let triggers: u32 = (0..fps * 13 / 4).map(|_| effect.tick(frame)).sum();
+            assert_eq!(6, triggers, "{fps} fps");
+        }
+    }
+
+    fn day_and_night() -> BackgroundCycle {
+        BackgroundCycle::external(vec![(0.25, LinearRgba::WHITE), (0.75, LinearRgba::BLACK)])
+    }
+
+    #[test]
+    fn background_cycle_hits_keyframes() {
+        let cycle = day_and_night();
+        assert_eq!(LinearRgba::WHITE, cycle.color_at(0.25));
+        assert_eq!(LinearRgba::BLACK, cycle.color_at(0.75));
+        assert_eq!(LinearRgba::gray(0.5), cycle.color_at(0.5));
+    }
+
+    #[test]
+    fn background_cycle_wraps_around() {
+        let cycle = day_and_night();
+        // Halfway from night at 0.75 to day at 1.25
+        assert_eq!(LinearRgba::gray(0.5), cycle.color_at(0.0));
+        assert_eq!(LinearRgba::gray(0.5), cycle.color_at(1.0));
+        assert_eq!(LinearRgba::gray(0.25), cycle.color_at(0.875));
+        assert_eq!(LinearRgba::gray(0.75), cycle.color_at(0.125));
+        let linear = |color: LinearRgba| Vec4::from_array(color.to_f32_array());
+        let color_at = |progress| linear(cycle.color_at(progress));
+        assert!(color_at(0.3).abs_diff_eq(color_at(1.3), 1e-5));
+        assert!(color_at(0.3).abs_diff_eq(color_at(-0.7), 1e-5));
+    }
+
+    #[test]
+    fn timed_background_cycle_colors_the_background() {
+        let mut app = App::new();
+        app.init_resource::<Time<Virtual>>()
+            .init_resource::<ClearColor>()
+            .insert_resource(BackgroundCycle::timed(
+                vec![(0.0, LinearRgba::BLACK), (0.5, LinearRgba::WHITE)],
+                4.0,
+            ))
+            .add_systems(Update, background_cycle);
+        let sprite = app
+            .world_mut()
+            .spawn((Sprite::default(), TintWithBackground))
+            .id();
+
+        for (seconds, expected) in [(1, 0.5), (1, 1.0), (2, 0.0), (5, 0.5)] {
+            app.world_mut()
+                .resource_mut::<Time<Virtual>>()
+                .advance_by(Duration::from_secs(seconds));
+            app.update();
+            let expected = Color::from(LinearRgba::gray(expected));
+            assert_eq!(expected, app.world().resource::<ClearColor>().0);
+            assert_eq!(expected, app.world().get::<Sprite>(sprite).unwrap().color);
+        }
+    }
+
+    #[test]
+    fn periodic_effect_catches_up_on_long_frames() {
+        let mut effect = PeriodicEffect::new(0.1).with_elapsed(Duration::from_millis(50));
+        assert_eq!(0, effect.tick(Duration::from_millis(40)));
+        assert_eq!(3, effect.tick(Duration::from_millis(300)));
+    }
+}
@@ -0,0 +1,432 @@
+//! Lightweight particles that move and change over their lifetime, without
+//! the overhead of the physics system
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, OnceLock},
+};
+
+/// Downward acceleration of particles with a gravity scale of 1.0, in units
+/// per second². Matches [`apply_gravity`](crate::apply_gravity) with the
+/// default [`Gravity`](crate::Gravity) at the physics tick rate.
+pub const PARTICLE_GRAVITY: f32 = 0.75 * (1000.0 / 33.0) * (1000.0 / 33.0);
+
+/// Describes how particles move and change over their lifetime. All values
+/// are evaluated from the particle's age, so no state is kept per frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleOptions {
+    /// Multiple of [`PARTICLE_GRAVITY`] pulling the particle down. Use
+    /// negative values to let particles rise.
+    pub gravity_scale: f32,
+    /// Share of the velocity lost per second, e.g. to slow down smoke
+    pub drag: f32,
+    /// Rotation in radians per second
+    pub spin: f32,
+    /// Color at the start and at the end of the lifetime
+    pub color: (LinearRgba, LinearRgba),
+    /// Scale at the start and at the end of the lifetime
+    pub scale: (f32, f32),
+}
+
+impl ParticleOptions {
+    /// Creates options for a particle of constant color and size, flying
+    /// straight
+    pub fn new(color: LinearRgba) -> Self {
+        Self {
+            gravity_scale: 0.0,
+            drag: 0.0,
+            spin: 0.0,
+            color: (color, color),
+            scale: (1.0, 1.0),
+        }
+    }
+
+    /// Lets gravity pull the particle down
+    pub fn with_gravity(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    /// Slows the particle down over time
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    /// Rotates the particle over time
+    pub fn with_spin(mut self, spin: f32) -> Self {
+        self.spin = spin;
+        self
+    }
+
+    /// Blends the color into `end` over the lifetime
+    pub fn fading_to(mut self, end: LinearRgba) -> Self {
+        self.color.1 = end;
+        self
+    }
+
+    /// Scales the particle from `start` to `end` over the lifetime
+    pub fn scaling(mut self, start: f32, end: f32) -> Self {
+        self.scale = (start, end);
+        self
+    }
+
+    /// Color at a normalized lifetime from 0.0 to 1.0
+    pub fn color_at(&self, t: f32) -> LinearRgba {
+        self.color.0.mix(&self.color.1, t)
+    }
+
+    /// Scale at a normalized lifetime from 0.0 to 1.0
+    pub fn scale_at(&self, t: f32) -> f32 {
+        self.scale.0.lerp(self.scale.1, t)
+    }
+
+    /// Velocity after `age` seconds, when starting with `initial` velocity.
+    /// Solves drag and gravity exactly, so the result doesn't depend on the
+    /// frame rate.
+    pub fn velocity_at(&self, initial: Vec2, age: f32) -> Vec2 {
+        let acceleration = Vec2::new(0.0, -PARTICLE_GRAVITY * self.gravity_scale);
+        if self.drag > 0.0 {
+            // The velocity approaches the terminal velocity exponentially
+            let terminal = acceleration / self.drag;
+            terminal + (initial - terminal) * (-self.drag * age).exp()
+        } else {
+            initial + acceleration * age
+        }
+    }
+}
+
+/// Directions of `count` points evenly spaced on the unit circle,
+/// counter-clockwise from (1, 0). Each table is computed once and cached for
+/// the rest of the program, so bursts of particles don't repeat the
+/// trigonometry.
+pub fn unit_circle_points(count: usize) -> &'static [Vec2] {
+    static TABLES: OnceLock<Mutex<HashMap<usize, &'static [Vec2]>>> = OnceLock::new();
+    let mut tables = TABLES.get_or_init(Default::default).lock().unwrap();
+    tables.entry(count).or_insert_with(|| {
+        let step = std::f32::consts::TAU / count as f32;
+        let points: Vec<Vec2> = (0..count)
+            .map(|i| Vec2::from_angle(i as f32 * step))
+            .collect();
+        Box::leak(points.into_boxed_slice())
+    })
+}
+
+/// A component that designates a particle entity
+#[derive(Component)]
+pub struct Particle {
+    /// How long does it take for a particle to fade away?
+    pub lifetime: f32,
+    /// Seconds since the particle was spawned
+    age: f32,
+    /// Velocity in units per second when the particle was spawned
+    initial_velocity: Vec2,
+    /// How the particle moves and changes over its lifetime
+    pub options: ParticleOptions,
+}
+
+impl Particle {
+    /// Creates a new particle living for `lifetime` seconds, starting with
+    /// `velocity` in units per second
+    pub fn new(lifetime: f32, velocity: Vec2, options: ParticleOptions) -> Self {
+        Self {
+            lifetime,
+            age: 0.0,
+            initial_velocity: velocity,
+            options,
+        }
+    }
+
+    /// Normalized lifetime from 0.0 (just spawned) to 1.0 (faded away)
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+
+    /// Velocity at a normalized lifetime from 0.0 to 1.0
+    pub fn velocity_at(&self, t: f32) -> Vec2 {
+        self.options
+            .velocity_at(self.initial_velocity, t * self.lifetime)
+    }
+}
+
+/// Tags particles spawned by a [`ParticlePool`]
+#[derive(Component)]
+pub struct PooledParticle;
+
+/// Resource capping the number of live particles. The entities of faded
+/// particles are hidden and reused, instead of being despawned and spawned
+/// again. Once the cap is reached, the oldest particle is recycled.
+#[derive(Resource, Debug)]
+pub struct ParticlePool {
+    /// Most particles alive at the same time
+    cap: usize,
+    /// Live particles, the oldest first
+    live: VecDeque<Entity>,
+    /// Hidden particles, ready to be reused
+    free: Vec<Entity>,
+}
+
+impl ParticlePool {
+    /// Creates an empty pool for up to `cap` live particles
+    pub fn new(cap: usize) -> Self {
+        Self {
+            cap: cap.max(1),
+            live: VecDeque::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Most particles alive at the same time
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    /// Number of live particles
+    pub fn live(&self) -> usize {
+        self.live.len()
+    }
+
+    /// Shows a particle, reusing a hidden entity if there is one. The
+    /// components are only added to newly spawned entities.
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        particle: Particle,
+        transform: Transform,
+        sprite: Sprite,
+        components: impl Bundle,
+    ) -> Entity {
+        let reused = if self.live.len() >= self.cap {
+            self.live.pop_front()
+        } else {
+            self.free.pop()
+        };
+        let entity = match reused {
+            Some(entity) => {
+                commands.entity(entity).try_insert((
+                    particle,
+                    transform,
+                    sprite,
+                    Visibility::Inherited,
+                ));
+                entity
+            }
+            None => commands
+                .spawn((particle, transform, sprite, PooledParticle, components))
+                .id(),
+        };
+        self.live.push_back(entity);
+        entity
+    }
+
+    /// Takes back a faded particle for reuse
+    fn release(&mut self, entity: Entity) {
+        // Particles mostly fade in the order they were spawned
+        if let Some(index) = self.live.iter().position(|live| *live == entity) {
+            self.live.remove(index);
+            self.free.push(entity);
+        }
+    }
+}
+
+/// System that moves, spins, scales and colors particles over their
+/// lifetime, and despawns them afterwards. Particles of a [`ParticlePool`]
+/// are hidden instead, so the pool can reuse them.
+pub fn particle_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        Has<PooledParticle>,
+    )>,
+    mut pool: Option<ResMut<ParticlePool>>,
+) {
+    let delta = time.delta_secs();
+    for (entity, mut particle, mut transform, mut sprite, pooled) in query.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            match (pooled, pool.as_mut()) {
+                (true, Some(pool)) => {
+                    pool.release(entity);
+                    commands
+                        .entity(entity)
+                        .remove::<Particle>()
+                        .insert(Visibility::Hidden);
+                }
+                _ => commands.entity(entity).despawn(),
+            }
+            continue;
+        }
+
+        let t = particle.progress();
+        let options = &particle.options;
+        transform.translation += particle.velocity_at(t).extend(0.0) * delta;
+        transform.rotate_z(options.spin * delta);
+        let scale = options.scale_at(t);
+        transform.scale = Vec3::new(scale, scale, 1.0);
+        sprite.color = options.color_at(t).into();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn particle_follows_curves_over_lifetime() {
+        let start = LinearRgba::new(1.0, 0.5, 0.0, 1.0);
+        let end = LinearRgba::new(0.0, 0.5, 1.0, 0.0);
+        let options = ParticleOptions::new(start)
+            .fading_to(end)
+            .scaling(1.0, 3.0)
+            // Halves the speed every second
+            .with_drag(std::f32::consts::LN_2);
+        let particle = Particle::new(2.0, Vec2::new(100.0, 0.0), options);
+
+        // (normalized lifetime, color, scale, velocity)
+        let samples = [
+            (0.0, start, 1.0, Vec2::new(100.0, 0.0)),
+            (
+                0.5,
+                LinearRgba::new(0.5, 0.5, 0.5, 0.5),
+                2.0,
+                Vec2::new(50.0, 0.0),
+            ),
+            (1.0, end, 3.0, Vec2::new(25.0, 0.0)),
+        ];
+        for (t, color, scale, velocity) in samples {
+            assert_eq!(color, options.color_at(t), "color at {t}");
+            assert_eq!(scale, options.scale_at(t), "scale at {t}");
+            assert!(
+                particle.velocity_at(t).abs_diff_eq(velocity, 1e-3),
+                "velocity at {t}"
+            );
+        }
+    }
+
+    #[test]
+    fn gravity_pulls_particles_down() {
+        let falling = ParticleOptions::new(LinearRgba::WHITE).with_gravity(1.0);
+        let rising = ParticleOptions::new(LinearRgba::WHITE).with_gravity(-0.5);
+        let initial = Vec2::new(10.0, 0.0);
+
+        assert_eq!(
+            Vec2::new(10.0, -PARTICLE_GRAVITY),
+            falling.velocity_at(initial, 1.0)
+        );
+        assert_eq!(
+            Vec2::new(10.0, PARTICLE_GRAVITY / 2.0),
+            rising.velocity_at(initial, 1.0)
+        );
+    }
+
+    #[test]
+    fn unit_circle_points_are_cached() {
+        let points = unit_circle_points(360);
+        assert_eq!(360, points.len());
+        assert!(points.iter().all(|p| (p.length() - 1.0).abs() < 1e-5));
+        assert_eq!(Vec2::X, points[0]);
+        assert!(points[90].abs_diff_eq(Vec2::Y, 1e-5));
+        assert!(std::ptr::eq(points, unit_circle_points(360)));
+
+        assert_eq!(4, unit_circle_points(4).len());
+        assert!(unit_circle_points(0).is_empty());
+    }
+
+    #[test]
+    fn particle_moves_and_despawns_after_lifetime() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_systems(Update, particle_system);
+        let particle = app
+            .world_mut()
+            .spawn((
+                Particle::new(
+                    1.0,
+                    Vec2::new(100.0, 0.0),
+                    ParticleOptions::new(LinearRgba::WHITE).scaling(1.0, 0.0),
+                ),
+                Transform::default(),
+                Sprite::default(),
+            ))
+            .id();
+        let advance = |app: &mut App, ms| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(ms));
+            app.update();
+        };
+
+        advance(&mut app, 500);
+        let transform = app.world().get::<Transform>(particle).unwrap();
+        assert_eq!(Vec3::new(50.0, 0.0, 0.0), transform.translation);
+        assert_eq!(Vec3::new(0.5, 0.5, 1.0), transform.scale);
+
+        advance(&mut app, 500);
+        assert!(app.world().get_entity(particle).is_err());
+    }
+
+    #[test]
+    fn pooled_particles_are_capped_and_reused() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(ParticlePool::new(3))
+            .add_systems(Update, particle_system);
+        let spawn = |app: &mut App, lifetime| {
+            let world = app.world_mut();
+            world.resource_scope(|world, mut pool: Mut<ParticlePool>| {
+                let entity = pool.spawn(
+                    &mut world.commands(),
+                    Particle::new(
+                        lifetime,
+                        Vec2::ZERO,
+                        ParticleOptions::new(LinearRgba::WHITE),
+                    ),
+                    Transform::default(),
+                    Sprite::default(),
+                    (),
+                );
+                world.flush();
+                entity
+            })
+        };
+        let advance = |app: &mut App, ms| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(ms));
+            app.update();
+        };
+
+        let short = spawn(&mut app, 0.5);
+        let long: Vec<Entity> = (0..2).map(|_| spawn(&mut app, 2.0)).collect();
+        // The oldest particle makes room for the fourth one
+        assert_eq!(short, spawn(&mut app, 2.0));
+        assert_eq!(3, app.world().resource::<ParticlePool>().live());
+
+        // Faded particles are hidden, and reused before spawning new ones
+        advance(&mut app, 2000);
+        assert_eq!(0, app.world().resource::<ParticlePool>().live());
+        assert_eq!(
+            Some(&Visibility::Hidden),
+            app.world().get::<Visibility>(long[0])
+        );
+        let reused = spawn(&mut app, 1.0);
+        assert!(reused == short || long.contains(&reused));
+        assert_eq!(
+            Some(&Visibility::Inherited),
+            app.world().get::<Visibility>(reused)
+        );
+        assert_eq!(
+            3,
+            app.world_mut()
+                .query::<&PooledParticle>()
+                .iter(app.world())
+                .count()
+        );
+    }
+}
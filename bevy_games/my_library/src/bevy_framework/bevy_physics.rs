@@ -1,4 +1,6 @@
-use bevy::prelude::*;
+use crate::{AxisAlignedBoundingBox, Rect2D};
+use bevy::{platform::collections::HashMap, prelude::*};
+use std::time::Duration;
 
 // How frequently should the physics tick fire (ms)
 const PHYSICS_TICK_TIME: u128 = 33;
@@ -7,35 +9,74 @@ const PHYSICS_TICK_TIME: u128 = 33;
 #[derive(Default)]
 pub struct PhysicsTimer(u128);
 
+/// Resource limiting how much time the physics clock accounts for in a
+/// single frame. A frame hitch, e.g. while the window has lost focus, is
+/// treated as if only this much time had passed.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct MaxPhysicsDelta(pub Duration);
+
+impl Default for MaxPhysicsDelta {
+    fn default() -> Self {
+        Self(Duration::from_millis(100))
+    }
+}
+
+/// Resource freezing the physics while set, e.g. while the player looks at
+/// an overview of the map. The physics clock neither ticks nor accounts for
+/// the time passed, so the physics continue where they stopped.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PhysicsPaused(pub bool);
+
 /// Event fired for each tick
 #[derive(Event)]
 pub struct PhysicsTick;
 
-/// System that keeps track of the time and emits PhysicsTick events
+/// Marks physics entities that are drawn where they are at the end of the
+/// frame, instead of being interpolated between ticks, e.g. objects that
+/// snap to a grid
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct NoInterpolation;
+
+/// System that keeps track of the time and emits PhysicsTick events. Fires
+/// as many ticks as fit into the time passed, keeping the remainder for the
+/// next frame. Between ticks, the transforms are interpolated, unless the
+/// entity is marked with [`NoInterpolation`]. Stops while [`PhysicsPaused`].
 pub fn physics_clock(
     mut clock: Local<PhysicsTimer>,
     time: Res<Time>,
+    max_delta: Res<MaxPhysicsDelta>,
+    paused: Res<PhysicsPaused>,
     mut on_tick: EventWriter<PhysicsTick>,
-    mut physics_position: Query<(&mut PhysicsPosition, &mut Transform)>,
+    mut physics_position: Query<(&mut PhysicsPosition, &mut Transform, Has<NoInterpolation>)>,
 ) {
-    let ms_since_last_call = time.delta().as_millis();
+    if paused.0 {
+        return;
+    }
+    let ms_since_last_call = time.delta().min(max_delta.0).as_millis();
     clock.0 += ms_since_last_call;
-    if clock.0 >= PHYSICS_TICK_TIME {
-        clock.0 = 0;
+    let ticks = clock.0 / PHYSICS_TICK_TIME;
+    if ticks > 0 {
+        clock.0 -= ticks * PHYSICS_TICK_TIME;
         physics_position
             .iter_mut()
-            .for_each(|(mut pos, mut transform)| {
+            .for_each(|(mut pos, mut transform, _)| {
                 transform.translation.x = pos.end_frame.x;
                 transform.translation.y = pos.end_frame.y;
                 pos.start_frame = pos.end_frame;
             });
-        on_tick.write(PhysicsTick);
+        for _ in 0..ticks {
+            on_tick.write(PhysicsTick);
+        }
     } else {
         let frame_progress = clock.0 as f32 / PHYSICS_TICK_TIME as f32;
         physics_position
             .iter_mut()
-            .for_each(|(pos, mut transform)| {
-                let interpolated_pos = pos.interpolate(frame_progress);
+            .for_each(|(pos, mut transform, snapped)| {
+                let interpolated_pos = if snapped {
+                    pos.end_frame
+                } else {
+                    pos.interpolate(frame_progress)
+                };
                 transform.translation.x = interpolated_pos.x;
                 transform.translation.y = interpolated_pos.y;
             });
@@ -44,6 +85,7 @@ pub fn physics_clock(
 
 /// Component to track movement over time as Velocity
 #[derive(Component)]
+#[require(Acceleration)]
 pub struct Velocity(pub Vec3);
 
 impl Default for Velocity {
@@ -64,6 +106,13 @@ impl Velocity {
     }
 }
 
+/// The change of velocity by the forces since the last integration, e.g.
+/// gravity and impulses. Collected by [`apply_gravity`] and [`sum_impulses`],
+/// and consumed by [`apply_velocity`], which needs the velocity from before
+/// the forces for Verlet integration.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
+pub struct Acceleration(pub Vec3);
+
 /// Apply an impulse to a physics-based component, affecting its velocity
 #[derive(Event)]
 pub struct Impulse {
@@ -78,37 +127,117 @@ pub struct Impulse {
 }
 
 /// System for calculating total forces applyed to an enitity within a physics tick
-pub fn sum_impulses(mut impulses: EventReader<Impulse>, mut velocities: Query<&mut Velocity>) {
+pub fn sum_impulses(
+    mut impulses: EventReader<Impulse>,
+    mut velocities: Query<(&mut Velocity, &mut Acceleration)>,
+) {
     let mut dedupe_by_source = std::collections::HashMap::new();
     for impulse in impulses.read() {
         dedupe_by_source.insert(impulse.source, impulse);
     }
     let mut absolute = std::collections::HashSet::new();
     for (_, impulse) in dedupe_by_source {
-        if let Ok(mut velocity) = velocities.get_mut(impulse.target) {
+        if let Ok((mut velocity, mut acceleration)) = velocities.get_mut(impulse.target) {
             if absolute.contains(&impulse.target) {
                 continue;
             }
             if impulse.absolute {
+                acceleration.0 += impulse.amount - velocity.0;
                 velocity.0 = impulse.amount;
                 absolute.insert(impulse.target);
             } else {
                 velocity.0 += impulse.amount;
+                acceleration.0 += impulse.amount;
             }
         }
     }
 }
 
+/// Resource selecting how velocities are integrated into positions on each
+/// physics tick
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub enum IntegrationMethod {
+    /// Explicit Euler integration: moves the position by the current velocity
+    #[default]
+    Euler,
+    /// Position Verlet integration: moves the position by the last tick's
+    /// displacement plus the acceleration of this tick. As the velocity is
+    /// implied by the position history, it is more stable for springs and
+    /// orbits.
+    Verlet,
+}
+
+impl IntegrationMethod {
+    /// Calculates the position at the end of a tick from the position of the
+    /// previous and the current tick, the current velocity and the
+    /// acceleration applied during this tick
+    pub fn step(&self, previous: Vec2, current: Vec2, velocity: Vec2, acceleration: Vec2) -> Vec2 {
+        match self {
+            IntegrationMethod::Euler => current + velocity,
+            IntegrationMethod::Verlet => 2.0 * current - previous + acceleration,
+        }
+    }
+}
+
+/// Components integrated by [`apply_velocity`]
+type Moving<'a> = (
+    Entity,
+    &'a mut Velocity,
+    &'a mut Acceleration,
+    &'a mut PhysicsPosition,
+);
+
 /// System that applies the calculated velocities to the transforms on
-/// each tick of the physics clock
+/// each tick of the physics clock. With Verlet integration, the
+/// [`Acceleration`] is spread evenly over the ticks of the frame, as the
+/// forces of all of them were collected before, and the velocity is set to
+/// the movement of the tick plus the acceleration still to come.
 pub fn apply_velocity(
     mut tick: EventReader<PhysicsTick>,
-    mut movement: Query<(&Velocity, &mut PhysicsPosition)>,
+    mut movement: Query<Moving>,
+    method: Res<IntegrationMethod>,
+    mut history: Local<HashMap<Entity, (Vec2, Vec2)>>,
 ) {
-    for _tick in tick.read() {
+    let ticks = tick.read().count();
+    for remaining_ticks in (1..=ticks).rev() {
+        // Remember the position of the last tick and the velocity it moved
+        // by, as the physics clock already moved the start frame forward.
+        let mut next_history = HashMap::new();
         movement
             .iter_mut()
-            .for_each(|(velocity, mut position)| position.end_frame += velocity.0.truncate());
+            .for_each(|(entity, mut velocity, mut acceleration, mut position)| {
+                let pending = acceleration.0;
+                let share = pending / remaining_ticks as f32;
+                acceleration.0 -= share;
+                let current = position.end_frame;
+                position.end_frame = match *method {
+                    IntegrationMethod::Euler => IntegrationMethod::Euler.step(
+                        current,
+                        current,
+                        velocity.0.truncate(),
+                        share.truncate(),
+                    ),
+                    IntegrationMethod::Verlet => {
+                        let initial = (velocity.0 - pending).truncate();
+                        // The velocity was changed otherwise, e.g. by a bounce,
+                        // so the position history doesn't apply anymore
+                        let previous = history
+                            .get(&entity)
+                            .filter(|(_, moved)| moved.abs_diff_eq(initial, 1e-4))
+                            .map_or(current - initial, |(previous, _)| *previous);
+                        let next = IntegrationMethod::Verlet.step(
+                            previous,
+                            current,
+                            initial,
+                            share.truncate(),
+                        );
+                        velocity.0 = (next - current).extend(velocity.0.z) + acceleration.0;
+                        next_history.insert(entity, (current, next - current));
+                        next
+                    }
+                };
+            });
+        *history = next_history;
     }
 }
 
@@ -117,20 +246,97 @@ pub fn apply_velocity(
 #[derive(Component)]
 pub struct ApplyGravity;
 
+/// Resource with the velocity [`apply_gravity`] takes away from marked
+/// entities per physics tick. Games may change it, e.g. for difficulty
+/// levels.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Gravity(pub f32);
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self(0.75)
+    }
+}
+
 /// System to apply gravity on marked entities for every tick
 /// of the physics clock.
 pub fn apply_gravity(
     mut tick: EventReader<PhysicsTick>,
-    mut gravity: Query<&mut Velocity, With<ApplyGravity>>,
+    strength: Res<Gravity>,
+    mut gravity: Query<(&mut Velocity, &mut Acceleration), With<ApplyGravity>>,
 ) {
     for _tick in tick.read() {
-        gravity.iter_mut().for_each(|mut velocity| {
-            velocity.0.y -= 0.75;
-        });
+        gravity
+            .iter_mut()
+            .for_each(|(mut velocity, mut acceleration)| {
+                velocity.0.y -= strength.0;
+                acceleration.0.y -= strength.0;
+            });
+    }
+}
+
+/// Resource with the area that entities marked with [`BounceAtBounds`] are
+/// kept in, usually the screen
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct ScreenBounds(pub Rect2D);
+
+impl ScreenBounds {
+    /// Bounds of a screen of the given size, centered on the origin
+    pub fn centered(size: Vec2) -> Self {
+        Self(Rect2D::new(-size / 2.0, size / 2.0))
+    }
+}
+
+/// Marks entities that bounce off the edges of the [`ScreenBounds`],
+/// instead of leaving the screen
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct BounceAtBounds;
+
+/// Share of its speed an entity keeps when bouncing off an edge of the
+/// [`ScreenBounds`], from 0 (stops) to 1 (elastic). Entities without it
+/// bounce off elastically.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Restitution(pub f32);
+
+/// Components of an entity kept inside the [`ScreenBounds`]
+type Bouncing<'a> = (
+    &'a mut PhysicsPosition,
+    &'a mut Velocity,
+    Option<&'a AxisAlignedBoundingBox>,
+    Option<&'a Restitution>,
+);
+
+/// System reflecting entities marked with [`BounceAtBounds`] at the edges of
+/// the [`ScreenBounds`]. The velocity along the axis of the edge is negated
+/// and scaled by the [`Restitution`], and the entity is moved back inside,
+/// including its bounding box.
+pub fn bounce_at_bounds(
+    bounds: Res<ScreenBounds>,
+    mut query: Query<Bouncing, With<BounceAtBounds>>,
+) {
+    for (mut position, mut velocity, bbox, restitution) in query.iter_mut() {
+        let restitution = restitution.map_or(1.0, |restitution| restitution.0);
+        let half_size = bbox.map_or(Vec2::ZERO, |bbox| bbox.as_rect(Vec2::ZERO).max());
+        let min = bounds.0.min() + half_size;
+        let max = (bounds.0.max() - half_size).max(min);
+        let current = position.current();
+        for axis in 0..2 {
+            if (current[axis] < min[axis] && velocity.0[axis] < 0.0)
+                || (current[axis] > max[axis] && velocity.0[axis] > 0.0)
+            {
+                velocity.0[axis] = -velocity.0[axis] * restitution;
+            }
+        }
+        position.end_frame = current.clamp(min, max);
     }
 }
 
 /// Collate the start and end frame positions of a physics entity
+///
+/// To push two colliding bodies apart, compare where they were at the start
+/// of the frame, before they overlapped: `a.separation_from(&b)` points from
+/// `b` toward `a`. Sum it over all contacts and normalize it to get the
+/// direction in which `a` bounces off.
 #[derive(Component)]
 pub struct PhysicsPosition {
     /// The position at the start of the fixed time frame
@@ -149,7 +355,371 @@ impl PhysicsPosition {
         }
     }
 
+    /// The position after this frame's movement, i.e. where the entity is
+    /// drawn on the next physics tick
+    pub fn current(&self) -> Vec2 {
+        self.end_frame
+    }
+
+    /// How far the entity moves within this frame. After all velocities have
+    /// been applied, this is the velocity of the last tick.
+    pub fn displacement(&self) -> Vec2 {
+        self.end_frame - self.start_frame
+    }
+
+    /// Points from `other` toward this entity, comparing where both were at
+    /// the start of the frame. It doesn't depend on how far the frame was
+    /// interpolated, nor on the movement of this frame, which may already
+    /// overlap.
+    pub fn separation_from(&self, other: &PhysicsPosition) -> Vec2 {
+        self.start_frame - other.start_frame
+    }
+
     fn interpolate(&self, t: f32) -> Vec2 {
         self.start_frame + (self.end_frame - self.start_frame) * t
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Simulates a unit mass on a spring and returns the maximum relative
+    /// deviation of its energy
+    fn oscillator_energy_drift(method: IntegrationMethod) -> f32 {
+        let stiffness = 0.05;
+        let energy =
+            |x: Vec2, v: Vec2| 0.5 * v.length_squared() + 0.5 * stiffness * x.length_squared();
+
+        let (mut previous, mut current, mut velocity) = (Vec2::X, Vec2::X, Vec2::ZERO);
+        let initial = energy(current, velocity);
+        let mut drift: f32 = 0.0;
+        for _ in 0..2000 {
+            let acceleration = -stiffness * current;
+            let next = method.step(previous, current, velocity, acceleration);
+            if method == IntegrationMethod::Verlet {
+                // Verlet has no explicit velocity, estimate it from the positions
+                velocity = (next - previous) / 2.0;
+            }
+            drift = drift.max((energy(current, velocity) - initial).abs() / initial);
+            if method == IntegrationMethod::Euler {
+                velocity += acceleration;
+            }
+            (previous, current) = (current, next);
+        }
+        drift
+    }
+
+    #[test]
+    fn verlet_keeps_oscillator_energy_stable() {
+        let euler = oscillator_energy_drift(IntegrationMethod::Euler);
+        let verlet = oscillator_energy_drift(IntegrationMethod::Verlet);
+
+        assert!(verlet < 0.1, "Verlet energy drift {verlet}");
+        assert!(euler > 10.0 * verlet, "Euler {euler} vs. Verlet {verlet}");
+    }
+
+    /// Marks a body pulled back to the origin by a spring
+    #[derive(Component)]
+    struct Spring;
+
+    /// Pushes the springs back to the origin on each physics tick
+    fn pull_springs(
+        mut tick: EventReader<PhysicsTick>,
+        springs: Query<(Entity, &PhysicsPosition), With<Spring>>,
+        mut impulses: EventWriter<Impulse>,
+    ) {
+        for _tick in tick.read() {
+            for (entity, position) in springs.iter() {
+                impulses.write(Impulse {
+                    target: entity,
+                    amount: (-0.05 * position.current()).extend(0.0),
+                    absolute: false,
+                    source: 1,
+                });
+            }
+        }
+    }
+
+    /// Runs a spring through the physics systems for 300 ticks, returning the
+    /// largest distance from the origin it reaches
+    fn spring_amplitude(method: IntegrationMethod) -> f32 {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .insert_resource(method)
+            .add_event::<PhysicsTick>()
+            .add_event::<Impulse>()
+            .add_systems(
+                Update,
+                (physics_clock, pull_springs, sum_impulses, apply_velocity).chain(),
+            );
+        let spring = app
+            .world_mut()
+            .spawn((
+                Spring,
+                PhysicsPosition::new(Vec2::X),
+                Velocity::default(),
+                Transform::default(),
+            ))
+            .id();
+
+        let mut amplitude: f32 = 0.0;
+        for _ in 0..300 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(33));
+            app.update();
+            let position = app.world().get::<PhysicsPosition>(spring).unwrap();
+            amplitude = amplitude.max(position.current().length());
+        }
+        amplitude
+    }
+
+    #[test]
+    fn springs_keep_their_amplitude_in_the_systems() {
+        // The spring starts at rest, one unit from the origin. Euler moves by
+        // the velocity after the pull, so it doesn't drift either.
+        for method in [IntegrationMethod::Euler, IntegrationMethod::Verlet] {
+            let amplitude = spring_amplitude(method);
+            assert!((amplitude - 1.0).abs() < 0.1, "{method:?}: {amplitude}");
+        }
+    }
+
+    /// Lets a body fall with Verlet integration for 132ms in frames of the
+    /// given length, and returns where it ends up
+    fn verlet_fall(frame: Duration) -> Vec2 {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .init_resource::<Gravity>()
+            .insert_resource(IntegrationMethod::Verlet)
+            .add_event::<PhysicsTick>()
+            .add_systems(
+                Update,
+                (physics_clock, apply_gravity, apply_velocity).chain(),
+            );
+        let body = app
+            .world_mut()
+            .spawn((
+                PhysicsPosition::new(Vec2::ZERO),
+                Velocity::default(),
+                Transform::default(),
+                ApplyGravity,
+            ))
+            .id();
+        for _ in 0..Duration::from_millis(132).as_millis() / frame.as_millis() {
+            app.world_mut().resource_mut::<Time>().advance_by(frame);
+            app.update();
+        }
+        app.world().get::<PhysicsPosition>(body).unwrap().end_frame
+    }
+
+    #[test]
+    fn verlet_spreads_the_acceleration_over_the_ticks_of_a_frame() {
+        let one_tick_per_frame = verlet_fall(Duration::from_millis(33));
+        assert!(one_tick_per_frame.y < 0.0);
+        assert_eq!(one_tick_per_frame, verlet_fall(Duration::from_millis(66)));
+    }
+
+    #[test]
+    fn verlet_matches_euler_for_constant_velocity() {
+        let velocity = Vec2::new(2.0, -1.0);
+        let current = Vec2::new(10.0, 10.0);
+
+        assert_eq!(
+            IntegrationMethod::Euler.step(current, current, velocity, Vec2::ZERO),
+            IntegrationMethod::Verlet.step(current - velocity, current, velocity, Vec2::ZERO)
+        );
+    }
+
+    #[test]
+    fn displacement_is_the_movement_of_the_frame() {
+        let mut position = PhysicsPosition::new(Vec2::new(3.0, 4.0));
+        assert_eq!(Vec2::ZERO, position.displacement());
+        assert_eq!(Vec2::new(3.0, 4.0), position.current());
+
+        position.end_frame += Vec2::new(2.0, -1.0);
+        position.end_frame += Vec2::new(0.5, 0.5);
+        assert_eq!(Vec2::new(2.5, -0.5), position.displacement());
+        assert_eq!(Vec2::new(5.5, 3.5), position.current());
+        assert_eq!(
+            position.start_frame + position.displacement() * 0.5,
+            position.interpolate(0.5)
+        );
+
+        // Bodies are separated by where they started, not where they moved to
+        let wall = PhysicsPosition::new(Vec2::new(7.0, 4.0));
+        assert_eq!(Vec2::new(-4.0, 0.0), position.separation_from(&wall));
+        assert_eq!(Vec2::new(4.0, 0.0), wall.separation_from(&position));
+    }
+
+    /// Runs the physics clock for a body moving 5 units per tick, advancing
+    /// the time by each delta. Returns the number of ticks and the body's x
+    /// after each frame.
+    fn run_frames(max_delta: Duration, deltas: &[Duration]) -> Vec<(usize, f32)> {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(MaxPhysicsDelta(max_delta))
+            .init_resource::<PhysicsPaused>()
+            .add_event::<PhysicsTick>()
+            .init_resource::<IntegrationMethod>()
+            .add_systems(Update, (physics_clock, apply_velocity).chain());
+        let body = app
+            .world_mut()
+            .spawn((
+                PhysicsPosition::new(Vec2::ZERO),
+                Velocity::new_2d(5.0, 0.0),
+                Transform::default(),
+            ))
+            .id();
+
+        deltas
+            .iter()
+            .map(|delta| {
+                app.world_mut().resource_mut::<Time>().advance_by(*delta);
+                app.update();
+                let ticks = app.world().resource::<Events<PhysicsTick>>().len();
+                app.world_mut()
+                    .resource_mut::<Events<PhysicsTick>>()
+                    .clear();
+                let x = app.world().get::<Transform>(body).unwrap().translation.x;
+                (ticks, x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_hitch_is_clamped() {
+        let frame = Duration::from_millis(16);
+        let stall = Duration::from_secs(5);
+        let frames = run_frames(
+            MaxPhysicsDelta::default().0,
+            &[frame, frame, frame, stall, frame],
+        );
+
+        // The stall fires the ticks of 100ms and moves the body by one step
+        assert_eq!(3, frames[3].0);
+        assert!(frames[3].1 - frames[2].1 <= 5.0);
+        // Afterwards, the body is interpolated over the ticks again
+        assert_eq!(0, frames[4].0);
+        assert!(frames[4].1 > frames[3].1 && frames[4].1 < frames[3].1 + 15.0);
+
+        // The stall only counts as much time as the maximum
+        let frames = run_frames(Duration::from_millis(20), &[frame, frame, stall]);
+        assert_eq!(1, frames[2].0);
+    }
+
+    #[test]
+    fn slow_frame_fires_all_ticks() {
+        let frames = run_frames(
+            MaxPhysicsDelta::default().0,
+            &[Duration::from_millis(100), Duration::from_millis(32)],
+        );
+        assert_eq!(3, frames[0].0);
+        // The remaining millisecond is kept for the next frame
+        assert_eq!(1, frames[1].0);
+    }
+
+    #[test]
+    fn gravity_is_read_from_the_resource() {
+        let mut app = App::new();
+        app.insert_resource(Gravity(2.0))
+            .add_event::<PhysicsTick>()
+            .add_systems(Update, apply_gravity);
+        let falling = app
+            .world_mut()
+            .spawn((Velocity::default(), ApplyGravity))
+            .id();
+        let floating = app.world_mut().spawn(Velocity::default()).id();
+        app.world_mut().send_event(PhysicsTick);
+        app.world_mut().send_event(PhysicsTick);
+        app.update();
+
+        assert_eq!(-4.0, app.world().get::<Velocity>(falling).unwrap().0.y);
+        assert_eq!(0.0, app.world().get::<Velocity>(floating).unwrap().0.y);
+    }
+
+    #[test]
+    fn entities_bounce_off_the_screen_edges() {
+        let mut app = App::new();
+        app.insert_resource(ScreenBounds::centered(Vec2::new(200.0, 100.0)))
+            .add_systems(Update, bounce_at_bounds);
+        let mut spawn = |x: f32, velocity: Velocity| {
+            app.world_mut()
+                .spawn((
+                    PhysicsPosition::new(Vec2::new(x, 0.0)),
+                    velocity,
+                    AxisAlignedBoundingBox::new(10.0, 10.0),
+                    BounceAtBounds,
+                ))
+                .id()
+        };
+        let leaving = spawn(103.0, Velocity::new_2d(2.0, 1.0));
+        let returning = spawn(-104.0, Velocity::new_2d(2.0, 0.0));
+        let inside = spawn(20.0, Velocity::new_2d(2.0, 0.0));
+        let damped = spawn(-103.0, Velocity::new_2d(-2.0, 1.0));
+        app.world_mut().entity_mut(damped).insert(Restitution(0.5));
+        app.update();
+
+        let state = |entity| {
+            (
+                app.world()
+                    .get::<PhysicsPosition>(entity)
+                    .unwrap()
+                    .current(),
+                app.world().get::<Velocity>(entity).unwrap().0.truncate(),
+            )
+        };
+        assert_eq!((Vec2::new(95.0, 0.0), Vec2::new(-2.0, 1.0)), state(leaving));
+        // Entities already moving back inside keep their velocity
+        assert_eq!(
+            (Vec2::new(-95.0, 0.0), Vec2::new(2.0, 0.0)),
+            state(returning)
+        );
+        assert_eq!((Vec2::new(20.0, 0.0), Vec2::new(2.0, 0.0)), state(inside));
+        assert_eq!((Vec2::new(-95.0, 0.0), Vec2::new(1.0, 1.0)), state(damped));
+    }
+
+    #[test]
+    fn snapped_bodies_are_not_interpolated() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .add_event::<PhysicsTick>()
+            .init_resource::<IntegrationMethod>()
+            .add_systems(Update, (physics_clock, apply_velocity).chain());
+        let mut spawn = |snapped: bool| {
+            let mut body = app.world_mut().spawn((
+                PhysicsPosition::new(Vec2::ZERO),
+                Velocity::new_2d(5.0, 0.0),
+                Transform::default(),
+            ));
+            if snapped {
+                body.insert(NoInterpolation);
+            }
+            body.id()
+        };
+        let (smooth, snapped) = (spawn(false), spawn(true));
+
+        // One tick moves both bodies, the next frame is in the middle of a tick
+        for delta in [33, 16] {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(delta));
+            app.update();
+        }
+
+        let x = |body: Entity| app.world().get::<Transform>(body).unwrap().translation.x;
+        let end_frame = app
+            .world()
+            .get::<PhysicsPosition>(snapped)
+            .unwrap()
+            .end_frame;
+        assert_eq!(end_frame.x, x(snapped));
+        assert!(x(smooth) < end_frame.x);
+    }
+}
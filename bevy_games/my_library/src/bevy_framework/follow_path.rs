@@ -0,0 +1,145 @@
+//! Scripted movement along waypoints, e.g. for patrols or cutscenes
+
+use super::{PhysicsPosition, PhysicsTick};
+use bevy::prelude::*;
+
+/// Moves an entity along a list of waypoints on each physics tick. At the
+/// last waypoint, it either starts over with the first one or stops.
+///
+/// The movement is applied to the entity's [`PhysicsPosition`], so it
+/// should not have a `Velocity` as well.
+#[derive(Component, Debug, Clone)]
+pub struct FollowPath {
+    /// The waypoints in world space
+    pub points: Vec<Vec2>,
+    /// Distance moved on each physics tick
+    pub speed: f32,
+    /// Should the entity return to the first waypoint after the last one?
+    pub looping: bool,
+    /// Index of the waypoint the entity is moving to
+    next: usize,
+}
+
+impl FollowPath {
+    /// Creates a new path, that stops at the last waypoint
+    pub fn new(points: Vec<Vec2>, speed: f32) -> Self {
+        Self {
+            points,
+            speed,
+            looping: false,
+            next: 0,
+        }
+    }
+
+    /// Returns to the first waypoint after the last one
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Index of the waypoint the entity is moving to, None once it stopped
+    /// at the last one
+    pub fn next_waypoint(&self) -> Option<usize> {
+        (self.next < self.points.len()).then_some(self.next)
+    }
+
+    /// Moves a position along the path by one tick. Distance left after
+    /// reaching a waypoint is used to move on to the next one.
+    fn advance(&mut self, mut position: Vec2) -> Vec2 {
+        let mut distance = self.speed;
+        // Each waypoint is reached at most once per tick, so paths of
+        // identical points can't loop forever
+        for _ in 0..self.points.len() {
+            let Some(next) = self.next_waypoint() else {
+                break;
+            };
+            let target = self.points[next];
+            let to_target = position.distance(target);
+            if to_target > distance {
+                return position.move_towards(target, distance);
+            }
+
+            position = target;
+            distance -= to_target;
+            self.next += 1;
+            if self.looping && self.next == self.points.len() {
+                self.next = 0;
+            }
+        }
+        position
+    }
+}
+
+/// System moving entities with a [`FollowPath`] towards their next waypoint
+/// on each physics tick
+pub fn follow_path(
+    mut tick: EventReader<PhysicsTick>,
+    mut query: Query<(&mut FollowPath, &mut PhysicsPosition)>,
+) {
+    for _tick in tick.read() {
+        for (mut path, mut position) in query.iter_mut() {
+            position.end_frame = path.advance(position.end_frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs the path for a number of ticks, returns the position and the
+    /// next waypoint after each
+    fn run_ticks(path: FollowPath, ticks: usize) -> Vec<(Vec2, Option<usize>)> {
+        let mut app = App::new();
+        app.add_event::<PhysicsTick>()
+            .add_systems(Update, follow_path);
+        let entity = app
+            .world_mut()
+            .spawn((path, PhysicsPosition::new(Vec2::ZERO)))
+            .id();
+
+        (0..ticks)
+            .map(|_| {
+                app.world_mut().send_event(PhysicsTick);
+                app.update();
+                let world = app.world();
+                (
+                    world.get::<PhysicsPosition>(entity).unwrap().end_frame,
+                    world.get::<FollowPath>(entity).unwrap().next_waypoint(),
+                )
+            })
+            .collect()
+    }
+
+    fn square() -> Vec<Vec2> {
+        vec![
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+            Vec2::ZERO,
+        ]
+    }
+
+    #[test]
+    fn waypoints_are_reached_in_order() {
+        let ticks = run_ticks(FollowPath::new(square(), 4.0), 12);
+        let mut waypoints: Vec<Option<usize>> = ticks.iter().map(|(_, next)| *next).collect();
+        waypoints.dedup();
+        assert_eq!(vec![Some(0), Some(1), Some(2), Some(3), None], waypoints);
+
+        // Leftover distance carries over to the next waypoint
+        assert_eq!(Vec2::new(10.0, 2.0), ticks[2].0);
+        assert_eq!(Vec2::new(10.0, 10.0), ticks[4].0);
+        // Stops at the last waypoint
+        assert!(ticks[9..].iter().all(|(p, _)| *p == Vec2::ZERO));
+    }
+
+    #[test]
+    fn looping_path_starts_over() {
+        let ticks = run_ticks(FollowPath::new(square(), 5.0).looping(), 12);
+        // One lap takes eight ticks
+        assert_eq!((Vec2::ZERO, Some(0)), ticks[7]);
+        assert_eq!(Vec2::new(5.0, 0.0), ticks[8].0);
+        assert_eq!((Vec2::new(10.0, 10.0), Some(2)), ticks[11]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Resetting gameplay resources when a new game starts
+
+use bevy::{
+    ecs::event::EventCursor,
+    prelude::*,
+    state::state::{StateTransition, StateTransitionSteps},
+};
+
+/// Event to reset all gameplay resources, e.g. for a restart button. The
+/// [`GameResetPlugin`], added by [`GameStatePlugin`](crate::GameStatePlugin),
+/// sends it whenever a game starts.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameReset;
+
+/// A callback resetting some part of the world
+type ResetHook = Box<dyn Fn(&mut World) + Send + Sync>;
+
+/// Resource holding the callbacks registered with [`GameResetExt`]
+#[derive(Resource, Default)]
+pub struct GameResetHooks {
+    hooks: Vec<ResetHook>,
+    /// The [`GameReset`] events handled so far. Shared by all instances of
+    /// [`run_game_reset`], so each event resets once.
+    cursor: EventCursor<GameReset>,
+}
+
+impl GameResetHooks {
+    /// Number of registered callbacks
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Checks if no callbacks are registered
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+/// System set calling the callbacks when a game starts. It runs in the
+/// state transition, before the `OnEnter` systems of the starting state, so
+/// their setup sees the resources already reset.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameResetSet;
+
+/// Plugin resetting the game whenever `start_state` is entered, and after
+/// each [`GameReset`] sent by the game
+pub struct GameResetPlugin<S: States> {
+    start_state: S,
+}
+
+impl<S: States> GameResetPlugin<S> {
+    /// Resets the game when entering `start_state`
+    pub fn new(start_state: S) -> Self {
+        Self { start_state }
+    }
+}
+
+impl<S: States> Plugin for GameResetPlugin<S> {
+    fn build(&self, app: &mut App) {
+        let start = self.start_state.clone();
+        let entering_start = move |mut transitions: EventReader<StateTransitionEvent<S>>| {
+            transitions
+                .read()
+                .any(|transition| transition.entered.as_ref() == Some(&start))
+        };
+        app.add_event::<GameReset>()
+            .init_resource::<GameResetHooks>()
+            .configure_sets(
+                StateTransition,
+                GameResetSet
+                    .after(StateTransitionSteps::TransitionSchedules)
+                    .before(StateTransitionSteps::EnterSchedules),
+            )
+            .add_systems(
+                StateTransition,
+                (send_game_reset.run_if(entering_start), run_game_reset)
+                    .chain()
+                    .in_set(GameResetSet),
+            )
+            .add_systems(Update, run_game_reset);
+    }
+}
+
+/// Registers what to reset on a [`GameReset`]
+pub trait GameResetExt {
+    /// Calls `reset` with the world whenever the game is reset, e.g. to
+    /// reseed the random number generator
+    fn on_game_reset(&mut self, reset: impl Fn(&mut World) + Send + Sync + 'static) -> &mut Self;
+
+    /// Replaces the resource by its default whenever the game is reset
+    fn reset_resource<R: Resource + Default>(&mut self) -> &mut Self {
+        self.on_game_reset(|world| world.insert_resource(R::default()))
+    }
+}
+
+impl GameResetExt for App {
+    fn on_game_reset(&mut self, reset: impl Fn(&mut World) + Send + Sync + 'static) -> &mut Self {
+        self.add_event::<GameReset>();
+        self.world_mut()
+            .get_resource_or_init::<GameResetHooks>()
+            .hooks
+            .push(Box::new(reset));
+        self
+    }
+}
+
+/// Sends a [`GameReset`], e.g. when the player clicks a restart button
+pub fn send_game_reset(mut reset: EventWriter<GameReset>) {
+    reset.write(GameReset);
+}
+
+/// System calling all registered callbacks once, if any [`GameReset`] was
+/// sent since the last reset. The events are left for other readers.
+pub fn run_game_reset(world: &mut World) {
+    world.try_resource_scope(|world, mut hooks: Mut<GameResetHooks>| {
+        let Some(events) = world.get_resource::<Events<GameReset>>() else {
+            return;
+        };
+        if hooks.cursor.read(events).count() == 0 {
+            return;
+        }
+        for hook in hooks.hooks.iter() {
+            hook(world);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::state::app::StatesPlugin;
+
+    #[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    enum Phase {
+        #[default]
+        Menu,
+        Playing,
+    }
+
+    #[derive(Resource, Default, Debug, PartialEq)]
+    struct Score(u32);
+
+    #[derive(Resource, Default)]
+    struct Reseeds(u32);
+
+    #[test]
+    fn reset_runs_each_callback_once() {
+        let mut app = App::new();
+        app.insert_resource(Score(42))
+            .init_resource::<Reseeds>()
+            .reset_resource::<Score>()
+            .on_game_reset(|world| world.resource_mut::<Reseeds>().0 += 1)
+            .add_systems(Update, run_game_reset);
+        assert_eq!(2, app.world().resource::<GameResetHooks>().len());
+
+        app.update();
+        assert_eq!(Score(42), *app.world().resource::<Score>());
+
+        // Several events in one frame reset once
+        app.world_mut().send_event(GameReset);
+        app.world_mut().send_event(GameReset);
+        app.update();
+        assert_eq!(Score(0), *app.world().resource::<Score>());
+        assert_eq!(1, app.world().resource::<Reseeds>().0);
+
+        app.world_mut().resource_mut::<Score>().0 = 7;
+        app.update();
+        assert_eq!(Score(7), *app.world().resource::<Score>());
+        assert_eq!(1, app.world().resource::<Reseeds>().0);
+    }
+
+    /// The score the game's setup found when entering the state
+    #[derive(Resource, Default)]
+    struct SetupSaw(Option<u32>);
+
+    /// [`GameReset`] events seen by the game itself
+    #[derive(Resource, Default)]
+    struct ResetsSeen(usize);
+
+    #[test]
+    fn starting_a_game_resets_before_its_setup() {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .init_state::<Phase>()
+            .add_plugins(GameResetPlugin::new(Phase::Playing))
+            .insert_resource(Score(42))
+            .init_resource::<SetupSaw>()
+            .init_resource::<ResetsSeen>()
+            .reset_resource::<Score>()
+            .add_systems(
+                OnEnter(Phase::Playing),
+                |score: Res<Score>, mut saw: ResMut<SetupSaw>| saw.0 = Some(score.0),
+            )
+            .add_systems(
+                Update,
+                |mut resets: EventReader<GameReset>, mut seen: ResMut<ResetsSeen>| {
+                    seen.0 += resets.read().count();
+                },
+            );
+        app.update();
+        assert_eq!(None, app.world().resource::<SetupSaw>().0);
+
+        app.world_mut()
+            .resource_mut::<NextState<Phase>>()
+            .set(Phase::Playing);
+        app.update();
+        assert_eq!(Some(0), app.world().resource::<SetupSaw>().0);
+        // The game still gets to read the event
+        assert_eq!(1, app.world().resource::<ResetsSeen>().0);
+
+        // Later frames neither reset again, nor repeat the event
+        app.world_mut().resource_mut::<Score>().0 = 7;
+        app.update();
+        app.update();
+        assert_eq!(Score(7), *app.world().resource::<Score>());
+        assert_eq!(1, app.world().resource::<ResetsSeen>().0);
+
+        // A restart button resets once, too
+        app.world_mut().send_event(GameReset);
+        app.update();
+        assert_eq!(Score(0), *app.world().resource::<Score>());
+        assert_eq!(2, app.world().resource::<ResetsSeen>().0);
+    }
+}
@@ -0,0 +1,84 @@
+//! Convert between world coordinates and tiles of a grid centered on the origin
+
+use bevy::prelude::*;
+
+/// Describes a grid of square tiles that is centered on the world origin.
+/// Tile (0, 0) is in the bottom-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridCoords {
+    /// Width and height of each tile in world units
+    pub tile_size: f32,
+    /// Number of tiles in each row
+    pub width: usize,
+    /// Number of tiles in each column
+    pub height: usize,
+}
+
+impl GridCoords {
+    /// Creates a new grid definition
+    pub fn new(tile_size: f32, width: usize, height: usize) -> Self {
+        Self {
+            tile_size,
+            width,
+            height,
+        }
+    }
+
+    /// World position of the bottom-left corner of the grid
+    fn offset(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32) / 2.0 * self.tile_size
+    }
+
+    /// Finds the tile containing a world position. None if the position is
+    /// outside of the grid.
+    pub fn world_to_tile(&self, position: Vec2) -> Option<(usize, usize)> {
+        let tile = ((position + self.offset()) / self.tile_size).floor();
+        if tile.x < 0.0 || tile.y < 0.0 {
+            return None;
+        }
+        let (x, y) = (tile.x as usize, tile.y as usize);
+        (x < self.width && y < self.height).then_some((x, y))
+    }
+
+    /// Calculates the world position of a tile's center
+    pub fn tile_to_world(&self, (x, y): (usize, usize)) -> Vec2 {
+        (Vec2::new(x as f32, y as f32) + 0.5) * self.tile_size - self.offset()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn center_and_corner_tiles_round_trip() {
+        let grid = GridCoords::new(24.0, 200, 100);
+        for tile in [(100, 50), (0, 0), (199, 0), (0, 99), (199, 99)] {
+            assert_eq!(Some(tile), grid.world_to_tile(grid.tile_to_world(tile)));
+        }
+        assert_eq!(Vec2::new(12.0, 12.0), grid.tile_to_world((100, 50)));
+        assert_eq!(Vec2::new(-2388.0, -1188.0), grid.tile_to_world((0, 0)));
+    }
+
+    #[test]
+    fn tile_edges_belong_to_the_upper_tile() {
+        let grid = GridCoords::new(24.0, 200, 100);
+        assert_eq!(Some((100, 50)), grid.world_to_tile(Vec2::ZERO));
+        assert_eq!(
+            Some((0, 0)),
+            grid.world_to_tile(Vec2::new(-2400.0, -1200.0))
+        );
+        assert_eq!(
+            Some((199, 99)),
+            grid.world_to_tile(Vec2::new(2399.9, 1199.9))
+        );
+    }
+
+    #[test]
+    fn positions_outside_of_grid_have_no_tile() {
+        let grid = GridCoords::new(24.0, 200, 100);
+        assert_eq!(None, grid.world_to_tile(Vec2::new(-2400.1, 0.0)));
+        assert_eq!(None, grid.world_to_tile(Vec2::new(0.0, 1200.0)));
+        assert_eq!(None, grid.world_to_tile(Vec2::new(2400.0, 0.0)));
+    }
+}
@@ -0,0 +1,49 @@
+//! Entities that only exist for a limited time
+
+use bevy::prelude::*;
+
+/// Despawns the entity after a number of seconds, e.g. for tracers or
+/// popups. The value is the remaining time in seconds.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Lifetime(pub f32);
+
+/// System counting down the remaining lifetime of entities, and despawning
+/// them once it reaches zero
+pub fn tick_lifetime(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Lifetime)>,
+) {
+    for (entity, mut lifetime) in query.iter_mut() {
+        lifetime.0 -= time.delta_secs();
+        if lifetime.0 <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn entity_is_despawned_after_lifetime() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_systems(Update, tick_lifetime);
+        let entity = app.world_mut().spawn(Lifetime(0.5)).id();
+        let advance = |app: &mut App, millis| {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(millis));
+            app.update();
+        };
+
+        advance(&mut app, 250);
+        assert_eq!(Some(&Lifetime(0.25)), app.world().get::<Lifetime>(entity));
+
+        advance(&mut app, 250);
+        assert!(app.world().get_entity(entity).is_err());
+    }
+}
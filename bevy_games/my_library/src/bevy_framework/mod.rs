@@ -13,6 +13,45 @@ pub use bevy_physics::*;
 mod bevy_collision;
 pub use bevy_collision::*;
 
+mod bevy_effects;
+pub use bevy_effects::*;
+
+mod bevy_particles;
+pub use bevy_particles::*;
+
+mod bevy_camera;
+pub use bevy_camera::*;
+
+mod background_task;
+pub use background_task::*;
+
+mod grid;
+pub use grid::*;
+
+mod tilemap;
+pub use tilemap::*;
+
+mod status_bar;
+pub use status_bar::*;
+
+mod lifetime;
+pub use lifetime::*;
+
+mod trail;
+pub use trail::*;
+
+mod follow_path;
+pub use follow_path::*;
+
+mod score_clock;
+pub use score_clock::*;
+
+mod game_reset;
+pub use game_reset::*;
+
+mod spawn_on_enter;
+pub use spawn_on_enter::*;
+
 /// This plugin provides game state handling. It requires an enumeration of
 /// known game states.
 ///
@@ -27,6 +66,9 @@ pub struct GameStatePlugin<T> {
 
     /// This state shows the game-over screen
     game_end_state: T,
+
+    /// Scale of the egui menus and overlays
+    ui_scale: f32,
 }
 
 impl<T> GameStatePlugin<T> {
@@ -36,18 +78,33 @@ impl<T> GameStatePlugin<T> {
             menu_state,
             game_start_state,
             game_end_state,
+            ui_scale: 1.0,
         }
     }
+
+    /// Scales the egui menus and overlays, e.g. to 1.5 for larger text
+    pub fn with_ui_scale(mut self, scale: f32) -> Self {
+        self.ui_scale = scale;
+        self
+    }
 }
 
 impl<T: States + Copy + FromWorld + FreelyMutableState + Default> Plugin for GameStatePlugin<T> {
     fn build(&self, app: &mut bevy::app::App) {
         app.init_state::<T>();
-        app.add_event::<PhysicsTick>().add_event::<Impulse>();
+        app.add_event::<PhysicsTick>()
+            .add_event::<Impulse>()
+            .add_event::<Flash>()
+            .add_event::<AnimationFinished>()
+            .init_resource::<IntegrationMethod>()
+            .init_resource::<MaxPhysicsDelta>()
+            .init_resource::<PhysicsPaused>()
+            .init_resource::<Gravity>();
 
         app.add_plugins(bevy_egui::EguiPlugin {
             enable_multipass_for_primary_context: false,
         });
+        app.add_plugins(crate::egui::EguiScalePlugin(self.ui_scale));
         let start = MenuResource {
             menu_state: self.menu_state,
             game_start_state: self.game_start_state,
@@ -65,12 +122,21 @@ impl<T: States + Copy + FromWorld + FreelyMutableState + Default> Plugin for Gam
             run => [ game_menus::run::<T> ],
             exit => [ cleanup::<game_menus::MenuElement> ]);
 
+        // Games register what to reset through `GameResetExt`
+        app.add_plugins(GameResetPlugin::new(self.game_start_state));
+
         app.add_systems(OnEnter(T::default()), crate::bevy_assets::setup)
             .add_systems(
                 Update,
                 crate::bevy_assets::run::<T>.run_if(in_state(T::default())),
             )
             .add_systems(OnExit(T::default()), crate::bevy_assets::exit);
+
+        #[cfg(feature = "dev")]
+        app.add_systems(
+            Update,
+            crate::bevy_assets::skip::<T>.run_if(in_state(T::default())),
+        );
     }
 }
 
@@ -78,6 +144,15 @@ impl<T: States + Copy + FromWorld + FreelyMutableState + Default> Plugin for Gam
 /// game state `x` are tagged with a common component `XElement`, the state can be
 /// cleaned up by `cleanup::<XElement>`  
 pub fn cleanup<T>(query: Query<Entity, With<T>>, mut commands: Commands)
+where
+    T: Component,
+{
+    despawn_tagged(&mut commands, &query);
+}
+
+/// Despawns all entities tagged with a given component from within another
+/// system, e.g. to clear a hand of cards without scheduling [`cleanup`]
+pub fn despawn_tagged<T>(commands: &mut Commands, query: &Query<Entity, With<T>>)
 where
     T: Component,
 {
@@ -86,6 +161,34 @@ where
         .for_each(|entity| commands.entity(entity).despawn())
 }
 
+/// Despawns all but the entity that got component `T` first and warns about
+/// it, e.g. to defend against a second camera or player being spawned by
+/// mistake. Systems using `single()` on `T` then keep working. Of entities
+/// that got `T` in the same tick, the one with the lowest id is kept.
+pub fn ensure_singleton<T>(query: Query<(Entity, Ref<T>)>, mut commands: Commands)
+where
+    T: Component,
+{
+    let Some(keep) = query
+        .iter()
+        .min_by_key(|(entity, component)| (component.added().get(), *entity))
+        .map(|(entity, _)| entity)
+    else {
+        return;
+    };
+    let extra = query.iter().count() - 1;
+    if extra > 0 {
+        warn!(
+            "Despawning {extra} extra entities with {}",
+            std::any::type_name::<T>()
+        );
+        query
+            .iter()
+            .filter(|(entity, _)| *entity != keep)
+            .for_each(|(entity, _)| commands.entity(entity).despawn());
+    }
+}
+
 #[derive(Resource)]
 pub(crate) struct MenuResource<T> {
     pub(crate) menu_state: T,
@@ -95,6 +198,10 @@ pub(crate) struct MenuResource<T> {
 
 /// The `add_phase!`-macro lets you specify which systems are used for a
 /// sepcific game phase.
+///
+/// The optional `transition` section runs systems only when leaving the
+/// phase for a specific other phase, e.g.
+/// `transition => [ GamePhase::GameOver => [play_stinger] ]`.
 #[macro_export]
 macro_rules! add_phase {
     (
@@ -102,9 +209,133 @@ macro_rules! add_phase {
         start => [ $($start:expr),*],
         run => [ $($run:expr),*],
         exit => [ $($exit:expr),*]
+    ) => {
+        $crate::add_phase!($app, $type, $phase,
+            start => [ $($start),* ],
+            run => [ $($run),* ],
+            exit => [ $($exit),* ],
+            transition => []
+        );
+    };
+    (
+        $app:expr, $type:ty, $phase:expr,
+        start => [ $($start:expr),*],
+        run => [ $($run:expr),*],
+        exit => [ $($exit:expr),*],
+        transition => [ $($to:expr => [ $($transition:expr),* ]),* ]
     ) => {
         $($app.add_systems(bevy::prelude::OnEnter::<$type>($phase), $start);)*
         $($app.add_systems(bevy::prelude::OnExit::<$type>($phase), $exit);)*
         $($app.add_systems(bevy::prelude::Update, $run.run_if(in_state($phase)));)*
+        $($(
+            $app.add_systems(
+                bevy::prelude::OnTransition::<$type> { exited: $phase, entered: $to },
+                $transition,
+            );
+        )*)*
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::{despawn_tagged, ensure_singleton};
+    use bevy::{prelude::*, state::app::StatesPlugin};
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
+    enum Phase {
+        #[default]
+        Menu,
+        Playing,
+        GameOver,
+    }
+
+    #[derive(Resource, Default)]
+    struct Stingers(usize);
+
+    fn play_stinger(mut stingers: ResMut<Stingers>) {
+        stingers.0 += 1;
+    }
+
+    fn go_to(app: &mut App, phase: Phase) {
+        app.world_mut()
+            .resource_mut::<NextState<Phase>>()
+            .set(phase);
+        app.update();
+    }
+
+    #[test]
+    fn transition_systems_run_on_their_edge_only() {
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .init_state::<Phase>()
+            .init_resource::<Stingers>();
+        add_phase!(app, Phase, Phase::Playing,
+            start => [],
+            run => [],
+            exit => [],
+            transition => [ Phase::GameOver => [play_stinger] ]
+        );
+        let stingers = |app: &App| app.world().resource::<Stingers>().0;
+
+        go_to(&mut app, Phase::Playing);
+        go_to(&mut app, Phase::Menu);
+        assert_eq!(0, stingers(&app));
+
+        go_to(&mut app, Phase::Playing);
+        go_to(&mut app, Phase::GameOver);
+        assert_eq!(1, stingers(&app));
+
+        go_to(&mut app, Phase::Menu);
+        go_to(&mut app, Phase::GameOver);
+        assert_eq!(1, stingers(&app));
+    }
+
+    #[derive(Component)]
+    struct Tagged;
+
+    #[test]
+    fn despawn_tagged_keeps_other_entities() {
+        let mut app = App::new();
+        app.add_systems(
+            Update,
+            |mut commands: Commands, query: Query<Entity, With<Tagged>>| {
+                despawn_tagged(&mut commands, &query);
+            },
+        );
+        let tagged: Vec<Entity> = (0..3)
+            .map(|_| app.world_mut().spawn((Tagged, Transform::default())).id())
+            .collect();
+        let others: Vec<Entity> = (0..2)
+            .map(|_| app.world_mut().spawn(Transform::default()).id())
+            .collect();
+        app.update();
+
+        assert!(tagged.iter().all(|e| app.world().get_entity(*e).is_err()));
+        assert!(others.iter().all(|e| app.world().get_entity(*e).is_ok()));
+    }
+
+    #[test]
+    fn ensure_singleton_leaves_exactly_one() {
+        let mut app = App::new();
+        app.add_systems(Update, ensure_singleton::<Tagged>);
+        // Spawned first, so it has the lowest id
+        let late = app.world_mut().spawn(Transform::default()).id();
+        let tagged: Vec<Entity> = (0..3).map(|_| app.world_mut().spawn(Tagged).id()).collect();
+        app.update();
+
+        let left: Vec<Entity> = app
+            .world_mut()
+            .query_filtered::<Entity, With<Tagged>>()
+            .iter(app.world())
+            .collect();
+        assert_eq!(vec![tagged[0]], left);
+        assert!(app.world().get_entity(late).is_ok());
+
+        // The entity with the lowest id got the component last, so it goes
+        assert!(late < tagged[0]);
+        app.world_mut().entity_mut(late).insert(Tagged);
+        app.update();
+        assert!(app.world().get_entity(tagged[0]).is_ok());
+        assert!(app.world().get_entity(late).is_err());
+    }
+}
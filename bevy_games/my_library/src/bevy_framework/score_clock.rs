@@ -0,0 +1,72 @@
+//! Scores that grow with the time survived
+
+use bevy::prelude::*;
+
+/// Accumulates a score over time, e.g. for games rewarding survival. Add
+/// [`score_clock`] to the systems of the gameplay state, and read `total`
+/// for display or submission.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct ScoreClock {
+    /// Points earned per second of play
+    pub points_per_second: f32,
+    /// Points earned so far
+    pub total: f32,
+}
+
+impl ScoreClock {
+    /// Creates a clock earning `points_per_second`, starting at zero
+    pub fn new(points_per_second: f32) -> Self {
+        Self {
+            points_per_second,
+            total: 0.0,
+        }
+    }
+
+    /// Starts over at zero, e.g. for a new game
+    pub fn reset(&mut self) {
+        self.total = 0.0;
+    }
+
+    /// Points earned so far, rounded down to whole points
+    pub fn points(&self) -> u32 {
+        self.total as u32
+    }
+}
+
+/// System adding the points earned since the last frame. It follows the game
+/// time, so no points are earned while the game is paused.
+pub fn score_clock(time: Res<Time>, mut clock: ResMut<ScoreClock>) {
+    clock.total += clock.points_per_second * time.delta_secs();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn score_grows_with_time() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(ScoreClock::new(2.5))
+            .add_systems(Update, score_clock);
+
+        // Ten seconds at 60 frames per second
+        for _ in 0..600 {
+            app.world_mut()
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(1.0 / 60.0));
+            app.update();
+        }
+        let clock = app.world().resource::<ScoreClock>();
+        assert!((clock.total - 25.0).abs() < 0.01, "{}", clock.total);
+        assert_eq!(
+            24,
+            ScoreClock {
+                total: 24.99,
+                ..*clock
+            }
+            .points()
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Declares entities to spawn whenever a state is entered
+
+use crate::AssetHandles;
+use bevy::prelude::*;
+use std::sync::Arc;
+
+/// Inserts the components of a declared entity
+type Insert = Arc<dyn Fn(&mut EntityCommands) + Send + Sync>;
+
+/// An image to spawn, with its position and extra components
+#[derive(Clone)]
+struct Entry {
+    tag: String,
+    position: Vec3,
+    insert: Insert,
+}
+
+/// Plugin spawning a list of images with their components whenever a state
+/// is entered, instead of spawning them in the phase's `start` system.
+/// Tag the entities with the phase's element component, so the `exit`
+/// cleanup of [`add_phase!`](crate::add_phase) despawns them again.
+///
+/// ```ignore
+/// app.add_plugins(
+///     SpawnOnEnter::new(GamePhase::Flapping)
+///         .spawn("bg_static", Vec3::new(0.0, 0.0, 1.0), FlappyElement),
+/// );
+/// ```
+pub struct SpawnOnEnter<S: States> {
+    state: S,
+    entries: Vec<Entry>,
+}
+
+impl<S: States> SpawnOnEnter<S> {
+    /// Creates an empty list of entities to spawn when entering `state`
+    pub fn new(state: S) -> Self {
+        Self {
+            state,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Spawns the image with tag `tag` at `position`, together with a clone
+    /// of `components`
+    pub fn spawn<B: Bundle + Clone>(mut self, tag: &str, position: Vec3, components: B) -> Self {
+        self.entries.push(Entry {
+            tag: tag.to_string(),
+            position,
+            insert: Arc::new(move |entity| {
+                entity.insert(components.clone());
+            }),
+        });
+        self
+    }
+
+    /// Number of declared entities
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if no entities are declared
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<S: States> Plugin for SpawnOnEnter<S> {
+    fn build(&self, app: &mut App) {
+        let entries = self.entries.clone();
+        app.add_systems(
+            OnEnter(self.state.clone()),
+            move |mut commands: Commands, assets: AssetHandles| {
+                for entry in entries.iter() {
+                    let mut entity = commands.spawn((
+                        Sprite::from_image(assets.image(&entry.tag)),
+                        Transform::from_translation(entry.position),
+                    ));
+                    (entry.insert)(&mut entity);
+                }
+            },
+        );
+    }
+
+    // Games may declare several lists, e.g. one per state
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AssetStore, LoadedAssets};
+    use bevy::state::app::StatesPlugin;
+
+    #[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    enum Phase {
+        #[default]
+        Menu,
+        Playing,
+    }
+
+    #[derive(Component, Clone)]
+    struct Element;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Speed(f32);
+
+    #[test]
+    fn entering_the_state_spawns_the_declared_entities() {
+        let mut loaded = LoadedAssets::default();
+        let mut store = AssetStore::default();
+        let mut images = Assets::<Image>::default();
+        let ship = images.add(Image::default());
+        store.insert_handle("ship", ship.clone().untyped(), &mut loaded);
+        store.insert_handle("rock", images.add(Image::default()).untyped(), &mut loaded);
+
+        let spawner = SpawnOnEnter::new(Phase::Playing)
+            .spawn("ship", Vec3::new(1.0, 2.0, 3.0), (Element, Speed(4.0)))
+            .spawn("rock", Vec3::ZERO, Element);
+        assert_eq!(2, spawner.len());
+        let mut app = App::new();
+        app.add_plugins(StatesPlugin)
+            .init_state::<Phase>()
+            .insert_resource(store)
+            .insert_resource(loaded)
+            .add_plugins(spawner)
+            .add_plugins(SpawnOnEnter::new(Phase::Menu));
+        app.update();
+        let mut elements = app.world_mut().query_filtered::<(), With<Element>>();
+        assert_eq!(0, elements.iter(app.world()).count());
+
+        app.world_mut()
+            .resource_mut::<NextState<Phase>>()
+            .set(Phase::Playing);
+        app.update();
+        assert_eq!(2, elements.iter(app.world()).count());
+        let (sprite, transform, speed) = app
+            .world_mut()
+            .query::<(&Sprite, &Transform, &Speed)>()
+            .single(app.world())
+            .unwrap();
+        assert_eq!(ship, sprite.image);
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), transform.translation);
+        assert_eq!(Speed(4.0), *speed);
+    }
+}
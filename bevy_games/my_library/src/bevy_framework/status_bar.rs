@@ -0,0 +1,248 @@
+//! Bars showing a value like health or fuel above an entity
+
+use bevy::{prelude::*, sprite::Anchor};
+
+/// How far above its entity a bar is drawn
+const BAR_Z: f32 = 1.0;
+
+/// The value shown by a [`StatusBar`], from 0 (empty) to 1 (full). Game
+/// code sets it, e.g. from the current health.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct BarValue(pub f32);
+
+impl Default for BarValue {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Shows the entity's [`BarValue`] as a bar, that follows the entity without
+/// rotating or scaling with it. The bar consists of two child sprites: the
+/// background and the fill in front of it.
+///
+/// The entity must not have a parent, as the bar is placed relative to its
+/// `Transform`.
+#[derive(Component, Debug, Clone)]
+#[require(BarValue)]
+pub struct StatusBar {
+    /// Width and height of the bar in world units
+    pub size: Vec2,
+    /// Color of the filled part
+    pub fill: Color,
+    /// Color of the empty part
+    pub background: Color,
+    /// Position of the bar's center relative to the entity in world space
+    pub offset: Vec2,
+    /// Should the bar be hidden while the value is full?
+    pub hide_when_full: bool,
+    /// The background and fill sprites, once spawned
+    parts: Option<(Entity, Entity)>,
+}
+
+/// Marks the sprites of a [`StatusBar`]
+#[derive(Component)]
+pub struct StatusBarPart;
+
+/// Query for the sprites of status bars
+type BarParts<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut Visibility),
+    (With<StatusBarPart>, Without<StatusBar>),
+>;
+
+impl StatusBar {
+    /// Creates a new bar with a size and fill color on a dark background,
+    /// drawn `offset` away from the entity
+    pub fn new(size: Vec2, fill: Color, offset: Vec2) -> Self {
+        Self {
+            size,
+            fill,
+            background: Color::srgba(0.0, 0.0, 0.0, 0.6),
+            offset,
+            hide_when_full: false,
+            parts: None,
+        }
+    }
+
+    /// Sets the color of the empty part
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Hides the bar while the value is full
+    pub fn hidden_when_full(mut self) -> Self {
+        self.hide_when_full = true;
+        self
+    }
+
+    /// Calculates the local transforms of the background and the fill,
+    /// cancelling out the rotation and scale of the entity
+    fn part_transforms(&self, parent: &Transform, value: f32) -> (Transform, Transform) {
+        let center = parent.translation + self.offset.extend(BAR_Z);
+        let background = Transform::from_translation(center);
+        // The fill starts at the left edge, slightly in front of the background
+        let fill = Transform::from_translation(center + Vec3::new(-self.size.x / 2.0, 0.0, 0.01))
+            .with_scale(Vec3::new(value, 1.0, 1.0));
+
+        let to_local = parent.compute_matrix().inverse();
+        (
+            Transform::from_matrix(to_local * background.compute_matrix()),
+            Transform::from_matrix(to_local * fill.compute_matrix()),
+        )
+    }
+
+    /// Checks if the bar is visible for a value
+    fn visibility(&self, value: f32) -> Visibility {
+        if self.hide_when_full && value >= 1.0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        }
+    }
+}
+
+/// System that spawns the sprites of new status bars and updates them to
+/// the current value and position of their entity. The sprites are children
+/// of the entity, so they are despawned with it.
+pub fn status_bars(
+    mut commands: Commands,
+    mut bars: Query<(Entity, &mut StatusBar, &BarValue, &Transform)>,
+    mut parts: BarParts,
+) {
+    for (entity, mut bar, value, transform) in bars.iter_mut() {
+        let value = value.0.clamp(0.0, 1.0);
+        let (background, fill) = bar.part_transforms(transform, value);
+        let visibility = bar.visibility(value);
+
+        match bar.parts {
+            Some(ids) => {
+                for (id, part_transform) in [(ids.0, background), (ids.1, fill)] {
+                    if let Ok((mut transform, mut part_visibility)) = parts.get_mut(id) {
+                        *transform = part_transform;
+                        part_visibility.set_if_neq(visibility);
+                    }
+                }
+            }
+            None => {
+                let background = commands
+                    .spawn((
+                        Sprite::from_color(bar.background, bar.size),
+                        background,
+                        visibility,
+                        StatusBarPart,
+                        ChildOf(entity),
+                    ))
+                    .id();
+                let fill = commands
+                    .spawn((
+                        Sprite {
+                            anchor: Anchor::CenterLeft,
+                            ..Sprite::from_color(bar.fill, bar.size)
+                        },
+                        fill,
+                        visibility,
+                        StatusBarPart,
+                        ChildOf(entity),
+                    ))
+                    .id();
+                bar.parts = Some((background, fill));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn setup(parent: Transform, value: f32, bar: StatusBar) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(TransformPlugin)
+            .add_systems(Update, status_bars);
+        let entity = app.world_mut().spawn((parent, bar, BarValue(value))).id();
+        app.update();
+        (app, entity)
+    }
+
+    fn parts(app: &App, entity: Entity) -> (GlobalTransform, GlobalTransform) {
+        let (background, fill) = app.world().get::<StatusBar>(entity).unwrap().parts.unwrap();
+        let global = |id| *app.world().get::<GlobalTransform>(id).unwrap();
+        (global(background), global(fill))
+    }
+
+    #[test]
+    fn bar_is_offset_in_world_space() {
+        let parent = Transform::from_xyz(100.0, 50.0, 10.0)
+            .with_rotation(Quat::from_rotation_z(1.0))
+            .with_scale(Vec3::splat(2.0));
+        let bar = StatusBar::new(Vec2::new(40.0, 4.0), Color::WHITE, Vec2::new(0.0, 30.0));
+        let (app, entity) = setup(parent, 0.5, bar);
+
+        let (background, fill) = parts(&app, entity);
+        assert!(
+            background
+                .translation()
+                .abs_diff_eq(Vec3::new(100.0, 80.0, 11.0), 1e-3)
+        );
+        assert!(background.rotation().abs_diff_eq(Quat::IDENTITY, 1e-5));
+        assert!(background.scale().abs_diff_eq(Vec3::ONE, 1e-5));
+        // The fill starts at the left edge of the background
+        assert!(
+            fill.translation()
+                .xy()
+                .abs_diff_eq(Vec2::new(80.0, 80.0), 1e-3)
+        );
+    }
+
+    #[test]
+    fn fill_scales_with_value() {
+        let bar = StatusBar::new(Vec2::new(40.0, 4.0), Color::WHITE, Vec2::ZERO);
+        let (mut app, entity) = setup(Transform::default(), 0.25, bar);
+        assert!((parts(&app, entity).1.scale().x - 0.25).abs() < 1e-5);
+
+        app.world_mut().get_mut::<BarValue>(entity).unwrap().0 = 0.75;
+        app.world_mut()
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation
+            .x = 10.0;
+        app.update();
+        let (background, fill) = parts(&app, entity);
+        assert!((fill.scale().x - 0.75).abs() < 1e-5);
+        assert!((background.translation().x - 10.0).abs() < 1e-3);
+
+        // Values out of range are clamped
+        app.world_mut().get_mut::<BarValue>(entity).unwrap().0 = 1.5;
+        app.update();
+        assert!((parts(&app, entity).1.scale().x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bar_can_hide_when_full() {
+        let bar = StatusBar::new(Vec2::new(40.0, 4.0), Color::WHITE, Vec2::ZERO).hidden_when_full();
+        let (mut app, entity) = setup(Transform::default(), 1.0, bar);
+        let (background, fill) = app.world().get::<StatusBar>(entity).unwrap().parts.unwrap();
+        let visibility = |app: &App, id| *app.world().get::<Visibility>(id).unwrap();
+        assert_eq!(Visibility::Hidden, visibility(&app, background));
+        assert_eq!(Visibility::Hidden, visibility(&app, fill));
+
+        app.world_mut().get_mut::<BarValue>(entity).unwrap().0 = 0.9;
+        app.update();
+        assert_eq!(Visibility::Inherited, visibility(&app, background));
+        assert_eq!(Visibility::Inherited, visibility(&app, fill));
+    }
+
+    #[test]
+    fn bar_is_despawned_with_its_entity() {
+        let bar = StatusBar::new(Vec2::new(40.0, 4.0), Color::WHITE, Vec2::ZERO);
+        let (mut app, entity) = setup(Transform::default(), 0.5, bar);
+        let mut query = app.world_mut().query_filtered::<(), With<StatusBarPart>>();
+        assert_eq!(2, query.iter(app.world()).count());
+
+        app.world_mut().despawn(entity);
+        app.update();
+        assert_eq!(0, query.iter(app.world()).count());
+    }
+}
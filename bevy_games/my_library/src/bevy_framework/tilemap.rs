@@ -0,0 +1,718 @@
+//! Grids of solid and open tiles, rendered as a single mesh or in chunks
+
+use super::GridCoords;
+use crate::{AssetStore, LoadedAssets, RandomNumberGenerator};
+use bevy::{asset::RenderAssetUsages, prelude::*, render::mesh::PrimitiveTopology};
+use std::ops::Range;
+
+/// How the texture is mapped onto the tiles of a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileUv {
+    /// Each tile shows the complete texture
+    PerTile,
+    /// The texture is stretched over the whole grid
+    Stretched,
+}
+
+/// A grid of tiles that are either solid or open. The grid is centered on
+/// the world origin, with tile (0, 0) in the bottom-left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileGrid {
+    /// Number of tiles in each row
+    width: usize,
+    /// Number of tiles in each column
+    height: usize,
+    /// Width and height of each tile in world units
+    tile_size: f32,
+    /// One bit per tile, set if the tile is solid
+    solid: Vec<u64>,
+}
+
+impl TileGrid {
+    /// Creates a new grid with all tiles either solid or open
+    pub fn new(width: usize, height: usize, tile_size: f32, solid: bool) -> Self {
+        let mut grid = Self {
+            width,
+            height,
+            tile_size,
+            solid: vec![0; (width * height).div_ceil(64)],
+        };
+        if solid {
+            // Keep the unused bits of the last word clear, so equal grids compare equal
+            grid.solid.fill(u64::MAX);
+            if let Some(last) = grid.solid.last_mut() {
+                *last >>= (64 - (width * height) % 64) % 64;
+            }
+        }
+        grid
+    }
+
+    /// Number of tiles in each row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of tiles in each column
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Width and height of each tile in world units
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    /// Describes how the tiles are positioned in world coordinates
+    pub fn coords(&self) -> GridCoords {
+        GridCoords::new(self.tile_size, self.width, self.height)
+    }
+
+    /// Calculates the 1d index for a given tile in the 2d matrix
+    fn tile_idx(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "Tile ({x}, {y}) is outside of the grid"
+        );
+        y * self.width + x
+    }
+
+    /// Checks if the tile at a position is solid
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        let idx = self.tile_idx(x, y);
+        self.solid[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    /// Checks if the tile at a world position is solid. Positions outside of
+    /// the grid count as open, like in [`raycast`](Self::raycast).
+    pub fn is_solid_world(&self, position: Vec2) -> bool {
+        self.coords()
+            .world_to_tile(position)
+            .is_some_and(|(x, y)| self.is_solid(x, y))
+    }
+
+    /// Makes the tile at a position solid or open
+    pub fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        let idx = self.tile_idx(x, y);
+        if solid {
+            self.solid[idx / 64] |= 1 << (idx % 64);
+        } else {
+            self.solid[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    /// Number of solid tiles in the grid
+    pub fn solid_count(&self) -> usize {
+        self.tiles().filter(|(x, y)| self.is_solid(*x, *y)).count()
+    }
+
+    /// Picks a random open tile, e.g. to spawn an entity on the floor.
+    /// Returns None if all tiles are solid.
+    pub fn random_open_tile(&self, rng: &mut RandomNumberGenerator) -> Option<(usize, usize)> {
+        let open: Vec<(usize, usize)> = self
+            .tiles()
+            .filter(|(x, y)| !self.is_solid(*x, *y))
+            .collect();
+        if open.is_empty() {
+            None
+        } else {
+            Some(open[rng.range(0..open.len())])
+        }
+    }
+
+    /// Iterates over the positions of all tiles, row by row
+    fn tiles(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+    }
+
+    /// Iterates over the solid tiles that can be touched from the outside:
+    /// tiles on the border of the grid, and tiles next to an open tile.
+    /// Only these need colliders, the others are surrounded by solid tiles.
+    pub fn edge_tiles(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tiles().filter(|&(x, y)| self.is_edge(x, y))
+    }
+
+    /// Checks if a tile is one of the [`edge_tiles`](Self::edge_tiles), e.g.
+    /// to find the tiles that need a collider after opening a tile
+    pub fn is_edge(&self, x: usize, y: usize) -> bool {
+        self.is_solid(x, y)
+            && (x == 0
+                || y == 0
+                || x == self.width - 1
+                || y == self.height - 1
+                || !self.is_solid(x - 1, y)
+                || !self.is_solid(x + 1, y)
+                || !self.is_solid(x, y - 1)
+                || !self.is_solid(x, y + 1))
+    }
+
+    /// Follows a line between two world positions through the grid, and
+    /// returns the first solid tile it crosses, e.g. to check the line of
+    /// sight. Positions outside of the grid count as open.
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<(usize, usize)> {
+        let offset = Vec2::new(self.width as f32, self.height as f32) / 2.0 * self.tile_size;
+        let start = (from + offset) / self.tile_size;
+        let delta = (to + offset) / self.tile_size - start;
+        let mut tile = start.floor();
+
+        // Walk from tile to tile, always crossing the nearest tile border.
+        // `t` is the fraction of the line walked when reaching the next
+        // border of each axis.
+        let axis = |start: f32, tile: f32, delta: f32| {
+            if delta > 0.0 {
+                ((tile + 1.0 - start) / delta, 1.0 / delta, 1.0)
+            } else if delta < 0.0 {
+                ((tile - start) / delta, -1.0 / delta, -1.0)
+            } else {
+                (f32::INFINITY, f32::INFINITY, 0.0)
+            }
+        };
+        let (mut t_x, step_t_x, step_x) = axis(start.x, tile.x, delta.x);
+        let (mut t_y, step_t_y, step_y) = axis(start.y, tile.y, delta.y);
+        loop {
+            if tile.x >= 0.0 && tile.y >= 0.0 {
+                let (x, y) = (tile.x as usize, tile.y as usize);
+                if x < self.width && y < self.height && self.is_solid(x, y) {
+                    return Some((x, y));
+                }
+            }
+            if t_x.min(t_y) > 1.0 {
+                return None;
+            }
+            if t_x < t_y {
+                tile.x += step_x;
+                t_x += step_t_x;
+            } else {
+                tile.y += step_y;
+                t_y += step_t_y;
+            }
+        }
+    }
+
+    /// Builds a mesh with two triangles per solid tile
+    pub fn build_mesh(&self, uv_source: TileUv) -> Mesh {
+        self.build_region_mesh(0..self.width, 0..self.height, uv_source)
+    }
+
+    /// Builds a mesh like [`build_mesh`](Self::build_mesh), with a vertex
+    /// color for each solid tile, e.g. to darken the deeper tiles. A
+    /// `ColorMaterial` tints its texture with the colors.
+    pub fn build_mesh_colored(
+        &self,
+        uv_source: TileUv,
+        color: impl Fn((usize, usize)) -> Color,
+    ) -> Mesh {
+        // Same order as the tiles in the mesh
+        let colors: Vec<[f32; 4]> = self
+            .tiles()
+            .filter(|&(x, y)| self.is_solid(x, y))
+            .flat_map(|tile| [color(tile).to_linear().to_f32_array(); 6])
+            .collect();
+        self.build_mesh(uv_source)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    }
+
+    /// Builds a mesh of the solid tiles in one chunk. The vertices are in
+    /// world units, like those of [`build_mesh`](Self::build_mesh), so the
+    /// chunks of a grid fit together without moving them.
+    pub fn build_chunk_mesh(&self, chunk: TileChunk, uv_source: TileUv) -> Mesh {
+        let (columns, rows) = chunk.tiles(self);
+        self.build_region_mesh(columns, rows, uv_source)
+    }
+
+    /// Builds the meshes of all chunks of `chunk_size` tiles, e.g. in a
+    /// background task before spawning them with [`spawn_tile_chunks`]
+    pub fn build_chunk_meshes(
+        &self,
+        chunk_size: usize,
+        uv_source: TileUv,
+    ) -> Vec<(TileChunk, Mesh)> {
+        self.chunks(chunk_size)
+            .map(|chunk| (chunk, self.build_chunk_mesh(chunk, uv_source)))
+            .collect()
+    }
+
+    /// Iterates over the chunks of `chunk_size` by `chunk_size` tiles that
+    /// cover the grid. Chunks on the right and top border may be smaller.
+    pub fn chunks(&self, chunk_size: usize) -> impl Iterator<Item = TileChunk> + use<> {
+        let columns = self.width.div_ceil(chunk_size);
+        let rows = self.height.div_ceil(chunk_size);
+        (0..rows).flat_map(move |y| {
+            (0..columns).map(move |x| TileChunk {
+                x,
+                y,
+                size: chunk_size,
+            })
+        })
+    }
+
+    /// Builds a mesh of the solid tiles in a rectangle of the grid
+    fn build_region_mesh(
+        &self,
+        columns: Range<usize>,
+        rows: Range<usize>,
+        uv_source: TileUv,
+    ) -> Mesh {
+        let mut position = Vec::new();
+        let mut uv = Vec::new();
+
+        let coords = self.coords();
+        let half_tile = self.tile_size / 2.0;
+        let grid_size = Vec2::new(self.width as f32, self.height as f32) * self.tile_size;
+        let grid_min = -grid_size / 2.0;
+
+        let tiles = rows.flat_map(|y| columns.clone().map(move |x| (x, y)));
+        for (x, y) in tiles.filter(|(x, y)| self.is_solid(*x, *y)) {
+            let center = coords.tile_to_world((x, y));
+            let (left, right) = (center.x - half_tile, center.x + half_tile);
+            let (bottom, top) = (center.y - half_tile, center.y + half_tile);
+            let corners = [
+                (left, bottom),
+                (right, bottom),
+                (right, top),
+                (right, top),
+                (left, top),
+                (left, bottom),
+            ];
+
+            for (corner_x, corner_y) in corners {
+                position.push([corner_x, corner_y, 0.0]);
+                uv.push(match uv_source {
+                    TileUv::PerTile => [
+                        (corner_x - left) / self.tile_size,
+                        (top - corner_y) / self.tile_size,
+                    ],
+                    TileUv::Stretched => [
+                        (corner_x - grid_min.x) / grid_size.x,
+                        1.0 - (corner_y - grid_min.y) / grid_size.y,
+                    ],
+                });
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, position)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uv)
+    }
+}
+
+/// Spawns a tile mesh, e.g. built by [`TileGrid::build_mesh`], textured with
+/// the image loaded under `texture_tag`. The components are added to the
+/// spawned entity.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_tilemap<B: Bundle>(
+    commands: &mut Commands,
+    mesh: Mesh,
+    assets: &AssetStore,
+    loaded_assets: &LoadedAssets,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    texture_tag: &str,
+    components: B,
+) -> Entity {
+    let material = ColorMaterial {
+        texture: Some(
+            assets
+                .get_handle(texture_tag, loaded_assets)
+                .unwrap_or_else(|| panic!("Image [{texture_tag}] is not loaded")),
+        ),
+        ..default()
+    };
+    commands
+        .spawn((
+            Mesh2d(meshes.add(mesh)),
+            MeshMaterial2d(materials.add(material)),
+            components,
+        ))
+        .id()
+}
+
+/// Tags an entity showing one square chunk of a [`TileGrid`]. Each chunk
+/// has its own mesh, so changing a tile only rebuilds its chunk, and Bevy
+/// culls the chunks outside of the view.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileChunk {
+    /// Column of the chunk, counted in chunks
+    pub x: usize,
+    /// Row of the chunk, counted in chunks
+    pub y: usize,
+    /// Number of tiles along each side of the chunk
+    pub size: usize,
+}
+
+impl TileChunk {
+    /// The chunk of `size` tiles containing a tile
+    pub fn containing(tile: (usize, usize), size: usize) -> Self {
+        Self {
+            x: tile.0 / size,
+            y: tile.1 / size,
+            size,
+        }
+    }
+
+    /// Columns and rows of the grid's tiles in the chunk
+    pub fn tiles(&self, grid: &TileGrid) -> (Range<usize>, Range<usize>) {
+        let (left, bottom) = (self.x * self.size, self.y * self.size);
+        (
+            left.min(grid.width)..(left + self.size).min(grid.width),
+            bottom.min(grid.height)..(bottom + self.size).min(grid.height),
+        )
+    }
+}
+
+/// Spawns chunk meshes, e.g. built by [`TileGrid::build_chunk_meshes`],
+/// textured with the image loaded under `texture_tag`. All chunks share one
+/// material. The components are added to each spawned entity.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_tile_chunks<B: Bundle + Clone>(
+    commands: &mut Commands,
+    chunks: Vec<(TileChunk, Mesh)>,
+    assets: &AssetStore,
+    loaded_assets: &LoadedAssets,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    texture_tag: &str,
+    components: B,
+) -> Vec<Entity> {
+    let material = materials.add(ColorMaterial {
+        texture: Some(
+            assets
+                .get_handle(texture_tag, loaded_assets)
+                .unwrap_or_else(|| panic!("Image [{texture_tag}] is not loaded")),
+        ),
+        ..default()
+    });
+    chunks
+        .into_iter()
+        .map(|(chunk, mesh)| {
+            commands
+                .spawn((
+                    chunk,
+                    Mesh2d(meshes.add(mesh)),
+                    MeshMaterial2d(material.clone()),
+                    components.clone(),
+                ))
+                .id()
+        })
+        .collect()
+}
+
+/// Rebuilds the meshes of the chunks containing the changed tiles. Returns
+/// the number of chunks rebuilt.
+pub fn rebuild_tile_chunks(
+    grid: &TileGrid,
+    changed: impl IntoIterator<Item = (usize, usize)>,
+    uv_source: TileUv,
+    chunks: &Query<(&TileChunk, &Mesh2d)>,
+    meshes: &mut Assets<Mesh>,
+) -> usize {
+    let changed: Vec<(usize, usize)> = changed.into_iter().collect();
+    let mut rebuilt = 0;
+    for (chunk, handle) in chunks.iter() {
+        if changed
+            .iter()
+            .any(|&tile| TileChunk::containing(tile, chunk.size) == *chunk)
+        {
+            meshes.insert(&handle.0, grid.build_chunk_mesh(*chunk, uv_source));
+            rebuilt += 1;
+        }
+    }
+    rebuilt
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::{ecs::system::RunSystemOnce, render::mesh::VertexAttributeValues};
+
+    fn uvs(mesh: &Mesh) -> Vec<[f32; 2]> {
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+            _ => panic!("Mesh has no UVs"),
+        }
+    }
+
+    #[test]
+    fn set_solid_only_changes_one_tile() {
+        let mut grid = TileGrid::new(100, 3, 24.0, true);
+        grid.set_solid(70, 1, false);
+        assert!(!grid.is_solid(70, 1));
+        assert!(grid.is_solid(69, 1) && grid.is_solid(71, 1) && grid.is_solid(70, 0));
+        assert_eq!(299, grid.solid_count());
+
+        grid.set_solid(70, 1, true);
+        assert_eq!(TileGrid::new(100, 3, 24.0, true), grid);
+
+        let mut filled = TileGrid::new(100, 3, 24.0, false);
+        (0..100).for_each(|x| (0..3).for_each(|y| filled.set_solid(x, y, true)));
+        assert_eq!(grid, filled);
+    }
+
+    #[test]
+    fn world_positions_find_their_tile() {
+        // 4x2 tiles of 24 units, from (-48, -24) to (48, 24)
+        let mut grid = TileGrid::new(4, 2, 24.0, false);
+        grid.set_solid(1, 0, true);
+        assert!(grid.is_solid_world(Vec2::new(-10.0, -10.0)));
+        assert!(grid.is_solid_world(Vec2::new(-24.0, -24.0)));
+        assert!(!grid.is_solid_world(Vec2::new(10.0, -10.0)));
+        assert!(!grid.is_solid_world(Vec2::new(-10.0, 10.0)));
+
+        // Outside of the grid is open, even next to a solid tile
+        grid.set_solid(0, 0, true);
+        assert!(!grid.is_solid_world(Vec2::new(-50.0, -10.0)));
+        assert!(!grid.is_solid_world(Vec2::new(-10.0, -30.0)));
+    }
+
+    #[test]
+    fn mesh_has_six_vertices_per_solid_tile() {
+        let mut grid = TileGrid::new(10, 10, 24.0, false);
+        assert_eq!(0, grid.build_mesh(TileUv::PerTile).count_vertices());
+
+        grid.set_solid(0, 0, true);
+        grid.set_solid(5, 7, true);
+        grid.set_solid(9, 9, true);
+        assert_eq!(18, grid.build_mesh(TileUv::PerTile).count_vertices());
+    }
+
+    fn positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+            _ => panic!("Mesh has no positions"),
+        }
+    }
+
+    /// A grid of 70x45 tiles, so the chunks on the border are cut off
+    fn random_grid() -> TileGrid {
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let mut grid = TileGrid::new(70, 45, 24.0, false);
+        for y in 0..45 {
+            for x in 0..70 {
+                grid.set_solid(x, y, rng.range(0..3) > 0);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn chunks_cover_every_tile_once() {
+        let grid = random_grid();
+        let mut tiles = Vec::new();
+        for chunk in grid.chunks(32) {
+            let (columns, rows) = chunk.tiles(&grid);
+            tiles.extend(rows.flat_map(|y| columns.clone().map(move |x| (x, y))));
+        }
+        assert_eq!(6, grid.chunks(32).count());
+        tiles.sort();
+        let mut expected: Vec<_> = grid.tiles().collect();
+        expected.sort();
+        assert_eq!(expected, tiles);
+        assert_eq!(
+            TileChunk {
+                x: 2,
+                y: 1,
+                size: 32
+            },
+            TileChunk::containing((69, 44), 32)
+        );
+    }
+
+    #[test]
+    fn chunk_meshes_add_up_to_the_whole_mesh() {
+        let grid = random_grid();
+        for uv_source in [TileUv::PerTile, TileUv::Stretched] {
+            let whole = grid.build_mesh(uv_source);
+            let mut quads = Vec::new();
+            for (_, mesh) in grid.build_chunk_meshes(32, uv_source) {
+                let positions = positions(&mesh)
+                    .chunks(6)
+                    .map(|quad| quad.to_vec())
+                    .collect::<Vec<_>>();
+                let uvs = uvs(&mesh)
+                    .chunks(6)
+                    .map(|quad| quad.to_vec())
+                    .collect::<Vec<_>>();
+                quads.extend(positions.into_iter().zip(uvs));
+            }
+            let mut expected: Vec<_> = positions(&whole)
+                .chunks(6)
+                .map(|quad| quad.to_vec())
+                .zip(uvs(&whole).chunks(6).map(|quad| quad.to_vec()))
+                .collect();
+            let order = |a: &(Vec<[f32; 3]>, Vec<[f32; 2]>), b: &(Vec<[f32; 3]>, Vec<[f32; 2]>)| {
+                a.partial_cmp(b).unwrap()
+            };
+            quads.sort_by(order);
+            expected.sort_by(order);
+            // No tile is missing or duplicated at the seams
+            assert_eq!(expected, quads);
+        }
+    }
+
+    #[test]
+    fn changing_a_tile_rebuilds_its_chunk() {
+        let mut grid = random_grid();
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        let chunks = grid.build_chunk_meshes(32, TileUv::PerTile);
+        let handles: Vec<Handle<Mesh>> = chunks
+            .into_iter()
+            .map(|(chunk, mesh)| {
+                let handle = world.resource_mut::<Assets<Mesh>>().add(mesh);
+                world.spawn((chunk, Mesh2d(handle.clone())));
+                handle
+            })
+            .collect();
+        let vertices = |world: &World, chunk: usize| {
+            world
+                .resource::<Assets<Mesh>>()
+                .get(&handles[chunk])
+                .unwrap()
+                .count_vertices()
+        };
+        let before: Vec<usize> = (0..6).map(|chunk| vertices(&world, chunk)).collect();
+
+        // Tile (40, 10) is in the second chunk
+        let solid = grid.is_solid(40, 10);
+        grid.set_solid(40, 10, !solid);
+        let rebuilt = world
+            .run_system_once(
+                move |chunks: Query<(&TileChunk, &Mesh2d)>, mut meshes: ResMut<Assets<Mesh>>| {
+                    rebuild_tile_chunks(&grid, [(40, 10)], TileUv::PerTile, &chunks, &mut meshes)
+                },
+            )
+            .unwrap();
+        assert_eq!(1, rebuilt);
+        for (chunk, before) in before.into_iter().enumerate() {
+            let expected = match (chunk, solid) {
+                (1, true) => before - 6,
+                (1, false) => before + 6,
+                _ => before,
+            };
+            assert_eq!(expected, vertices(&world, chunk));
+        }
+    }
+
+    #[test]
+    fn colored_meshes_have_one_color_per_vertex() {
+        let grid = random_grid();
+        let mesh = grid.build_mesh_colored(TileUv::PerTile, |(_, y)| {
+            Color::linear_rgb(y as f32 / 45.0, 0.0, 0.0)
+        });
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("Mesh has no colors");
+        };
+        assert_eq!(mesh.count_vertices(), colors.len());
+
+        // All corners of a tile share its color
+        let positions = positions(&mesh);
+        for (quad, colors) in positions.chunks(6).zip(colors.chunks(6)) {
+            let center_y = quad.iter().map(|position| position[1]).sum::<f32>() / 6.0;
+            let (_, y) = grid
+                .coords()
+                .world_to_tile(Vec2::new(quad[0][0] + 1.0, center_y))
+                .unwrap();
+            assert!(
+                colors
+                    .iter()
+                    .all(|color| *color == [y as f32 / 45.0, 0.0, 0.0, 1.0])
+            );
+        }
+    }
+
+    #[test]
+    fn per_tile_uvs_show_the_whole_texture_upright() {
+        let grid = TileGrid::new(2, 1, 24.0, true);
+        let mesh = grid.build_mesh(TileUv::PerTile);
+        let expected = [
+            [0.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 0.0],
+            [0.0, 1.0],
+        ];
+        assert_eq!(expected.repeat(2), uvs(&mesh));
+    }
+
+    #[test]
+    fn stretched_uvs_cover_the_texture_once() {
+        let grid = TileGrid::new(2, 1, 24.0, true);
+        let mesh = grid.build_mesh(TileUv::Stretched);
+        let expected = vec![
+            [0.0, 1.0],
+            [0.5, 1.0],
+            [0.5, 0.0],
+            [0.5, 0.0],
+            [0.0, 0.0],
+            [0.0, 1.0],
+            [0.5, 1.0],
+            [1.0, 1.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [0.5, 0.0],
+            [0.5, 1.0],
+        ];
+        assert_eq!(expected, uvs(&mesh));
+    }
+
+    #[test]
+    fn random_open_tile_finds_the_only_open_tile() {
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let mut grid = TileGrid::new(9, 7, 24.0, true);
+        assert_eq!(None, grid.random_open_tile(&mut rng));
+
+        grid.set_solid(6, 2, false);
+        for _ in 0..10 {
+            assert_eq!(Some((6, 2)), grid.random_open_tile(&mut rng));
+        }
+    }
+
+    #[test]
+    fn edge_tiles_are_on_the_border_or_next_to_open_tiles() {
+        let mut grid = TileGrid::new(7, 7, 24.0, true);
+        // The border ring of 24 tiles is the only edge
+        assert_eq!(24, grid.edge_tiles().count());
+        assert!(!grid.edge_tiles().any(|tile| tile == (3, 3)));
+
+        // Opening the center exposes its four neighbours
+        grid.set_solid(3, 3, false);
+        let edges: Vec<_> = grid.edge_tiles().collect();
+        assert_eq!(28, edges.len());
+        assert!(!edges.contains(&(3, 3)));
+        for tile in [(2, 3), (4, 3), (3, 2), (3, 4)] {
+            assert!(edges.contains(&tile));
+        }
+        // Diagonal neighbours stay enclosed
+        assert!(!edges.contains(&(2, 2)));
+        assert!(grid.is_edge(2, 3) && !grid.is_edge(2, 2) && !grid.is_edge(3, 3));
+    }
+
+    #[test]
+    fn raycast_stops_at_the_first_solid_tile() {
+        let mut grid = TileGrid::new(10, 10, 24.0, false);
+        grid.set_solid(5, 2, true);
+        grid.set_solid(7, 2, true);
+        let coords = grid.coords();
+        let (left, right) = (coords.tile_to_world((1, 2)), coords.tile_to_world((8, 2)));
+
+        assert_eq!(Some((5, 2)), grid.raycast(left, right));
+        assert_eq!(Some((7, 2)), grid.raycast(right, left));
+        // Diagonal lines only hit the tiles they pass through
+        let above = coords.tile_to_world((1, 5));
+        assert_eq!(None, grid.raycast(above, coords.tile_to_world((4, 2))));
+        assert_eq!(
+            Some((5, 2)),
+            grid.raycast(above, coords.tile_to_world((6, 1)))
+        );
+        // A line within one open tile, and lines outside of the grid
+        assert_eq!(None, grid.raycast(left, left + Vec2::splat(5.0)));
+        assert_eq!(
+            None,
+            grid.raycast(Vec2::splat(-500.0), Vec2::new(-500.0, 500.0))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Motion trails behind moving entities
+
+use bevy::{asset::RenderAssetUsages, prelude::*, render::mesh::PrimitiveTopology};
+use std::collections::VecDeque;
+
+/// A sampled position of a trail
+#[derive(Debug, Clone, Copy)]
+struct TrailPoint {
+    /// Position in world space
+    position: Vec2,
+    /// Seconds since the point was sampled
+    age: f32,
+}
+
+/// Draws a trail behind a moving entity. The entity's position is sampled
+/// whenever it moved far enough, and each point expires after a while, so
+/// resting entities have no trail.
+///
+/// The trail is drawn by a child mesh in world space. The entity must not
+/// have a parent, as the mesh is placed relative to its `Transform`.
+#[derive(Component, Debug)]
+pub struct Trail {
+    /// Maximum number of points, the oldest are dropped first
+    pub max_points: usize,
+    /// Minimal distance between two points
+    pub spacing: f32,
+    /// Width of the trail in world units
+    pub width: f32,
+    /// Color at the entity and at the end of the trail
+    pub color: (LinearRgba, LinearRgba),
+    /// Seconds until a point expires
+    pub point_lifetime: f32,
+    /// Sampled points, oldest first
+    points: VecDeque<TrailPoint>,
+    /// The last sampled position, kept after its point expired
+    last_sample: Option<Vec2>,
+    /// Entity and handle of the mesh, once spawned
+    mesh: Option<(Entity, Handle<Mesh>)>,
+}
+
+/// Marks the mesh of a [`Trail`]
+#[derive(Component)]
+pub struct TrailMesh;
+
+impl Trail {
+    /// Creates a new trail with a maximum number of points sampled
+    /// `spacing` units apart. It fades out from `color` and each point
+    /// expires after half a second.
+    pub fn new(max_points: usize, spacing: f32, width: f32, color: LinearRgba) -> Self {
+        Self {
+            max_points,
+            spacing,
+            width,
+            color: (color, color.with_alpha(0.0)),
+            point_lifetime: 0.5,
+            points: VecDeque::with_capacity(max_points),
+            last_sample: None,
+            mesh: None,
+        }
+    }
+
+    /// Blends the color into `end` towards the end of the trail
+    pub fn fading_to(mut self, end: LinearRgba) -> Self {
+        self.color.1 = end;
+        self
+    }
+
+    /// Sets how many seconds each point is visible
+    pub fn with_point_lifetime(mut self, seconds: f32) -> Self {
+        self.point_lifetime = seconds;
+        self
+    }
+
+    /// Number of points in the trail
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Checks if the trail has no points
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Iterates over the positions of the points, oldest first
+    pub fn points(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.points.iter().map(|point| point.position)
+    }
+
+    /// Ages the points by `delta` seconds, drops expired ones and samples
+    /// the position, if it is far enough from the last sample
+    fn sample(&mut self, position: Vec2, delta: f32) {
+        for point in self.points.iter_mut() {
+            point.age += delta;
+        }
+        while self
+            .points
+            .front()
+            .is_some_and(|point| point.age >= self.point_lifetime)
+        {
+            self.points.pop_front();
+        }
+
+        let far_enough = self
+            .last_sample
+            .is_none_or(|last| last.distance(position) >= self.spacing);
+        if far_enough {
+            self.last_sample = Some(position);
+            self.points.push_back(TrailPoint { position, age: 0.0 });
+            while self.points.len() > self.max_points {
+                self.points.pop_front();
+            }
+        }
+    }
+
+    /// Builds a triangle strip with two vertices per point, in world space.
+    /// Trails with less than two points have no vertices.
+    fn build_mesh(&self) -> Mesh {
+        let mut position = Vec::new();
+        let mut color = Vec::new();
+
+        if self.points.len() >= 2 {
+            let last = self.points.len() - 1;
+            for (i, point) in self.points.iter().enumerate() {
+                // Extrude the point sideways to the direction of the trail
+                let before = self.points[i.saturating_sub(1)].position;
+                let after = self.points[(i + 1).min(last)].position;
+                let side = (after - before).normalize_or_zero().perp() * self.width / 2.0;
+
+                let t = (point.age / self.point_lifetime).clamp(0.0, 1.0);
+                let point_color = self.color.0.mix(&self.color.1, t).to_f32_array();
+                for corner in [point.position + side, point.position - side] {
+                    position.push([corner.x, corner.y, 0.0]);
+                    color.push(point_color);
+                }
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleStrip,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, position)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, color)
+    }
+}
+
+/// System sampling the position of entities with a [`Trail`] and updating
+/// their trail meshes. The meshes are children of the entity, so they are
+/// despawned with it.
+pub fn trail_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut trails: Query<(Entity, &mut Trail, &Transform)>,
+    mut trail_meshes: Query<&mut Transform, (With<TrailMesh>, Without<Trail>)>,
+) {
+    for (entity, mut trail, transform) in trails.iter_mut() {
+        let was_empty = trail.is_empty();
+        trail.sample(transform.translation.xy(), time.delta_secs());
+        if was_empty && trail.is_empty() {
+            continue;
+        }
+
+        // Cancel out the entity's transform, so the mesh stays in world space,
+        // drawn right behind the entity
+        let behind = Mat4::from_translation(Vec3::new(0.0, 0.0, transform.translation.z - 0.1));
+        let local = Transform::from_matrix(transform.compute_matrix().inverse() * behind);
+        let mesh = trail.build_mesh();
+        match &trail.mesh {
+            Some((mesh_entity, handle)) => {
+                if let Some(trail_mesh) = meshes.get_mut(handle) {
+                    *trail_mesh = mesh;
+                }
+                if let Ok(mut mesh_transform) = trail_meshes.get_mut(*mesh_entity) {
+                    *mesh_transform = local;
+                }
+            }
+            None => {
+                let handle = meshes.add(mesh);
+                let mesh_entity = commands
+                    .spawn((
+                        Mesh2d(handle.clone()),
+                        MeshMaterial2d(materials.add(ColorMaterial::default())),
+                        local,
+                        TrailMesh,
+                        ChildOf(entity),
+                    ))
+                    .id();
+                trail.mesh = Some((mesh_entity, handle));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trail() -> Trail {
+        Trail::new(5, 10.0, 4.0, LinearRgba::WHITE).with_point_lifetime(1.0)
+    }
+
+    #[test]
+    fn trail_keeps_at_most_max_points() {
+        let mut trail = trail();
+        for x in 0..20 {
+            trail.sample(Vec2::new(x as f32 * 10.0, 0.0), 0.01);
+        }
+        assert_eq!(5, trail.len());
+        let xs: Vec<f32> = trail.points().map(|p| p.x).collect();
+        assert_eq!(vec![150.0, 160.0, 170.0, 180.0, 190.0], xs);
+    }
+
+    #[test]
+    fn trail_samples_by_distance() {
+        let mut trail = trail();
+        for x in 0..=30 {
+            trail.sample(Vec2::new(x as f32, 0.0), 0.01);
+        }
+        let xs: Vec<f32> = trail.points().map(|p| p.x).collect();
+        assert_eq!(vec![0.0, 10.0, 20.0, 30.0], xs);
+    }
+
+    #[test]
+    fn points_of_resting_entity_expire() {
+        let mut trail = trail();
+        trail.sample(Vec2::ZERO, 0.0);
+        trail.sample(Vec2::new(10.0, 0.0), 0.5);
+        trail.sample(Vec2::new(10.0, 0.0), 0.6);
+        assert_eq!(
+            vec![Vec2::new(10.0, 0.0)],
+            trail.points().collect::<Vec<_>>()
+        );
+        trail.sample(Vec2::new(10.0, 0.0), 0.5);
+        assert!(trail.is_empty());
+    }
+
+    #[test]
+    fn mesh_has_two_vertices_per_point() {
+        let mut trail = trail();
+        trail.sample(Vec2::ZERO, 0.0);
+        assert_eq!(0, trail.build_mesh().count_vertices());
+
+        // An L-shaped path of four points
+        for position in [(10.0, 0.0), (20.0, 0.0), (20.0, 10.0)] {
+            trail.sample(Vec2::from(position), 0.1);
+        }
+        let mesh = trail.build_mesh();
+        assert_eq!(8, mesh.count_vertices());
+        let Some(bevy::render::mesh::VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Mesh has no positions");
+        };
+        // The first point is extruded perpendicular to the path
+        assert_eq!([0.0, 2.0, 0.0], positions[0]);
+        assert_eq!([0.0, -2.0, 0.0], positions[1]);
+        // The last point is extruded perpendicular to the last segment
+        assert_eq!([18.0, 10.0, 0.0], positions[6]);
+        assert_eq!([22.0, 10.0, 0.0], positions[7]);
+    }
+
+    #[test]
+    fn trail_mesh_is_despawned_with_entity() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<ColorMaterial>>()
+            .add_systems(Update, trail_system);
+        let entity = app.world_mut().spawn((Transform::default(), trail())).id();
+        app.update();
+
+        let mut query = app.world_mut().query_filtered::<(), With<TrailMesh>>();
+        assert_eq!(1, query.iter(app.world()).count());
+        app.world_mut().despawn(entity);
+        app.update();
+        assert_eq!(0, query.iter(app.world()).count());
+    }
+}
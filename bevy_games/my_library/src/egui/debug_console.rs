@@ -0,0 +1,195 @@
+//! In-game console to run debug commands, e.g. "set shields 500"
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use bevy_egui::{EguiContexts, egui};
+
+/// Key to open and close the console
+pub const CONSOLE_KEY: KeyCode = KeyCode::Backquote;
+
+/// A console command. It gets the world and the words after the command's
+/// name, and returns a line to show in the console.
+type ConsoleCommand = Box<dyn Fn(&mut World, &[&str]) -> anyhow::Result<String> + Send + Sync>;
+
+/// Resource holding the registered commands and the console's history
+#[derive(Resource, Default)]
+pub struct DebugConsole {
+    /// Commands by their name
+    commands: HashMap<String, ConsoleCommand>,
+    /// Lines submitted, but not run yet
+    pending: Vec<String>,
+    /// The line being typed
+    input: String,
+    /// Submitted lines and their output
+    pub history: Vec<String>,
+    /// Whether the console window is shown
+    pub open: bool,
+}
+
+impl DebugConsole {
+    /// Submits a line, which runs with the next [`run_console_commands`]
+    pub fn submit(&mut self, line: impl ToString) {
+        self.pending.push(line.to_string());
+    }
+
+    /// Names of all registered commands, sorted
+    pub fn command_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+}
+
+/// Registers commands for the [`DebugConsole`]
+pub trait DebugConsoleExt {
+    /// Runs `command` whenever a line starting with `name` is submitted. The
+    /// remaining words of the line are passed as arguments.
+    fn console_command(
+        &mut self,
+        name: &str,
+        command: impl Fn(&mut World, &[&str]) -> anyhow::Result<String> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl DebugConsoleExt for App {
+    fn console_command(
+        &mut self,
+        name: &str,
+        command: impl Fn(&mut World, &[&str]) -> anyhow::Result<String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<DebugConsole>()
+            .commands
+            .insert(name.to_string(), Box::new(command));
+        self
+    }
+}
+
+/// Shows an egui console, toggled with [`CONSOLE_KEY`]. Type `help` to list
+/// the registered commands.
+pub struct DebugConsolePlugin;
+
+impl Plugin for DebugConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugConsole>().add_systems(
+            Update,
+            (toggle_console, console_window, run_console_commands).chain(),
+        );
+    }
+}
+
+fn toggle_console(keyboard: Res<ButtonInput<KeyCode>>, mut console: ResMut<DebugConsole>) {
+    if keyboard.just_pressed(CONSOLE_KEY) {
+        console.open = !console.open;
+    }
+}
+
+fn console_window(mut console: ResMut<DebugConsole>, mut egui_context: EguiContexts) {
+    if !console.open {
+        return;
+    }
+    let console = console.as_mut();
+    egui::Window::new("Console").show(egui_context.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in console.history.iter() {
+                    ui.monospace(line);
+                }
+            });
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut console.input)
+                .hint_text("help")
+                .desired_width(f32::INFINITY),
+        );
+        if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+            let line = std::mem::take(&mut console.input);
+            console.pending.push(line);
+            response.request_focus();
+        }
+    });
+}
+
+/// Runs the submitted lines on the world, writing their output or error to
+/// the history
+pub fn run_console_commands(world: &mut World) {
+    let Some(mut console) = world.get_resource_mut::<DebugConsole>() else {
+        return;
+    };
+    if console.pending.is_empty() {
+        return;
+    }
+    let pending = std::mem::take(&mut console.pending);
+    world.resource_scope(|world, mut console: Mut<DebugConsole>| {
+        for line in pending {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((name, args)) = words.split_first() else {
+                continue;
+            };
+            console.history.push(format!("> {line}"));
+            let output = match console.commands.get(*name) {
+                Some(command) => command(world, args).unwrap_or_else(|error| error.to_string()),
+                None if *name == "help" => console.command_names().join(", "),
+                None => format!("Unknown command {name}, try help"),
+            };
+            if !output.is_empty() {
+                console.history.push(output);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Stats {
+        shields: i32,
+        fuel: i32,
+    }
+
+    #[test]
+    fn submitted_lines_run_their_command() {
+        let mut app = App::new();
+        app.init_resource::<Stats>()
+            .console_command("set", |world, args| {
+                let [stat, value] = args else {
+                    anyhow::bail!("Usage: set <stat> <value>");
+                };
+                let value: i32 = value.parse()?;
+                let mut stats = world.resource_mut::<Stats>();
+                match *stat {
+                    "shields" => stats.shields = value,
+                    "fuel" => stats.fuel = value,
+                    _ => anyhow::bail!("Unknown stat {stat}"),
+                }
+                Ok(format!("{stat} = {value}"))
+            })
+            .add_systems(Update, run_console_commands);
+
+        let mut console = app.world_mut().resource_mut::<DebugConsole>();
+        console.submit("set  shields 500");
+        console.submit("set fuel");
+        console.submit("launch");
+        console.submit("help");
+        app.update();
+
+        let stats = app.world().resource::<Stats>();
+        assert_eq!((500, 0), (stats.shields, stats.fuel));
+        let console = app.world().resource::<DebugConsole>();
+        assert_eq!(
+            vec![
+                "> set  shields 500",
+                "shields = 500",
+                "> set fuel",
+                "Usage: set <stat> <value>",
+                "> launch",
+                "Unknown command launch, try help",
+                "> help",
+                "set",
+            ],
+            console.history
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Game-over flow to submit a final score to a high-score server
+
+use super::validated_text_input;
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+
+/// Maximum number of characters in a player's name
+const MAX_NAME_LEN: usize = 20;
+
+/// Sends high-score entries to a server
+pub trait HighScoreClient: Send + Sync + 'static {
+    /// Submits a player's score. This is called from a system, so it should
+    /// not block, e.g. by sending the request from another thread.
+    fn submit(&self, name: &str, score: u32);
+}
+
+/// Resource that tracks a final score until it is submitted through a
+/// [`HighScoreClient`]. Use [`high_score_submission`] to show the flow.
+#[derive(Resource)]
+pub struct HighScoreSubmit<C: HighScoreClient> {
+    /// The client sending the score
+    client: C,
+    /// The final score, None until a game is over
+    score: Option<u32>,
+    /// Name entered by the player, kept for the next game
+    pub player_name: String,
+    /// Additional lines shown below the score, e.g. the world seed
+    pub details: Vec<String>,
+    /// Has the score been submitted yet?
+    submitted: bool,
+}
+
+impl<C: HighScoreClient> HighScoreSubmit<C> {
+    /// Creates a new submission flow, waiting for a score
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            score: None,
+            player_name: String::new(),
+            details: Vec::new(),
+            submitted: false,
+        }
+    }
+
+    /// Starts the flow for the final score of a game
+    pub fn start(&mut self, score: u32) {
+        self.score = Some(score);
+        self.details.clear();
+        self.submitted = false;
+    }
+
+    /// The final score, if a game is over
+    pub fn score(&self) -> Option<u32> {
+        self.score
+    }
+
+    /// Checks if the final score was submitted
+    pub fn is_submitted(&self) -> bool {
+        self.submitted
+    }
+
+    /// Submits the final score with the player's name. Each score is only
+    /// submitted once, further calls return false.
+    pub fn submit(&mut self) -> bool {
+        match self.score {
+            Some(score) if !self.submitted => {
+                self.client.submit(&self.player_name, score);
+                self.submitted = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// System showing the final score, asking for the player's name and
+/// submitting both through the client. Confirms the submission afterwards.
+pub fn high_score_submission<C: HighScoreClient>(
+    mut submission: ResMut<HighScoreSubmit<C>>,
+    mut egui_context: EguiContexts,
+) {
+    let Some(score) = submission.score else {
+        return;
+    };
+    let submission = submission.as_mut();
+
+    egui::Window::new("Final Score").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("Final score: {score}"));
+        for detail in submission.details.iter() {
+            ui.label(detail);
+        }
+        if submission.submitted {
+            ui.label(format!(
+                "Thank you, {}! Your score was submitted.",
+                submission.player_name
+            ));
+            return;
+        }
+
+        ui.label("Please enter your name:");
+        validated_text_input(ui, &mut submission.player_name, MAX_NAME_LEN, |c| {
+            c.is_alphanumeric() || c == ' '
+        });
+        let has_name = !submission.player_name.trim().is_empty();
+        if ui
+            .add_enabled(has_name, egui::Button::new("Submit Score"))
+            .clicked()
+        {
+            submission.submit();
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::window::PrimaryWindow;
+    use bevy_egui::{EguiContext, EguiUserTextures};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default, Clone)]
+    struct MockClient {
+        submitted: Arc<Mutex<Vec<(String, u32)>>>,
+    }
+
+    impl HighScoreClient for MockClient {
+        fn submit(&self, name: &str, score: u32) {
+            self.submitted
+                .lock()
+                .unwrap()
+                .push((name.to_string(), score));
+        }
+    }
+
+    /// Runs a frame of the app inside an egui pass receiving the events,
+    /// and returns the texts drawn with their screen rects
+    fn frame(app: &mut App, events: Vec<egui::Event>) -> Vec<(String, egui::Rect)> {
+        let mut contexts = app.world_mut().query::<&mut EguiContext>();
+        let ctx = contexts
+            .single_mut(app.world_mut())
+            .unwrap()
+            .get_mut()
+            .clone();
+        ctx.begin_pass(egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(800.0, 600.0),
+            )),
+            events,
+            ..default()
+        });
+        app.update();
+        ctx.end_pass()
+            .shapes
+            .into_iter()
+            .filter_map(|clipped| match clipped.shape {
+                egui::Shape::Text(text) => {
+                    Some((text.galley.text().to_string(), text.visual_bounding_rect()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Events of a click at a position
+    fn click(pos: egui::Pos2) -> Vec<egui::Event> {
+        let button = |pressed| egui::Event::PointerButton {
+            pos,
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        };
+        vec![egui::Event::PointerMoved(pos), button(true), button(false)]
+    }
+
+    #[test]
+    fn score_is_submitted_once_across_frames() {
+        let client = MockClient::default();
+        let mut app = App::new();
+        app.init_resource::<EguiUserTextures>()
+            .insert_resource(HighScoreSubmit::new(client.clone()))
+            .add_systems(Update, high_score_submission::<MockClient>);
+        app.world_mut()
+            .spawn((Window::default(), PrimaryWindow, EguiContext::default()));
+
+        // Nothing is shown before the game is over
+        assert!(frame(&mut app, Vec::new()).is_empty());
+
+        let mut submission = app
+            .world_mut()
+            .resource_mut::<HighScoreSubmit<MockClient>>();
+        submission.player_name = "Ada".to_string();
+        submission.start(1234);
+        // The player keeps clicking where the submit button is drawn
+        let mut button = None;
+        for _ in 0..10 {
+            let events = button.map_or(Vec::new(), click);
+            let texts = frame(&mut app, events);
+            if let Some((_, rect)) = texts.iter().find(|(text, _)| text == "Submit Score") {
+                button = Some(rect.center());
+            }
+        }
+
+        let submission = app.world().resource::<HighScoreSubmit<MockClient>>();
+        assert!(button.is_some());
+        assert!(submission.is_submitted());
+        assert_eq!(
+            vec![("Ada".to_string(), 1234)],
+            *client.submitted.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Bars for stats in the HUD, e.g. health or fuel
+
+use bevy_egui::egui;
+
+/// Shows a bar filled in proportion to `current` of `max`, labeled with
+/// `label`
+pub fn stat_bar(
+    ui: &mut egui::Ui,
+    label: &str,
+    current: f32,
+    max: f32,
+    color: egui::Color32,
+) -> egui::Response {
+    ui.add(
+        egui::ProgressBar::new(stat_fraction(current, max))
+            .fill(color)
+            .text(label),
+    )
+}
+
+/// How full a stat bar is, capped to 0.0..=1.0. Empty if there is no
+/// maximum.
+pub fn stat_fraction(current: f32, max: f32) -> f32 {
+    if max <= 0.0 {
+        return 0.0;
+    }
+    (current / max).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fractions_are_capped() {
+        assert_eq!(0.25, stat_fraction(25.0, 100.0));
+        assert_eq!(1.0, stat_fraction(100.0, 100.0));
+        assert_eq!(1.0, stat_fraction(150.0, 100.0));
+        assert_eq!(0.0, stat_fraction(-10.0, 100.0));
+    }
+
+    #[test]
+    fn bars_without_maximum_are_empty() {
+        assert_eq!(0.0, stat_fraction(10.0, 0.0));
+        assert_eq!(0.0, stat_fraction(0.0, 0.0));
+        assert_eq!(0.0, stat_fraction(10.0, -5.0));
+    }
+}
@@ -0,0 +1,52 @@
+//! Text input with validation
+
+use bevy_egui::egui;
+
+/// Shows a single line text input that only accepts up to `max_len`
+/// characters passing the `allowed` filter. Invalid input is removed as
+/// the user types.
+pub fn validated_text_input(
+    ui: &mut egui::Ui,
+    text: &mut String,
+    max_len: usize,
+    allowed: impl Fn(char) -> bool,
+) -> egui::Response {
+    let response = ui.add(egui::TextEdit::singleline(text).char_limit(max_len));
+    if response.changed() {
+        *text = validate_text(text, max_len, allowed);
+    }
+    response
+}
+
+/// Removes characters not passing the `allowed` filter and cuts the text
+/// after `max_len` characters
+pub fn validate_text(text: &str, max_len: usize, allowed: impl Fn(char) -> bool) -> String {
+    text.chars().filter(|c| allowed(*c)).take(max_len).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn valid_text_is_kept() {
+        assert_eq!("Ada", validate_text("Ada", 10, char::is_alphanumeric));
+    }
+
+    #[test]
+    fn overlong_text_is_cut() {
+        assert_eq!(
+            "Lovela",
+            validate_text("Lovelace", 6, char::is_alphanumeric)
+        );
+    }
+
+    #[test]
+    fn disallowed_characters_are_removed() {
+        assert_eq!(
+            "Ada42",
+            validate_text("<Ada> 4;2", 10, |c| c.is_ascii_alphanumeric())
+        );
+        assert_eq!("", validate_text("!?", 10, |c| c.is_ascii_alphanumeric()));
+    }
+}
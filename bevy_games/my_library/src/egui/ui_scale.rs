@@ -0,0 +1,109 @@
+//! Scaling of all egui windows, e.g. for high-DPI displays
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+
+/// Resource with the scale of the egui windows, on top of the display's
+/// own scale factor. Set it through `GameStatePlugin::with_ui_scale`, or
+/// change it at runtime, e.g. from an options menu.
+///
+/// Named after egui, as `bevy::prelude::UiScale` scales the Bevy UI.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct EguiScale(pub f32);
+
+impl Default for EguiScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl EguiScale {
+    /// Applies the scale to an egui context. The display's pixels per point
+    /// are kept, so text stays sharp on high-DPI displays.
+    pub fn apply(&self, ctx: &egui::Context) {
+        if ctx.zoom_factor() != self.0 {
+            ctx.set_zoom_factor(self.0);
+        }
+    }
+}
+
+/// Plugin applying the [`EguiScale`] on every frame, starting with the given
+/// scale. Added by `GameStatePlugin`.
+pub struct EguiScalePlugin(pub f32);
+
+impl Plugin for EguiScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EguiScale(self.0))
+            .add_systems(PreUpdate, apply_egui_scale);
+    }
+}
+
+/// System applying the [`EguiScale`] to the primary egui context, before
+/// the menus and overlays are drawn
+pub fn apply_egui_scale(scale: Res<EguiScale>, mut egui_context: EguiContexts) {
+    if let Some(ctx) = egui_context.try_ctx_mut() {
+        scale.apply(ctx);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::window::PrimaryWindow;
+    use bevy_egui::{EguiContext, EguiUserTextures};
+
+    #[test]
+    fn scale_is_applied_to_context() {
+        let ctx = egui::Context::default();
+        let native = ctx.pixels_per_point();
+
+        // The scale takes effect with the next pass
+        let pass = |ctx: &egui::Context| {
+            let _ = ctx.run(egui::RawInput::default(), |_| {});
+        };
+
+        EguiScale(1.5).apply(&ctx);
+        pass(&ctx);
+        assert_eq!(1.5, ctx.zoom_factor());
+        assert_eq!(native * 1.5, ctx.pixels_per_point());
+
+        EguiScale::default().apply(&ctx);
+        pass(&ctx);
+        assert_eq!(native, ctx.pixels_per_point());
+    }
+
+    #[test]
+    fn plugin_applies_changed_scale() {
+        let mut app = App::new();
+        app.init_resource::<EguiUserTextures>()
+            .add_plugins(EguiScalePlugin(1.25));
+        let window = app
+            .world_mut()
+            .spawn((Window::default(), PrimaryWindow, EguiContext::default()))
+            .id();
+        let ctx = app
+            .world_mut()
+            .get_mut::<EguiContext>(window)
+            .unwrap()
+            .get_mut()
+            .clone();
+        let native = ctx.pixels_per_point();
+        // A frame within an egui pass. The scale takes effect with the pass
+        // after it was set.
+        let frame = |app: &mut App| {
+            ctx.begin_pass(egui::RawInput::default());
+            app.update();
+            let _ = ctx.end_pass();
+        };
+
+        frame(&mut app);
+        frame(&mut app);
+        assert_eq!(1.25, ctx.zoom_factor());
+
+        app.world_mut().resource_mut::<EguiScale>().0 = 2.0;
+        frame(&mut app);
+        frame(&mut app);
+        assert_eq!(2.0, ctx.zoom_factor());
+        assert_eq!(native * 2.0, ctx.pixels_per_point());
+    }
+}
@@ -11,7 +11,7 @@
 //! Feature flags
 //! -------------
 //!
-//! The following feature flags are supported: `xorshift`, `pcg`, `locking`
+//! The following feature flags are supported: `xorshift`, `pcg`, `secure`, `locking`, `dev`
 //!
 //! ### Random number generation
 //!
@@ -21,6 +21,16 @@
 //! - You can control which random number generation algorithm is used by specifying one of:
 //!     - `xorshift` to use the XorShift algorithm
 //!     - `pcg` to use the PCG algorithm
+//!     - `secure` to use the cryptographically strong `StdRng`, seeded by the OS.
+//!       It overrides the other algorithms, and ignores the seeds passed to
+//!       `RandomNumberGenerator::seeded`, so seeded results are not reproducible.
+//!
+//! ### Development
+//!
+//! - The `dev` feature enables shortcuts for iterating on your game. Pressing
+//!   `F12` on the loading screen skips to the menu, even if assets are still pending.
+//!   It also provides the `egui::DebugConsolePlugin`, an in-game console for
+//!   commands registered with `egui::DebugConsoleExt`.
 
 #![warn(missing_docs)]
 
@@ -52,4 +62,21 @@ pub mod anyhow {
 /// Wraps the bevy_egui crate;
 pub mod egui {
     pub use bevy_egui::*;
+
+    mod text_input;
+    pub use text_input::*;
+
+    mod high_score;
+    pub use high_score::*;
+
+    mod ui_scale;
+    pub use ui_scale::*;
+
+    mod stat_bar;
+    pub use stat_bar::*;
+
+    #[cfg(feature = "dev")]
+    mod debug_console;
+    #[cfg(feature = "dev")]
+    pub use debug_console::*;
 }
@@ -7,21 +7,46 @@ use rand::{
 };
 use std::sync::Mutex;
 
-#[cfg(all(not(feature = "xorshift"), not(feature = "pcg")))]
+#[cfg(any(
+    feature = "secure",
+    all(not(feature = "xorshift"), not(feature = "pcg"))
+))]
 type RngCore = rand::prelude::StdRng;
+#[cfg(any(
+    feature = "secure",
+    all(not(feature = "xorshift"), not(feature = "pcg"))
+))]
+const ALGORITHM: &str = "StdRng";
 
-#[cfg(feature = "xorshift")]
+#[cfg(all(feature = "xorshift", not(feature = "secure")))]
 type RngCore = rand_xorshift::XorShiftRng;
+#[cfg(all(feature = "xorshift", not(feature = "secure")))]
+const ALGORITHM: &str = "XorShift";
 
-#[cfg(feature = "pcg")]
+#[cfg(all(feature = "pcg", not(feature = "secure")))]
 type RngCore = rand_pcg::Pcg64Mcg;
+#[cfg(all(feature = "pcg", not(feature = "secure")))]
+const ALGORITHM: &str = "PCG";
+
+/// Creates the generator for a seed
+#[cfg(not(feature = "secure"))]
+fn seeded_core(seed: u64) -> RngCore {
+    RngCore::seed_from_u64(seed)
+}
+
+/// Ignores the seed, so the numbers cannot be predicted from a known seed
+#[cfg(feature = "secure")]
+fn seeded_core(_seed: u64) -> RngCore {
+    RngCore::from_os_rng()
+}
 
 #[derive(bevy::prelude::Resource)]
 /// `RandomNumberGenerator` holds random number generation state and offers
 /// random number generation services to your program.
 ///
 /// `RandomNumberGenerator` defaults to using the [PCG](https://crates.io/crates/rand_pcg) algorithm.
-/// You can specify `xorshift` as a feature flag to use it instead.
+/// You can specify `xorshift` as a feature flag to use it instead, or
+/// `secure` for the cryptographically strong `StdRng`, seeded by the OS.
 ///
 /// By default, `RandomNumberGenerator` requires mutability--- it is shared in Bevy with
 /// `ResMut<RandomNumberGenerator`. If you prefer interior mutability instead
@@ -50,6 +75,10 @@ impl RandomNumberGenerator {
     /// Creates a `RandomNumberGenerator` with a specified random seed.
     /// Given the same requests, it will produce the *same results* each time.
     ///
+    /// **Note:** with the `secure` feature, the seed is ignored and the
+    /// generator is seeded by the OS. Seeded results are *not* reproducible
+    /// then, e.g. worlds generated from a seed differ each time.
+    ///
     /// # Arguments
     ///
     /// * `seed` - the random seed to use.
@@ -61,14 +90,27 @@ impl RandomNumberGenerator {
     /// let mut rng1 = RandomNumberGenerator::seeded(1);
     /// let mut rng2 = RandomNumberGenerator::seeded(1);
     /// let results: (u32, u32) = ( rng1.next(), rng2.next());
+    /// # #[cfg(not(feature = "secure"))]
     /// assert_eq!(results.0, results.1);
     /// ```
     pub fn seeded(seed: u64) -> Self {
         Self {
-            rng: Mutex::new(RngCore::seed_from_u64(seed)),
+            rng: Mutex::new(seeded_core(seed)),
         }
     }
 
+    /// Reseeds the generator with a randomly selected seed, e.g. to
+    /// reshuffle a game without replacing the resource
+    pub fn randomize(&self) {
+        *self.rng.lock().unwrap() = RngCore::from_os_rng();
+    }
+
+    /// Name of the algorithm selected by the feature flags, e.g. to log it
+    /// with bug reports: "PCG", "XorShift" or "StdRng"
+    pub fn algorithm(&self) -> &'static str {
+        ALGORITHM
+    }
+
     /// Generates a random number within a specified range.
     ///
     /// # Arguments
@@ -100,6 +142,72 @@ impl RandomNumberGenerator {
         let mut lock = self.rng.lock().unwrap();
         lock.random()
     }
+
+    /// Lazily generates random numbers within a specified range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use my_library::RandomNumberGenerator;
+    /// let mut rng = RandomNumberGenerator::new();
+    /// let five_digits: Vec<u32> = rng.iter_range(0..10).take(5).collect();
+    /// ```
+    ///
+    /// Each value locks the generator on its own, so other users of the
+    /// generator may draw numbers in between.
+    pub fn iter_range<T, R>(&self, range: R) -> impl Iterator<Item = T>
+    where
+        T: SampleUniform + PartialOrd,
+        R: SampleRange<T> + Clone,
+    {
+        std::iter::repeat_with(move || self.range(range.clone()))
+    }
+
+    /// Lazily generates new random numbers of the requested type.
+    ///
+    /// Each value locks the generator on its own, so other users of the
+    /// generator may draw numbers in between.
+    pub fn iter_next<T>(&self) -> impl Iterator<Item = T>
+    where
+        StandardUniform: Distribution<T>,
+    {
+        std::iter::repeat_with(move || self.next())
+    }
+
+    /// Picks `k` distinct items, each with a chance proportional to its
+    /// weight. The weights of the remaining items are re-normalized after
+    /// each pick. `k` is clamped to the number of items, and items without a
+    /// positive weight are never picked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use my_library::RandomNumberGenerator;
+    /// let rng = RandomNumberGenerator::seeded(1);
+    /// let loot = [("sword", 1.0), ("potion", 5.0), ("gold", 10.0)];
+    /// let drops = rng.weighted_sample(&loot, 2);
+    /// assert_eq!(2, drops.len());
+    /// ```
+    pub fn weighted_sample<'a, T>(&self, items: &'a [(T, f32)], k: usize) -> Vec<&'a T> {
+        let mut remaining: Vec<&'a (T, f32)> =
+            items.iter().filter(|(_, weight)| *weight > 0.0).collect();
+        let mut picked = Vec::with_capacity(k.min(remaining.len()));
+        while picked.len() < k && !remaining.is_empty() {
+            let total: f32 = remaining.iter().map(|(_, weight)| weight).sum();
+            let mut roll = self.range(0.0..total);
+            // Rounding may leave a tiny rest, which belongs to the last item
+            let mut index = remaining.len() - 1;
+            for (i, (_, weight)) in remaining.iter().enumerate() {
+                if roll < *weight {
+                    index = i;
+                    break;
+                }
+                roll -= weight;
+            }
+            picked.push(&remaining.remove(index).0);
+        }
+        picked
+    }
 }
 
 impl Default for RandomNumberGenerator {
@@ -124,6 +232,7 @@ mod test {
     }
 
     #[test]
+    #[cfg(not(feature = "secure"))]
     fn test_reproducibility() {
         let rng = (
             RandomNumberGenerator::seeded(1),
@@ -138,6 +247,43 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_randomize() {
+        let rng = RandomNumberGenerator::seeded(1);
+        let other = RandomNumberGenerator::seeded(1);
+        rng.randomize();
+
+        let numbers: Vec<u64> = rng.iter_next().take(4).collect();
+        let seeded: Vec<u64> = other.iter_next().take(4).collect();
+        assert_ne!(seeded, numbers);
+    }
+
+    #[test]
+    #[cfg(feature = "secure")]
+    fn test_secure_range_bounds() {
+        let rng = RandomNumberGenerator::new();
+        let numbers: Vec<u32> = rng.iter_range(1..10).take(1000).collect();
+        assert!(numbers.iter().all(|n| (1..10).contains(n)));
+
+        let seeded = RandomNumberGenerator::seeded(1);
+        let n: f32 = seeded.range(-1.0..1.0);
+        assert!((-1.0..1.0).contains(&n));
+    }
+
+    #[test]
+    fn test_algorithm_follows_the_features() {
+        let expected = if cfg!(feature = "secure") {
+            "StdRng"
+        } else if cfg!(feature = "xorshift") {
+            "XorShift"
+        } else if cfg!(feature = "pcg") {
+            "PCG"
+        } else {
+            "StdRng"
+        };
+        assert_eq!(expected, RandomNumberGenerator::new().algorithm());
+    }
+
     #[test]
     fn test_next_types() {
         let rng = RandomNumberGenerator::new();
@@ -156,6 +302,58 @@ mod test {
             assert!(n < 5000.0);
         }
     }
+
+    #[test]
+    fn test_iter_range_bounds() {
+        let rng = RandomNumberGenerator::new();
+        let numbers: Vec<i32> = rng.iter_range(-3..=7).take(1000).collect();
+
+        assert_eq!(1000, numbers.len());
+        assert!(numbers.iter().all(|n| (-3..=7).contains(n)));
+    }
+
+    #[test]
+    #[cfg(not(feature = "secure"))]
+    fn test_iter_next_reproducibility() {
+        let rng = RandomNumberGenerator::seeded(1);
+        let numbers: Vec<u64> = rng.iter_next().take(10).collect();
+        let other = RandomNumberGenerator::seeded(1);
+
+        for n in numbers {
+            assert_eq!(n, other.next::<u64>());
+        }
+    }
+
+    #[test]
+    fn test_weighted_sample() {
+        let items: Vec<(usize, f32)> = (0..8).map(|i| (i, i as f32 + 0.5)).collect();
+        let sample = |seed, k| {
+            let rng = RandomNumberGenerator::seeded(seed);
+            rng.weighted_sample(&items, k)
+                .into_iter()
+                .copied()
+                .collect::<Vec<usize>>()
+        };
+
+        let picked = sample(3, 5);
+        assert_eq!(5, picked.len());
+        let mut distinct = picked.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(5, distinct.len());
+        #[cfg(not(feature = "secure"))]
+        assert_eq!(picked, sample(3, 5));
+
+        assert_eq!(8, sample(3, 20).len());
+        assert!(sample(3, 0).is_empty());
+    }
+
+    #[test]
+    fn test_weighted_sample_skips_weightless_items() {
+        let rng = RandomNumberGenerator::seeded(1);
+        let items = [("never", 0.0), ("always", 1.0), ("nope", -1.0)];
+        assert_eq!(vec![&"always"], rng.weighted_sample(&items, 3));
+    }
 }
 
 /// `Random` is a Bevy plugin that inserts a `RandomNumberGenerator`
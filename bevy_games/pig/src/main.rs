@@ -1,11 +1,13 @@
-use bevy::{prelude::*, window::WindowResolution};
+use bevy::{ecs::system::SystemParam, prelude::*, window::WindowResolution};
 use bevy_egui::{EguiContexts, EguiPlugin, egui};
 use my_library::*;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default, States)]
 enum GamePhase {
     #[default]
+    Loading,
     MainMenu,
+    Setup,
     Start,
     Player,
     Cpu,
@@ -16,12 +18,344 @@ enum GamePhase {
 #[derive(Component)]
 pub struct GameElement;
 
+/// Marks entities of the rules setup screen
+#[derive(Component)]
+struct SetupElement;
+
+/// Variants of the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PigVariant {
+    /// Each roll throws the configured number of dice, any die showing the
+    /// bust face loses the hand
+    Classic,
+    /// Each roll throws two dice. A single one loses the hand, double ones
+    /// lose the banked score as well.
+    TwoDice {
+        /// Must doubles other than ones roll again?
+        doubles_roll_again: bool,
+    },
+}
+
+impl PigVariant {
+    /// Name shown in the variant selection
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Classic => "Classic",
+            Self::TwoDice { .. } => "Two dice",
+        }
+    }
+}
+
+/// What a roll does to the turn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum RollOutcome {
+    /// The faces are added to the hand
+    #[default]
+    Score,
+    /// The hand is lost
+    Bust,
+    /// The hand and the banked score are lost
+    Wipeout,
+}
+
+/// Chances of the outcomes of a throw
+#[derive(Debug, Default)]
+struct RollChances {
+    bust: f64,
+    wipeout: f64,
+    /// Chances of scoring throws by their sum, and whether they must roll
+    /// again
+    sums: Vec<(usize, bool, f64)>,
+}
+
+/// Rules of the game, adjustable on the setup screen before a game starts
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+struct PigRules {
+    /// Points needed to win the game
+    target_score: usize,
+    /// Number of dice thrown with each roll in the classic variant
+    dice_per_roll: usize,
+    /// Rolling this face with any die loses the hand in the classic variant
+    bust_on: usize,
+    /// The variant being played
+    variant: PigVariant,
+}
+
+impl Default for PigRules {
+    fn default() -> Self {
+        Self {
+            target_score: 100,
+            dice_per_roll: 1,
+            bust_on: 1,
+            variant: PigVariant::Classic,
+        }
+    }
+}
+
+impl PigRules {
+    /// Number of dice thrown with each roll
+    fn dice_per_roll(&self) -> usize {
+        match self.variant {
+            PigVariant::Classic => self.dice_per_roll,
+            PigVariant::TwoDice { .. } => 2,
+        }
+    }
+
+    /// Decides what a roll does to the turn
+    fn outcome(&self, roll: &[usize]) -> RollOutcome {
+        match self.variant {
+            PigVariant::Classic if roll.contains(&self.bust_on) => RollOutcome::Bust,
+            PigVariant::Classic => RollOutcome::Score,
+            PigVariant::TwoDice { .. } => match roll.iter().filter(|face| **face == 1).count() {
+                0 => RollOutcome::Score,
+                1 => RollOutcome::Bust,
+                _ => RollOutcome::Wipeout,
+            },
+        }
+    }
+
+    /// Checks if a scoring roll has to be followed by another one
+    fn must_roll_again(&self, roll: &[usize]) -> bool {
+        let doubles = roll.len() == 2 && roll[0] == roll[1];
+        matches!(
+            self.variant,
+            PigVariant::TwoDice {
+                doubles_roll_again: true
+            }
+        ) && doubles
+            && self.outcome(roll) == RollOutcome::Score
+    }
+
+    /// Checks if a score wins the game
+    fn has_won(&self, score: usize) -> bool {
+        score >= self.target_score
+    }
+
+    /// Rolls the dice of one throw
+    fn roll(&self, rng: &RandomNumberGenerator) -> Vec<usize> {
+        (0..self.dice_per_roll()).map(|_| roll_die(rng)).collect()
+    }
+
+    /// Calculates the chances of the outcomes of a throw, by going through
+    /// all combinations of faces
+    fn roll_outcomes(&self) -> RollChances {
+        let dice = self.dice_per_roll();
+        let chance = 6f64.powi(-(dice as i32));
+        let mut chances = RollChances::default();
+        let mut sums = std::collections::BTreeMap::new();
+        for combination in 0..6usize.pow(dice as u32) {
+            let roll: Vec<usize> = (0..dice)
+                .map(|die| combination / 6usize.pow(die as u32) % 6 + 1)
+                .collect();
+            match self.outcome(&roll) {
+                RollOutcome::Score => {
+                    let key = (roll.iter().sum::<usize>(), self.must_roll_again(&roll));
+                    *sums.entry(key).or_insert(0.0) += chance;
+                }
+                RollOutcome::Bust => chances.bust += chance,
+                RollOutcome::Wipeout => chances.wipeout += chance,
+            }
+        }
+        chances.sums = sums
+            .into_iter()
+            .map(|((sum, again), chance)| (sum, again, chance))
+            .collect();
+        chances
+    }
+}
+
+/// Rolls dice according to the rules
+#[derive(SystemParam)]
+struct Dice<'w> {
+    rules: Res<'w, PigRules>,
+    rng: Res<'w, RandomNumberGenerator>,
+}
+
+impl Dice<'_> {
+    /// Rolls the dice of one throw
+    fn roll(&self) -> Vec<usize> {
+        self.rules.roll(&self.rng)
+    }
+}
+
+/// How the CPU decides to keep its hand or to roll again
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+enum CpuStrategy {
+    /// Holds once the hand is worth this many points
+    HoldAtN(usize),
+    /// Holds once the hand makes up for the player's lead, plus a margin
+    KeepPace(usize),
+    /// Holds at the thresholds of the [`OptimalPolicy`]
+    Optimal,
+}
+
+impl Default for CpuStrategy {
+    fn default() -> Self {
+        Self::HoldAtN(20)
+    }
+}
+
+impl CpuStrategy {
+    /// Name shown in the strategy selection
+    fn name(&self) -> &'static str {
+        match self {
+            Self::HoldAtN(_) => "Hold at N",
+            Self::KeepPace(_) => "Keep pace",
+            Self::Optimal => "Optimal",
+        }
+    }
+
+    /// Checks if the CPU keeps its hand instead of rolling again. A hand
+    /// that wins the game is always kept, an empty hand never.
+    fn holds(
+        &self,
+        rules: &PigRules,
+        policy: &OptimalPolicy,
+        cpu_score: usize,
+        player_score: usize,
+        hand_total: usize,
+    ) -> bool {
+        if hand_total == 0 {
+            return false;
+        }
+        rules.has_won(cpu_score + hand_total)
+            || match self {
+                Self::HoldAtN(n) => hand_total >= *n,
+                Self::KeepPace(margin) => {
+                    hand_total >= player_score.saturating_sub(cpu_score) + margin
+                }
+                Self::Optimal => hand_total >= policy.threshold(cpu_score, player_score),
+            }
+    }
+}
+
+/// Hold thresholds that maximize the chance to win against an optimal
+/// opponent, generated for a set of rules by value iteration
 #[derive(Resource)]
-/// This holds the handle the dice graphics. It represents an index to the
-/// stored graphics for reuse
-struct GameAssets {
-    image: Handle<Image>,
-    layout: Handle<TextureAtlasLayout>,
+struct OptimalPolicy {
+    /// The rules the thresholds were generated for
+    rules: PigRules,
+    /// Hand total to hold at for each pair of own and opponent score
+    thresholds: Vec<usize>,
+}
+
+impl OptimalPolicy {
+    /// Generates the thresholds for the rules. For each pair of scores, the
+    /// chance to win at the start of a turn depends on the opponent's chances
+    /// after this turn, so the pairs are solved from the highest combined
+    /// score down, iterating the pairs of equal combined score until their
+    /// chances converge. Wiping out the banked score leads to lower scores,
+    /// which are only solved later, so then the whole sweep is repeated
+    /// until it converges as well.
+    fn new(rules: PigRules) -> Self {
+        let target = rules.target_score;
+        let chances = rules.roll_outcomes();
+        let idx = |own: usize, opponent: usize| own * target + opponent;
+
+        // Chance to win at the start of a turn
+        let mut win = vec![0.0; target * target];
+        let mut thresholds = vec![target; target * target];
+        // Chance to win during a turn, for each hand total, when rolling and
+        // when choosing the better move
+        let mut rolling = vec![0.0; target];
+        let mut turn = vec![0.0; target];
+
+        loop {
+            let mut sweep_change: f64 = 0.0;
+            for combined in (0..=2 * (target - 1)).rev() {
+                let pairs: Vec<(usize, usize)> = (0..target)
+                    .filter_map(|own| Some((own, combined.checked_sub(own)?)))
+                    .filter(|(_, opponent)| *opponent < target)
+                    .collect();
+                loop {
+                    let mut change: f64 = 0.0;
+                    for &(own, opponent) in pairs.iter() {
+                        let to_win = target - own;
+                        let after_bust = chances.bust * (1.0 - win[idx(opponent, own)])
+                            + chances.wipeout * (1.0 - win[idx(opponent, 0)]);
+                        let mut threshold = to_win;
+                        for hand in (0..to_win).rev() {
+                            let roll = after_bust
+                                + chances
+                                    .sums
+                                    .iter()
+                                    .map(|&(sum, again, chance)| {
+                                        // Reaching the target wins by holding.
+                                        // Being forced to roll on is rare
+                                        // enough to count as a win, too.
+                                        let next = hand + sum;
+                                        chance
+                                            * match (next < to_win, again) {
+                                                (false, _) => 1.0,
+                                                (true, true) => rolling[next],
+                                                (true, false) => turn[next],
+                                            }
+                                    })
+                                    .sum::<f64>();
+                            let hold = if hand > 0 {
+                                1.0 - win[idx(opponent, own + hand)]
+                            } else {
+                                0.0
+                            };
+                            if hand > 0 && hold >= roll {
+                                threshold = hand;
+                            }
+                            rolling[hand] = roll;
+                            turn[hand] = roll.max(hold);
+                        }
+                        change = change.max((turn[0] - win[idx(own, opponent)]).abs());
+                        win[idx(own, opponent)] = turn[0];
+                        thresholds[idx(own, opponent)] = threshold;
+                    }
+                    sweep_change = sweep_change.max(change);
+                    if change < 1e-7 {
+                        break;
+                    }
+                }
+            }
+            if chances.wipeout == 0.0 || sweep_change < 1e-7 {
+                break;
+            }
+        }
+
+        Self { rules, thresholds }
+    }
+
+    /// Hand total to hold at for the scores
+    fn threshold(&self, own: usize, opponent: usize) -> usize {
+        let max = self.rules.target_score - 1;
+        self.thresholds[own.min(max) * self.rules.target_score + opponent.min(max)]
+    }
+}
+
+/// Decides the moves of the CPU
+#[derive(SystemParam)]
+struct CpuPlayer<'w> {
+    dice: Dice<'w>,
+    strategy: Res<'w, CpuStrategy>,
+    policy: Res<'w, OptimalPolicy>,
+}
+
+impl CpuPlayer<'_> {
+    /// Checks if the CPU keeps its hand instead of rolling again
+    fn holds(&self, scores: &Scores, hand_total: usize) -> bool {
+        self.strategy.holds(
+            &self.dice.rules,
+            &self.policy,
+            scores.cpu,
+            scores.player,
+            hand_total,
+        )
+    }
+}
+
+game_assets! {
+    /// This holds the handle the dice graphics. It represents an index to the
+    /// stored graphics for reuse
+    struct GameAssets {
+        // Dice asset, a grid of 6 images in a row with 52 pixels in size
+        sprite_sheet dice = ("dice.png", 52.0, 52.0, 6, 1),
+    }
 }
 
 #[derive(Clone, Copy, Resource)]
@@ -31,9 +365,294 @@ struct Scores {
     cpu: usize,
 }
 
+impl Scores {
+    /// The side ahead, the player wins a tie
+    fn winner(&self) -> Side {
+        if self.player < self.cpu {
+            Side::Cpu
+        } else {
+            Side::Player
+        }
+    }
+}
+
 #[derive(Component)]
-/// These is a marker to represent dice on the screen
-struct HandDie;
+/// These is a marker to represent dice on the screen, with the rolled face
+struct HandDie(usize);
+
+/// Time in ms the dice roll before they settle on the rolled face
+const ROLL_MS: u128 = 600;
+
+/// Dice of the last roll, while they are still rolling
+#[derive(Resource, Default)]
+struct RollingDice {
+    /// Dice that haven't settled yet
+    dice: Vec<Entity>,
+    /// Faces of the rolled dice
+    roll: Vec<usize>,
+    /// What the roll does to the turn once the dice settled
+    outcome: RollOutcome,
+}
+
+impl RollingDice {
+    /// Checks if the dice are still rolling
+    fn in_flight(&self) -> bool {
+        !self.dice.is_empty()
+    }
+
+    /// Checks if the hand may be kept, i.e. the dice settled and don't have
+    /// to roll again
+    fn may_hold(&self, rules: &PigRules) -> bool {
+        !self.in_flight() && !rules.must_roll_again(&self.roll)
+    }
+}
+
+/// Moves chosen by the player or the CPU
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+enum TurnAction {
+    /// Roll the dice again
+    Roll,
+    /// Keep the hand score and end the turn
+    Hold,
+}
+
+/// The two sides of a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Player,
+    Cpu,
+}
+
+impl Side {
+    /// Name shown in the turn log
+    fn name(&self) -> &'static str {
+        match self {
+            Side::Player => "Player",
+            Side::Cpu => "CPU",
+        }
+    }
+}
+
+/// Something that happened during a turn
+#[derive(Clone, Debug, PartialEq)]
+enum LogEntry {
+    /// The dice of a roll settled on these faces
+    Rolled(Side, Vec<usize>),
+    /// The roll lost the hand
+    Bust(Side),
+    /// The roll lost the hand and the banked score
+    Wipeout(Side),
+    /// The hand score was banked, giving a new total score
+    Held {
+        side: Side,
+        banked: usize,
+        total: usize,
+    },
+}
+
+impl LogEntry {
+    /// Describes the entry for the log panel
+    fn text(&self) -> String {
+        match self {
+            LogEntry::Rolled(side, roll) => {
+                let faces: Vec<String> = roll.iter().map(ToString::to_string).collect();
+                format!("{} rolled {}", side.name(), faces.join(", "))
+            }
+            LogEntry::Bust(side) => format!("{} went bust", side.name()),
+            LogEntry::Wipeout(side) => format!("{} rolled double ones, score lost", side.name()),
+            LogEntry::Held {
+                side,
+                banked,
+                total,
+            } => format!("{} banked {banked} (total {total})", side.name()),
+        }
+    }
+
+    /// Highlight color of busts and banked points
+    fn color(&self) -> Option<egui::Color32> {
+        match self {
+            LogEntry::Rolled(..) => None,
+            LogEntry::Bust(_) | LogEntry::Wipeout(_) => Some(egui::Color32::RED),
+            LogEntry::Held { .. } => Some(egui::Color32::GREEN),
+        }
+    }
+}
+
+/// History of the current game, kept until the next game starts
+#[derive(Resource, Default, Debug)]
+struct TurnLog(Vec<LogEntry>);
+
+impl TurnLog {
+    /// The last `count` entries, oldest first
+    fn recent(&self, count: usize) -> &[LogEntry] {
+        &self.0[self.0.len().saturating_sub(count)..]
+    }
+}
+
+/// File the session statistics are saved to
+const STATS_FILE: &str = "pig_stats.ron";
+
+/// Where to save the session statistics after each game, if at all
+#[derive(Resource)]
+struct StatsFile(Option<std::path::PathBuf>);
+
+/// Statistics of the games played in this session, kept across games
+#[derive(Resource, Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SessionStats {
+    games_played: usize,
+    player_wins: usize,
+    cpu_wins: usize,
+    /// The player's highest score banked in a single turn
+    best_turn: usize,
+    /// The player's most rolls in a row without going bust, across turns
+    longest_streak: usize,
+    /// Rolls without going bust since the player's last bust
+    #[serde(skip)]
+    streak: usize,
+}
+
+impl SessionStats {
+    /// Adds a finished game, with its final scores and the log of its turns
+    fn record_game(&mut self, scores: &Scores, log: &TurnLog) {
+        self.games_played += 1;
+        match scores.winner() {
+            Side::Player => self.player_wins += 1,
+            Side::Cpu => self.cpu_wins += 1,
+        }
+
+        let mut entries = log.0.iter().peekable();
+        while let Some(entry) = entries.next() {
+            match entry {
+                LogEntry::Rolled(Side::Player, _) => {
+                    let lost = matches!(
+                        entries.peek(),
+                        Some(LogEntry::Bust(Side::Player) | LogEntry::Wipeout(Side::Player))
+                    );
+                    self.streak = if lost { 0 } else { self.streak + 1 };
+                    self.longest_streak = self.longest_streak.max(self.streak);
+                }
+                LogEntry::Held {
+                    side: Side::Player,
+                    banked,
+                    ..
+                } => self.best_turn = self.best_turn.max(*banked),
+                _ => {}
+            }
+        }
+    }
+
+    /// Share of the games won by the player, in percent
+    fn win_rate(&self) -> f32 {
+        if self.games_played == 0 {
+            0.0
+        } else {
+            self.player_wins as f32 * 100.0 / self.games_played as f32
+        }
+    }
+
+    /// Reads statistics saved by a previous session
+    fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Saves the statistics for the next session
+    fn save(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Shows the statistics in a window
+    fn show(&self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "Games played: {} (won {:.0}%)",
+            self.games_played,
+            self.win_rate()
+        ));
+        ui.label(format!("Player wins: {}", self.player_wins));
+        ui.label(format!("CPU wins: {}", self.cpu_wins));
+        ui.label(format!("Best turn: {}", self.best_turn));
+        ui.label(format!("Longest streak: {} rolls", self.longest_streak));
+    }
+}
+
+/// The turn of the player or the CPU
+#[derive(SystemParam)]
+struct Turn<'w> {
+    phase: Res<'w, State<GamePhase>>,
+    next: ResMut<'w, NextState<GamePhase>>,
+    scores: ResMut<'w, Scores>,
+    log: ResMut<'w, TurnLog>,
+}
+
+impl Turn<'_> {
+    /// Whose turn it is
+    fn side(&self) -> Side {
+        match self.phase.get() {
+            GamePhase::Cpu => Side::Cpu,
+            _ => Side::Player,
+        }
+    }
+
+    /// Adds the hand score to the score of whoever's turn it is
+    fn keep(&mut self, hand_total: usize) {
+        let side = self.side();
+        let total = match side {
+            Side::Player => &mut self.scores.player,
+            Side::Cpu => &mut self.scores.cpu,
+        };
+        *total += hand_total;
+        let total = *total;
+        self.log.0.push(LogEntry::Held {
+            side,
+            banked: hand_total,
+            total,
+        });
+    }
+
+    /// Records the settled dice of a roll
+    fn rolled(&mut self, roll: &[usize], outcome: RollOutcome) {
+        let side = self.side();
+        self.log.0.push(LogEntry::Rolled(side, roll.to_vec()));
+        match outcome {
+            RollOutcome::Score => {}
+            RollOutcome::Bust => self.log.0.push(LogEntry::Bust(side)),
+            RollOutcome::Wipeout => self.log.0.push(LogEntry::Wipeout(side)),
+        }
+    }
+
+    /// Loses the banked score of whoever's turn it is
+    fn wipe_out(&mut self) {
+        match self.side() {
+            Side::Player => self.scores.player = 0,
+            Side::Cpu => self.scores.cpu = 0,
+        }
+    }
+
+    /// Passes the dice to the other side
+    fn end(&mut self) {
+        match self.side() {
+            Side::Player => self.next.set(GamePhase::Cpu),
+            Side::Cpu => self.next.set(GamePhase::Player),
+        }
+    }
+
+    /// Color of the dice rolled in this turn
+    fn dice_color(&self) -> Color {
+        match self.side() {
+            Side::Cpu => Color::Srgba(Srgba::new(0.0, 0.0, 1.0, 1.0)),
+            Side::Player => Color::WHITE,
+        }
+    }
+}
+
+/// The dice cycle through random faces while rolling
+fn dice_animations() -> Animations {
+    let frames = (0..6)
+        .map(|face| AnimationFrame::new(face, 50, vec![AnimationOption::GoToRandomFrame]))
+        .collect();
+    Animations::new().with_animation("Rolling", PerFrameAnimation::new(frames))
+}
 
 /// Wraps `Timer` in a bevy resource.
 #[derive(Resource)]
@@ -42,36 +661,77 @@ struct HandTImer(Timer);
 #[derive(Resource)]
 struct FinalScore(Scores);
 
-fn main() {
+/// Rolls a single six-sided die, all rolls of the game go through here
+fn roll_die(rng: &RandomNumberGenerator) -> usize {
+    rng.range(1..=6)
+}
+
+/// Seed of the next game, if it should be replayable. Two seeded games with
+/// the same decisions see the same dice.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+struct GameSeed(Option<u64>);
+
+impl GameSeed {
+    /// Reads the seed from the `PIG_SEED` environment variable
+    fn from_env() -> Self {
+        Self(
+            std::env::var("PIG_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok()),
+        )
+    }
+}
+
+/// Seeds the dice for a replayable game, or shuffles them for a fresh one
+fn seed_dice(seed: Res<GameSeed>, rng: Res<RandomNumberGenerator>, mut commands: Commands) {
+    match seed.0 {
+        Some(seed) => commands.insert_resource(RandomNumberGenerator::seeded(seed)),
+        None => rng.randomize(),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
     let mut app = App::new();
 
+    add_phase!(app, GamePhase, GamePhase::Setup,
+        start => [setup_rules],
+        run => [rules_menu],
+        exit => [cleanup::<SetupElement>]
+    );
+
     add_phase!(app, GamePhase, GamePhase::Start,
-        start => [setup ],
+        start => [seed_dice, setup, prepare_cpu],
         run => [start_game],
         exit => []
     );
 
     add_phase!(app, GamePhase, GamePhase::Player,
         start => [],
-        run => [player, check_game_over, display_score],
+        run => [player, take_turn, cycle_animations, settle_roll, check_game_over, display_score, turn_log_panel],
         exit => []
     );
 
     add_phase!(app, GamePhase, GamePhase::Cpu,
         start => [],
-        run => [cpu, check_game_over, display_score],
+        run => [cpu, take_turn, cycle_animations, settle_roll, check_game_over, display_score, turn_log_panel],
         exit => []
     );
 
-    add_phase!(app, GamePhase, GamePhase::End,
+    add_phase!(app, GamePhase, GamePhase::MainMenu,
         start => [],
+        run => [display_session_stats],
+        exit => []
+    );
+
+    add_phase!(app, GamePhase, GamePhase::End,
+        start => [record_stats],
         run => [end_game],
         exit => [cleanup::<GameElement>]
     );
 
     add_phase!(app, GamePhase, GamePhase::GameOver,
         start => [],
-        run => [display_final_score],
+        run => [display_final_score, display_session_stats, turn_log_panel],
         exit => []
     );
 
@@ -88,31 +748,123 @@ fn main() {
     })
     .add_plugins(GameStatePlugin::new(
         GamePhase::MainMenu,
-        GamePhase::Start,
+        GamePhase::Setup,
         GamePhase::GameOver,
     ))
     .add_plugins(RandomPlugin)
+    .add_plugins(GameAssets::asset_manager()?)
+    .init_resource::<PigRules>()
+    .init_resource::<CpuStrategy>()
+    .insert_resource(OptimalPolicy::new(PigRules::default()))
+    .insert_resource(dice_animations())
+    .insert_resource(SessionStats::load(STATS_FILE.as_ref()).unwrap_or_default())
+    .insert_resource(StatsFile(Some(STATS_FILE.into())))
+    .insert_resource(GameSeed::from_env())
+    .add_event::<TurnAction>()
     .run();
+
+    Ok(())
 }
 
-fn setup(
-    asset_server: Res<AssetServer>,
-    mut commands: Commands,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+fn setup_rules(mut commands: Commands) {
+    commands.spawn((Camera2d, SetupElement));
+}
+
+/// Lets the player adjust the rules and the CPU strategy, the last ones are
+/// kept for the next game
+fn rules_menu(
+    mut rules: ResMut<PigRules>,
+    mut strategy: ResMut<CpuStrategy>,
+    mut seed: ResMut<GameSeed>,
+    mut state: ResMut<NextState<GamePhase>>,
+    mut egui_context: EguiContexts,
 ) {
-    commands.spawn(Camera2d::default()).insert(GameElement);
+    egui::Window::new("Rules").show(egui_context.ctx_mut(), |ui| {
+        let rules = rules.as_mut();
+        ui.add(egui::Slider::new(&mut rules.target_score, 10..=200).text("Points to win"));
+        egui::ComboBox::from_label("Variant")
+            .selected_text(rules.variant.name())
+            .show_ui(ui, |ui| {
+                for option in [
+                    PigVariant::Classic,
+                    PigVariant::TwoDice {
+                        doubles_roll_again: false,
+                    },
+                ] {
+                    let selected = rules.variant.name() == option.name();
+                    if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                        rules.variant = option;
+                    }
+                }
+            });
+        match &mut rules.variant {
+            PigVariant::Classic => {
+                ui.add(egui::Slider::new(&mut rules.dice_per_roll, 1..=6).text("Dice per roll"));
+                ui.add(egui::Slider::new(&mut rules.bust_on, 1..=6).text("Bust on"));
+            }
+            PigVariant::TwoDice { doubles_roll_again } => {
+                ui.checkbox(doubles_roll_again, "Doubles must roll again");
+            }
+        }
 
-    // Load dice asset, define a grid of 6 images in a row with 52 pixels in size
-    let texture = asset_server.load("dice.png");
-    let layout = TextureAtlasLayout::from_grid(UVec2::splat(52), 6, 1, None, None);
-    let texture_alias_layout = texture_atlas_layouts.add(layout);
+        let strategy = strategy.as_mut();
+        egui::ComboBox::from_label("CPU strategy")
+            .selected_text(strategy.name())
+            .show_ui(ui, |ui| {
+                for option in [
+                    CpuStrategy::HoldAtN(20),
+                    CpuStrategy::KeepPace(10),
+                    CpuStrategy::Optimal,
+                ] {
+                    let selected = strategy.name() == option.name();
+                    if ui.selectable_label(selected, option.name()).clicked() && !selected {
+                        *strategy = option;
+                    }
+                }
+            });
+        match strategy {
+            CpuStrategy::HoldAtN(n) => {
+                ui.add(egui::Slider::new(n, 1..=100).text("CPU holds at"));
+            }
+            CpuStrategy::KeepPace(margin) => {
+                ui.add(egui::Slider::new(margin, 0..=50).text("CPU margin"));
+            }
+            CpuStrategy::Optimal => {}
+        }
 
-    commands.insert_resource(GameAssets {
-        image: texture,
-        layout: texture_alias_layout,
+        ui.horizontal(|ui| {
+            let mut seeded = seed.0.is_some();
+            ui.checkbox(&mut seeded, "Seeded game");
+            if seeded {
+                ui.add(egui::DragValue::new(seed.0.get_or_insert(0)));
+            } else {
+                seed.0 = None;
+            }
+        });
+
+        if ui.button("Start Game").clicked() {
+            state.set(GamePhase::Start);
+        }
     });
+}
+
+/// Generates the optimal policy again, if the rules changed since
+fn prepare_cpu(
+    rules: Res<PigRules>,
+    strategy: Res<CpuStrategy>,
+    mut policy: ResMut<OptimalPolicy>,
+) {
+    if *strategy == CpuStrategy::Optimal && policy.rules != *rules {
+        *policy = OptimalPolicy::new(*rules);
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2d::default()).insert(GameElement);
 
     commands.insert_resource(Scores { cpu: 0, player: 0 });
+    commands.insert_resource(RollingDice::default());
+    commands.insert_resource(TurnLog::default());
     commands.insert_resource(HandTImer(Timer::from_seconds(0.5, TimerMode::Repeating)));
 }
 
@@ -123,31 +875,42 @@ fn display_score(scores: Res<Scores>, mut egui_context: EguiContexts) {
     });
 }
 
+/// Number of dice shown in a row, further dice continue in the next row
+const DICE_PER_ROW: usize = 15;
+
+/// Spawns the dice of a roll after the dice already in the hand. They roll
+/// for a while before showing their face.
 fn spawn_die(
-    hand_query: &Query<(Entity, &Sprite), With<HandDie>>,
+    hand_query: &Query<(Entity, &HandDie)>,
     commands: &mut Commands,
     assets: &GameAssets,
-    new_roll: usize,
+    roll: &[usize],
     color: Color,
-) {
-    let rolled_die = hand_query.iter().count() as f32 * 52.0;
-
-    let mut sprite = Sprite::from_atlas_image(
-        assets.image.clone(),
-        TextureAtlas {
-            layout: assets.layout.clone(),
-            index: new_roll - 1,
-        },
-    );
+) -> Vec<Entity> {
+    let dice_in_hand = hand_query.iter().count();
+
+    roll.iter()
+        .enumerate()
+        .map(|(i, face)| {
+            let (image, layout) = assets.dice();
+            let mut sprite = Sprite::from_atlas_image(image, TextureAtlas { layout, index: 0 });
 
-    sprite.color = color;
+            sprite.color = color;
 
-    commands.spawn((
-        sprite,
-        Transform::from_xyz(rolled_die - 400.0, 60.0, 1.0),
-        HandDie,
-        GameElement,
-    ));
+            let slot = dice_in_hand + i;
+            let x = (slot % DICE_PER_ROW) as f32 * 52.0 - 400.0;
+            let y = 60.0 - (slot / DICE_PER_ROW) as f32 * 52.0;
+            commands
+                .spawn((
+                    sprite,
+                    Transform::from_xyz(x, y, 1.0),
+                    HandDie(*face),
+                    AnimationCycle::new("Rolling").settle_after(ROLL_MS, face - 1),
+                    GameElement,
+                ))
+                .id()
+        })
+        .collect()
 }
 
 fn start_game(mut state: ResMut<NextState<GamePhase>>) {
@@ -159,107 +922,806 @@ fn end_game(mut state: ResMut<NextState<GamePhase>>, scores: Res<Scores>, mut co
     state.set(GamePhase::GameOver);
 }
 
-fn check_game_over(mut state: ResMut<NextState<GamePhase>>, scores: Res<Scores>) {
-    if scores.cpu >= 100 || scores.player >= 100 {
+/// Adds the finished game to the session statistics, and saves them
+fn record_stats(
+    mut stats: ResMut<SessionStats>,
+    scores: Res<Scores>,
+    log: Res<TurnLog>,
+    file: Res<StatsFile>,
+) {
+    stats.record_game(&scores, &log);
+    if let Some(path) = &file.0
+        && let Err(error) = stats.save(path)
+    {
+        warn!("Session statistics not saved: {error}");
+    }
+}
+
+fn display_session_stats(stats: Res<SessionStats>, mut egui_context: EguiContexts) {
+    egui::Window::new("Session Statistics").show(egui_context.ctx_mut(), |ui| stats.show(ui));
+}
+
+fn check_game_over(
+    mut state: ResMut<NextState<GamePhase>>,
+    scores: Res<Scores>,
+    rules: Res<PigRules>,
+) {
+    if rules.has_won(scores.cpu) || rules.has_won(scores.player) {
         state.set(GamePhase::End);
     }
 }
 
-fn display_final_score(scores: Res<FinalScore>, mut egui_context: EguiContexts) {
+fn display_final_score(
+    scores: Res<FinalScore>,
+    seed: Res<GameSeed>,
+    mut egui_context: EguiContexts,
+) {
     egui::Window::new("Total Scores").show(egui_context.ctx_mut(), |ui| {
         ui.label(&format!("Player: {}", scores.0.player));
         ui.label(&format!("CPU: {}", scores.0.cpu));
-        if scores.0.player < scores.0.cpu {
+        if scores.0.winner() == Side::Cpu {
             ui.label("CPU is the winner!");
         } else {
             ui.label("Player is the winner!");
         }
+        if let Some(seed) = seed.0 {
+            ui.label(&format!("Seed: {seed} (share it to replay the same dice)"));
+        }
     });
 }
 
-fn clear_die(hand_query: &Query<(Entity, &Sprite), With<HandDie>>, commands: &mut Commands) {
+/// Number of entries shown at the top of the turn log
+const RECENT_LOG_ENTRIES: usize = 8;
+
+/// Shows a log entry, highlighted by its kind
+fn log_label(ui: &mut egui::Ui, entry: &LogEntry) {
+    match entry.color() {
+        Some(color) => ui.colored_label(color, entry.text()),
+        None => ui.label(entry.text()),
+    };
+}
+
+/// Side panel with the latest turns, and the full history of the game below
+fn turn_log_panel(log: Res<TurnLog>, mut egui_context: EguiContexts) {
+    egui::SidePanel::right("turn_log").show(egui_context.ctx_mut(), |ui| {
+        ui.heading("Turn Log");
+        for entry in log.recent(RECENT_LOG_ENTRIES) {
+            log_label(ui, entry);
+        }
+
+        ui.separator();
+        ui.collapsing("Full history", |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for entry in log.0.iter() {
+                    log_label(ui, entry);
+                }
+            });
+        });
+    });
+}
+
+fn clear_die(hand_query: &Query<(Entity, &HandDie)>, commands: &mut Commands) {
     hand_query
         .iter()
         .for_each(|(entity, _)| commands.entity(entity).despawn());
 }
 
+/// Sum of the faces of all dice in the hand
+fn hand_total(hand_query: &Query<(Entity, &HandDie)>) -> usize {
+    hand_query.iter().map(|(_, die)| die.0).sum()
+}
+
 fn player(
-    hand_query: Query<(Entity, &Sprite), With<HandDie>>,
-    mut commands: Commands,
-    rng: Res<RandomNumberGenerator>,
-    assets: Res<GameAssets>,
-    mut scores: ResMut<Scores>,
-    mut state: ResMut<NextState<GamePhase>>,
+    hand_query: Query<(Entity, &HandDie)>,
+    rolling: Res<RollingDice>,
+    rules: Res<PigRules>,
+    mut actions: EventWriter<TurnAction>,
     mut egui_context: EguiContexts,
 ) {
     egui::Window::new("Play Options").show(egui_context.ctx_mut(), |ui| {
-        let hand_score: usize = hand_query
-            .iter()
-            .map(|(_, ts)| ts.texture_atlas.as_ref().unwrap().index + 1)
-            .sum();
-
-        ui.label(&format!("Score for this hand: {hand_score}"));
-
-        if ui.button("Roll Dice").clicked() {
-            let new_roll = rng.range(1..7);
-            if new_roll == 1 {
-                // End turn
-                clear_die(&hand_query, &mut commands);
-                state.set(GamePhase::Cpu);
-            } else {
-                spawn_die(&hand_query, &mut commands, &assets, new_roll, Color::WHITE);
-            }
+        if rolling.in_flight() {
+            ui.label("Rolling...");
+        } else {
+            ui.label(&format!("Score for this hand: {}", hand_total(&hand_query)));
+        }
+        if !rolling.in_flight() && !rolling.may_hold(&rules) {
+            ui.label("Doubles - roll again!");
         }
 
-        if ui.button("Pass - Keep Hand Score").clicked() {
-            let hand_total: usize = hand_query
-                .iter()
-                .map(|(_, ts)| ts.texture_atlas.as_ref().unwrap().index + 1)
-                .sum();
-            scores.player += hand_total;
-            clear_die(&hand_query, &mut commands);
-            state.set(GamePhase::Cpu);
+        if ui
+            .add_enabled(!rolling.in_flight(), egui::Button::new("Roll Dice"))
+            .clicked()
+        {
+            actions.write(TurnAction::Roll);
+        }
+
+        if ui
+            .add_enabled(
+                rolling.may_hold(&rules),
+                egui::Button::new("Pass - Keep Hand Score"),
+            )
+            .clicked()
+        {
+            actions.write(TurnAction::Hold);
         }
     });
 }
 
 fn cpu(
-    hand_query: Query<(Entity, &Sprite), With<HandDie>>,
-    mut state: ResMut<NextState<GamePhase>>,
-    mut scores: ResMut<Scores>,
-    rng: Res<RandomNumberGenerator>,
-    mut commands: Commands,
-    assets: Res<GameAssets>,
+    hand_query: Query<(Entity, &HandDie)>,
+    scores: Res<Scores>,
+    rolling: Res<RollingDice>,
+    brain: CpuPlayer,
+    mut actions: EventWriter<TurnAction>,
     mut timer: ResMut<HandTImer>,
     time: Res<Time>,
 ) {
     timer.0.tick(time.delta());
-    if timer.0.just_finished() {
-        let hand_total: usize = hand_query
-            .iter()
-            .map(|(_, ts)| ts.texture_atlas.as_ref().unwrap().index + 1)
-            .sum();
-
-        if hand_total < 20 && scores.cpu + hand_total < 100 {
-            let new_roll = rng.range(1..=6);
-            if new_roll == 1 {
-                clear_die(&hand_query, &mut commands);
-                state.set(GamePhase::Player);
+    if timer.0.just_finished() && !rolling.in_flight() {
+        if rolling.may_hold(&brain.dice.rules) && brain.holds(&scores, hand_total(&hand_query)) {
+            actions.write(TurnAction::Hold);
+        } else {
+            actions.write(TurnAction::Roll);
+        }
+    }
+}
+
+/// Carries out the move of the player or the CPU. Moves are ignored while
+/// the dice of the last roll are still rolling.
+fn take_turn(
+    mut actions: ResMut<Events<TurnAction>>,
+    hand_query: Query<(Entity, &HandDie)>,
+    mut rolling: ResMut<RollingDice>,
+    mut commands: Commands,
+    dice: Dice,
+    assets: GameAssets,
+    mut turn: Turn,
+) {
+    // Draining keeps the other side from seeing the move after the turn
+    // changed, only the latest move of a frame counts
+    let Some(action) = actions.drain().last() else {
+        return;
+    };
+    if rolling.in_flight() {
+        return;
+    }
+
+    match action {
+        TurnAction::Roll => {
+            let roll = dice.roll();
+            rolling.outcome = dice.rules.outcome(&roll);
+            let color = turn.dice_color();
+            rolling.dice = spawn_die(&hand_query, &mut commands, &assets, &roll, color);
+            rolling.roll = roll;
+        }
+        TurnAction::Hold => {
+            if !rolling.may_hold(&dice.rules) {
+                return;
+            }
+            rolling.roll.clear();
+            turn.keep(hand_total(&hand_query));
+            clear_die(&hand_query, &mut commands);
+            turn.end();
+        }
+    }
+}
+
+/// Waits for the rolling dice to settle, and ends the turn if they lost the
+/// hand
+fn settle_roll(
+    mut finished: EventReader<AnimationFinished>,
+    hand_query: Query<(Entity, &HandDie)>,
+    mut rolling: ResMut<RollingDice>,
+    mut commands: Commands,
+    mut turn: Turn,
+) {
+    if !rolling.in_flight() {
+        finished.clear();
+        return;
+    }
+    for event in finished.read() {
+        rolling.dice.retain(|die| *die != event.entity);
+    }
+    if rolling.in_flight() {
+        return;
+    }
+
+    turn.rolled(&rolling.roll, rolling.outcome);
+    match rolling.outcome {
+        RollOutcome::Score => {}
+        RollOutcome::Bust => {
+            clear_die(&hand_query, &mut commands);
+            turn.end();
+        }
+        RollOutcome::Wipeout => {
+            clear_die(&hand_query, &mut commands);
+            turn.wipe_out();
+            turn.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn house_rules() -> PigRules {
+        PigRules {
+            target_score: 20,
+            dice_per_roll: 2,
+            bust_on: 6,
+            variant: PigVariant::Classic,
+        }
+    }
+
+    /// The optimal policy for the default rules, shared by the tests as it
+    /// takes a while to generate
+    fn default_policy() -> &'static OptimalPolicy {
+        static POLICY: std::sync::OnceLock<OptimalPolicy> = std::sync::OnceLock::new();
+        POLICY.get_or_init(|| OptimalPolicy::new(PigRules::default()))
+    }
+
+    /// Plays a game between two strategies, and checks if the first one wins
+    fn first_wins(
+        rules: &PigRules,
+        policy: &OptimalPolicy,
+        strategies: [CpuStrategy; 2],
+        starting: usize,
+        rng: &RandomNumberGenerator,
+    ) -> bool {
+        let (mut scores, mut current, mut hand, mut forced) = ([0, 0], starting, 0, false);
+        loop {
+            let other = 1 - current;
+            let holds = !forced
+                && strategies[current].holds(rules, policy, scores[current], scores[other], hand);
+            if holds {
+                scores[current] += hand;
+                if rules.has_won(scores[current]) {
+                    return current == 0;
+                }
+                (current, hand) = (other, 0);
             } else {
-                spawn_die(
-                    &hand_query,
-                    &mut commands,
-                    &assets,
-                    new_roll,
-                    Color::Srgba(Srgba::new(0.0, 0.0, 1.0, 1.0)),
-                );
+                let roll = rules.roll(rng);
+                forced = rules.must_roll_again(&roll);
+                match rules.outcome(&roll) {
+                    RollOutcome::Score => hand += roll.iter().sum::<usize>(),
+                    RollOutcome::Bust => (current, hand) = (other, 0),
+                    RollOutcome::Wipeout => {
+                        scores[current] = 0;
+                        (current, hand) = (other, 0);
+                    }
+                }
             }
-        } else {
-            scores.cpu += hand_total;
-            state.set(GamePhase::Player);
-            hand_query
+        }
+    }
+
+    #[test]
+    fn scripted_cpu_game_follows_rules() {
+        let rules = house_rules();
+        let policy = OptimalPolicy::new(rules);
+        let strategy = CpuStrategy::HoldAtN(8);
+        let mut rolls = [[2, 3], [1, 1], [6, 2], [4, 5], [1, 2], [3, 3], [5, 5]].into_iter();
+        let (mut score, mut hand, mut busts) = (0, 0, 0);
+
+        while !rules.has_won(score) {
+            if strategy.holds(&rules, &policy, score, 0, hand) {
+                score += hand;
+                hand = 0;
+                continue;
+            }
+            let roll = rolls.next().expect("The CPU should have held");
+            if rules.outcome(&roll) == RollOutcome::Bust {
+                hand = 0;
+                busts += 1;
+            } else {
+                hand += roll.iter().sum::<usize>();
+            }
+        }
+
+        // Ones are safe under these rules, only the roll with a six busts
+        assert_eq!(1, busts);
+        // Holds at 9 and 18, then wins with a hand of 10
+        assert_eq!(28, score);
+        assert!(rolls.next().is_none());
+    }
+
+    #[test]
+    fn hold_at_n_decisions() {
+        let rules = PigRules::default();
+        let policy = default_policy();
+        let holds =
+            |cpu, player, hand| CpuStrategy::HoldAtN(20).holds(&rules, policy, cpu, player, hand);
+        assert!(!holds(0, 0, 19));
+        assert!(holds(0, 0, 20));
+        assert!(!holds(50, 90, 19));
+        // A winning hand is kept, an empty one never
+        assert!(holds(95, 0, 5));
+        assert!(!CpuStrategy::HoldAtN(0).holds(&rules, policy, 0, 0, 0));
+    }
+
+    #[test]
+    fn keep_pace_decisions() {
+        let rules = PigRules::default();
+        let policy = default_policy();
+        let holds =
+            |cpu, player, hand| CpuStrategy::KeepPace(5).holds(&rules, policy, cpu, player, hand);
+        // Behind by 30, the hand has to catch up
+        assert!(!holds(10, 40, 34));
+        assert!(holds(10, 40, 35));
+        // Ahead, the margin is enough
+        assert!(!holds(50, 20, 4));
+        assert!(holds(50, 20, 5));
+    }
+
+    #[test]
+    fn optimal_decisions() {
+        let rules = PigRules::default();
+        let policy = default_policy();
+        let holds =
+            |cpu, player, hand| CpuStrategy::Optimal.holds(&rules, policy, cpu, player, hand);
+        // Opening turn
+        assert!(!holds(0, 0, 20));
+        assert!(holds(0, 0, 21));
+        // Far behind an opponent about to win, only a win is worth keeping
+        assert!(!holds(0, 99, 60));
+        assert!(holds(0, 99, 100));
+        // Plays safer when far ahead
+        assert!(policy.threshold(80, 0) < policy.threshold(0, 80));
+    }
+
+    #[test]
+    fn optimal_beats_hold_at_20() {
+        let rules = PigRules::default();
+        let policy = default_policy();
+        let rng = RandomNumberGenerator::seeded(1);
+        let strategies = [CpuStrategy::Optimal, CpuStrategy::HoldAtN(20)];
+
+        let games = 20_000;
+        let wins = (0..games)
+            .filter(|game| first_wins(&rules, policy, strategies, game % 2, &rng))
+            .count();
+        assert!(wins > games * 51 / 100, "Optimal won {wins} of {games}");
+    }
+
+    #[test]
+    fn game_ends_at_target_score() {
+        let run = |rules: PigRules, scores: Scores| {
+            let mut app = App::new();
+            app.add_plugins(bevy::state::app::StatesPlugin)
+                .insert_state(GamePhase::Cpu)
+                .insert_resource(rules)
+                .insert_resource(scores)
+                .add_systems(Update, check_game_over);
+            app.update();
+            app.update();
+            *app.world().resource::<State<GamePhase>>().get()
+        };
+
+        let scores = Scores {
+            player: 12,
+            cpu: 20,
+        };
+        assert_eq!(GamePhase::End, run(house_rules(), scores));
+        assert_eq!(GamePhase::Cpu, run(PigRules::default(), scores));
+    }
+
+    /// Runs turns of the player with scripted moves
+    fn turn_app(rules: PigRules, seed: u64) -> App {
+        let mut store = AssetStore::default();
+        store.insert_atlas_handle("dice", default(), default());
+        let mut app = App::new();
+        app.add_plugins(bevy::state::app::StatesPlugin)
+            .insert_state(GamePhase::Player)
+            .insert_resource(rules)
+            .insert_resource(RandomNumberGenerator::seeded(seed))
+            .insert_resource(Scores { player: 0, cpu: 0 })
+            .insert_resource(dice_animations())
+            .insert_resource(store)
+            .init_resource::<LoadedAssets>()
+            .init_resource::<RollingDice>()
+            .init_resource::<TurnLog>()
+            .init_resource::<Time<Virtual>>()
+            .init_resource::<Time<Real>>()
+            .add_event::<TurnAction>()
+            .add_event::<AnimationFinished>()
+            .add_systems(Update, (take_turn, cycle_animations, settle_roll).chain());
+        app
+    }
+
+    /// Lets 100ms pass, after sending a move
+    fn next_frame(app: &mut App, action: Option<TurnAction>) {
+        let world = app.world_mut();
+        if let Some(action) = action {
+            world.send_event(action);
+        }
+        let frame = std::time::Duration::from_millis(100);
+        world.resource_mut::<Time<Real>>().advance_by(frame);
+        world.resource_mut::<Time<Virtual>>().advance_by(frame);
+        app.update();
+    }
+
+    fn dice(app: &mut App) -> Vec<(usize, usize)> {
+        app.world_mut()
+            .query::<(&HandDie, &Sprite)>()
+            .iter(app.world())
+            .map(|(die, sprite)| (die.0, sprite.texture_atlas.as_ref().unwrap().index))
+            .collect()
+    }
+
+    fn phase(app: &App) -> GamePhase {
+        *app.world().resource::<State<GamePhase>>().get()
+    }
+
+    #[test]
+    fn moves_are_ignored_while_dice_roll() {
+        let rules = PigRules {
+            target_score: 100,
+            dice_per_roll: 2,
+            // No die shows a seven, so rolls never bust
+            bust_on: 7,
+            variant: PigVariant::Classic,
+        };
+        let mut app = turn_app(rules, 3);
+        next_frame(&mut app, Some(TurnAction::Roll));
+        assert_eq!(2, dice(&mut app).len());
+        assert!(app.world().resource::<RollingDice>().in_flight());
+
+        // Neither rolling again nor holding is possible until the dice settle
+        next_frame(&mut app, Some(TurnAction::Roll));
+        next_frame(&mut app, Some(TurnAction::Hold));
+        next_frame(&mut app, None);
+        assert_eq!(2, dice(&mut app).len());
+        assert_eq!(0, app.world().resource::<Scores>().player);
+        assert_eq!(GamePhase::Player, phase(&app));
+
+        for _ in 0..3 {
+            next_frame(&mut app, None);
+        }
+        assert!(!app.world().resource::<RollingDice>().in_flight());
+        // The dice show the rolled faces
+        let settled = dice(&mut app);
+        assert!(settled.iter().all(|(face, index)| *index == face - 1));
+
+        next_frame(&mut app, Some(TurnAction::Hold));
+        next_frame(&mut app, None);
+        let hand: usize = settled.iter().map(|(face, _)| face).sum();
+        assert_eq!(hand, app.world().resource::<Scores>().player);
+        assert!(dice(&mut app).is_empty());
+        assert_eq!(GamePhase::Cpu, phase(&app));
+    }
+
+    #[test]
+    fn bust_ends_turn_once_dice_settled() {
+        let rules = PigRules {
+            target_score: 100,
+            dice_per_roll: 30,
+            bust_on: 1,
+            variant: PigVariant::Classic,
+        };
+        let mut app = turn_app(rules, 3);
+        next_frame(&mut app, Some(TurnAction::Roll));
+        assert_eq!(
+            RollOutcome::Bust,
+            app.world().resource::<RollingDice>().outcome
+        );
+
+        // The frame of the roll counts towards the 600ms
+        for _ in 0..4 {
+            next_frame(&mut app, None);
+            assert_eq!(GamePhase::Player, phase(&app));
+            assert_eq!(30, dice(&mut app).len());
+        }
+        next_frame(&mut app, None);
+        next_frame(&mut app, None);
+        assert!(dice(&mut app).is_empty());
+        assert_eq!(GamePhase::Cpu, phase(&app));
+    }
+
+    #[test]
+    fn turn_log_records_rolls_busts_and_holds() {
+        let mut app = turn_app(
+            PigRules {
+                target_score: 100,
+                dice_per_roll: 2,
+                bust_on: 7,
+                variant: PigVariant::Classic,
+            },
+            3,
+        );
+        let settle = |app: &mut App| {
+            for _ in 0..6 {
+                next_frame(app, None);
+            }
+        };
+        next_frame(&mut app, Some(TurnAction::Roll));
+        let first: Vec<usize> = dice(&mut app).iter().map(|(face, _)| *face).collect();
+        // Rolls are logged once the dice settled
+        assert!(app.world().resource::<TurnLog>().0.is_empty());
+        settle(&mut app);
+        next_frame(&mut app, Some(TurnAction::Hold));
+        next_frame(&mut app, None);
+        assert_eq!(GamePhase::Cpu, phase(&app));
+
+        // With 30 dice, the CPU's roll contains a one
+        *app.world_mut().resource_mut::<PigRules>() = PigRules {
+            target_score: 100,
+            dice_per_roll: 30,
+            bust_on: 1,
+            variant: PigVariant::Classic,
+        };
+        next_frame(&mut app, Some(TurnAction::Roll));
+        settle(&mut app);
+        next_frame(&mut app, None);
+        assert_eq!(GamePhase::Player, phase(&app));
+
+        let banked: usize = first.iter().sum();
+        let log = &app.world().resource::<TurnLog>().0;
+        assert_eq!(4, log.len());
+        assert_eq!(LogEntry::Rolled(Side::Player, first.clone()), log[0]);
+        assert_eq!(
+            LogEntry::Held {
+                side: Side::Player,
+                banked,
+                total: banked
+            },
+            log[1]
+        );
+        assert!(matches!(&log[2], LogEntry::Rolled(Side::Cpu, roll) if roll.len() == 30));
+        assert_eq!(LogEntry::Bust(Side::Cpu), log[3]);
+
+        assert_eq!(
+            format!("Player banked {banked} (total {banked})"),
+            log[1].text()
+        );
+        assert_eq!("CPU went bust", log[3].text());
+        assert_eq!(Some(egui::Color32::RED), log[3].color());
+    }
+
+    fn two_dice(doubles_roll_again: bool) -> PigRules {
+        PigRules {
+            variant: PigVariant::TwoDice { doubles_roll_again },
+            ..PigRules::default()
+        }
+    }
+
+    #[test]
+    fn two_dice_outcomes() {
+        let rules = two_dice(true);
+        assert_eq!(2, rules.roll(&RandomNumberGenerator::seeded(1)).len());
+        assert_eq!(RollOutcome::Score, rules.outcome(&[3, 5]));
+        assert_eq!(RollOutcome::Bust, rules.outcome(&[1, 5]));
+        assert_eq!(RollOutcome::Bust, rules.outcome(&[6, 1]));
+        assert_eq!(RollOutcome::Wipeout, rules.outcome(&[1, 1]));
+        assert_eq!(RollOutcome::Score, rules.outcome(&[4, 4]));
+
+        assert!(rules.must_roll_again(&[4, 4]));
+        assert!(!rules.must_roll_again(&[1, 1]));
+        assert!(!rules.must_roll_again(&[4, 5]));
+        assert!(!two_dice(false).must_roll_again(&[4, 4]));
+
+        let chances = rules.roll_outcomes();
+        assert!((chances.bust - 10.0 / 36.0).abs() < 1e-9);
+        assert!((chances.wipeout - 1.0 / 36.0).abs() < 1e-9);
+        // Double twos are the only way to roll a four
+        assert_eq!(
+            vec![(4, true, 1.0 / 36.0)],
+            chances
+                .sums
                 .iter()
-                .for_each(|(entity, _)| commands.entity(entity).despawn());
+                .filter(|(sum, ..)| *sum == 4)
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Starts a turn of the player with a banked score of 40, whose first
+    /// roll matches the condition
+    fn two_dice_turn(rules: PigRules, first_roll: impl Fn(&[usize]) -> bool) -> App {
+        let seed = (0..)
+            .find(|seed| first_roll(&rules.roll(&RandomNumberGenerator::seeded(*seed))))
+            .unwrap();
+        let mut app = turn_app(rules, seed);
+        app.insert_resource(Scores {
+            player: 40,
+            cpu: 10,
+        });
+        next_frame(&mut app, Some(TurnAction::Roll));
+        for _ in 0..6 {
+            next_frame(&mut app, None);
+        }
+        app
+    }
+
+    #[test]
+    fn single_one_loses_the_hand() {
+        let mut app = two_dice_turn(two_dice(false), |roll| {
+            roll.iter().filter(|face| **face == 1).count() == 1
+        });
+        assert_eq!(GamePhase::Cpu, phase(&app));
+        assert_eq!(40, app.world().resource::<Scores>().player);
+        assert!(dice(&mut app).is_empty());
+    }
+
+    #[test]
+    fn double_ones_wipe_the_banked_score() {
+        let mut app = two_dice_turn(two_dice(false), |roll| roll == [1, 1]);
+        assert_eq!(GamePhase::Cpu, phase(&app));
+        let scores = app.world().resource::<Scores>();
+        assert_eq!((0, 10), (scores.player, scores.cpu));
+        assert!(dice(&mut app).is_empty());
+        let log = &app.world().resource::<TurnLog>().0;
+        assert_eq!(Some(&LogEntry::Wipeout(Side::Player)), log.last());
+    }
+
+    #[test]
+    fn doubles_score_and_may_force_another_roll() {
+        let is_double = |roll: &[usize]| roll[0] == roll[1] && roll[0] != 1;
+
+        // Doubles score normally
+        let mut app = two_dice_turn(two_dice(false), is_double);
+        let faces: Vec<usize> = dice(&mut app).iter().map(|(face, _)| *face).collect();
+        next_frame(&mut app, Some(TurnAction::Hold));
+        next_frame(&mut app, None);
+        assert_eq!(GamePhase::Cpu, phase(&app));
+        assert_eq!(
+            40 + faces.iter().sum::<usize>(),
+            app.world().resource::<Scores>().player
+        );
+
+        // Unless they have to roll again
+        let mut app = two_dice_turn(two_dice(true), is_double);
+        next_frame(&mut app, Some(TurnAction::Hold));
+        next_frame(&mut app, None);
+        assert_eq!(GamePhase::Player, phase(&app));
+        assert_eq!(40, app.world().resource::<Scores>().player);
+        assert_eq!(2, dice(&mut app).len());
+        next_frame(&mut app, Some(TurnAction::Roll));
+        assert_eq!(4, dice(&mut app).len());
+    }
+
+    #[test]
+    fn two_dice_policy_plays_safer_with_a_score_to_lose() {
+        let rules = PigRules {
+            target_score: 40,
+            ..two_dice(false)
+        };
+        let policy = OptimalPolicy::new(rules);
+        let classic = OptimalPolicy::new(PigRules {
+            target_score: 40,
+            ..PigRules::default()
+        });
+        // Ahead with a lot banked, risking double ones isn't worth it
+        assert!(policy.threshold(30, 0) <= classic.threshold(30, 0));
+        let rng = RandomNumberGenerator::seeded(2);
+        let strategies = [CpuStrategy::Optimal, CpuStrategy::HoldAtN(20)];
+        let games = 2_000;
+        let wins = (0..games)
+            .filter(|game| first_wins(&rules, &policy, strategies, game % 2, &rng))
+            .count();
+        assert!(wins > games / 2, "Optimal won {wins} of {games}");
+    }
+
+    #[test]
+    fn dice_are_spawned_from_the_asset_store() {
+        // All files are declared for the asset manager, and exist
+        assert!(GameAssets::asset_manager().is_ok());
+
+        let image = Assets::<Image>::default().add(Image::default());
+        let layout = Assets::<TextureAtlasLayout>::default()
+            .add(TextureAtlasLayout::new_empty(UVec2::splat(52)));
+        let mut store = AssetStore::default();
+        store.insert_atlas_handle("dice", image.clone(), layout.clone());
+        let mut app = App::new();
+        app.insert_resource(store).init_resource::<LoadedAssets>();
+        app.world_mut()
+            .run_system_once(
+                |mut commands: Commands, hand: Query<(Entity, &HandDie)>, assets: GameAssets| {
+                    spawn_die(&hand, &mut commands, &assets, &[2, 5], Color::WHITE);
+                },
+            )
+            .unwrap();
+
+        let mut sprites = app.world_mut().query_filtered::<&Sprite, With<HandDie>>();
+        let sprites: Vec<&Sprite> = sprites.iter(app.world()).collect();
+        assert_eq!(2, sprites.len());
+        for sprite in sprites {
+            assert_eq!(image, sprite.image);
+            assert_eq!(layout, sprite.texture_atlas.as_ref().unwrap().layout);
+        }
+    }
+
+    /// Plays a game to the end through the turn systems, both sides rolling
+    /// twice before they hold, and records it in the statistics
+    fn recorded_game(
+        seed: u64,
+        stats: SessionStats,
+        file: StatsFile,
+    ) -> (SessionStats, (Scores, TurnLog)) {
+        let mut app = turn_app(house_rules(), seed);
+        app.insert_resource(stats)
+            .insert_resource(file)
+            .add_systems(Update, check_game_over.after(settle_roll))
+            .add_systems(OnEnter(GamePhase::End), record_stats);
+        'game: for _ in 0..100 {
+            for action in [TurnAction::Roll, TurnAction::Roll, TurnAction::Hold] {
+                next_frame(&mut app, Some(action));
+                for _ in 0..6 {
+                    next_frame(&mut app, None);
+                }
+                if phase(&app) == GamePhase::End {
+                    break 'game;
+                }
+            }
         }
+        assert_eq!(GamePhase::End, phase(&app));
+        let world = app.world_mut();
+        let log = std::mem::take(&mut world.resource_mut::<TurnLog>().0);
+        let scores = *world.resource::<Scores>();
+        (
+            world.resource::<SessionStats>().clone(),
+            (scores, TurnLog(log)),
+        )
+    }
+
+    #[test]
+    fn session_stats_aggregate_games() {
+        let path = std::env::temp_dir().join(format!("pig_stats_{}.ron", std::process::id()));
+        // Without a file, the statistics are only kept in memory
+        let (stats, first) = recorded_game(1, SessionStats::default(), StatsFile(None));
+        assert_eq!(1, stats.games_played);
+        assert!(!path.exists());
+        let (stats, second) = recorded_game(2, stats, StatsFile(Some(path.clone())));
+
+        // Replaying the logs of both games adds up to the same statistics
+        let mut replayed = SessionStats::default();
+        for (scores, log) in [&first, &second] {
+            replayed.record_game(scores, log);
+        }
+        assert_eq!(replayed, stats);
+        // The seeds let the player win the first game and the CPU the second
+        assert_eq!(Side::Player, first.0.winner());
+        assert_eq!(Side::Cpu, second.0.winner());
+        assert_eq!(2, stats.games_played);
+        assert_eq!((1, 1), (stats.player_wins, stats.cpu_wins));
+        assert_eq!(15, stats.best_turn);
+        assert_eq!(3, stats.longest_streak);
+        assert_eq!(50.0, stats.win_rate());
+
+        let loaded = SessionStats::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(SessionStats { streak: 0, ..stats }, loaded.unwrap());
+    }
+
+    /// Plays the same decisions in a game started with the given seed
+    fn scripted_game(seed: u64) -> (Vec<LogEntry>, (usize, usize)) {
+        let mut app = turn_app(PigRules::default(), 0);
+        app.insert_resource(GameSeed(Some(seed)))
+            .add_systems(Startup, seed_dice);
+        for _ in 0..10 {
+            for action in [TurnAction::Roll, TurnAction::Roll, TurnAction::Hold] {
+                next_frame(&mut app, Some(action));
+                for _ in 0..6 {
+                    next_frame(&mut app, None);
+                }
+            }
+        }
+        let world = app.world();
+        let scores = world.resource::<Scores>();
+        (
+            world.resource::<TurnLog>().0.clone(),
+            (scores.player, scores.cpu),
+        )
+    }
+
+    #[test]
+    fn seeded_games_replay_the_same_dice() {
+        let (log, scores) = scripted_game(42);
+        assert!(
+            log.iter()
+                .any(|entry| matches!(entry, LogEntry::Rolled(..)))
+        );
+        assert_eq!((log.clone(), scores), scripted_game(42));
+        assert_ne!(log, scripted_game(43).0);
     }
 }